@@ -0,0 +1,63 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use linfb::shape::{Color, Image, Shape};
+use linfb::{Canvas, Compositor, RenderTarget};
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 720;
+const IMAGE_SIZE: u32 = 160;
+
+/// Scene of 20 opaque images in a 5x4 grid, overlapping each other a bit
+fn opaque_images() -> Vec<(Image, usize, usize)> {
+    (0..20)
+        .map(|index| {
+            let buffer = image::RgbaImage::from_fn(IMAGE_SIZE, IMAGE_SIZE, |x, y| {
+                image::Rgba([x as u8, y as u8, index as u8 * 12, 255])
+            });
+            let (column, row) = (index % 5, index / 5);
+            (Image::from(buffer), column * 240, row * 170)
+        })
+        .collect()
+}
+
+/// Rendering of opaque images, which are copied row by row, compared to blending them pixel by
+/// pixel
+fn render_opaque_images(c: &mut Criterion) {
+    let images = opaque_images();
+    let background = Color::from((0, 0, 0));
+    let mut group = c.benchmark_group("render 20 opaque images");
+
+    let mut compositor = Compositor::new(WIDTH, HEIGHT, background);
+    for (index, (image, x, y)) in images.iter().enumerate() {
+        compositor.add(&format!("image {}", index), image.clone().at(*x, *y));
+    }
+    let mut canvas = Canvas::default();
+    group.bench_function("compositor", |b| {
+        b.iter(|| compositor.render_into(&mut canvas))
+    });
+
+    let rendered: Vec<_> = images
+        .iter()
+        .map(|(image, x, y)| (image.render(), *x, *y))
+        .collect();
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, background);
+    group.bench_function("per pixel", |b| {
+        b.iter(|| {
+            canvas.reset(WIDTH, HEIGHT, background);
+            for (rows, x, y) in &rendered {
+                for (inner_y, row) in rows.iter().enumerate() {
+                    for (inner_x, color) in row.iter().enumerate() {
+                        if let Some(color) = color {
+                            canvas.draw_pixel(x + inner_x, y + inner_y, *color);
+                        }
+                    }
+                }
+            }
+        })
+    });
+    assert_eq!(canvas.to_rows(), compositor.render());
+    group.finish();
+}
+
+criterion_group!(benches, render_opaque_images);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+use linfb::{BufferStrategy, Framebuffer, FramebufferBackend, MemoryFramebuffer};
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+
+/// Device which can't be mapped, so flushed bytes are written to its file like with
+/// [`linfb::Transport::Write`]
+struct WrittenDevice {
+    memory: MemoryFramebuffer,
+    file: File,
+}
+
+impl WrittenDevice {
+    fn new(screen_info: fb_var_screeninfo) -> Self {
+        let path = std::env::temp_dir().join(format!("linfb-bench-{}", std::process::id()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        Self {
+            memory: MemoryFramebuffer::new(screen_info).unwrap(),
+            file,
+        }
+    }
+}
+
+impl FramebufferBackend for WrittenDevice {
+    fn screen_info(&self) -> &fb_var_screeninfo {
+        self.memory.screen_info()
+    }
+
+    fn fix_info(&self) -> &fb_fix_screeninfo {
+        self.memory.fix_info()
+    }
+
+    fn map(&self) -> &[u8] {
+        self.memory.map()
+    }
+
+    fn map_mut(&mut self) -> &mut [u8] {
+        self.memory.map_mut()
+    }
+
+    fn flush(&mut self, range: Range<usize>) -> io::Result<()> {
+        let start = range.start as u64;
+        self.file.write_all_at(&self.memory.map()[range], start)
+    }
+}
+
+/// Flush of a frame where a band of 10% of rows changes every time. DoubleCopy only gets changed
+/// rows to device, but has to compare whole buffers, which costs about as much as copying them
+/// when device memory is ordinary memory
+fn flush_partial_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flush 10% changed frame");
+    for &backend in &["memory", "written device"] {
+        for &strategy in &[BufferStrategy::Single, BufferStrategy::DoubleCopy] {
+            let screen_info = fb_var_screeninfo::argb8888(WIDTH, HEIGHT);
+            let mut framebuffer = match backend {
+                "memory" => Framebuffer::in_memory(screen_info).unwrap(),
+                _ => Framebuffer::with_backend(WrittenDevice::new(screen_info)).unwrap(),
+            };
+            assert_eq!(framebuffer.set_buffer_strategy(strategy), strategy);
+            framebuffer.flush();
+
+            let mut frame = 0u8;
+            let id = BenchmarkId::new(format!("{:?}", strategy), backend);
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    frame = frame.wrapping_add(1);
+                    framebuffer.fill_rect(0, 0, WIDTH, HEIGHT / 10, (frame, 0, 0));
+                    framebuffer.flush();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, flush_partial_frame);
+criterion_main!(benches);
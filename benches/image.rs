@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use linfb::shape::{Color, Image, Shape};
+use linfb::{Canvas, RenderTarget};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+/// Full HD background decoded from JPEG, so every pixel is opaque
+fn background() -> Image {
+    let buffer = image::RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        image::Rgb([(x / 8) as u8, (y / 5) as u8, ((x + y) / 12) as u8])
+    });
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgb8(buffer)
+        .write_to(&mut jpeg, image::ImageOutputFormat::JPEG(90))
+        .unwrap();
+    Image::from_buffer(&jpeg).unwrap()
+}
+
+/// Drawing background image onto canvas by copying rows from its buffer, compared to converting
+/// every pixel and drawing rows of options, like it was done before
+fn draw_background(c: &mut Criterion) {
+    let image = background();
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let black = Color::from((0, 0, 0));
+    let mut group = c.benchmark_group("draw 1920x1080 JPEG background");
+
+    let mut canvas = Canvas::new(width, height, black);
+    group.bench_function("blit", |b| b.iter(|| image.render_into(&mut canvas, 0, 0)));
+    let blitted = canvas.clone();
+
+    let mut canvas = Canvas::new(width, height, black);
+    group.bench_function("per pixel", |b| {
+        b.iter(|| {
+            let rows = image.render_region(linfb::Rect::new(0, 0, width, height));
+            for (y, row) in rows.iter().enumerate() {
+                canvas.draw_row(0, y, row);
+            }
+        })
+    });
+    assert_eq!(canvas, blitted);
+    group.finish();
+}
+
+criterion_group!(benches, draw_background);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use linfb::shape::{Color, Rectangle, Shape};
+use linfb::{Canvas, RenderTarget};
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+
+/// Filling canvas with a full HD background rectangle by streaming its rows, compared to
+/// rendering its whole pixel grid first, and to plain filling of memory
+fn fill_background(c: &mut Criterion) {
+    let background = Rectangle::builder()
+        .width(WIDTH)
+        .height(HEIGHT)
+        .border_width(2)
+        .border_color((255, 255, 255))
+        .fill_color((20, 40, 80))
+        .build()
+        .unwrap();
+    let black = Color::from((0, 0, 0));
+    let mut group = c.benchmark_group("fill 1920x1080 background rectangle");
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, black);
+    group.bench_function("render_into", |b| {
+        b.iter(|| background.render_into(&mut canvas, 0, 0))
+    });
+    let streamed = canvas.clone();
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, black);
+    group.bench_function("render", |b| {
+        b.iter(|| {
+            for (y, row) in background.render().iter().enumerate() {
+                canvas.draw_row(0, y, row);
+            }
+        })
+    });
+    assert_eq!(canvas, streamed);
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, black);
+    let fill = Color::from((20, 40, 80));
+    group.bench_function("reset", |b| b.iter(|| canvas.reset(WIDTH, HEIGHT, fill)));
+    group.finish();
+}
+
+criterion_group!(benches, fill_background);
+criterion_main!(benches);
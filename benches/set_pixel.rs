@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+use linfb::{Framebuffer, PixelFormat};
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+
+/// Screen with given size of pixel and layout of channels
+fn screen_info(bits_per_pixel: u32, format: PixelFormat) -> fb_var_screeninfo {
+    let channel = |(offset, length)| fb_bitfield::new(offset, length);
+    fb_var_screeninfo::new(
+        WIDTH,
+        HEIGHT,
+        bits_per_pixel,
+        channel(format.red),
+        channel(format.green),
+        channel(format.blue),
+        channel(format.alpha),
+    )
+}
+
+/// Filling the whole screen pixel by pixel, which encodes every pixel with precomputed shifts
+/// and masks of layout
+fn set_every_pixel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set every pixel");
+    let layouts = [
+        ("XRGB8888", 32, PixelFormat::XRGB8888),
+        ("ARGB8888", 32, PixelFormat::ARGB8888),
+        ("BGRA8888", 32, PixelFormat::BGRA8888),
+        ("RGB565", 16, PixelFormat::RGB565),
+    ];
+    for &(name, bits_per_pixel, format) in &layouts {
+        let mut framebuffer = Framebuffer::in_memory(screen_info(bits_per_pixel, format)).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                for y in 0..HEIGHT {
+                    for x in 0..WIDTH {
+                        framebuffer.set_pixel(x, y, (x as u8, y as u8, 128, 255));
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, set_every_pixel);
+criterion_main!(benches);
@@ -0,0 +1,587 @@
+//! Time-based animation of shape properties. [`Animator`] moves and fades shapes of
+//! [`Compositor`] independently of frame rate: advance it by real elapsed time on every frame,
+//! e.g. from the update closure of [`AnimationLoop`]:
+//! ```
+//! # use std::time::Duration;
+//! # use linfb::Compositor;
+//! # use linfb::animation::{Animator, Easing};
+//! # use linfb::shape::{Rectangle, Shape};
+//! let mut compositor = Compositor::new(200, 100, (0, 0, 0).into());
+//! compositor.add("logo", Rectangle::builder()
+//!     .width(10)
+//!     .height(10)
+//!     .build()
+//!     .unwrap()
+//!     .at(0, 0));
+//!
+//! let mut animator = Animator::new();
+//! let second = Duration::from_secs(1);
+//! animator
+//!     .animate_position(&mut compositor, "logo", (0, 0), (100, 50), second, Easing::Linear)
+//!     .animate_opacity(&mut compositor, "logo", 1.0, 0.0, 2 * second, Easing::Linear);
+//!
+//! let frame = Duration::from_millis(250);
+//! assert!(!animator.tick(frame, &mut compositor));
+//! let logo = compositor.get_positioned("logo").unwrap();
+//! assert_eq!((logo.x, logo.y), (25, 13));
+//! assert_eq!(logo.opacity, 0.875);
+//!
+//! for _ in 0..3 {
+//!     assert!(!animator.tick(frame, &mut compositor));
+//! }
+//! let logo = compositor.get_positioned("logo").unwrap();
+//! assert_eq!((logo.x, logo.y), (100, 50));
+//! assert_eq!(logo.opacity, 0.5);
+//!
+//! // Completion is reported when nothing is left to animate
+//! for _ in 0..3 {
+//!     assert!(!animator.tick(frame, &mut compositor));
+//! }
+//! assert!(animator.tick(frame, &mut compositor));
+//! assert_eq!(compositor.get_positioned("logo").unwrap().opacity, 0.0);
+//! assert!(animator.is_empty());
+//! ```
+
+#[cfg(feature = "framebuffer")]
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+use crate::compositor::Compositor;
+
+// Used only by AnimationLoop, which draws on framebuffer
+#[cfg(feature = "framebuffer")]
+use crate::canvas::{Canvas, Rect, RenderTarget};
+#[cfg(feature = "framebuffer")]
+use crate::compositor::Snapshot;
+#[cfg(feature = "framebuffer")]
+use crate::limiter::FrameLimiter;
+#[cfg(feature = "framebuffer")]
+use crate::{BufferStrategy, Framebuffer};
+
+/// Function which maps linear progress of animation to eased progress. Both go from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Easing {
+    /// Constant speed
+    Linear,
+    /// Starts slowly and accelerates
+    EaseInCubic,
+    /// Starts fast and decelerates
+    EaseOutCubic,
+    /// Accelerates in the first half and decelerates in the second
+    EaseInOutCubic,
+    /// Bounces off the end value, like a dropped ball
+    Bounce,
+}
+
+impl Easing {
+    /// Eased progress at linear progress `t`. `t` is clamped to `[0, 1]`, start and end are kept
+    /// in place by every easing:
+    /// ```
+    /// # use linfb::animation::Easing;
+    /// for &easing in &[
+    ///     Easing::Linear,
+    ///     Easing::EaseInCubic,
+    ///     Easing::EaseOutCubic,
+    ///     Easing::EaseInOutCubic,
+    ///     Easing::Bounce,
+    /// ] {
+    ///     assert_eq!(easing.apply(0.0), 0.0);
+    ///     assert_eq!(easing.apply(1.0), 1.0);
+    ///     assert_eq!(easing.apply(5.0), 1.0);
+    ///     for i in 0..=100 {
+    ///         let value = easing.apply(i as f32 / 100.0);
+    ///         assert!(value >= 0.0 && value <= 1.0);
+    ///     }
+    /// }
+    /// assert_eq!(Easing::Linear.apply(0.25), 0.25);
+    /// assert_eq!(Easing::EaseInCubic.apply(0.5), 0.125);
+    /// assert_eq!(Easing::EaseOutCubic.apply(0.5), 0.875);
+    /// assert_eq!(Easing::EaseInOutCubic.apply(0.5), 0.5);
+    /// ```
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0f32, 1f32);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInCubic => t * t * t,
+            Easing::EaseOutCubic => 1f32 - (1f32 - t).powi(3),
+            Easing::EaseInOutCubic if t < 0.5 => 4f32 * t * t * t,
+            Easing::EaseInOutCubic => 1f32 - (2f32 - 2f32 * t).powi(3) / 2f32,
+            Easing::Bounce => bounce(t),
+        }
+    }
+}
+
+/// Standard "ease out bounce" curve: parabolic arcs with decreasing height
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    let value = if t < 1f32 / D {
+        N * t * t
+    } else if t < 2f32 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    };
+    value.min(1f32)
+}
+
+/// Progress of one animation over time
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::animation::{Easing, Tween};
+/// let mut tween = Tween::new(Duration::from_millis(100), Easing::EaseInCubic);
+/// assert_eq!(tween.value(), 0.0);
+/// tween.advance(Duration::from_millis(50));
+/// assert_eq!(tween.value(), 0.125);
+/// assert_eq!(tween.interpolate(10.0, 18.0), 11.0);
+/// assert!(!tween.finished());
+/// tween.advance(Duration::from_millis(80));
+/// assert_eq!(tween.value(), 1.0);
+/// assert!(tween.finished());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tween {
+    duration: Duration,
+    easing: Easing,
+    elapsed: Duration,
+}
+
+impl Tween {
+    /// Create tween which goes from 0 to 1 in given time
+    pub fn new(duration: Duration, easing: Easing) -> Self {
+        Self {
+            duration,
+            easing,
+            elapsed: Duration::default(),
+        }
+    }
+
+    /// Move tween forward in time
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.elapsed = Duration::min(self.elapsed + elapsed, self.duration);
+    }
+
+    /// Linear progress, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        if self.duration == Duration::default() {
+            1f32
+        } else {
+            self.elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        }
+    }
+
+    /// Eased progress, from 0 to 1
+    pub fn value(&self) -> f32 {
+        self.easing.apply(self.progress())
+    }
+
+    /// Value between `from` and `to` at current eased progress
+    pub fn interpolate(&self, from: f32, to: f32) -> f32 {
+        from + (to - from) * self.value()
+    }
+
+    /// Is the whole duration elapsed
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Animated property of shape
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Property {
+    Position((usize, usize), (usize, usize)),
+    Opacity(f32, f32),
+}
+
+impl Property {
+    fn same_kind(&self, other: &Property) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+#[derive(Debug)]
+struct Binding {
+    name: String,
+    property: Property,
+    tween: Tween,
+}
+
+impl Binding {
+    /// Set property of shape to its value at current progress. Missing shapes are skipped
+    fn apply(&self, compositor: &mut Compositor) {
+        let shape = match compositor.get_positioned(&self.name) {
+            Some(shape) => shape,
+            None => return,
+        };
+        match self.property {
+            Property::Position((from_x, from_y), (to_x, to_y)) => {
+                let coord = |from, to| {
+                    self.tween
+                        .interpolate(from as f32, to as f32)
+                        .round()
+                        .max(0f32) as usize
+                };
+                shape.x = coord(from_x, to_x);
+                shape.y = coord(from_y, to_y);
+            }
+            Property::Opacity(from, to) => {
+                shape.opacity = self.tween.interpolate(from, to).clamp(0f32, 1f32);
+            }
+        }
+    }
+}
+
+/// Set of running animations of named shapes in [`Compositor`]. See [module docs](self)
+///
+/// Starting a new animation of the same property of the same shape replaces the old one:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::Compositor;
+/// # use linfb::animation::{Animator, Easing};
+/// # use linfb::shape::{Rectangle, Shape};
+/// # let mut compositor = Compositor::new(200, 100, (0, 0, 0).into());
+/// # compositor.add("logo", Rectangle::builder()
+/// #     .width(10)
+/// #     .height(10)
+/// #     .build()
+/// #     .unwrap()
+/// #     .at(0, 0));
+/// let mut animator = Animator::new();
+/// let second = Duration::from_secs(1);
+/// animator.animate_position(&mut compositor, "logo", (0, 0), (100, 0), second, Easing::Linear);
+/// animator.tick(second / 2, &mut compositor);
+/// assert_eq!(compositor.get_positioned("logo").unwrap().x, 50);
+///
+/// animator.animate_position(&mut compositor, "logo", (50, 0), (0, 0), second, Easing::Linear);
+/// assert_eq!(animator.len(), 1);
+/// animator.tick(second / 2, &mut compositor);
+/// assert_eq!(compositor.get_positioned("logo").unwrap().x, 25);
+/// assert!(animator.tick(second / 2, &mut compositor));
+/// assert_eq!(compositor.get_positioned("logo").unwrap().x, 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct Animator {
+    bindings: Vec<Binding>,
+}
+
+impl Animator {
+    /// Create animator without animations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move shape with given name from `from` to `to`. Shape is moved to `from` immediately
+    pub fn animate_position(
+        &mut self,
+        compositor: &mut Compositor,
+        name: &str,
+        from: (usize, usize),
+        to: (usize, usize),
+        duration: Duration,
+        easing: Easing,
+    ) -> &mut Self {
+        self.start(
+            compositor,
+            name,
+            Property::Position(from, to),
+            duration,
+            easing,
+        )
+    }
+
+    /// Change [opacity](crate::shape::PositionedShape::opacity) of shape with given name from
+    /// `from` to `to`. Opacity is set to `from` immediately
+    pub fn animate_opacity(
+        &mut self,
+        compositor: &mut Compositor,
+        name: &str,
+        from: f32,
+        to: f32,
+        duration: Duration,
+        easing: Easing,
+    ) -> &mut Self {
+        self.start(
+            compositor,
+            name,
+            Property::Opacity(from, to),
+            duration,
+            easing,
+        )
+    }
+
+    fn start(
+        &mut self,
+        compositor: &mut Compositor,
+        name: &str,
+        property: Property,
+        duration: Duration,
+        easing: Easing,
+    ) -> &mut Self {
+        self.bindings
+            .retain(|binding| binding.name != name || !binding.property.same_kind(&property));
+        let binding = Binding {
+            name: name.into(),
+            property,
+            tween: Tween::new(duration, easing),
+        };
+        binding.apply(compositor);
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Advance every animation by `elapsed` and update shapes of compositor. Finished animations
+    /// leave their shapes at end values and are removed. Returns `true` if all animations are
+    /// finished
+    pub fn tick(&mut self, elapsed: Duration, compositor: &mut Compositor) -> bool {
+        for binding in self.bindings.iter_mut() {
+            binding.tween.advance(elapsed);
+            binding.apply(compositor);
+        }
+        self.bindings.retain(|binding| !binding.tween.finished());
+        self.is_empty()
+    }
+
+    /// Number of running animations
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// Are there no running animations
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+/// Default value of [`AnimationLoop::full_redraw_threshold`]
+#[cfg(feature = "framebuffer")]
+const FULL_REDRAW_THRESHOLD: f32 = 0.5;
+
+/// Loop which draws [`Compositor`] on [`Framebuffer`] at constant frame rate, redrawing only what
+/// changed. Every frame the update closure is called with time elapsed since the previous frame,
+/// then regions found by [`Compositor::diff`] are re-rendered with [`Compositor::render_region`]
+/// and flushed with [`Framebuffer::flush_region`]. The first frame is drawn fully, and so is every
+/// frame which damages too much of the screen, see [`AnimationLoop::full_redraw_threshold`].
+///
+/// Bouncing rectangle which is always drawn the same as if the whole compositor was redrawn:
+/// ```
+/// # use std::ops::ControlFlow;
+/// # use linfb::{Compositor, Framebuffer};
+/// # use linfb::animation::AnimationLoop;
+/// # use linfb::shape::{Rectangle, Shape};
+/// # use linfb::sys::fb_var_screeninfo;
+/// let screen_info = fb_var_screeninfo::argb8888(64, 48);
+/// let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+/// let mut compositor = framebuffer.compositor((0, 0, 64).into());
+/// compositor.add("ball", Rectangle::builder()
+///     .width(8)
+///     .height(8)
+///     .border_width(0)
+///     .fill_color((255, 255, 0))
+///     .build()
+///     .unwrap()
+///     .at(0, 0));
+///
+/// let (mut dx, mut dy) = (3i32, 2i32);
+/// let mut frames = 0;
+/// AnimationLoop::run(&mut framebuffer, &mut compositor, 1000, |compositor, _elapsed| {
+///     frames += 1;
+///     if frames > 50 {
+///         return ControlFlow::Break(());
+///     }
+///     let ball = compositor.get_positioned("ball").unwrap();
+///     let (x, y) = (ball.x as i32 + dx, ball.y as i32 + dy);
+///     if x < 0 || x > 64 - 8 {
+///         dx = -dx;
+///     }
+///     if y < 0 || y > 48 - 8 {
+///         dy = -dy;
+///     }
+///     ball.x = x.clamp(0, 64 - 8) as usize;
+///     ball.y = y.clamp(0, 48 - 8) as usize;
+///     ControlFlow::Continue(())
+/// });
+///
+/// let mut expected = Framebuffer::in_memory(screen_info).unwrap();
+/// expected.draw(0, 0, &compositor);
+/// expected.flush();
+/// assert_eq!(framebuffer.front_buffer(), expected.front_buffer());
+/// ```
+///
+/// Shapes outside of damaged regions are never re-rendered:
+/// ```
+/// # use std::cell::Cell;
+/// # use std::ops::ControlFlow;
+/// # use std::rc::Rc;
+/// # use linfb::{Framebuffer, RenderTarget};
+/// # use linfb::animation::AnimationLoop;
+/// # use linfb::shape::{Color, Rectangle, Shape};
+/// # use linfb::sys::fb_var_screeninfo;
+/// let screen_info = fb_var_screeninfo::argb8888(64, 48);
+/// #[derive(Clone)]
+/// struct Counted(Rc<Cell<usize>>);
+///
+/// impl Shape for Counted {
+///     fn render(&self) -> Vec<Vec<Option<Color>>> {
+///         vec![vec![Some((255, 255, 255).into()); 10]; 10]
+///     }
+///
+///     fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+///         self.0.set(self.0.get() + 1);
+///         for (inner_y, row) in self.render().iter().enumerate() {
+///             target.draw_row(x, y + inner_y, row);
+///         }
+///     }
+///
+///     fn dimensions(&self) -> (usize, usize) {
+///         (10, 10)
+///     }
+///
+///     fn content_hash(&self) -> u64 {
+///         0
+///     }
+/// }
+///
+/// let renders = Rc::new(Cell::new(0));
+/// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+/// let mut compositor = framebuffer.compositor((0, 0, 0).into());
+/// compositor
+///     .add("static", Counted(renders.clone()).at(50, 30))
+///     .add("moving", Rectangle::builder()
+///         .width(4)
+///         .height(4)
+///         .build()
+///         .unwrap()
+///         .at(0, 0));
+///
+/// let moving = |frames: usize| {
+///     let mut frame = 0;
+///     move |compositor: &mut linfb::Compositor, _elapsed| {
+///         frame += 1;
+///         if frame > frames {
+///             return ControlFlow::Break(());
+///         }
+///         compositor.get_positioned("moving").unwrap().x = frame;
+///         ControlFlow::Continue(())
+///     }
+/// };
+/// AnimationLoop::run(&mut framebuffer, &mut compositor, 1000, moving(20));
+/// // Only the first, full frame
+/// assert_eq!(renders.get(), 1);
+///
+/// // Every frame is drawn fully if any damage is too much
+/// renders.set(0);
+/// AnimationLoop::new(1000)
+///     .full_redraw_threshold(0.0)
+///     .run_with(&mut framebuffer, &mut compositor, moving(20));
+/// assert_eq!(renders.get(), 20);
+/// ```
+#[cfg(feature = "framebuffer")]
+#[derive(Debug)]
+pub struct AnimationLoop {
+    limiter: FrameLimiter,
+    threshold: f32,
+    canvas: Canvas,
+}
+
+#[cfg(feature = "framebuffer")]
+impl AnimationLoop {
+    /// Create loop with given target frame rate. Panics if `fps` is zero
+    pub fn new(fps: u32) -> Self {
+        Self {
+            limiter: FrameLimiter::new(fps as f32),
+            threshold: FULL_REDRAW_THRESHOLD,
+            canvas: Canvas::default(),
+        }
+    }
+
+    /// Fraction of screen area which can be damaged in one frame before the whole frame is redrawn
+    /// instead, because many small regions are slower than one big. Default is 0.5
+    pub fn full_redraw_threshold(mut self, fraction: f32) -> Self {
+        self.threshold = fraction;
+        self
+    }
+
+    /// Run loop with default settings until `update` returns [`ControlFlow::Break`]. Panics if
+    /// `fps` is zero
+    pub fn run<F>(framebuffer: &mut Framebuffer, compositor: &mut Compositor, fps: u32, update: F)
+    where
+        F: FnMut(&mut Compositor, Duration) -> ControlFlow<()>,
+    {
+        Self::new(fps).run_with(framebuffer, compositor, update);
+    }
+
+    /// Run loop until `update` returns [`ControlFlow::Break`]. Nothing is drawn in the frame
+    /// where it happens
+    pub fn run_with<F>(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        compositor: &mut Compositor,
+        mut update: F,
+    ) where
+        F: FnMut(&mut Compositor, Duration) -> ControlFlow<()>,
+    {
+        let mut previous: Option<Snapshot> = None;
+        let mut elapsed = Duration::default();
+        while let ControlFlow::Continue(()) = update(compositor, elapsed) {
+            let damage = match &previous {
+                // Back page of page flipping holds the frame before the last one, so it can't be
+                // patched
+                Some(previous)
+                    if framebuffer.buffer_strategy() != BufferStrategy::DoubleFlip
+                        && framebuffer.buffer_strategy() != BufferStrategy::TripleFlip =>
+                {
+                    Some(compositor.damage_since(previous))
+                }
+                _ => None,
+            };
+            let screen_area = (framebuffer.width() * framebuffer.height()) as f32;
+            let damage = damage.filter(|rects| {
+                let area: usize = rects.iter().map(|rect| rect.width * rect.height).sum();
+                area as f32 <= screen_area * self.threshold
+            });
+
+            match &damage {
+                Some(rects) => {
+                    for &rect in rects {
+                        compositor.render_region(&mut self.canvas, rect);
+                        self.copy_region(framebuffer, rect);
+                    }
+                }
+                None => {
+                    compositor.render_into(&mut self.canvas);
+                    let bounds = Rect::new(0, 0, self.canvas.width(), self.canvas.height());
+                    self.copy_region(framebuffer, bounds);
+                }
+            }
+            previous = Some(compositor.snapshot());
+
+            elapsed = self.limiter.tick().frame_time;
+            match damage {
+                Some(rects) => {
+                    for rect in rects {
+                        framebuffer.flush_region(rect);
+                    }
+                }
+                None => framebuffer.flush(),
+            }
+        }
+    }
+
+    /// Copy region of rendered canvas to the same place of framebuffer
+    fn copy_region(&self, framebuffer: &mut Framebuffer, region: Rect) {
+        let width = self.canvas.width();
+        for y in region.y..region.y + region.height {
+            let start = y * width + region.x;
+            framebuffer.draw_colors(
+                region.x,
+                y,
+                &self.canvas.pixels()[start..start + region.width],
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use derive_builder::Builder;
+
+use crate::error::{Error::*, Result};
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+
+/// Part of a ring between two angles, e.g. a gauge. Shape is a square with side `2 * radius + 1`
+/// and the center of ring in the middle pixel. Pixel is covered if distance from its center to
+/// the center of ring is from `radius + 0.5 - thickness` to `radius + 0.5`, i.e. radius and
+/// thickness are measured to the edges of pixels, so ring is exactly `thickness` px wide along
+/// the axes. Everything else is [`None`]:
+/// ```
+/// # use linfb::shape::{Arc, Shape};
+/// let ring = Arc::builder()
+///     .radius(5)
+///     .thickness(2)
+///     .color((255, 0, 0))
+///     .build()
+///     .unwrap();
+/// assert_eq!(ring.dimensions(), (11, 11));
+/// let rendered = ring.render();
+/// let middle: String = rendered[5]
+///     .iter()
+///     .map(|pixel| if pixel.is_some() { '#' } else { '.' })
+///     .collect();
+/// assert_eq!(middle, "##.......##");
+///
+/// // Default angles make a full ring, which has no seam: it's the same after rotation by 90°
+/// let rotated: Vec<Vec<_>> = (0..11)
+///     .map(|y| (0..11).map(|x| rendered[10 - x][y]).collect())
+///     .collect();
+/// assert_eq!(rotated, rendered);
+/// for &(start, end) in &[(0.0, 360.0), (90.0, 450.0), (-30.0, 330.0), (0.0, 720.0)] {
+///     let full = Arc::builder()
+///         .radius(5)
+///         .thickness(2)
+///         .start_angle_deg(start)
+///         .end_angle_deg(end)
+///         .color((255, 0, 0))
+///         .build()
+///         .unwrap();
+///     assert_eq!(full.render(), rendered);
+/// }
+/// ```
+///
+/// Angles are in degrees counterclockwise from 3 o'clock, like in math, so 90° is 12 o'clock.
+/// Arc goes counterclockwise from the start angle to the end one and wraps past 360°, so the end
+/// angle can be less than the start one:
+/// ```
+/// # use linfb::shape::{Arc, Shape};
+/// let arc = |start, end| {
+///     Arc::builder()
+///         .radius(4)
+///         .thickness(2)
+///         .start_angle_deg(start)
+///         .end_angle_deg(end)
+///         .color((255, 255, 255))
+///         .build()
+///         .unwrap()
+/// };
+/// // Points at 3, 12, 9 and 6 o'clock
+/// let clock = |arc: &Arc| [(8, 4), (4, 0), (0, 4), (4, 8)].map(|(x, y)| arc.contains(x, y));
+/// assert_eq!(clock(&arc(0.0, 90.0)), [true, true, false, false]);
+/// assert_eq!(clock(&arc(90.0, 180.0)), [false, true, true, false]);
+/// assert_eq!(clock(&arc(180.0, 270.0)), [false, false, true, true]);
+/// assert_eq!(clock(&arc(315.0, 45.0)), [true, false, false, false]);
+/// assert_eq!(clock(&arc(-45.0, 45.0)), [true, false, false, false]);
+/// assert_eq!(clock(&arc(100.0, 350.0)), [false, false, true, true]);
+///
+/// // Upper half covers the top rows and nothing below the middle one
+/// let upper = arc(0.0, 180.0).render();
+/// assert!(upper[0].iter().any(|pixel| pixel.is_some()));
+/// assert!(upper[5..].iter().flatten().all(|pixel| pixel.is_none()));
+/// // Zero length arc is empty
+/// assert!(arc(30.0, 30.0).render().iter().flatten().all(|pixel| pixel.is_none()));
+/// ```
+///
+/// Thickness must be at least 1, thickness more than radius makes a disc:
+/// ```
+/// # use linfb::shape::{Arc, Shape};
+/// let error = Arc::builder()
+///     .radius(5)
+///     .thickness(0)
+///     .color((0, 0, 0))
+///     .build()
+///     .unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Arc: thickness must be at least 1");
+///
+/// let disc = Arc::builder().radius(3).thickness(10).color((0, 0, 0)).build().unwrap();
+/// assert!(disc.contains(3, 3));
+/// assert_eq!(disc.render().iter().flatten().filter(|pixel| pixel.is_some()).count(), 37);
+/// ```
+#[derive(Clone, Debug, Builder)]
+#[builder(build_fn(name = "build_unvalidated"))]
+pub struct Arc {
+    /// Outer radius of ring in px, not including the middle pixel
+    pub radius: usize,
+    /// Width of ring in px. Builder default is 1
+    #[builder(default = "1")]
+    pub thickness: usize,
+    /// Angle where arc starts, in degrees counterclockwise from 3 o'clock. Builder default is 0
+    #[builder(default)]
+    pub start_angle_deg: f32,
+    /// Angle where arc ends, in degrees counterclockwise from 3 o'clock. Arc covers whole ring if
+    /// it's at least 360° more than the start angle. Builder default is 360
+    #[builder(default = "360f32")]
+    pub end_angle_deg: f32,
+    /// Color of arc
+    #[builder(setter(into))]
+    pub color: Paint,
+}
+
+impl Arc {
+    /// Create a default [`ArcBuilder`]
+    pub fn builder() -> ArcBuilder {
+        ArcBuilder::default()
+    }
+
+    /// Whether pixel at x, y is covered by arc. Pixel must be inside of shape
+    fn covers(&self, x: usize, y: usize) -> bool {
+        let outer = self.radius as f64 + 0.5;
+        let inner = outer - self.thickness as f64;
+        let (dx, dy) = (x as f64 - self.radius as f64, y as f64 - self.radius as f64);
+        let distance = dx.hypot(dy);
+        if distance > outer || distance < inner {
+            return false;
+        }
+        let (start, end) = (self.start_angle_deg as f64, self.end_angle_deg as f64);
+        if end - start >= 360f64 {
+            return true;
+        }
+        // Screen y axis goes down, so it's flipped to get counterclockwise angle
+        let angle = (-dy).atan2(dx).to_degrees();
+        (angle - start).rem_euclid(360f64) <= (end - start).rem_euclid(360f64)
+    }
+}
+
+impl ArcBuilder {
+    /// Build [`Arc`]. Returns [`Error::InvalidShape`](crate::Error::InvalidShape) if required
+    /// fields are not set or thickness is 0
+    pub fn build(&self) -> Result<Arc> {
+        let arc = self.build_unvalidated().map_err(|reason| InvalidShape {
+            shape: "Arc",
+            reason,
+        })?;
+        if arc.thickness == 0 {
+            return Err(InvalidShape {
+                shape: "Arc",
+                reason: "thickness must be at least 1".into(),
+            });
+        }
+        Ok(arc)
+    }
+}
+
+impl Shape for Arc {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let size = 2 * self.radius + 1;
+        let color = self.color.resolve(palette);
+        (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| color.filter(|_| self.covers(x, y)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let size = 2 * self.radius + 1;
+        (size, size)
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let size = 2 * self.radius + 1;
+        x < size && y < size && self.covers(x, y)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.radius,
+            self.thickness,
+            self.start_angle_deg.to_bits(),
+            self.end_angle_deg.to_bits(),
+            &self.color,
+        ))
+    }
+}
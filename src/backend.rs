@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+
+use memmap::{MmapMut, MmapOptions};
+
+use crate::error::{Error::*, Result};
+use crate::framebuffer::check_pixel_format;
+use crate::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+use crate::sys::{get_fix_screeninfo, get_var_screeninfo};
+use crate::Transport;
+
+/// Memory which [`Framebuffer`](crate::Framebuffer) puts pixels into to get them displayed.
+/// Framebuffer draws into [`FramebufferBackend::map_mut`] and calls
+/// [`FramebufferBackend::flush`] for every changed row, so all drawing APIs work the same way on
+/// every backend. Implemented by [`FbdevBackend`] for real devices and by [`MemoryFramebuffer`]
+/// for headless rendering and tests, see [`Framebuffer::with_backend`](crate::Framebuffer::with_backend)
+pub trait FramebufferBackend: Send {
+    /// Mode of screen, which describes resolution and layout of pixel
+    fn screen_info(&self) -> &fb_var_screeninfo;
+
+    /// Layout of memory, e.g. length of padded rows
+    fn fix_info(&self) -> &fb_fix_screeninfo;
+
+    /// Memory of the whole virtual screen, at least
+    /// [`fb_fix_screeninfo::map_length`] bytes long
+    fn map(&self) -> &[u8];
+
+    /// Mutable memory of the whole virtual screen, see [`FramebufferBackend::map`]
+    fn map_mut(&mut self) -> &mut [u8];
+
+    /// Get given bytes of memory displayed after they're changed. Default implementation does
+    /// nothing, which is right for memory which is displayed as is
+    fn flush(&mut self, range: Range<usize>) -> io::Result<()> {
+        let _ = range;
+        Ok(())
+    }
+}
+
+/// Memory of framebuffer device, e.g. `/dev/fb0`, used by
+/// [`Framebuffer::open`](crate::Framebuffer::open). Memory of most devices is mapped, but some
+/// drivers don't support it, then ordinary memory is written to device on flush, see
+/// [`Transport`]
+pub struct FbdevBackend {
+    memory: MmapMut,
+    /// Device which memory is written to with [`Transport::Write`]
+    file: Option<File>,
+    screen_info: fb_var_screeninfo,
+    fix_info: fb_fix_screeninfo,
+}
+
+impl FbdevBackend {
+    /// Read current mode of device and map its memory. Returns an error if file is not a
+    /// framebuffer device or its memory can't hold the screen
+    pub fn new(file: &File) -> Result<Self> {
+        let mut screen_info: fb_var_screeninfo = Default::default();
+        let mut fix_info: fb_fix_screeninfo = Default::default();
+        unsafe {
+            get_var_screeninfo(file.as_raw_fd(), &mut screen_info).map_err(Ioctl)?;
+            get_fix_screeninfo(file.as_raw_fd(), &mut fix_info).map_err(Ioctl)?;
+        };
+        check_layout(&screen_info, &fix_info)?;
+
+        let length = fix_info.map_length(&screen_info);
+        if let Ok(memory) = unsafe { MmapOptions::new().len(length).map_mut(file) } {
+            return Ok(Self::from_mapping(memory, screen_info, fix_info));
+        }
+        let mut memory = MmapOptions::new().len(length).map_anon()?;
+        // Contents are only needed for reading screen back, so they're not required
+        let _ = file.read_exact_at(&mut memory, 0);
+        Ok(Self {
+            memory,
+            file: Some(file.try_clone()?),
+            screen_info,
+            fix_info,
+        })
+    }
+
+    /// Backend for memory which is already mapped, e.g. DRM dumb buffer
+    pub(crate) fn from_mapping(
+        memory: MmapMut,
+        screen_info: fb_var_screeninfo,
+        fix_info: fb_fix_screeninfo,
+    ) -> Self {
+        Self {
+            memory,
+            file: None,
+            screen_info,
+            fix_info,
+        }
+    }
+
+    /// How pixels get to device, see [`Transport`]
+    pub fn transport(&self) -> Transport {
+        match self.file {
+            Some(_) => Transport::Write,
+            None => Transport::Mmap,
+        }
+    }
+}
+
+impl FramebufferBackend for FbdevBackend {
+    fn screen_info(&self) -> &fb_var_screeninfo {
+        &self.screen_info
+    }
+
+    fn fix_info(&self) -> &fb_fix_screeninfo {
+        &self.fix_info
+    }
+
+    fn map(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn map_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    fn flush(&mut self, range: Range<usize>) -> io::Result<()> {
+        match &self.file {
+            Some(file) => file.write_all_at(&self.memory[range.clone()], range.start as u64),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Framebuffer in ordinary memory with any resolution and layout of pixel, which displays
+/// nothing. Used by [`Framebuffer::in_memory`](crate::Framebuffer::in_memory), so code drawing
+/// on framebuffer can be tested without a real device:
+/// ```
+/// # use linfb::{Framebuffer, FramebufferBackend, MemoryFramebuffer};
+/// # use linfb::sys::fb_var_screeninfo;
+/// let backend = MemoryFramebuffer::new(fb_var_screeninfo::argb8888(4, 2)).unwrap();
+/// assert_eq!(backend.map().len(), 4 * 2 * 4);
+///
+/// let mut framebuffer = Framebuffer::with_backend(backend).unwrap();
+/// framebuffer.set_pixel(1, 0, (255, 0, 0));
+/// framebuffer.flush();
+/// // Bytes are in BGRA order
+/// assert_eq!(&framebuffer.front_buffer()[4..8], &[0, 0, 255, 255]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MemoryFramebuffer {
+    memory: Vec<u8>,
+    screen_info: fb_var_screeninfo,
+    fix_info: fb_fix_screeninfo,
+}
+
+impl MemoryFramebuffer {
+    /// Create zeroed framebuffer with unpadded rows. Returns an error if pixel size is not
+    /// supported
+    pub fn new(screen_info: fb_var_screeninfo) -> Result<Self> {
+        let fix_info = fb_fix_screeninfo::new(&screen_info);
+        Self::with_layout(screen_info, fix_info)
+    }
+
+    /// Same as [`MemoryFramebuffer::new`], but with memory layout described by `fix_info`, e.g.
+    /// with padded rows. Returns an error if memory can't hold the visible screen
+    pub fn with_layout(
+        screen_info: fb_var_screeninfo,
+        fix_info: fb_fix_screeninfo,
+    ) -> Result<Self> {
+        check_layout(&screen_info, &fix_info)?;
+        Ok(Self {
+            memory: vec![0u8; fix_info.map_length(&screen_info)],
+            screen_info,
+            fix_info,
+        })
+    }
+}
+
+impl FramebufferBackend for MemoryFramebuffer {
+    fn screen_info(&self) -> &fb_var_screeninfo {
+        &self.screen_info
+    }
+
+    fn fix_info(&self) -> &fb_fix_screeninfo {
+        &self.fix_info
+    }
+
+    fn map(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn map_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+/// Check that pixel size is supported and memory of given length can hold the visible screen at
+/// its position in virtual screen
+pub(crate) fn check_layout(
+    screen_info: &fb_var_screeninfo,
+    fix_info: &fb_fix_screeninfo,
+) -> Result<()> {
+    check_memory(screen_info, fix_info, fix_info.map_length(screen_info))
+}
+
+/// Same as [`check_layout`], but for memory of given length
+pub(crate) fn check_memory(
+    screen_info: &fb_var_screeninfo,
+    fix_info: &fb_fix_screeninfo,
+    length: usize,
+) -> Result<()> {
+    check_pixel_format(screen_info)?;
+    let stride = fix_info.stride(screen_info);
+    let pixel_size = screen_info.bits_per_pixel as usize / 8;
+    if length < screen_info.yoffset as usize * stride + fix_info.page_size(screen_info)
+        || stride < (screen_info.xoffset + screen_info.xres) as usize * pixel_size
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "framebuffer memory is too small for its resolution",
+        )
+        .into());
+    }
+    Ok(())
+}
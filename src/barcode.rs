@@ -0,0 +1,309 @@
+#[cfg(feature = "text")]
+use std::sync::Arc;
+
+#[cfg(feature = "text")]
+use rusttype::Font;
+
+use crate::canvas::over;
+use crate::error::{Error::*, Result};
+#[cfg(feature = "text")]
+use crate::shape::Caption;
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+
+/// Widths of alternating bars and spaces, starting with a bar, of every Code 128 symbol. The last
+/// one is the stop pattern
+const PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+const CODE_B: u8 = 100;
+const CODE_C: u8 = 99;
+const START_B: u8 = 104;
+const START_C: u8 = 105;
+const STOP: u8 = 106;
+
+/// Code 128 barcode. Data is encoded with code sets B and C, switching to C for runs of digits
+/// where it makes the barcode shorter. Barcode is surrounded by a quiet zone of 10 modules:
+/// ```
+/// # use linfb::shape::{Barcode, Shape};
+/// let barcode = Barcode::code128("PJJ123C").unwrap();
+/// // Start B, data, checksum and stop
+/// assert_eq!(barcode.symbols(), &[104, 48, 42, 42, 17, 18, 19, 35, 55, 106]);
+/// let modules: String = barcode
+///     .modules()
+///     .iter()
+///     .map(|&bar| if bar { '1' } else { '0' })
+///     .collect();
+/// assert!(modules.starts_with(&format!("{}11010010000", "0".repeat(10))));
+/// assert!(modules.ends_with(&format!("1100011101011{}", "0".repeat(10))));
+///
+/// // Digits are packed in pairs
+/// assert_eq!(
+///     Barcode::code128("1234567890").unwrap().symbols(),
+///     &[105, 12, 34, 56, 78, 90, 85, 106],
+/// );
+/// assert_eq!(
+///     Barcode::code128("AB12345678").unwrap().symbols()[..8],
+///     [104, 33, 34, 99, 12, 34, 56, 78],
+/// );
+///
+/// // Every symbol is 11 modules and stop pattern is 13
+/// let barcode = Barcode::code128("AB12345678").unwrap().module_width(3).height(40);
+/// let data_symbols = barcode.symbols().len() - 3;
+/// assert_eq!(barcode.dimensions(), ((11 * data_symbols + 35 + 2 * 10) * 3, 40));
+/// let rendered = barcode.render();
+/// assert_eq!(rendered[0][30], Some((0, 0, 0).into()));
+/// assert_eq!(rendered[39][29], Some((255, 255, 255).into()));
+/// ```
+///
+/// Only printable ASCII characters can be encoded:
+/// ```
+/// # use linfb::Error;
+/// # use linfb::shape::Barcode;
+/// assert!(matches!(Barcode::code128("café"), Err(Error::InvalidBarcodeCharacter('é'))));
+/// assert!(Barcode::code128("tab\t").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Barcode {
+    data: String,
+    symbols: Vec<u8>,
+    module_width: usize,
+    height: usize,
+    quiet_zone: usize,
+    bar_color: Paint,
+    space_color: Option<Paint>,
+    #[cfg(feature = "text")]
+    caption: Option<Caption>,
+}
+
+impl Barcode {
+    /// Encode data as Code 128 barcode with modules of 2 px, 50 px high. Returns
+    /// [`InvalidBarcodeCharacter`](crate::Error::InvalidBarcodeCharacter) if data contains
+    /// characters other than printable ASCII
+    pub fn code128(data: &str) -> Result<Self> {
+        if let Some(invalid) = data.chars().find(|&c| !(' '..='\x7f').contains(&c)) {
+            return Err(InvalidBarcodeCharacter(invalid));
+        }
+        let mut symbols = encode(data.as_bytes());
+        let checksum = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| usize::max(i, 1) * value as usize)
+            .sum::<usize>()
+            % 103;
+        symbols.push(checksum as u8);
+        symbols.push(STOP);
+        Ok(Self {
+            data: data.into(),
+            symbols,
+            module_width: 2,
+            height: 50,
+            quiet_zone: 10,
+            bar_color: (0, 0, 0).into(),
+            space_color: Some((255, 255, 255).into()),
+            #[cfg(feature = "text")]
+            caption: None,
+        })
+    }
+
+    /// Set width of the narrowest bar in px
+    pub fn module_width(mut self, width: usize) -> Self {
+        self.module_width = usize::max(width, 1);
+        self
+    }
+
+    /// Set height of bars in px
+    pub fn height(mut self, height: usize) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Set width of empty space on both sides of barcode in modules. Scanners need at least 10
+    pub fn quiet_zone(mut self, modules: usize) -> Self {
+        self.quiet_zone = modules;
+        self
+    }
+
+    /// Set color of bars. Default is black
+    pub fn bar_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.bar_color = color.into();
+        self
+    }
+
+    /// Set color of spaces and quiet zone, [`None`] makes them transparent. Default is white
+    pub fn space_color<P: Into<Paint>>(mut self, color: Option<P>) -> Self {
+        self.space_color = color.map(Into::into);
+        self
+    }
+
+    /// Draw encoded data under bars with given font and size, in color of bars
+    /// ```
+    /// # use linfb::shape::{Barcode, FontBuilder, Shape};
+    /// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+    /// let barcode = Barcode::code128("ABC-123").unwrap().height(30).human_readable(font, 16);
+    /// let rendered = barcode.render();
+    /// assert_eq!(rendered.len(), barcode.dimensions().1);
+    /// assert!(rendered.len() > 30);
+    /// assert!(rendered[30..].iter().flatten().any(|&c| c == Some((0, 0, 0).into())));
+    /// ```
+    #[cfg(feature = "text")]
+    pub fn human_readable<F: Into<Arc<Font<'static>>>>(mut self, font: F, size: u32) -> Self {
+        let mut caption = Caption::builder();
+        caption.text(self.data.clone()).size(size).font(font.into());
+        match &self.bar_color {
+            Paint::Fixed(color) => caption.color(*color),
+            Paint::Palette(reference) => caption.color_ref(reference.clone()),
+        };
+        self.caption = caption.build().ok();
+        self
+    }
+
+    /// Encoded data
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// Values of all symbols, including start, checksum and stop
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    /// Modules of barcode including quiet zones, `true` for bars
+    pub fn modules(&self) -> Vec<bool> {
+        let mut modules = vec![false; self.quiet_zone];
+        for &symbol in &self.symbols {
+            for (i, width) in PATTERNS[symbol as usize].bytes().enumerate() {
+                let bar = i % 2 == 0;
+                modules.extend((0..width - b'0').map(|_| bar));
+            }
+        }
+        modules.extend((0..self.quiet_zone).map(|_| false));
+        modules
+    }
+
+    /// Rendered human-readable text, if it's enabled
+    fn text(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        #[cfg(feature = "text")]
+        {
+            if let Some(caption) = &self.caption {
+                return caption.render_with_palette(palette);
+            }
+        }
+        let _ = palette;
+        Vec::new()
+    }
+}
+
+/// Number of ASCII digits at the start of data
+fn digits(data: &[u8]) -> usize {
+    data.iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+/// Values of start symbol and data symbols
+fn encode(data: &[u8]) -> Vec<u8> {
+    let leading = digits(data);
+    let mut code_c = leading >= 4 || (leading == data.len() && leading >= 2 && leading % 2 == 0);
+    let mut symbols = vec![if code_c { START_C } else { START_B }];
+    let mut i = 0;
+    while i < data.len() {
+        let run = digits(&data[i..]);
+        if code_c {
+            if run >= 2 {
+                symbols.push((data[i] - b'0') * 10 + data[i + 1] - b'0');
+                i += 2;
+            } else {
+                symbols.push(CODE_B);
+                code_c = false;
+            }
+        } else if run >= 6 || (run >= 4 && i + run == data.len()) {
+            // Odd digit goes first, so the rest is packed in pairs
+            if run % 2 == 1 {
+                symbols.push(data[i] - b' ');
+                i += 1;
+            }
+            symbols.push(CODE_C);
+            code_c = true;
+        } else {
+            symbols.push(data[i] - b' ');
+            i += 1;
+        }
+    }
+    symbols
+}
+
+impl Shape for Barcode {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let bar_color = self.bar_color.resolve(palette);
+        let space_color = self
+            .space_color
+            .as_ref()
+            .and_then(|paint| paint.resolve(palette));
+        let row: Vec<Option<Color>> = self
+            .modules()
+            .into_iter()
+            .flat_map(|bar| {
+                let color = if bar { bar_color } else { space_color };
+                std::iter::repeat(color).take(self.module_width)
+            })
+            .collect();
+        let width = row.len();
+        let mut rows = vec![row; self.height];
+        for text_row in self.text(palette) {
+            let offset = width.saturating_sub(text_row.len()) / 2;
+            let mut row = vec![space_color; width];
+            for (pixel, color) in row[offset..].iter_mut().zip(text_row) {
+                if let Some(color) = color.filter(|color| color.alpha > 0) {
+                    *pixel = Some(match *pixel {
+                        Some(below) => over(color, below),
+                        None => color,
+                    });
+                }
+            }
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let modules = 11 * (self.symbols.len() - 1) + 13 + 2 * self.quiet_zone;
+        #[cfg(feature = "text")]
+        let text_height = self
+            .caption
+            .as_ref()
+            .map_or(0, |caption| caption.measure().1);
+        #[cfg(not(feature = "text"))]
+        let text_height = 0;
+        (modules * self.module_width, self.height + text_height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        #[cfg(feature = "text")]
+        let text = self.caption.as_ref().map(Shape::content_hash);
+        #[cfg(not(feature = "text"))]
+        let text: Option<u64> = None;
+        hash_of(&(
+            &self.symbols,
+            self.module_width,
+            self.height,
+            self.quiet_zone,
+            &self.bar_color,
+            &self.space_color,
+            text,
+        ))
+    }
+}
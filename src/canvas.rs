@@ -0,0 +1,528 @@
+use crate::shape::{Color, Palette};
+use crate::PixelFormat;
+
+/// Something shapes can be drawn onto without materializing their full pixel grid. Implemented by
+/// [`Canvas`] and, with `framebuffer` feature, [`Framebuffer`](crate::Framebuffer).
+///
+/// Every method must silently ignore pixels which are out of target bounds.
+pub trait RenderTarget {
+    /// Width of target in pixels
+    fn width(&self) -> usize;
+
+    /// Height of target in pixels
+    fn height(&self) -> usize;
+
+    /// Draw a single pixel at x, y
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color);
+
+    /// Draw `len` pixels of the same color, starting at x, y and going right. Targets should
+    /// override it with something faster than the default pixel-by-pixel implementation.
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        for inner_x in 0..len {
+            self.draw_pixel(x + inner_x, y, color);
+        }
+    }
+
+    /// Draw a row of pixels starting at x, y. [`None`] pixels are skipped
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        for (inner_x, color) in row.iter().enumerate() {
+            if let Some(color) = color {
+                self.draw_pixel(x + inner_x, y, *color);
+            }
+        }
+    }
+
+    /// Draw a row of pixels without gaps starting at x, y
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        for (inner_x, color) in row.iter().enumerate() {
+            self.draw_pixel(x + inner_x, y, *color);
+        }
+    }
+
+    /// Palette which [`PaletteRef`](crate::shape::PaletteRef)s of shapes drawn onto target are
+    /// resolved against. Default implementation returns [`None`], so parts of shapes painted with
+    /// palette colors are not drawn
+    fn palette(&self) -> Option<&Palette> {
+        None
+    }
+}
+
+/// Rectangular area of screen or canvas
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// Create rectangle with top left angle at x, y and given size
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Is area of rectangle zero
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Check if point at x, y is inside of rectangle
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+
+    /// Common part of two rectangles. Will return [`None`] if they don't intersect
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = usize::max(self.x, other.x);
+        let y = usize::max(self.y, other.y);
+        let right = usize::min(self.x + self.width, other.x + other.width);
+        let bottom = usize::min(self.y + self.height, other.y + other.height);
+        if right > x && bottom > y {
+            Some(Rect::new(x, y, right - x, bottom - y))
+        } else {
+            None
+        }
+    }
+}
+
+/// Flat buffer of pixels, which shapes are composited onto. Every pixel drawn on canvas is
+/// alpha-blended over the previous contents, transparent previous contents are treated as if
+/// they're placed over black background.
+///
+/// ```
+/// # use linfb::{Canvas, Compositor};
+/// # use linfb::shape::{Color, Rectangle, Shape};
+/// let rectangle = Rectangle::builder()
+///     .width(4)
+///     .height(3)
+///     .border_color((255, 0, 0))
+///     .fill_color((0, 0, 255, 128))
+///     .build()
+///     .unwrap();
+/// let mut canvas = Canvas::new(6, 5, (0, 0, 0).into());
+/// rectangle.render_into(&mut canvas, 1, 1);
+/// assert_eq!(canvas.get(1, 1), Some((255, 0, 0).into()));
+/// assert_eq!(canvas.get(2, 2), Some((0, 0, 128).into()));
+/// assert_eq!(canvas.get(5, 4), Some((0, 0, 0).into()));
+///
+/// // Streaming path is equivalent to rendering the whole grid
+/// let mut compositor = Compositor::new(6, 5, (0, 0, 0).into());
+/// compositor.add("rectangle", rectangle.at(1, 1));
+/// assert_eq!(canvas.to_rows(), compositor.render());
+/// ```
+///
+/// Drawing whole rows takes fast paths for opaque pixels, but gives exactly the same result as
+/// drawing pixels one by one:
+/// ```
+/// # use linfb::{Canvas, RenderTarget};
+/// # use linfb::shape::Color;
+/// let mut seed = 42u32;
+/// let mut random = move || {
+///     seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+///     (seed >> 16) as u8
+/// };
+/// for _ in 0..20 {
+///     let background = Color::from((random(), random(), random(), random()));
+///     let mut fast = Canvas::new(64, 2, background);
+///     let mut slow = fast.clone();
+///     let row: Vec<Option<Color>> = (0..70)
+///         .map(|_| match random() % 4 {
+///             0 => None,
+///             1 => Some((random(), random(), random(), 0).into()),
+///             2 => Some((random(), random(), random(), random()).into()),
+///             _ => Some((random(), random(), random()).into()),
+///         })
+///         .collect();
+///     let colors: Vec<Color> = row.iter().map(|c| c.unwrap_or((1, 2, 3, 4).into())).collect();
+///
+///     fast.draw_row(3, 0, &row);
+///     fast.draw_colors(5, 1, &colors);
+///     for (x, color) in row.iter().enumerate() {
+///         if let Some(color) = color {
+///             slow.draw_pixel(3 + x, 0, *color);
+///         }
+///     }
+///     for (x, color) in colors.iter().enumerate() {
+///         slow.draw_pixel(5 + x, 1, *color);
+///     }
+///     assert_eq!(fast, slow);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Canvas {
+    /// Create canvas with given size, filled with background color
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    /// Change size of canvas and fill it with background color. Existing allocation is reused
+    /// when possible
+    pub fn reset(&mut self, width: usize, height: usize, background: Color) {
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        self.pixels.resize(width * height, background);
+    }
+
+    /// Fill part of canvas with given color, without blending. Parts of rectangle outside of canvas
+    /// are skipped
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        if let Some(rect) = rect.intersection(&Rect::new(0, 0, self.width, self.height)) {
+            for y in rect.y..rect.y + rect.height {
+                let start = y * self.width + rect.x;
+                for pixel in self.pixels[start..start + rect.width].iter_mut() {
+                    *pixel = color;
+                }
+            }
+        }
+    }
+
+    /// Fill the whole canvas with given color, without blending
+    pub fn fill(&mut self, color: Color) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = color;
+        }
+    }
+
+    /// Get pixel at x, y. Will return [`None`] if coordinates are out of bounds
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// All pixels of canvas, row by row
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Pixels of canvas for writing without blending
+    pub(crate) fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// Iterate over rows of canvas
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks(usize::max(self.width, 1))
+    }
+
+    /// Pack pixels row by row into bytes of given format, without padding between rows. Every
+    /// pixel takes [`PixelFormat::pixel_size`] bytes in native byte order. Canvas doesn't depend
+    /// on any device, so it can be rendered anywhere and copied into memory managed by caller:
+    /// ```
+    /// # use linfb::{Canvas, PixelFormat, RenderTarget};
+    /// let mut canvas = Canvas::new(2, 2, (0, 0, 0).into());
+    /// canvas.draw_pixel(1, 0, (255, 128, 0).into());
+    /// let bytes = canvas.as_bytes(PixelFormat::XRGB8888);
+    /// assert_eq!(bytes.len(), 16);
+    /// assert_eq!(&bytes[4..8], &0x00ff8000u32.to_ne_bytes());
+    /// assert_eq!(&bytes[8..], &[0; 8]);
+    ///
+    /// // 16-bit formats take two bytes per pixel
+    /// let bytes = canvas.as_bytes(PixelFormat::RGB565);
+    /// assert_eq!(bytes.len(), 8);
+    /// assert_eq!(&bytes[2..4], &(31u16 << 11 | 32 << 5).to_ne_bytes());
+    /// ```
+    pub fn as_bytes(&self, format: PixelFormat) -> Vec<u8> {
+        let pixel_size = format.pixel_size();
+        let mut bytes = Vec::with_capacity(self.pixels.len() * pixel_size);
+        for color in &self.pixels {
+            let pixel = format.encode(*color).to_ne_bytes();
+            if cfg!(target_endian = "big") {
+                bytes.extend_from_slice(&pixel[4 - pixel_size..]);
+            } else {
+                bytes.extend_from_slice(&pixel[..pixel_size]);
+            }
+        }
+        bytes
+    }
+
+    /// Convert canvas into the grid format used by [`Shape::render`](crate::shape::Shape::render)
+    pub fn to_rows(&self) -> Vec<Vec<Option<Color>>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        self.rows()
+            .map(|row| row.iter().copied().map(Some).collect())
+            .collect()
+    }
+
+    /// Blend run of pixels. Loop is free of branches, so it can be vectorized
+    fn blend_run(pixels: &mut [Color], colors: &[Color]) {
+        for (pixel, color) in pixels.iter_mut().zip(colors.iter()) {
+            *pixel = blend(*pixel, *color);
+        }
+    }
+
+    /// Part of row with given length at x, y which is inside canvas
+    fn clip_row(&mut self, x: usize, y: usize, len: usize) -> Option<&mut [Color]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let start = y * self.width + x;
+        let end = start + usize::min(len, self.width - x);
+        Some(&mut self.pixels[start..end])
+    }
+}
+
+/// Place color over previous color. Transparent previous color is treated as if it's placed over
+/// black background, result is always opaque
+pub(crate) fn blend(prev_color: Color, color: Color) -> Color {
+    let opacity = color.alpha as f32 / 255f32;
+    let rev_opacity = 1f32 - opacity;
+    // Multiplying by opacity of 1 keeps opaque pixels intact, so no branching is needed here
+    let prev_color = prev_color * (prev_color.alpha as f32 / 255f32);
+    Color {
+        red: (color.red as f32 * opacity + prev_color.red as f32 * rev_opacity) as u8,
+        green: (color.green as f32 * opacity + prev_color.green as f32 * rev_opacity) as u8,
+        blue: (color.blue as f32 * opacity + prev_color.blue as f32 * rev_opacity) as u8,
+        alpha: 255,
+    }
+}
+
+/// Place color over another one, keeping translucency of result
+pub(crate) fn over(color: Color, below: Color) -> Color {
+    let alpha = color.alpha as f32 / 255f32;
+    let below_alpha = below.alpha as f32 / 255f32 * (1f32 - alpha);
+    let result_alpha = alpha + below_alpha;
+    if result_alpha == 0f32 {
+        return below;
+    }
+    let channel = |top: u8, bottom: u8| {
+        ((top as f32 * alpha + bottom as f32 * below_alpha) / result_alpha).round() as u8
+    };
+    Color {
+        red: channel(color.red, below.red),
+        green: channel(color.green, below.green),
+        blue: channel(color.blue, below.blue),
+        alpha: (result_alpha * 255f32).round() as u8,
+    }
+}
+
+impl RenderTarget for Canvas {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            let pixel = &mut self.pixels[y * self.width + x];
+            *pixel = blend(*pixel, color);
+        }
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if let Some(span) = self.clip_row(x, y, len) {
+            if color.alpha == 255 {
+                // Opaque pixels fully replace previous contents
+                for pixel in span.iter_mut() {
+                    *pixel = color;
+                }
+            } else {
+                for pixel in span.iter_mut() {
+                    *pixel = blend(*pixel, color);
+                }
+            }
+        }
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        if let Some(pixels) = self.clip_row(x, y, row.len()) {
+            for (pixel, color) in pixels.iter_mut().zip(row.iter()) {
+                match color {
+                    Some(color) if color.alpha == 255 => *pixel = *color,
+                    Some(color) => *pixel = blend(*pixel, *color),
+                    None => (),
+                }
+            }
+        }
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        if let Some(pixels) = self.clip_row(x, y, row.len()) {
+            let row = &row[..pixels.len()];
+            let mut start = 0;
+            while start < row.len() {
+                // Split row into runs of opaque and translucent pixels
+                let opaque = row[start].alpha == 255;
+                let len = row[start..]
+                    .iter()
+                    .position(|color| (color.alpha == 255) != opaque)
+                    .unwrap_or(row.len() - start);
+                let end = start + len;
+                if opaque {
+                    pixels[start..end].copy_from_slice(&row[start..end]);
+                } else {
+                    Self::blend_run(&mut pixels[start..end], &row[start..end]);
+                }
+                start = end;
+            }
+        }
+    }
+}
+
+/// Target which multiplies alpha of every pixel by given opacity
+pub(crate) struct Faded<'a> {
+    pub target: &'a mut dyn RenderTarget,
+    pub opacity: f32,
+}
+
+impl Faded<'_> {
+    fn fade(&self, color: Color) -> Color {
+        Color {
+            alpha: (color.alpha as f32 * self.opacity).round() as u8,
+            ..color
+        }
+    }
+}
+
+impl RenderTarget for Faded<'_> {
+    fn width(&self) -> usize {
+        self.target.width()
+    }
+
+    fn height(&self) -> usize {
+        self.target.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        let color = self.fade(color);
+        self.target.draw_pixel(x, y, color);
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        let color = self.fade(color);
+        self.target.draw_span(x, y, len, color);
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        let row: Vec<_> = row
+            .iter()
+            .map(|color| color.map(|c| self.fade(c)))
+            .collect();
+        self.target.draw_row(x, y, &row);
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        let row: Vec<_> = row.iter().map(|color| self.fade(*color)).collect();
+        self.target.draw_colors(x, y, &row);
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.target.palette()
+    }
+}
+
+/// Target which passes through only pixels inside of given region
+pub(crate) struct Clipped<'a> {
+    pub target: &'a mut dyn RenderTarget,
+    pub region: Rect,
+}
+
+impl Clipped<'_> {
+    /// Part of row with given length at x, y which is inside region, as offset into row and
+    /// length
+    fn clip(&self, x: usize, y: usize, len: usize) -> Option<(usize, usize)> {
+        let row = Rect::new(x, y, len, 1).intersection(&self.region)?;
+        Some((row.x - x, row.width))
+    }
+}
+
+impl RenderTarget for Clipped<'_> {
+    fn width(&self) -> usize {
+        self.target.width()
+    }
+
+    fn height(&self) -> usize {
+        self.target.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if self.region.contains(x, y) {
+            self.target.draw_pixel(x, y, color);
+        }
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if let Some((offset, len)) = self.clip(x, y, len) {
+            self.target.draw_span(x + offset, y, len, color);
+        }
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        if let Some((offset, len)) = self.clip(x, y, row.len()) {
+            self.target
+                .draw_row(x + offset, y, &row[offset..offset + len]);
+        }
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        if let Some((offset, len)) = self.clip(x, y, row.len()) {
+            self.target
+                .draw_colors(x + offset, y, &row[offset..offset + len]);
+        }
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.target.palette()
+    }
+}
+
+/// Target which resolves palette references against given palette, falling back to the palette
+/// of wrapped target
+pub(crate) struct Themed<'a> {
+    pub target: &'a mut dyn RenderTarget,
+    pub palette: Option<&'a Palette>,
+}
+
+impl RenderTarget for Themed<'_> {
+    fn width(&self) -> usize {
+        self.target.width()
+    }
+
+    fn height(&self) -> usize {
+        self.target.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.target.draw_pixel(x, y, color);
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        self.target.draw_span(x, y, len, color);
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        self.target.draw_row(x, y, row);
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        self.target.draw_colors(x, y, row);
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.palette.or_else(|| self.target.palette())
+    }
+}
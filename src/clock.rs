@@ -0,0 +1,353 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "text")]
+use std::sync::Arc;
+
+#[cfg(feature = "text")]
+use rusttype::Font;
+
+use crate::canvas::over;
+#[cfg(feature = "text")]
+use crate::shape::Caption;
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+
+/// Analog clock with hour ticks, hour and minute hands and optional seconds hand and numerals.
+/// Clock is a square with side `2 * radius + 1` and its center at `(radius, radius)`. Hands move
+/// continuously, e.g. at 6:30 the hour hand is halfway between 6 and 7:
+/// ```
+/// # use linfb::shape::{Clock, Shape};
+/// let mut clock = Clock::new(50)
+///     .face_color((255, 255, 255))
+///     .hand_color((0, 0, 0))
+///     .seconds_hand(false);
+/// // Pixel at given angle clockwise from 12 and distance from center
+/// let at = |angle: f32, distance: f32| {
+///     let (sin, cos) = angle.to_radians().sin_cos();
+///     ((50.5 + distance * sin) as usize, (50.5 - distance * cos) as usize)
+/// };
+/// let color = |clock: &Clock, (x, y): (usize, usize)| clock.render()[y][x];
+/// let (hand, face) = (Some((0, 0, 0).into()), Some((255, 255, 255).into()));
+///
+/// clock.set_time(3, 0, 0);
+/// assert_eq!(clock.hand_angles(), (90.0, 0.0, 0.0));
+/// assert_eq!(color(&clock, at(90.0, 20.0)), hand);
+/// assert_eq!(color(&clock, at(0.0, 35.0)), hand);
+/// // Hour hand is shorter than minute hand
+/// assert_eq!(color(&clock, at(90.0, 35.0)), face);
+///
+/// clock.set_time(6, 30, 0);
+/// assert_eq!(clock.hand_angles(), (195.0, 180.0, 0.0));
+/// assert_eq!(color(&clock, at(195.0, 20.0)), hand);
+/// assert_eq!(color(&clock, at(180.0, 35.0)), hand);
+/// assert_eq!(color(&clock, at(90.0, 20.0)), face);
+///
+/// clock.set_time(12, 0, 0);
+/// assert_eq!(clock.hand_angles(), (0.0, 0.0, 0.0));
+/// assert_eq!(color(&clock, at(0.0, 20.0)), hand);
+/// assert_eq!(color(&clock, at(0.0, 35.0)), hand);
+/// assert_eq!(color(&clock, at(180.0, 20.0)), face);
+///
+/// // Corners are outside of face
+/// assert_eq!(clock.render()[0][0], None);
+/// assert_eq!(clock.dimensions(), (101, 101));
+/// ```
+///
+/// Seconds hand is enabled by default and drawn over other hands:
+/// ```
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # use linfb::shape::{Clock, Shape};
+/// let mut clock = Clock::new(50).second_hand_color((255, 0, 0));
+/// clock.set_now(UNIX_EPOCH + Duration::from_secs(10 * 3600 + 10 * 60 + 45));
+/// assert_eq!(clock.time(), (10, 10, 45));
+/// assert_eq!(clock.hand_angles(), (305.375, 64.5, 270.0));
+/// assert_eq!(clock.render()[50][10], Some((255, 0, 0).into()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Clock {
+    radius: usize,
+    face_color: Option<Paint>,
+    tick_color: Option<Paint>,
+    hand_color: Option<Paint>,
+    second_hand_color: Option<Paint>,
+    seconds_hand: bool,
+    #[cfg(feature = "text")]
+    numerals: Option<Arc<Font<'static>>>,
+    time: (u32, u32, u32),
+}
+
+impl Clock {
+    /// Create clock with given radius, white face and black ticks and hands, showing 12:00:00
+    pub fn new(radius: usize) -> Self {
+        Self {
+            radius,
+            face_color: Some((255, 255, 255).into()),
+            tick_color: Some((0, 0, 0).into()),
+            hand_color: Some((0, 0, 0).into()),
+            second_hand_color: Some((255, 0, 0).into()),
+            seconds_hand: true,
+            #[cfg(feature = "text")]
+            numerals: None,
+            time: (0, 0, 0),
+        }
+    }
+
+    /// Set color of clock face
+    pub fn face_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.face_color = Some(color.into());
+        self
+    }
+
+    /// Set color of hour ticks and numerals
+    pub fn tick_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.tick_color = Some(color.into());
+        self
+    }
+
+    /// Set color of hour and minute hands
+    pub fn hand_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.hand_color = Some(color.into());
+        self
+    }
+
+    /// Set color of seconds hand
+    pub fn second_hand_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.second_hand_color = Some(color.into());
+        self
+    }
+
+    /// Show or hide seconds hand
+    pub fn seconds_hand(mut self, enabled: bool) -> Self {
+        self.seconds_hand = enabled;
+        self
+    }
+
+    /// Draw hour numerals with given font, built with
+    /// [`FontBuilder`](crate::shape::FontBuilder)
+    /// ```
+    /// # use linfb::shape::{Clock, FontBuilder, Shape};
+    /// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+    /// let plain = Clock::new(50).seconds_hand(false);
+    /// let with_numerals = Clock::new(50).seconds_hand(false).numerals(font);
+    /// let differs = |x: usize, y: usize| plain.render()[y][x] != with_numerals.render()[y][x];
+    /// // Ink around "12" and "6", nothing changes at the center
+    /// assert!((40..60).any(|x| (10..25).any(|y| differs(x, y))));
+    /// assert!((40..60).any(|x| (76..90).any(|y| differs(x, y))));
+    /// assert!(!differs(50, 50));
+    /// ```
+    #[cfg(feature = "text")]
+    pub fn numerals<F: Into<Arc<Font<'static>>>>(mut self, font: F) -> Self {
+        self.numerals = Some(font.into());
+        self
+    }
+
+    /// Set displayed time. Hours are taken modulo 12, overflowing minutes and seconds are carried
+    pub fn set_time(&mut self, hour: u32, minute: u32, second: u32) {
+        let total = (hour % 12) * 3600 + minute * 60 + second;
+        self.time = (total / 3600 % 12, total / 60 % 60, total % 60);
+    }
+
+    /// Set displayed time from system clock time, in UTC. Add an offset to `now` to show local
+    /// time
+    pub fn set_now(&mut self, now: SystemTime) {
+        let seconds = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() % 86400)
+            .unwrap_or(0) as u32;
+        self.set_time(seconds / 3600, seconds / 60 % 60, seconds % 60);
+    }
+
+    /// Displayed time as (hour, minute, second), hour is from 0 to 11
+    pub fn time(&self) -> (u32, u32, u32) {
+        self.time
+    }
+
+    /// Angles of hour, minute and seconds hands in degrees, clockwise from 12
+    pub fn hand_angles(&self) -> (f32, f32, f32) {
+        let (hour, minute, second) = self.time;
+        let (hour, minute, second) = (hour as f32, minute as f32, second as f32);
+        (
+            (hour + minute / 60f32 + second / 3600f32) * 30f32,
+            (minute + second / 60f32) * 6f32,
+            second * 6f32,
+        )
+    }
+
+    #[cfg(feature = "text")]
+    fn draw_numerals(&self, grid: &mut [Vec<Option<Color>>], color: Color) {
+        let font = match &self.numerals {
+            Some(font) => font,
+            None => return,
+        };
+        let radius = self.radius as f32;
+        let center = radius + 0.5;
+        for hour in 1..=12 {
+            let caption = Caption::builder()
+                .text(hour.to_string())
+                .size(u32::max((radius * 0.22).round() as u32, 6))
+                .font(font.clone())
+                .color(color)
+                .build()
+                .expect("all required fields of caption are set");
+            let rendered = caption.render();
+            let (width, height) = (
+                rendered.first().map(Vec::len).unwrap_or(0) as f32,
+                rendered.len() as f32,
+            );
+            let (sin, cos) = (hour as f32 * 30f32).to_radians().sin_cos();
+            let left = center + radius * 0.68 * sin - width / 2f32;
+            let top = center - radius * 0.68 * cos - height / 2f32;
+            if left < 0f32 || top < 0f32 {
+                continue;
+            }
+            for (inner_y, row) in rendered.iter().enumerate() {
+                for (inner_x, pixel) in row.iter().enumerate() {
+                    let (x, y) = (left as usize + inner_x, top as usize + inner_y);
+                    if let (Some(color), Some(target)) =
+                        (pixel, grid.get_mut(y).and_then(|row| row.get_mut(x)))
+                    {
+                        *target = Some(target.map_or(*color, |below| over(*color, below)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Distance from point to segment between `from` and `to`
+fn distance_to_segment(point: (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0f32 {
+        0f32
+    } else {
+        (((point.0 - from.0) * dx + (point.1 - from.1) * dy) / length).clamp(0f32, 1f32)
+    };
+    let (x, y) = (from.0 + t * dx, from.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+/// Radial segment of clock as (from, to, width)
+type Segment = ((f32, f32), (f32, f32), f32);
+
+impl Shape for Clock {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let size = 2 * self.radius + 1;
+        let radius = self.radius as f32;
+        let center = radius + 0.5;
+        // Segment going from `inner` to `outer` radius at given angle
+        let radial = |angle: f32, inner: f32, outer: f32, width: f32| -> Segment {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            (
+                (center + inner * sin, center - inner * cos),
+                (center + outer * sin, center - outer * cos),
+                f32::max(width, 1f32),
+            )
+        };
+
+        let resolve =
+            |paint: &Option<Paint>| paint.as_ref().and_then(|paint| paint.resolve(palette));
+        let face_color = resolve(&self.face_color);
+        let tick_color = resolve(&self.tick_color);
+        let hand_color = resolve(&self.hand_color);
+        let second_hand_color = resolve(&self.second_hand_color);
+
+        let ticks: Vec<Segment> = (0..12)
+            .map(|hour| {
+                radial(
+                    hour as f32 * 30f32,
+                    radius * 0.85,
+                    radius * 0.98,
+                    radius * 0.04,
+                )
+            })
+            .collect();
+        let (hour, minute, second) = self.hand_angles();
+        let hands = [
+            radial(hour, 0f32, radius * 0.5, radius * 0.08),
+            radial(minute, 0f32, radius * 0.75, radius * 0.05),
+        ];
+        let seconds: Vec<Segment> = Some(radial(second, 0f32, radius * 0.9, radius * 0.02))
+            .filter(|_| self.seconds_hand)
+            .into_iter()
+            .collect();
+        let hits = |segments: &[Segment], point| {
+            segments
+                .iter()
+                .any(|&(from, to, width)| distance_to_segment(point, from, to) <= width / 2f32)
+        };
+        let layer = |pixel: Option<Color>, color: Option<Color>| match (pixel, color) {
+            (Some(below), Some(color)) => Some(over(color, below)),
+            (None, color) => color,
+            (pixel, None) => pixel,
+        };
+
+        let mut grid: Vec<Vec<Option<Color>>> = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| {
+                        let point = (x as f32 + 0.5, y as f32 + 0.5);
+                        let distance =
+                            ((point.0 - center).powi(2) + (point.1 - center).powi(2)).sqrt();
+                        let mut pixel = None;
+                        if distance <= radius + 0.5 {
+                            pixel = face_color;
+                        }
+                        if hits(&ticks, point) {
+                            pixel = layer(pixel, tick_color);
+                        }
+                        pixel
+                    })
+                    .collect()
+            })
+            .collect();
+
+        #[cfg(feature = "text")]
+        {
+            if let Some(color) = tick_color {
+                self.draw_numerals(&mut grid, color);
+            }
+        }
+
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let point = (x as f32 + 0.5, y as f32 + 0.5);
+                if hits(&hands, point) {
+                    *pixel = layer(*pixel, hand_color);
+                }
+                if hits(&seconds, point) {
+                    *pixel = layer(*pixel, second_hand_color);
+                }
+            }
+        }
+        grid
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let size = 2 * self.radius + 1;
+        (size, size)
+    }
+
+    fn content_hash(&self) -> u64 {
+        #[cfg(feature = "text")]
+        let numerals = self
+            .numerals
+            .as_ref()
+            .map(|font| Arc::as_ptr(font) as usize);
+        #[cfg(not(feature = "text"))]
+        let numerals: Option<usize> = None;
+        hash_of(&(
+            self.radius,
+            &self.face_color,
+            &self.tick_color,
+            &self.hand_color,
+            &self.second_hand_color,
+            self.seconds_hand,
+            numerals,
+            self.time,
+        ))
+    }
+}
@@ -1,8 +1,44 @@
-use crate::shape::{Color, PositionedShape, Shape};
-use derive_builder::Builder;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::canvas::{Canvas, Clipped, Faded, Rect, RenderTarget, Themed};
+use crate::error::{Error, Result};
+use crate::shape::{hash_of, Color, Palette, PositionedShape, Shape};
+#[cfg(feature = "framebuffer")]
+use crate::Framebuffer;
 
 /// Shape that can contain other shapes. Can deal with transparency and overlaps.
-#[derive(Builder)]
+///
+/// [`Display`](fmt::Display) output is a short summary of scene with one line per shape, from
+/// bottom to top. Positions are the actual ones, with anchors and safe area applied:
+/// ```
+/// # use linfb::Compositor;
+/// # use linfb::shape::{Anchor, Rectangle, Shape};
+/// # let square = |size| Rectangle::builder()
+/// #     .width(size)
+/// #     .height(size)
+/// #     .build()
+/// #     .unwrap();
+/// let mut compositor = Compositor::new(100, 80, (0, 0, 0).into());
+/// compositor
+///     .add("background", square(100).at(0, 0))
+///     .add("logo", square(10).at(5, 5).anchored(Anchor::BottomRight));
+/// compositor.get_positioned("background").unwrap().opacity = 0.5;
+/// compositor.get_positioned("logo").unwrap().visible = false;
+/// assert_eq!(
+///     compositor.to_string(),
+///     "Compositor 100x80, background #000000ff\n\
+///      background: Rectangle 100x100 at (0, 0), opacity 0.5\n\
+///      logo: Rectangle 10x10 at (85, 65), hidden\n",
+/// );
+/// let debug = format!("{:?}", compositor);
+/// assert!(debug.contains("\"logo\""));
+/// assert!(debug.contains("anchor: Some(BottomRight)"));
+/// ```
+#[derive(Clone, Debug)]
 pub struct Compositor {
     /// Width of compositor in pixels
     pub width: usize,
@@ -11,10 +47,155 @@ pub struct Compositor {
     /// Background color. Transparent backgrounds will be treated as if they're placed over black
     /// background
     pub background: Color,
-    #[builder(setter(skip))]
     shapes: Vec<(String, PositionedShape)>,
+    /// Unique ids of shapes, in the same order as shapes
+    ids: Vec<u64>,
+    /// Position of shape in `shapes` by its id
+    index: HashMap<u64, usize>,
+    palette: Option<Arc<Palette>>,
+    safe_area: (usize, usize, usize, usize),
+    show_safe_area: bool,
+    apply_to_absolute: bool,
+}
+
+/// Builder of [`Compositor`], which allows to describe the whole scene in one expression:
+/// ```
+/// # #[cfg(feature = "framebuffer")] {
+/// # use linfb::{Compositor, Framebuffer};
+/// # use linfb::shape::{Rectangle, Shape};
+/// # use linfb::sys::fb_var_screeninfo;
+/// let screen_info = fb_var_screeninfo::argb8888(40, 30);
+/// let square = |size, color: (u8, u8, u8)| {
+///     Rectangle::builder()
+///         .width(size)
+///         .height(size)
+///         .fill_color(color)
+///         .build()
+///         .unwrap()
+/// };
+/// let framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+/// let built = Compositor::builder()
+///     .size_of(&framebuffer)
+///     .with_shape("red", square(20, (255, 0, 0)).at(5, 5))
+///     .with_shape("green", square(10, (0, 255, 0)).at(15, 10))
+///     .build()
+///     .unwrap();
+///
+/// let mut imperative = Compositor::new(40, 30, (0, 0, 0).into());
+/// imperative
+///     .add("red", square(20, (255, 0, 0)).at(5, 5))
+///     .add("green", square(10, (0, 255, 0)).at(15, 10));
+/// assert_eq!(built.render(), imperative.render());
+/// # }
+/// ```
+///
+/// Size is required and must be nonzero:
+/// ```
+/// # use linfb::{Compositor, Error};
+/// assert!(matches!(
+///     Compositor::builder().width(10).build(),
+///     Err(Error::UninitializedField("height")),
+/// ));
+/// assert!(matches!(
+///     Compositor::builder().width(10).height(0).build(),
+///     Err(Error::ZeroSize(10, 0)),
+/// ));
+/// ```
+#[derive(Default)]
+pub struct CompositorBuilder {
+    width: Option<usize>,
+    height: Option<usize>,
+    background: Option<Color>,
+    shapes: Vec<(String, PositionedShape)>,
+}
+
+impl CompositorBuilder {
+    /// Width of compositor in pixels
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Height of compositor in pixels
+    pub fn height(mut self, height: usize) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Use size of framebuffer screen
+    #[cfg(feature = "framebuffer")]
+    pub fn size_of(self, framebuffer: &Framebuffer) -> Self {
+        self.width(framebuffer.width()).height(framebuffer.height())
+    }
+
+    /// Background color, opaque black by default. See [`Compositor::background`]
+    pub fn background<C: Into<Color>>(mut self, background: C) -> Self {
+        self.background = Some(background.into());
+        self
+    }
+
+    /// Add a shape, as [`Compositor::add`] does. Shapes are added in the order of calls
+    pub fn with_shape(mut self, name: &str, shape: PositionedShape) -> Self {
+        self.shapes.push((name.into(), shape));
+        self
+    }
+
+    /// Build [`Compositor`]. Returns an error if width or height is not set or zero
+    pub fn build(self) -> Result<Compositor> {
+        let width = self.width.ok_or(Error::UninitializedField("width"))?;
+        let height = self.height.ok_or(Error::UninitializedField("height"))?;
+        if width == 0 || height == 0 {
+            return Err(Error::ZeroSize(width, height));
+        }
+        let mut compositor = Compositor::new(
+            width,
+            height,
+            self.background.unwrap_or_else(|| (0, 0, 0).into()),
+        );
+        for (name, shape) in self.shapes {
+            compositor.add(&name, shape);
+        }
+        Ok(compositor)
+    }
+}
+
+/// Typed reference to shape of [`Compositor`], returned by [`Compositor::add_handled`]. Unlike
+/// names, handles always point to the same shape and become stale when it's removed
+pub struct ShapeHandle<T> {
+    id: u64,
+    shape: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ShapeHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ShapeHandle<T> {}
+
+impl<T> PartialEq for ShapeHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for ShapeHandle<T> {}
+
+impl<T> fmt::Debug for ShapeHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ShapeHandle").field(&self.id).finish()
+    }
 }
 
+/// Color of the safe area outline drawn by [`Compositor::show_safe_area`]
+const SAFE_AREA_COLOR: Color = Color {
+    red: 255,
+    green: 0,
+    blue: 255,
+    alpha: 255,
+};
+
 impl Compositor {
     /// Create empty compositor with given size and background
     pub fn new(width: usize, height: usize, background: Color) -> Self {
@@ -23,6 +204,12 @@ impl Compositor {
             height,
             background,
             shapes: Vec::new(),
+            ids: Vec::new(),
+            index: HashMap::new(),
+            palette: None,
+            safe_area: (0, 0, 0, 0),
+            show_safe_area: false,
+            apply_to_absolute: false,
         }
     }
 
@@ -36,10 +223,103 @@ impl Compositor {
     ///
     /// Uniqueness of names is not enforced, but recommended
     pub fn add(&mut self, name: &str, shape: PositionedShape) -> &mut Self {
-        self.shapes.push((name.into(), shape));
+        self.push(name, shape);
         self
     }
 
+    fn push(&mut self, name: &str, shape: PositionedShape) -> u64 {
+        // Ids are never reused, even by other compositors, so stale handles can't point to other
+        // shapes
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        self.index.insert(id, self.shapes.len());
+        self.shapes.push((name.into(), shape));
+        self.ids.push(id);
+        id
+    }
+
+    /// Same as [`Compositor::add`], but returns a [`ShapeHandle`] which gives access to inner
+    /// shape of type `T` without looking it up by name:
+    /// ```
+    /// # use linfb::{Compositor, ShapeHandle};
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// # let square = |size| Rectangle::builder()
+    /// #     .width(size)
+    /// #     .height(size)
+    /// #     .fill_color((255, 255, 255))
+    /// #     .build()
+    /// #     .unwrap();
+    /// let mut compositor = Compositor::new(100, 100, (0, 0, 0).into());
+    /// compositor.add("first", square(10).at(0, 0));
+    /// let handle: ShapeHandle<Rectangle> = compositor.add_handled("second", square(20).at(5, 5));
+    ///
+    /// compositor.resolve(&handle).unwrap().width = 30;
+    /// compositor.resolve_positioned(&handle).unwrap().x = 50;
+    /// // String API sees the same shape
+    /// assert_eq!(compositor.get::<Rectangle>("second").unwrap().width, 30);
+    /// assert_eq!(compositor.find_at(60, 10), Some("second"));
+    ///
+    /// // Handle is stale after removal, even if another shape with the same name is added
+    /// assert!(compositor.remove("first").is_some());
+    /// assert_eq!(compositor.resolve(&handle).unwrap().width, 30);
+    /// assert!(compositor.remove_handled(&handle).is_some());
+    /// compositor.add_handled::<Rectangle>("second", square(20).at(5, 5));
+    /// assert!(compositor.resolve(&handle).is_none());
+    /// assert!(compositor.resolve_positioned(&handle).is_none());
+    /// assert!(compositor.remove_handled(&handle).is_none());
+    /// ```
+    ///
+    /// If inner shape is not of type `T`, handle never resolves to it.
+    pub fn add_handled<T: Shape>(&mut self, name: &str, shape: PositionedShape) -> ShapeHandle<T> {
+        ShapeHandle {
+            id: self.push(name, shape),
+            shape: PhantomData,
+        }
+    }
+
+    /// Get [`PositionedShape`] added with [`Compositor::add_handled`]. Will return [`None`] if
+    /// shape was removed
+    pub fn resolve_positioned<T: Shape>(
+        &mut self,
+        handle: &ShapeHandle<T>,
+    ) -> Option<&mut PositionedShape> {
+        let position = *self.index.get(&handle.id)?;
+        Some(&mut self.shapes[position].1)
+    }
+
+    /// Get inner shape added with [`Compositor::add_handled`]. Will return [`None`] if shape was
+    /// removed
+    pub fn resolve<T: Shape>(&mut self, handle: &ShapeHandle<T>) -> Option<&mut T> {
+        self.resolve_positioned(handle)
+            .and_then(|shape| shape.inner_mut::<T>())
+    }
+
+    /// Remove the first shape with given name and return it
+    pub fn remove(&mut self, name: &str) -> Option<PositionedShape> {
+        let position = self
+            .shapes
+            .iter()
+            .position(|(curr_name, _)| curr_name == name)?;
+        Some(self.remove_at(position))
+    }
+
+    /// Remove shape added with [`Compositor::add_handled`] and return it. Will return [`None`] if
+    /// shape was already removed
+    pub fn remove_handled<T: Shape>(&mut self, handle: &ShapeHandle<T>) -> Option<PositionedShape> {
+        let position = *self.index.get(&handle.id)?;
+        Some(self.remove_at(position))
+    }
+
+    fn remove_at(&mut self, position: usize) -> PositionedShape {
+        let (_name, shape) = self.shapes.remove(position);
+        let id = self.ids.remove(position);
+        self.index.remove(&id);
+        for (i, id) in self.ids.iter().enumerate().skip(position) {
+            self.index.insert(*id, i);
+        }
+        shape
+    }
+
     /// Get a previously added [`PositionedShape`] by it's name. Will return [`None`] if shape
     /// with such name was never added.
     pub fn get_positioned(&mut self, name: &str) -> Option<&mut PositionedShape> {
@@ -67,46 +347,532 @@ impl Compositor {
         self.get_positioned(name)
             .and_then(|shape| shape.inner_mut::<T>())
     }
-}
 
-impl Shape for Compositor {
-    fn render(&self) -> Vec<Vec<Option<Color>>> {
-        let mut result = vec![vec![Some(self.background); self.width]; self.height];
-        for (_name, shape) in &self.shapes {
-            for (y, row) in shape.shape.render().iter().enumerate() {
-                for (x, color) in row.iter().enumerate() {
-                    let real_x = shape.x + x;
-                    let real_y = shape.y + y;
-                    if real_y >= result.len() || real_x >= result[real_y].len() {
+    /// Install [`Palette`] which is used to resolve [`PaletteRef`](crate::shape::PaletteRef)s of
+    /// shapes, replacing the previous one. Compositor without palette uses palette of
+    /// [`RenderTarget`] it's drawn onto, so nested compositors use palette of their parent
+    pub fn set_palette(&mut self, palette: Palette) -> &mut Self {
+        self.palette = Some(Arc::new(palette));
+        self
+    }
+
+    /// Currently installed [`Palette`]
+    pub fn palette(&self) -> Option<&Palette> {
+        self.palette.as_deref()
+    }
+
+    /// Get name of the topmost visible shape at x, y, i.e. the last added one. Shapes are checked
+    /// with [`PositionedShape::hit_test`], so transparent parts of shapes are not matched. Will
+    /// return [`None`] if there are no shapes at this point.
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// # let square = |size| Rectangle::builder()
+    /// #     .width(size)
+    /// #     .height(size)
+    /// #     .fill_color((255, 0, 0))
+    /// #     .build()
+    /// #     .unwrap();
+    /// let mut compositor = Compositor::new(100, 100, (0, 0, 0).into());
+    /// compositor
+    ///     .add("bottom", square(50).at(0, 0))
+    ///     .add("middle", square(30).at(10, 10))
+    ///     .add("top", square(10).at(20, 20));
+    /// assert_eq!(compositor.find_at(25, 25), Some("top"));
+    /// assert_eq!(compositor.shapes_at(25, 25), vec!["top", "middle", "bottom"]);
+    /// assert_eq!(compositor.shapes_at(35, 35), vec!["middle", "bottom"]);
+    /// assert_eq!(compositor.find_at(60, 60), None);
+    ///
+    /// compositor.get_positioned("top").unwrap().visible = false;
+    /// assert_eq!(compositor.find_at(25, 25), Some("middle"));
+    /// ```
+    pub fn find_at(&self, x: usize, y: usize) -> Option<&str> {
+        self.shapes_at_iter(x, y).next()
+    }
+
+    /// Get names of all visible shapes at x, y, from top to bottom. See [`Compositor::find_at`]
+    pub fn shapes_at(&self, x: usize, y: usize) -> Vec<&str> {
+        self.shapes_at_iter(x, y).collect()
+    }
+
+    fn shapes_at_iter(&self, x: usize, y: usize) -> impl Iterator<Item = &str> {
+        self.shapes
+            .iter()
+            .rev()
+            .filter(move |(_name, shape)| {
+                let (shape_x, shape_y) = self.position_of(shape);
+                shape.visible
+                    && x >= shape_x
+                    && y >= shape_y
+                    && shape.shape.contains(x - shape_x, y - shape_y)
+            })
+            .map(|(name, _shape)| name.as_str())
+    }
+
+    /// Set margins (top, right, bottom, left) of safe area, e.g. to compensate TV overscan.
+    /// [Anchored](PositionedShape::anchored) shapes are placed relative to the safe area and kept
+    /// inside of it:
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{Anchor, Rectangle, Shape};
+    /// let mut compositor = Compositor::new(100, 80, (0, 0, 0).into());
+    /// compositor.set_safe_area((4, 5, 4, 5));
+    /// compositor.add("logo", Rectangle::builder()
+    ///     .width(10)
+    ///     .height(10)
+    ///     .border_width(0)
+    ///     .fill_color((255, 255, 255))
+    ///     .build()
+    ///     .unwrap()
+    ///     .at(0, 0)
+    ///     .anchored(Anchor::BottomRight));
+    /// let rendered = compositor.render();
+    /// assert_eq!(rendered[75][94], Some((255, 255, 255).into()));
+    /// assert_eq!(rendered[76][95], Some((0, 0, 0).into()));
+    /// assert_eq!(compositor.find_at(85, 66), Some("logo"));
+    ///
+    /// // Offsets point inwards and can't move the shape out of the safe area
+    /// compositor.get_positioned("logo").unwrap().x = 1000;
+    /// assert_eq!(compositor.find_at(5, 66), Some("logo"));
+    /// ```
+    pub fn set_safe_area(&mut self, margins: (usize, usize, usize, usize)) -> &mut Self {
+        self.safe_area = margins;
+        self
+    }
+
+    /// Get safe area, i.e. the compositor without margins set with [`Compositor::set_safe_area`]
+    pub fn safe_area(&self) -> Rect {
+        let (top, right, bottom, left) = self.safe_area;
+        Rect::new(
+            left,
+            top,
+            self.width.saturating_sub(left + right),
+            self.height.saturating_sub(top + bottom),
+        )
+    }
+
+    /// Draw a thin outline of safe area over all shapes, so margins can be calibrated on a real
+    /// screen
+    pub fn show_safe_area(&mut self, show: bool) -> &mut Self {
+        self.show_safe_area = show;
+        self
+    }
+
+    /// Offset shapes which are not anchored by the top left safe area margins. Default is `false`
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let mut compositor = Compositor::new(20, 20, (0, 0, 0).into());
+    /// compositor.set_safe_area((2, 0, 0, 3)).apply_to_absolute(true);
+    /// compositor.add("dot", Rectangle::builder()
+    ///     .width(1)
+    ///     .height(1)
+    ///     .border_width(0)
+    ///     .fill_color((255, 255, 255))
+    ///     .build()
+    ///     .unwrap()
+    ///     .at(1, 1));
+    /// assert_eq!(compositor.find_at(4, 3), Some("dot"));
+    /// ```
+    pub fn apply_to_absolute(&mut self, apply: bool) -> &mut Self {
+        self.apply_to_absolute = apply;
+        self
+    }
+
+    /// Actual position of shape in compositor, taking anchors and safe area into account
+    fn position_of(&self, shape: &PositionedShape) -> (usize, usize) {
+        let area = self.safe_area();
+        match shape.anchor {
+            None if self.apply_to_absolute => (area.x + shape.x, area.y + shape.y),
+            None => (shape.x, shape.y),
+            Some(anchor) => {
+                let (width, height) = shape.shape.dimensions();
+                let (horizontal, vertical) = anchor.align();
+                (
+                    horizontal.place(area.x, area.width, width, shape.x),
+                    vertical.place(area.y, area.height, height, shape.y),
+                )
+            }
+        }
+    }
+
+    /// Position and size of shape in compositor
+    fn bounds_of(&self, shape: &PositionedShape) -> Rect {
+        let (x, y) = self.position_of(shape);
+        let (width, height) = shape.shape.dimensions();
+        Rect::new(x, y, width, height)
+    }
+
+    fn draw_safe_area(&self, target: &mut dyn RenderTarget) {
+        if !self.show_safe_area {
+            return;
+        }
+        let area = self.safe_area();
+        if area.is_empty() {
+            return;
+        }
+        let (right, bottom) = (area.x + area.width - 1, area.y + area.height - 1);
+        target.draw_span(area.x, area.y, area.width, SAFE_AREA_COLOR);
+        target.draw_span(area.x, bottom, area.width, SAFE_AREA_COLOR);
+        for y in area.y..=bottom {
+            target.draw_pixel(area.x, y, SAFE_AREA_COLOR);
+            target.draw_pixel(right, y, SAFE_AREA_COLOR);
+        }
+    }
+
+    /// Everything except shapes which affects rendering of the whole compositor
+    fn settings(&self) -> Settings {
+        (
+            self.width,
+            self.height,
+            self.background,
+            self.safe_area,
+            self.show_safe_area,
+            self.apply_to_absolute,
+            self.palette.as_deref().map(hash_of),
+        )
+    }
+
+    /// Render compositor into caller-owned [`Canvas`], reusing its allocation. Canvas is resized
+    /// to the size of compositor and its previous contents are discarded, so the result is the
+    /// same as with [`Shape::render`]. Use it to avoid allocating a new grid on every frame:
+    /// ```
+    /// # use linfb::{Canvas, Compositor};
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let mut canvas = Canvas::default();
+    ///
+    /// let mut first = Compositor::new(10, 10, (0, 0, 0).into());
+    /// first.add("rect", Rectangle::builder()
+    ///     .width(10)
+    ///     .height(10)
+    ///     .fill_color((255, 0, 0))
+    ///     .build()
+    ///     .unwrap()
+    ///     .at(0, 0));
+    /// first.render_into(&mut canvas);
+    ///
+    /// let mut second = Compositor::new(8, 6, (0, 0, 255, 128).into());
+    /// second.add("rect", Rectangle::builder()
+    ///     .width(2)
+    ///     .height(2)
+    ///     .border_width(0)
+    ///     .fill_color((0, 255, 0))
+    ///     .build()
+    ///     .unwrap()
+    ///     .at(3, 3));
+    /// second.render_into(&mut canvas);
+    ///
+    /// assert_eq!(canvas.to_rows(), second.render());
+    /// ```
+    ///
+    /// Note that this method shadows [`Shape::render_into`], use `Shape::render_into(&compositor,
+    /// ...)` to draw compositor onto arbitrary [`RenderTarget`](crate::RenderTarget).
+    pub fn render_into(&self, canvas: &mut Canvas) {
+        self.render_themed(canvas, None);
+    }
+
+    /// Same as [`Compositor::render_into`], but palette of compositor falls back to given one
+    pub(crate) fn render_themed(&self, canvas: &mut Canvas, palette: Option<&Palette>) {
+        canvas.reset(self.width, self.height, self.background);
+        let mut target = Themed {
+            target: canvas,
+            palette: self.palette().or(palette),
+        };
+        for (_name, shape) in self.shapes.iter().filter(|(_name, shape)| shape.visible) {
+            let (x, y) = self.position_of(shape);
+            draw_shape(shape, &mut target, x, y);
+        }
+        self.draw_safe_area(&mut target);
+    }
+
+    /// Re-render only given region of canvas, leaving the rest of it untouched. Shapes which don't
+    /// intersect the region are skipped. If canvas has different size than compositor, it's fully
+    /// re-rendered with [`Compositor::render_into`] instead.
+    /// ```
+    /// # use linfb::{Canvas, Compositor};
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let scene = |x| {
+    ///     let mut compositor = Compositor::new(10, 10, (0, 0, 0).into());
+    ///     compositor.add("rect", Rectangle::builder()
+    ///         .width(4)
+    ///         .height(4)
+    ///         .fill_color((255, 0, 0))
+    ///         .build()
+    ///         .unwrap()
+    ///         .at(x, 3));
+    ///     compositor
+    /// };
+    /// let (before, after) = (scene(0), scene(5));
+    /// let mut canvas = Canvas::default();
+    /// before.render_into(&mut canvas);
+    ///
+    /// for rect in after.diff(&before) {
+    ///     after.render_region(&mut canvas, rect);
+    /// }
+    /// assert_eq!(canvas.to_rows(), after.render());
+    /// ```
+    pub fn render_region(&self, canvas: &mut Canvas, region: Rect) {
+        if (canvas.width(), canvas.height()) != (self.width, self.height) {
+            self.render_into(canvas);
+            return;
+        }
+        let region = match region.intersection(&self.bounds()) {
+            Some(region) => region,
+            None => return,
+        };
+        canvas.fill_rect(region, self.background);
+        let mut clipped = Clipped {
+            target: canvas,
+            region,
+        };
+        let mut target = Themed {
+            target: &mut clipped,
+            palette: self.palette(),
+        };
+        for (_name, shape) in self.shapes.iter().filter(|(_name, shape)| shape.visible) {
+            let bounds = self.bounds_of(shape);
+            if bounds.intersection(&region).is_some() {
+                draw_shape(shape, &mut target, bounds.x, bounds.y);
+            }
+        }
+        self.draw_safe_area(&mut target);
+    }
+
+    /// Find regions which differ between `previous` and this compositor. Shapes are matched by
+    /// name and compared by position, size, visibility, relative z-order and
+    /// [`Shape::content_hash`]. Changed shape damages both its old and new bounds, so only these
+    /// regions need to be re-rendered with [`Compositor::render_region`] and flushed with
+    /// [`Framebuffer::flush_region`](crate::Framebuffer::flush_region). Change of compositor size,
+    /// background or palette damages the whole compositor.
+    /// ```
+    /// # use linfb::{Compositor, Rect};
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let scene = |x| {
+    ///     let mut compositor = Compositor::new(100, 100, (0, 0, 0).into());
+    ///     compositor.add("rect", Rectangle::builder()
+    ///         .width(10)
+    ///         .height(10)
+    ///         .fill_color((255, 0, 0))
+    ///         .build()
+    ///         .unwrap()
+    ///         .at(x, 20));
+    ///     compositor
+    /// };
+    /// assert!(scene(10).diff(&scene(10)).is_empty());
+    /// assert_eq!(
+    ///     scene(50).diff(&scene(10)),
+    ///     vec![Rect::new(10, 20, 10, 10), Rect::new(50, 20, 10, 10)],
+    /// );
+    /// ```
+    pub fn diff(&self, previous: &Compositor) -> Vec<Rect> {
+        self.damage_since(&previous.snapshot())
+    }
+
+    /// Same as [`Compositor::diff`], but against a previously taken [`Snapshot`], so the previous
+    /// compositor doesn't need to be kept around
+    pub(crate) fn damage_since(&self, previous: &Snapshot) -> Vec<Rect> {
+        let bounds = self.bounds();
+        if self.settings() != previous.settings {
+            return Some(bounds)
+                .into_iter()
+                .filter(|rect| !rect.is_empty())
+                .collect();
+        }
+
+        let old = &previous.shapes;
+        let new = self.snapshot().shapes;
+        let old_index: HashMap<_, _> = old.iter().enumerate().map(|(i, s)| (&s.key, i)).collect();
+        let new_index: HashMap<_, _> = new.iter().enumerate().map(|(i, s)| (&s.key, i)).collect();
+        // Z-order is compared between shapes present in both scenes, so adding or removing shape
+        // doesn't damage everything above it
+        let old_order = z_order(old, &new_index);
+        let new_order = z_order(&new, &old_index);
+
+        let mut damage = Vec::new();
+        for (i, state) in new.iter().enumerate() {
+            match old_index.get(&state.key) {
+                Some(&j) => {
+                    let previous = &old[j];
+                    if !state.visible && !previous.visible {
                         continue;
                     }
-
-                    if let Some(color) = color {
-                        let opacity = color.alpha as f32 / 255f32;
-                        let rev_opacity = 1f32 - opacity;
-                        // Can unwrap here because result initialized without None's
-                        let mut prev_color = result[real_y][real_x].unwrap();
-                        if prev_color.alpha != 255 {
-                            prev_color *= (prev_color.alpha as f32) / 255f32;
-                            prev_color.alpha = 255;
-                        }
-                        let new_color = Some(Color {
-                            red: (color.red as f32 * opacity + prev_color.red as f32 * rev_opacity)
-                                as u8,
-                            green: (color.green as f32 * opacity
-                                + prev_color.green as f32 * rev_opacity)
-                                as u8,
-                            blue: (color.blue as f32 * opacity
-                                + prev_color.blue as f32 * rev_opacity)
-                                as u8,
-                            alpha: 255,
-                        });
-
-                        result[real_y][real_x] = new_color;
+                    if state != previous || new_order.get(&i) != old_order.get(&j) {
+                        damage.extend(previous.damage());
+                        damage.extend(state.damage());
                     }
                 }
+                None => damage.extend(state.damage()),
+            }
+        }
+        for state in old.iter().filter(|s| !new_index.contains_key(&s.key)) {
+            damage.extend(state.damage());
+        }
+
+        let mut result: Vec<Rect> = Vec::new();
+        for rect in damage.iter().filter_map(|rect| rect.intersection(&bounds)) {
+            if !result.contains(&rect) {
+                result.push(rect);
             }
         }
         result
     }
+
+    fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// State of compositor used by [`Compositor::diff`]
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        let mut occurrences = HashMap::new();
+        let shapes = self
+            .shapes
+            .iter()
+            .map(|(name, shape)| {
+                // Names are not required to be unique, so n-th shape with some name is matched
+                // with n-th shape with this name in the other scene
+                let occurrence = occurrences.entry(name.as_str()).or_insert(0);
+                *occurrence += 1;
+                ShapeState {
+                    key: (name.clone(), *occurrence),
+                    rect: self.bounds_of(shape),
+                    visible: shape.visible,
+                    opacity: shape.opacity.to_bits(),
+                    hash: shape.shape.content_hash(),
+                }
+            })
+            .collect();
+        Snapshot {
+            settings: self.settings(),
+            shapes,
+        }
+    }
+}
+
+type Settings = (
+    usize,
+    usize,
+    Color,
+    (usize, usize, usize, usize),
+    bool,
+    bool,
+    Option<u64>,
+);
+
+/// State of compositor at some moment, which can be compared with its later state by
+/// [`Compositor::damage_since`]
+pub(crate) struct Snapshot {
+    settings: Settings,
+    shapes: Vec<ShapeState>,
+}
+
+#[derive(PartialEq)]
+struct ShapeState {
+    key: (String, usize),
+    rect: Rect,
+    visible: bool,
+    opacity: u32,
+    hash: u64,
+}
+
+impl ShapeState {
+    fn damage(&self) -> Option<Rect> {
+        Some(self.rect).filter(|rect| self.visible && !rect.is_empty())
+    }
+}
+
+/// Draw shape with its opacity applied. Fully transparent shapes are not drawn at all
+fn draw_shape(shape: &PositionedShape, target: &mut dyn RenderTarget, x: usize, y: usize) {
+    if shape.opacity >= 1f32 {
+        shape.shape.render_into(target, x, y);
+    } else if shape.opacity > 0f32 {
+        let mut target = Faded {
+            target,
+            opacity: shape.opacity,
+        };
+        shape.shape.render_into(&mut target, x, y);
+    }
+}
+
+/// Positions of shapes among shapes which are also present in `other`
+fn z_order(
+    states: &[ShapeState],
+    other: &HashMap<&(String, usize), usize>,
+) -> HashMap<usize, usize> {
+    states
+        .iter()
+        .enumerate()
+        .filter(|(_i, state)| other.contains_key(&state.key))
+        .enumerate()
+        .map(|(order, (i, _state))| (i, order))
+        .collect()
+}
+
+impl fmt::Display for Compositor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Color {
+            red,
+            green,
+            blue,
+            alpha,
+        } = self.background;
+        writeln!(
+            f,
+            "Compositor {}x{}, background #{:02x}{:02x}{:02x}{:02x}",
+            self.width, self.height, red, green, blue, alpha
+        )?;
+        for (name, shape) in self.shapes.iter() {
+            let bounds = self.bounds_of(shape);
+            write!(
+                f,
+                "{}: {} {}x{} at ({}, {})",
+                name,
+                shape.shape.debug_name(),
+                bounds.width,
+                bounds.height,
+                bounds.x,
+                bounds.y
+            )?;
+            if !shape.visible {
+                write!(f, ", hidden")?;
+            }
+            if shape.opacity != 1f32 {
+                write!(f, ", opacity {}", shape.opacity)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Shape for Compositor {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let mut canvas = Canvas::default();
+        self.render_themed(&mut canvas, palette);
+        canvas.to_rows()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        let shapes: Vec<_> = self
+            .shapes
+            .iter()
+            .map(|(name, shape)| {
+                (
+                    name,
+                    self.position_of(shape),
+                    shape.visible,
+                    shape.opacity.to_bits(),
+                    shape.shape.content_hash(),
+                )
+            })
+            .collect();
+        hash_of(&(self.settings(), shapes))
+    }
 }
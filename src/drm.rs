@@ -0,0 +1,309 @@
+//! Minimal DRM interface: just enough of kernel mode setting to show a dumb buffer on the first
+//! connected display. Structures mirror ones from `drm_mode.h`
+#![allow(non_camel_case_types)]
+
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+use memmap::{MmapMut, MmapOptions};
+
+use crate::error::{Error::*, Result};
+use crate::sys::{fb_bitfield, fb_fix_screeninfo, fb_var_screeninfo};
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_card_res {
+    fb_id_ptr: u64,
+    crtc_id_ptr: u64,
+    connector_id_ptr: u64,
+    encoder_id_ptr: u64,
+    count_fbs: u32,
+    count_crtcs: u32,
+    count_connectors: u32,
+    count_encoders: u32,
+    min_width: u32,
+    max_width: u32,
+    min_height: u32,
+    max_height: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+struct drm_mode_modeinfo {
+    clock: u32,
+    hdisplay: u16,
+    hsync_start: u16,
+    hsync_end: u16,
+    htotal: u16,
+    hskew: u16,
+    vdisplay: u16,
+    vsync_start: u16,
+    vsync_end: u16,
+    vtotal: u16,
+    vscan: u16,
+    vrefresh: u32,
+    flags: u32,
+    type_: u32,
+    name: [u8; 32],
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_get_connector {
+    encoders_ptr: u64,
+    modes_ptr: u64,
+    props_ptr: u64,
+    prop_values_ptr: u64,
+    count_modes: u32,
+    count_props: u32,
+    count_encoders: u32,
+    encoder_id: u32,
+    connector_id: u32,
+    connector_type: u32,
+    connector_type_id: u32,
+    connection: u32,
+    mm_width: u32,
+    mm_height: u32,
+    subpixel: u32,
+    pad: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_get_encoder {
+    encoder_id: u32,
+    encoder_type: u32,
+    crtc_id: u32,
+    possible_crtcs: u32,
+    possible_clones: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_crtc {
+    set_connectors_ptr: u64,
+    count_connectors: u32,
+    crtc_id: u32,
+    fb_id: u32,
+    x: u32,
+    y: u32,
+    gamma_size: u32,
+    mode_valid: u32,
+    mode: drm_mode_modeinfo,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_create_dumb {
+    height: u32,
+    width: u32,
+    bpp: u32,
+    flags: u32,
+    handle: u32,
+    pitch: u32,
+    size: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_map_dumb {
+    handle: u32,
+    pad: u32,
+    offset: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_fb_cmd {
+    fb_id: u32,
+    width: u32,
+    height: u32,
+    pitch: u32,
+    bpp: u32,
+    depth: u32,
+    handle: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct drm_mode_fb_dirty_cmd {
+    fb_id: u32,
+    flags: u32,
+    color: u32,
+    num_clips: u32,
+    clips_ptr: u64,
+}
+
+/// Same as [`nix::ioctl_readwrite`], but generated function is private. Functions generated by nix
+/// are `pub`, which Rust 1.73 doesn't allow with private argument structs
+macro_rules! ioctl_readwrite {
+    ($name:ident, $ioty:expr, $nr:expr, $ty:ty) => {
+        unsafe fn $name(fd: nix::libc::c_int, data: *mut $ty) -> nix::Result<nix::libc::c_int> {
+            let request = nix::request_code_readwrite!($ioty, $nr, std::mem::size_of::<$ty>());
+            nix::errno::Errno::result(nix::libc::ioctl(fd, request as _, data))
+        }
+    };
+}
+
+ioctl_readwrite!(get_resources, b'd', 0xA0, drm_mode_card_res);
+ioctl_readwrite!(set_crtc, b'd', 0xA2, drm_mode_crtc);
+ioctl_readwrite!(get_encoder, b'd', 0xA6, drm_mode_get_encoder);
+ioctl_readwrite!(get_connector, b'd', 0xA7, drm_mode_get_connector);
+ioctl_readwrite!(add_fb, b'd', 0xAE, drm_mode_fb_cmd);
+ioctl_readwrite!(dirty_fb, b'd', 0xB1, drm_mode_fb_dirty_cmd);
+ioctl_readwrite!(create_dumb, b'd', 0xB2, drm_mode_create_dumb);
+ioctl_readwrite!(map_dumb, b'd', 0xB3, drm_mode_map_dumb);
+
+/// Value of `connection` of connected connector
+const CONNECTED: u32 = 1;
+
+/// Dumb buffer shown on display, as memory, screen info synthesized from display mode and id of
+/// DRM framebuffer
+pub(crate) type DumbBuffer = (MmapMut, fb_var_screeninfo, fb_fix_screeninfo, u32);
+
+/// Create XRGB8888 dumb buffer with preferred mode of the first connected display, map it and
+/// show it on display
+pub(crate) fn open_dumb_buffer(file: &File) -> Result<DumbBuffer> {
+    let fd = file.as_raw_fd();
+
+    let mut resources = drm_mode_card_res::default();
+    unsafe { get_resources(fd, &mut resources).map_err(Ioctl)? };
+    let mut crtcs = vec![0u32; resources.count_crtcs as usize];
+    let mut connectors = vec![0u32; resources.count_connectors as usize];
+    resources = drm_mode_card_res {
+        crtc_id_ptr: crtcs.as_mut_ptr() as u64,
+        connector_id_ptr: connectors.as_mut_ptr() as u64,
+        count_crtcs: crtcs.len() as u32,
+        count_connectors: connectors.len() as u32,
+        ..Default::default()
+    };
+    unsafe { get_resources(fd, &mut resources).map_err(Ioctl)? };
+    crtcs.truncate(resources.count_crtcs as usize);
+    connectors.truncate(resources.count_connectors as usize);
+
+    let (connector, mode) = connectors
+        .iter()
+        .find_map(|&id| connected_mode(fd, id))
+        .ok_or(NoConnectedDisplay)?;
+
+    let mut encoder = drm_mode_get_encoder {
+        encoder_id: connector.encoder_id,
+        ..Default::default()
+    };
+    let crtc_id = if connector.encoder_id != 0
+        && unsafe { get_encoder(fd, &mut encoder) }.is_ok()
+        && encoder.crtc_id != 0
+    {
+        encoder.crtc_id
+    } else {
+        // Display is not lit yet, take any CRTC which can drive it
+        crtcs
+            .iter()
+            .enumerate()
+            .find(|(i, _)| encoder.possible_crtcs & (1 << i) != 0)
+            .or_else(|| crtcs.iter().enumerate().next())
+            .map(|(_, &id)| id)
+            .ok_or(NoConnectedDisplay)?
+    };
+
+    let (width, height) = (mode.hdisplay as u32, mode.vdisplay as u32);
+    let mut dumb = drm_mode_create_dumb {
+        width,
+        height,
+        bpp: 32,
+        ..Default::default()
+    };
+    unsafe { create_dumb(fd, &mut dumb).map_err(Ioctl)? };
+    let mut fb = drm_mode_fb_cmd {
+        width,
+        height,
+        pitch: dumb.pitch,
+        bpp: 32,
+        depth: 24,
+        handle: dumb.handle,
+        ..Default::default()
+    };
+    unsafe { add_fb(fd, &mut fb).map_err(Ioctl)? };
+    let mut map = drm_mode_map_dumb {
+        handle: dumb.handle,
+        ..Default::default()
+    };
+    unsafe { map_dumb(fd, &mut map).map_err(Ioctl)? };
+    let memory = unsafe {
+        MmapOptions::new()
+            .offset(map.offset)
+            .len(dumb.size as usize)
+            .map_mut(file)?
+    };
+
+    let mut connector_id = connector.connector_id;
+    let mut crtc = drm_mode_crtc {
+        set_connectors_ptr: &mut connector_id as *mut u32 as u64,
+        count_connectors: 1,
+        crtc_id,
+        fb_id: fb.fb_id,
+        mode_valid: 1,
+        mode,
+        ..Default::default()
+    };
+    unsafe { set_crtc(fd, &mut crtc).map_err(Ioctl)? };
+
+    let mut screen_info = fb_var_screeninfo::new(
+        width,
+        height,
+        32,
+        fb_bitfield::new(16, 8),
+        fb_bitfield::new(8, 8),
+        fb_bitfield::new(0, 8),
+        fb_bitfield::new(0, 0),
+    );
+    screen_info.width = connector.mm_width;
+    screen_info.height = connector.mm_height;
+    // Timings are converted to fbdev ones, so refresh rate can be computed the same way
+    screen_info.pixclock = 1_000_000_000u32.checked_div(mode.clock).unwrap_or(0);
+    screen_info.right_margin = mode.hsync_start.saturating_sub(mode.hdisplay) as u32;
+    screen_info.hsync_len = mode.hsync_end.saturating_sub(mode.hsync_start) as u32;
+    screen_info.left_margin = mode.htotal.saturating_sub(mode.hsync_end) as u32;
+    screen_info.lower_margin = mode.vsync_start.saturating_sub(mode.vdisplay) as u32;
+    screen_info.vsync_len = mode.vsync_end.saturating_sub(mode.vsync_start) as u32;
+    screen_info.upper_margin = mode.vtotal.saturating_sub(mode.vsync_end) as u32;
+    let fix_info = fb_fix_screeninfo {
+        line_length: dumb.pitch,
+        smem_len: dumb.size as u32,
+        ..Default::default()
+    };
+    Ok((memory, screen_info, fix_info, fb.fb_id))
+}
+
+/// Connector and its preferred mode, if display is connected to it
+fn connected_mode(fd: i32, id: u32) -> Option<(drm_mode_get_connector, drm_mode_modeinfo)> {
+    let mut connector = drm_mode_get_connector {
+        connector_id: id,
+        ..Default::default()
+    };
+    unsafe { get_connector(fd, &mut connector).ok()? };
+    if connector.connection != CONNECTED || connector.count_modes == 0 {
+        return None;
+    }
+    let mut modes = vec![drm_mode_modeinfo::default(); connector.count_modes as usize];
+    connector = drm_mode_get_connector {
+        connector_id: id,
+        modes_ptr: modes.as_mut_ptr() as u64,
+        count_modes: modes.len() as u32,
+        ..Default::default()
+    };
+    unsafe { get_connector(fd, &mut connector).ok()? };
+    // Modes are sorted by driver, preferred one goes first
+    let mode = *modes.first()?;
+    Some((connector, mode))
+}
+
+/// Tell driver that contents of DRM framebuffer changed. Needed by drivers which don't scan out
+/// memory directly, like ones of USB displays, others just ignore it
+pub(crate) fn mark_dirty(file: &File, fb_id: u32) {
+    let mut dirty = drm_mode_fb_dirty_cmd {
+        fb_id,
+        ..Default::default()
+    };
+    let _ = unsafe { dirty_fb(file.as_raw_fd(), &mut dirty) };
+}
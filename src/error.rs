@@ -1,12 +1,55 @@
-#[cfg(feature = "images")]
-use image;
-#[cfg(feature = "text")]
-use rusttype;
 use std::fmt;
+use std::io;
 
 #[derive(Debug)]
 pub enum Error {
     InvalidColorString(String, &'static str),
+    UnknownPaletteColor(String),
+    InvalidCoefficient(f32),
+    NoFramebuffers,
+    /// Resolutions of mirrored framebuffers, as (width, height)
+    ResolutionMismatch((usize, usize), (usize, usize)),
+    /// Required field of builder is not set
+    UninitializedField(&'static str),
+    /// Size of compositor, as (width, height)
+    ZeroSize(usize, usize),
+    /// Builder of shape got impossible configuration. Reason names the field which is wrong
+    InvalidShape {
+        shape: &'static str,
+        reason: String,
+    },
+    /// Coordinates x, y are outside of the screen
+    OutOfBounds(u32, u32),
+    /// Gamma ramp can't be loaded, reason says why
+    InvalidGamma(String),
+    /// Framebuffer has pixels of given size in bits, linfb supports only 32, 24 and 16 bits
+    UnsupportedPixelFormat(u32),
+    /// Device or memory for framebuffer can't be opened or mapped
+    Io(io::Error),
+    /// Device doesn't answer framebuffer ioctls, e.g. because it's not a framebuffer
+    #[cfg(feature = "framebuffer")]
+    Ioctl(nix::Error),
+    /// Rectangles given to
+    /// [`Framebuffer::split_regions`](crate::Framebuffer::split_regions) overlap
+    #[cfg(feature = "framebuffer")]
+    OverlappingRegions(crate::Rect, crate::Rect),
+    /// DRM device has no connected display to show framebuffer on
+    #[cfg(feature = "drm")]
+    NoConnectedDisplay,
+    /// Console devices can't be opened, e.g. because of permissions
+    #[cfg(feature = "vt")]
+    VtAccess(io::Error),
+    /// All virtual terminals are in use
+    #[cfg(feature = "vt")]
+    NoFreeVt,
+    /// Virtual terminal can't be queried or switched to
+    #[cfg(feature = "vt")]
+    VtSwitch(nix::Error),
+    /// Virtual terminal can't be switched to graphics mode
+    #[cfg(feature = "vt")]
+    VtMode(nix::Error),
+    #[cfg(feature = "barcode")]
+    InvalidBarcodeCharacter(char),
     #[cfg(feature = "text")]
     FontNotFound,
     #[cfg(feature = "text")]
@@ -23,6 +66,65 @@ impl fmt::Display for Error {
             InvalidColorString(color, description) => {
                 write!(f, "invalid color string: {}; {}", color, description)
             }
+            UnknownPaletteColor(name) => write!(f, "no color named {} in palette", name),
+            InvalidCoefficient(coeff) => write!(f, "coefficient {} is not in [0, 1]", coeff),
+            NoFramebuffers => write!(f, "no framebuffers given"),
+            ResolutionMismatch((width1, height1), (width2, height2)) => write!(
+                f,
+                "resolutions of mirrored framebuffers differ: {}x{} and {}x{}",
+                width1, height1, width2, height2
+            ),
+            UninitializedField(field) => write!(f, "field {} must be initialized", field),
+            ZeroSize(width, height) => {
+                write!(f, "size must be nonzero, got {}x{}", width, height)
+            }
+            InvalidShape { shape, reason } => write!(f, "invalid {}: {}", shape, reason),
+            OutOfBounds(x, y) => write!(f, "point {}, {} is outside of the screen", x, y),
+            InvalidGamma(reason) => write!(f, "invalid gamma ramp: {}", reason),
+            UnsupportedPixelFormat(bits) => write!(
+                f,
+                "unsupported pixel size of {} bits, only 32, 24 and 16 bits are supported",
+                bits
+            ),
+            Io(err) => write!(f, "I/O error: {}", err),
+
+            #[cfg(feature = "framebuffer")]
+            Ioctl(err) => write!(f, "framebuffer ioctl failed: {}", err),
+
+            #[cfg(feature = "framebuffer")]
+            OverlappingRegions(first, second) => write!(
+                f,
+                "regions {}x{} at {}, {} and {}x{} at {}, {} overlap",
+                first.width,
+                first.height,
+                first.x,
+                first.y,
+                second.width,
+                second.height,
+                second.x,
+                second.y
+            ),
+
+            #[cfg(feature = "drm")]
+            NoConnectedDisplay => write!(f, "no display is connected to DRM device"),
+
+            #[cfg(feature = "vt")]
+            VtAccess(err) => write!(f, "can't open console: {}", err),
+
+            #[cfg(feature = "vt")]
+            NoFreeVt => write!(f, "no free virtual terminal"),
+
+            #[cfg(feature = "vt")]
+            VtSwitch(err) => write!(f, "can't switch virtual terminal: {}", err),
+
+            #[cfg(feature = "vt")]
+            VtMode(err) => write!(f, "can't set graphics mode of virtual terminal: {}", err),
+
+            #[cfg(feature = "barcode")]
+            InvalidBarcodeCharacter(c) => {
+                write!(f, "character {:?} can't be encoded in barcode", c)
+            }
+
             #[cfg(feature = "text")]
             FontNotFound => write!(f, "font with given constraints is not found"),
 
@@ -35,6 +137,14 @@ impl fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 #[cfg(feature = "text")]
 impl From<rusttype::Error> for Error {
     fn from(err: rusttype::Error) -> Self {
@@ -0,0 +1,3820 @@
+use std::fs::{File, OpenOptions};
+use std::ops::ControlFlow;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::Path;
+
+use crate::backend::{check_memory, FbdevBackend, FramebufferBackend, MemoryFramebuffer};
+use crate::canvas::{self, Canvas, Faded, Rect, RenderTarget};
+use crate::shape::{Color, Palette, PositionedShape, Shape};
+use crate::sys::wait_for_vsync;
+use crate::sys::{blank, get_fix_screeninfo, get_var_screeninfo, pan_display, put_var_screeninfo};
+use crate::sys::{fb_bitfield, fb_cmap, fb_fix_screeninfo, fb_var_screeninfo};
+use crate::sys::{fb_vblank, get_vblank};
+use crate::sys::{get_cmap, put_cmap};
+use crate::{Compositor, FrameInfo, FrameLimiter, FrameStats, PixelFormat, PixelLayout};
+
+use crate::{
+    Error::{InvalidGamma, Ioctl, OutOfBounds, OverlappingRegions, UnsupportedPixelFormat},
+    Result,
+};
+
+#[cfg(any(feature = "text", feature = "images"))]
+use crate::shape;
+
+#[cfg(feature = "images")]
+use crate::recording::Recorder;
+#[cfg(feature = "images")]
+use crate::RecordingHandle;
+
+/// Value of `activate` of screen info which applies panning at the next vertical blank
+const FB_ACTIVATE_VBL: u32 = 16;
+
+/// Number of entries in gamma ramps generated and read by [`Framebuffer`]
+const GAMMA_SIZE: usize = 256;
+
+/// Clockwise rotation of everything drawn on [`Framebuffer`] relative to the physical screen, see
+/// [`Framebuffer::set_rotation`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// Rotation from `rotate` field of screen info, which holds one of `FB_ROTATE_*` constants
+    fn from_screen_info(screen_info: &fb_var_screeninfo) -> Self {
+        match screen_info.rotate {
+            1 => Rotation::Deg90,
+            2 => Rotation::Deg180,
+            3 => Rotation::Deg270,
+            _ => Rotation::Deg0,
+        }
+    }
+
+    /// Physical coordinates of pixel at rotated coordinates x, y of screen with given physical
+    /// size
+    fn to_physical(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (width - 1 - y, x),
+            Rotation::Deg180 => (width - 1 - x, height - 1 - y),
+            Rotation::Deg270 => (y, height - 1 - x),
+        }
+    }
+
+    /// Whether width and height of screen are swapped
+    fn is_transposed(self) -> bool {
+        self == Rotation::Deg90 || self == Rotation::Deg270
+    }
+}
+
+/// How pixels get to device, see [`Framebuffer::transport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Memory of device is mapped and written directly
+    Mmap,
+    /// Device can't be mapped, so pixels are kept in ordinary memory and written to device file
+    /// row by row on flush
+    Write,
+}
+
+/// How colors of shape are combined with the buffer being drawn into, see
+/// [`Framebuffer::draw_with`]. Combined color is mixed with previous one by alpha of shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Place color over previous one, same as [`Framebuffer::draw_blended`]
+    Normal,
+    /// Sum of channels, saturated at 255. Brightens, useful for glows and light effects
+    Add,
+    /// Product of channels, scaled to 0..=255. Darkens, useful for shadows and vignettes
+    Multiply,
+    /// Inverted product of inverted channels. Brightens without saturating as quickly as
+    /// [`BlendMode::Add`]
+    Screen,
+}
+
+impl BlendMode {
+    /// Combine color with previous one. Result is opaque, like with [`canvas::blend`]
+    fn apply(self, previous: Color, color: Color) -> Color {
+        if self == BlendMode::Normal {
+            return canvas::blend(previous, color);
+        }
+        let alpha = color.alpha as f32 / 255f32;
+        let channel = |below: u8, above: u8| {
+            let (below_value, above_value) = (below as f32, above as f32);
+            let combined = match self {
+                BlendMode::Normal => above_value,
+                BlendMode::Add => f32::min(below_value + above_value, 255f32),
+                BlendMode::Multiply => below_value * above_value / 255f32,
+                BlendMode::Screen => {
+                    255f32 - (255f32 - below_value) * (255f32 - above_value) / 255f32
+                }
+            };
+            (below_value + (combined - below_value) * alpha).round() as u8
+        };
+        Color {
+            red: channel(previous.red, color.red),
+            green: channel(previous.green, color.green),
+            blue: channel(previous.blue, color.blue),
+            alpha: 255,
+        }
+    }
+}
+
+/// Strategy of buffering used by [`Framebuffer`], see [`Framebuffer::set_buffer_strategy`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferStrategy {
+    /// Draw into internal buffer and copy all of it to the real framebuffer on every flush
+    Single,
+    /// Keep a second internal buffer with contents of the real framebuffer and copy only changed
+    /// bytes on flush
+    DoubleCopy,
+    /// Draw into the offscreen half of virtual screen and pan to it on flush, so nothing is
+    /// copied at all. Requires virtual screen to be at least twice as high as the visible one
+    DoubleFlip,
+    /// Cycle through three pages of virtual screen: one is displayed, one is queued to be
+    /// displayed at the next vertical blank and one is drawn into, so drawing never waits for the
+    /// screen. Requires virtual screen to be at least three times as high as the visible one
+    TripleFlip,
+}
+
+/// Counters of page flips, see [`Framebuffer::flip_stats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FlipStats {
+    /// Number of frames queued for display by flushes
+    pub presented: u64,
+    /// Number of queued frames which were replaced by the next one before they could be shown,
+    /// i.e. less than one screen refresh later
+    pub dropped: u64,
+}
+
+/// VESA blanking level of screen, see [`Framebuffer::blank`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlankMode {
+    /// Screen is on
+    Unblank = 0,
+    /// Screen is blank, but everything is powered
+    Normal = 1,
+    /// Vertical sync is suspended
+    VsyncSuspend = 2,
+    /// Horizontal sync is suspended
+    HsyncSuspend = 3,
+    /// Screen is powered down
+    Powerdown = 4,
+}
+
+/// How new video mode is applied by [`Framebuffer::set_mode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeActivation {
+    /// Switch to mode immediately
+    Now = 0,
+    /// Only check that driver supports mode, nothing is changed
+    Test = 2,
+}
+
+/// Result of [`Framebuffer::refresh`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeChange {
+    /// Mode of device is the same, nothing was done
+    Unchanged,
+    /// Mode of device was changed and picked up. Sizes of screen are (width, height), as seen
+    /// through rotation
+    Changed {
+        old: (usize, usize),
+        new: (usize, usize),
+    },
+}
+
+/// Basic object used to manipulate framebuffer.
+/// You should normally use [Shape] and [Compositor] to draw on it
+pub struct Framebuffer {
+    screen: Vec<u8>,
+    front: Vec<u8>,
+    /// Information about framebuffer
+    pub screen_info: fb_var_screeninfo,
+    fix_info: fb_fix_screeninfo,
+    stride: usize,
+    /// Size of one pixel in bytes
+    pixel_size: usize,
+    /// Layout of pixel, computed once so drawing doesn't look into `screen_info`
+    codec: Codec,
+    framebuffer: Box<dyn FramebufferBackend>,
+    file: Option<File>,
+    direct: bool,
+    strategy: BufferStrategy,
+    /// Was strategy chosen with [`Framebuffer::set_buffer_strategy`], so it's kept by
+    /// [`Framebuffer::swap_buffers`]
+    explicit_strategy: bool,
+    /// First column and row of virtual screen which are displayed
+    origin: (usize, usize),
+    /// First row of page which was displayed before the last flip, it can still be scanned out
+    previous_row: usize,
+    last_flip: Option<std::time::Instant>,
+    flip_stats: FlipStats,
+    transport: Transport,
+    rotation: Rotation,
+    /// Gamma ramps of in-memory framebuffer, empty until they're set
+    gamma: (Vec<u16>, Vec<u16>, Vec<u16>),
+    scratch: Canvas,
+    palette: Option<std::sync::Arc<Palette>>,
+    /// Id of DRM framebuffer, for framebuffers opened with [`Framebuffer::open_drm`]
+    #[cfg(feature = "drm")]
+    drm_fb: Option<u32>,
+    #[cfg(feature = "images")]
+    recording: Option<Recorder>,
+}
+
+impl Framebuffer {
+    /// Try to open `/dev/fb0` and create Framebuffer object.
+    /// It requires root privileges on most systems.
+    /// Returns an error if `/dev/fb0` can't be opened or its pixel size is not supported, see
+    /// [`Framebuffer::open_path`]
+    pub fn open() -> Result<Self> {
+        Self::open_path("/dev/fb0")
+    }
+
+    /// Same as [`Framebuffer::open`], but in direct mode from the start, so internal buffer is
+    /// never allocated, e.g. for devices which can't spare memory for a copy of a large screen.
+    /// All drawing goes straight to the real framebuffer and reading pixels reads it, so frames
+    /// can tear, see [`Framebuffer::direct_mode`]
+    /// ```no_run
+    /// # use linfb::Framebuffer;
+    /// let mut framebuffer = Framebuffer::open_direct().unwrap();
+    /// assert!(framebuffer.is_direct());
+    /// framebuffer.fill_rect(0, 0, 100, 100, (255, 0, 0));
+    /// ```
+    pub fn open_direct() -> Result<Self> {
+        Self::open_direct_path("/dev/fb0")
+    }
+
+    /// Same as [`Framebuffer::open_path`], but in direct mode from the start, see
+    /// [`Framebuffer::open_direct`]
+    pub fn open_direct_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(false)
+            .open(path)?;
+        Self::from_file_direct(file)
+    }
+
+    /// Same as [`Framebuffer::open`], but for any framebuffer device, e.g. `/dev/fb1`. Files which
+    /// are not framebuffers are an error:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// let path = std::env::temp_dir().join("linfb-not-a-framebuffer");
+    /// std::fs::write(&path, [0u8; 64]).unwrap();
+    /// let result = Framebuffer::open_path(&path);
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(matches!(result, Err(Error::Ioctl(_))));
+    /// assert!(matches!(Framebuffer::open_path("/nonexistent/fb0"), Err(Error::Io(_))));
+    /// ```
+    ///
+    /// Devices with pixel size other than 32, 24 or 16 bits are rejected with
+    /// [`Error::UnsupportedPixelFormat`]
+    pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(false)
+            .open(path)?;
+        Self::from_file(file)
+    }
+
+    /// Create Framebuffer object from already opened framebuffer device, e.g. one opened before
+    /// dropping privileges. File must be opened for reading and writing. Framebuffer takes
+    /// ownership of file, so it's kept open as long as memory of device is mapped, and is
+    /// validated the same way as in [`Framebuffer::open_path`]:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// let path = std::env::temp_dir().join("linfb-not-a-framebuffer-file");
+    /// std::fs::write(&path, [0u8; 64]).unwrap();
+    /// let file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(matches!(Framebuffer::from_file(file), Err(Error::Ioctl(_))));
+    /// ```
+    ///
+    /// If device reports that the screen is rotated, drawing is rotated the same way, see
+    /// [`Framebuffer::set_rotation`]
+    pub fn from_file(file: File) -> Result<Self> {
+        Self::from_device(file, false)
+    }
+
+    /// Same as [`Framebuffer::from_file`], but in direct mode from the start, see
+    /// [`Framebuffer::open_direct`]. Files which are not framebuffers are an error as well:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// let path = std::env::temp_dir().join("linfb-not-a-framebuffer-direct");
+    /// std::fs::write(&path, [0u8; 64]).unwrap();
+    /// let file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(matches!(Framebuffer::from_file_direct(file), Err(Error::Ioctl(_))));
+    /// ```
+    pub fn from_file_direct(file: File) -> Result<Self> {
+        Self::from_device(file, true)
+    }
+
+    fn from_device(file: File, direct: bool) -> Result<Self> {
+        let backend = FbdevBackend::new(&file)?;
+        let transport = backend.transport();
+        let rotation = Rotation::from_screen_info(backend.screen_info());
+        let mut framebuffer = Self::from_backend(Box::new(backend), Some(file), direct);
+        framebuffer.transport = transport;
+        framebuffer.set_rotation(rotation);
+        Ok(framebuffer)
+    }
+
+    /// Try to open `/dev/dri/card0` and show a framebuffer on it using kernel mode setting, for
+    /// systems without fbdev emulation. See [`Framebuffer::open_drm_path`]
+    #[cfg(feature = "drm")]
+    pub fn open_drm() -> Result<Self> {
+        Self::open_drm_path("/dev/dri/card0")
+    }
+
+    /// Same as [`Framebuffer::open_drm`], but for any DRM device. A dumb buffer with the preferred
+    /// mode of the first connected display is created and shown on it, so the caller must be DRM
+    /// master, i.e. no compositor or X server may run on this device. Pixel layout is always
+    /// XRGB8888.
+    ///
+    /// Framebuffer works as usual, except that [`Framebuffer::blank`], [`Framebuffer::set_mode`]
+    /// and page flipping are not supported, and [`Framebuffer::flush_vsync`] doesn't wait. Returns
+    /// [`Error::NoConnectedDisplay`](crate::Error::NoConnectedDisplay) if no display is
+    /// connected, and [`Error::Ioctl`](crate::Error::Ioctl) for files which are not DRM devices:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// let path = std::env::temp_dir().join("linfb-not-a-drm-device");
+    /// std::fs::write(&path, [0u8; 64]).unwrap();
+    /// let result = Framebuffer::open_drm_path(&path);
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(matches!(result, Err(Error::Ioctl(_))));
+    /// assert!(matches!(Framebuffer::open_drm_path("/nonexistent/card0"), Err(Error::Io(_))));
+    /// ```
+    #[cfg(feature = "drm")]
+    pub fn open_drm_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(false)
+            .open(path)?;
+        let (memory, screen_info, fix_info, fb_id) = crate::drm::open_dumb_buffer(&file)?;
+        let backend = FbdevBackend::from_mapping(memory, screen_info, fix_info);
+        let mut framebuffer = Self::from_backend(Box::new(backend), Some(file), false);
+        framebuffer.drm_fb = Some(fb_id);
+        Ok(framebuffer)
+    }
+
+    /// How pixels get to device. Memory of most devices is mapped, but some drivers, e.g. of
+    /// DisplayLink adapters, don't support it. Framebuffer falls back to
+    /// [`Transport::Write`] for them automatically, and everything works the same way, except
+    /// that pixels drawn in direct mode are only displayed on [`Framebuffer::flush`] and
+    /// [`BufferStrategy::DoubleFlip`] is not supported. In-memory framebuffers are always
+    /// mapped:
+    /// ```
+    /// # use linfb::{Framebuffer, Transport};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.transport(), Transport::Mmap);
+    /// ```
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Change resolution and pixel size of screen, like `fbset` does. Drivers can round the mode,
+    /// so check [`Framebuffer::screen_info`] after switching. Framebuffer memory is mapped again
+    /// and internal buffers are resized, their contents are lost, while direct mode and
+    /// [`BufferStrategy`] are kept if possible. Virtual screen gets the size of visible one. Layout
+    /// of pixel is chosen by driver, in-memory framebuffers keep it, dropping channels which don't
+    /// fit into new pixel size.
+    ///
+    /// With [`ModeActivation::Test`] nothing is changed, so it can be used to check if driver
+    /// supports the mode. If driver rejects the mode or it can't be used, an error is returned
+    /// and the previous mode is kept:
+    /// ```
+    /// # use linfb::{Error, Framebuffer, ModeActivation, PixelFormat, RenderTarget};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.set_mode(8, 6, 24, ModeActivation::Test).unwrap();
+    /// assert_eq!(framebuffer.width(), 4);
+    ///
+    /// framebuffer.set_mode(8, 6, 24, ModeActivation::Now).unwrap();
+    /// assert_eq!((framebuffer.width(), framebuffer.height()), (8, 6));
+    /// framebuffer.fill_rect(0, 0, 8, 6, (255, 0, 0));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.front_buffer().len(), 8 * 6 * 3);
+    /// assert_eq!(framebuffer.get_pixel(7, 5), (255, 0, 0).into());
+    ///
+    /// assert!(matches!(
+    ///     framebuffer.set_mode(16, 16, 8, ModeActivation::Now),
+    ///     Err(Error::UnsupportedPixelFormat(8))
+    /// ));
+    /// assert_eq!(framebuffer.front_buffer().len(), 8 * 6 * 3);
+    /// ```
+    pub fn set_mode(
+        &mut self,
+        xres: u32,
+        yres: u32,
+        bits_per_pixel: u32,
+        activation: ModeActivation,
+    ) -> Result<()> {
+        let mut screen_info = self.screen_info.clone();
+        screen_info.xres = xres;
+        screen_info.yres = yres;
+        screen_info.xres_virtual = xres;
+        screen_info.yres_virtual = yres;
+        screen_info.xoffset = 0;
+        screen_info.yoffset = 0;
+        screen_info.bits_per_pixel = bits_per_pixel;
+        screen_info.activate = activation as u32;
+        check_pixel_format(&screen_info)?;
+
+        let (backend, transport): (Box<dyn FramebufferBackend>, _) = match &self.file {
+            Some(file) => {
+                let fd = file.as_raw_fd();
+                unsafe { put_var_screeninfo(fd, &mut screen_info).map_err(Ioctl)? };
+                if activation == ModeActivation::Test {
+                    return Ok(());
+                }
+                match FbdevBackend::new(file) {
+                    Ok(backend) => {
+                        let transport = backend.transport();
+                        (Box::new(backend), transport)
+                    }
+                    Err(err) => {
+                        let mut previous = self.screen_info.clone();
+                        previous.activate = ModeActivation::Now as u32;
+                        let _ = unsafe { put_var_screeninfo(fd, &mut previous) };
+                        return Err(err);
+                    }
+                }
+            }
+            None => {
+                if activation == ModeActivation::Test {
+                    return Ok(());
+                }
+                // There is no driver to choose layout of pixel, so channels which don't fit just
+                // disappear
+                screen_info.activate = 0;
+                for channel in &mut [
+                    &mut screen_info.red,
+                    &mut screen_info.green,
+                    &mut screen_info.blue,
+                    &mut screen_info.transp,
+                ] {
+                    if channel.offset + channel.length > bits_per_pixel {
+                        **channel = fb_bitfield::new(0, 0);
+                    }
+                }
+                let backend = MemoryFramebuffer::new(screen_info)?;
+                (Box::new(backend), Transport::Mmap)
+            }
+        };
+
+        self.replace_mapping(backend, transport);
+        Ok(())
+    }
+
+    /// Check whether mode of device was changed by something else since it was mapped, e.g. by
+    /// `fbset` or after hotplug, so drawing would use stale geometry. Only resolution, size of
+    /// virtual screen, length of row and layout of pixel are compared, panning doesn't count.
+    /// This reads mode of device without mapping anything, so it's cheap enough to be called
+    /// every frame, see [`Framebuffer::refresh`]. In-memory and DRM framebuffers are never
+    /// changed
+    pub fn has_mode_changed(&self) -> Result<bool> {
+        #[cfg(feature = "drm")]
+        {
+            if self.drm_fb.is_some() {
+                return Ok(false);
+            }
+        }
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(false),
+        };
+        let mut screen_info: fb_var_screeninfo = Default::default();
+        let mut fix_info: fb_fix_screeninfo = Default::default();
+        unsafe {
+            get_var_screeninfo(file.as_raw_fd(), &mut screen_info).map_err(Ioctl)?;
+            get_fix_screeninfo(file.as_raw_fd(), &mut fix_info).map_err(Ioctl)?;
+        };
+        let geometry = |screen_info: &fb_var_screeninfo, fix_info: &fb_fix_screeninfo| {
+            (
+                (screen_info.xres, screen_info.yres),
+                (screen_info.xres_virtual, screen_info.yres_virtual),
+                (screen_info.bits_per_pixel, screen_info.grayscale),
+                [
+                    screen_info.red.clone(),
+                    screen_info.green.clone(),
+                    screen_info.blue.clone(),
+                    screen_info.transp.clone(),
+                ],
+                (fix_info.line_length, fix_info.smem_len),
+            )
+        };
+        Ok(geometry(&screen_info, &fix_info) != geometry(&self.screen_info, &self.fix_info))
+    }
+
+    /// Pick up mode of device if it was changed by something else, see
+    /// [`Framebuffer::has_mode_changed`]. Memory of device is mapped again and internal buffers
+    /// are resized like in [`Framebuffer::set_mode`], so their contents are lost and the whole
+    /// frame must be redrawn. Returned [`ModeChange`] holds sizes of screen, so compositors can
+    /// be rebuilt:
+    /// ```no_run
+    /// # use linfb::{Framebuffer, ModeChange};
+    /// let mut framebuffer = Framebuffer::open().unwrap();
+    /// let mut compositor = framebuffer.compositor((0, 0, 0).into());
+    /// loop {
+    ///     if let ModeChange::Changed { new: (width, height), .. } = framebuffer.refresh().unwrap() {
+    ///         println!("Screen is {}x{} now", width, height);
+    ///         compositor = framebuffer.compositor((0, 0, 0).into());
+    ///     }
+    ///     framebuffer.draw(0, 0, &compositor);
+    ///     framebuffer.flush();
+    /// }
+    /// ```
+    ///
+    /// Returns an error if new mode can't be read or used, e.g. its pixel size is not supported,
+    /// then framebuffer keeps the previous mapping. In-memory framebuffers are never changed:
+    /// ```
+    /// # use linfb::{Framebuffer, ModeChange};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.set_pixel(1, 1, (255, 0, 0));
+    /// assert!(!framebuffer.has_mode_changed().unwrap());
+    /// assert_eq!(framebuffer.refresh().unwrap(), ModeChange::Unchanged);
+    /// assert_eq!(framebuffer.get_pixel(1, 1), (255, 0, 0).into());
+    /// ```
+    pub fn refresh(&mut self) -> Result<ModeChange> {
+        if !self.has_mode_changed()? {
+            return Ok(ModeChange::Unchanged);
+        }
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(ModeChange::Unchanged),
+        };
+        let old = (self.width(), self.height());
+        let backend = FbdevBackend::new(file)?;
+        let transport = backend.transport();
+        self.replace_mapping(Box::new(backend), transport);
+        Ok(ModeChange::Changed {
+            old,
+            new: (self.width(), self.height()),
+        })
+    }
+
+    /// Switch to new backend and its mode, keeping settings which don't depend on mode
+    fn replace_mapping(&mut self, backend: Box<dyn FramebufferBackend>, transport: Transport) {
+        let (direct, strategy, rotation) = (self.direct, self.strategy, self.rotation);
+        let explicit_strategy = self.explicit_strategy;
+        let file = self.file.take();
+        let gamma = std::mem::take(&mut self.gamma);
+        let palette = self.palette.take();
+        #[cfg(feature = "images")]
+        let recording = self.recording.take();
+        *self = Self::from_backend(backend, file, direct);
+        self.transport = transport;
+        self.gamma = gamma;
+        self.palette = palette;
+        #[cfg(feature = "images")]
+        {
+            self.recording = recording;
+        }
+        self.use_strategy(strategy);
+        self.explicit_strategy = explicit_strategy;
+        self.direct_mode(direct);
+        self.rotation = rotation;
+    }
+
+    /// Same as [`Framebuffer::from_file`], but for raw file descriptor.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor which is not owned by anything else, it's closed when
+    /// Framebuffer is dropped or creation fails
+    pub unsafe fn from_raw_fd(fd: RawFd) -> Result<Self> {
+        Self::from_file(File::from_raw_fd(fd))
+    }
+
+    /// Create Framebuffer object backed by [`MemoryFramebuffer`] instead of a real device. Nothing
+    /// will be displayed, but everything else works as usual, so it's useful for testing and
+    /// headless rendering. Pixel size must be 32, 24 or 16 bits, smaller pixels are packed into 3
+    /// or 2 bytes without padding:
+    /// ```
+    /// # use linfb::{Error, Framebuffer, RenderTarget};
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let rgb565 = |bits_per_pixel| fb_var_screeninfo::new(
+    ///     3,
+    ///     2,
+    ///     bits_per_pixel,
+    ///     fb_bitfield::new(11, 5),
+    ///     fb_bitfield::new(5, 6),
+    ///     fb_bitfield::new(0, 5),
+    ///     fb_bitfield::new(0, 0),
+    /// );
+    /// let mut framebuffer = Framebuffer::in_memory(rgb565(16)).unwrap();
+    /// framebuffer.set_pixel(1, 0, (255, 128, 64));
+    /// framebuffer.fill_rect(0, 1, 3, 1, (0, 255, 0));
+    /// framebuffer.flush();
+    /// let front = framebuffer.front_buffer();
+    /// assert_eq!(front.len(), 12);
+    /// let pixel = |x: usize, y: usize| u16::from_ne_bytes([front[(y * 3 + x) * 2], front[(y * 3 + x) * 2 + 1]]);
+    /// assert_eq!(pixel(1, 0), 31 << 11 | 32 << 5 | 8);
+    /// assert_eq!(pixel(0, 0), 0);
+    /// assert_eq!(pixel(2, 1), 63 << 5);
+    /// // Channels are scaled back up when reading
+    /// assert_eq!(framebuffer.get_pixel(1, 0), (255, 130, 66).into());
+    /// assert_eq!(framebuffer.get_pixel(2, 1), (0, 255, 0).into());
+    ///
+    /// assert!(matches!(
+    ///     Framebuffer::in_memory(rgb565(8)),
+    ///     Err(Error::UnsupportedPixelFormat(8))
+    /// ));
+    /// ```
+    pub fn in_memory(screen_info: fb_var_screeninfo) -> Result<Self> {
+        let fix_info = fb_fix_screeninfo::new(&screen_info);
+        Self::in_memory_with_layout(screen_info, fix_info)
+    }
+
+    /// Same as [`Framebuffer::in_memory`], but with memory layout described by `fix_info`, e.g.
+    /// with padded rows:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::argb8888(3, 2);
+    /// let mut fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// fix_info.line_length = 16;
+    /// fix_info.smem_len = 32;
+    /// let mut framebuffer =
+    ///     Framebuffer::in_memory_with_layout(screen_info.clone(), fix_info.clone()).unwrap();
+    /// framebuffer.fill_rect(0, 0, 3, 2, (255, 255, 255));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.fix_info().line_length, 16);
+    /// assert_eq!(framebuffer.front_buffer().len(), 32);
+    /// assert_eq!(framebuffer.get_pixel(2, 1), (255, 255, 255).into());
+    /// // Padding at the end of row is not touched
+    /// assert_eq!(&framebuffer.front_buffer()[12..16], &[0, 0, 0, 0]);
+    /// assert_eq!(&framebuffer.front_buffer()[16..20], &[255, 255, 255, 255]);
+    ///
+    /// // Memory which can't hold the whole screen is an error
+    /// fix_info.smem_len = 20;
+    /// assert!(Framebuffer::in_memory_with_layout(screen_info, fix_info).is_err());
+    /// ```
+    pub fn in_memory_with_layout(
+        screen_info: fb_var_screeninfo,
+        fix_info: fb_fix_screeninfo,
+    ) -> Result<Self> {
+        Self::with_backend(MemoryFramebuffer::with_layout(screen_info, fix_info)?)
+    }
+
+    /// Create Framebuffer object drawing into given backend, e.g. [`MemoryFramebuffer`] or a
+    /// custom one which sends pixels somewhere. Everything except operations which need a device,
+    /// like panning with the driver and gamma, works as usual. Returns an error if pixel size is
+    /// not supported or memory of backend can't hold the visible screen:
+    /// ```
+    /// # use std::ops::Range;
+    /// # use std::sync::{Arc, Mutex};
+    /// # use linfb::{Framebuffer, FramebufferBackend, MemoryFramebuffer};
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// /// Backend which remembers flushed bytes
+    /// struct Tracking {
+    ///     inner: MemoryFramebuffer,
+    ///     flushed: Arc<Mutex<Vec<Range<usize>>>>,
+    /// }
+    ///
+    /// impl FramebufferBackend for Tracking {
+    ///     fn screen_info(&self) -> &fb_var_screeninfo {
+    ///         self.inner.screen_info()
+    ///     }
+    ///
+    ///     fn fix_info(&self) -> &fb_fix_screeninfo {
+    ///         self.inner.fix_info()
+    ///     }
+    ///
+    ///     fn map(&self) -> &[u8] {
+    ///         self.inner.map()
+    ///     }
+    ///
+    ///     fn map_mut(&mut self) -> &mut [u8] {
+    ///         self.inner.map_mut()
+    ///     }
+    ///
+    ///     fn flush(&mut self, range: Range<usize>) -> std::io::Result<()> {
+    ///         self.flushed.lock().unwrap().push(range);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let inner = MemoryFramebuffer::new(fb_var_screeninfo::argb8888(4, 2)).unwrap();
+    /// let flushed = Arc::new(Mutex::new(Vec::new()));
+    /// let backend = Tracking { inner, flushed: flushed.clone() };
+    /// let mut framebuffer = Framebuffer::with_backend(backend).unwrap();
+    /// framebuffer.fill_rect(0, 0, 4, 2, (255, 255, 255));
+    /// framebuffer.flush();
+    /// // Every changed row is flushed
+    /// assert_eq!(*flushed.lock().unwrap(), vec![0..16, 16..32]);
+    /// ```
+    pub fn with_backend<B: FramebufferBackend + 'static>(backend: B) -> Result<Self> {
+        check_memory(
+            backend.screen_info(),
+            backend.fix_info(),
+            backend.map().len(),
+        )?;
+        Ok(Self::from_backend(Box::new(backend), None, false))
+    }
+
+    /// Create Framebuffer object for backend. Internal buffer is not allocated in direct mode
+    fn from_backend(
+        framebuffer: Box<dyn FramebufferBackend>,
+        file: Option<File>,
+        direct: bool,
+    ) -> Self {
+        let screen_info = framebuffer.screen_info().clone();
+        let fix_info = framebuffer.fix_info().clone();
+        let screen = if direct {
+            Vec::new()
+        } else {
+            vec![0u8; fix_info.page_size(&screen_info)]
+        };
+        let origin = (screen_info.xoffset as usize, screen_info.yoffset as usize);
+        Self {
+            screen,
+            front: Vec::new(),
+            framebuffer,
+            file,
+            stride: fix_info.stride(&screen_info),
+            pixel_size: screen_info.bits_per_pixel as usize / 8,
+            codec: Codec::new(&screen_info),
+            origin,
+            fix_info,
+            screen_info,
+            direct,
+            strategy: BufferStrategy::Single,
+            explicit_strategy: false,
+            previous_row: origin.1,
+            last_flip: None,
+            flip_stats: FlipStats::default(),
+            transport: Transport::Mmap,
+            rotation: Rotation::Deg0,
+            gamma: Default::default(),
+            scratch: Canvas::default(),
+            palette: None,
+            #[cfg(feature = "drm")]
+            drm_fb: None,
+            #[cfg(feature = "images")]
+            recording: None,
+        }
+    }
+
+    /// Enable or disable direct mode. In direct mode all drawing goes straight to the real
+    /// framebuffer, internal buffer is freed and [`Framebuffer::flush`] does nothing. This halves
+    /// memory traffic, but allows tearing: screen can be scanned out in the middle of drawing, so
+    /// partially drawn frames can be visible. Direct mode takes priority over
+    /// [`BufferStrategy`].
+    ///
+    /// Contents of the buffer being drawn into are preserved when switching modes, so enabling
+    /// direct mode immediately displays everything drawn since the last flush.
+    ///
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Color, Rectangle};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(8, 8);
+    /// let rectangle = Rectangle::builder()
+    ///     .width(4)
+    ///     .height(4)
+    ///     .border_color((255, 0, 0))
+    ///     .fill_color((0, 255, 0))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let mut buffered = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// buffered.draw(2, 2, &rectangle);
+    /// buffered.fill_rect(0, 0, 2, 8, (0, 0, 255));
+    /// buffered.flush();
+    ///
+    /// let mut direct = Framebuffer::in_memory(screen_info).unwrap();
+    /// direct.direct_mode(true);
+    /// direct.draw(2, 2, &rectangle);
+    /// direct.fill_rect(0, 0, 2, 8, (0, 0, 255));
+    /// direct.flush();
+    /// assert_eq!(buffered.front_buffer(), direct.front_buffer());
+    ///
+    /// // Leaving direct mode keeps what was drawn
+    /// let direct_pixels: Vec<_> = (0..64).map(|i| direct.get_pixel(i % 8, i / 8)).collect();
+    /// direct.direct_mode(false);
+    /// for y in 0..8 {
+    ///     for x in 0..8 {
+    ///         assert_eq!(buffered.get_pixel(x, y), direct.get_pixel(x, y));
+    ///         assert_eq!(direct_pixels[(y * 8 + x) as usize], direct.get_pixel(x, y));
+    ///     }
+    /// }
+    /// ```
+    pub fn direct_mode(&mut self, enabled: bool) {
+        self.switch_buffers(enabled, self.strategy);
+    }
+
+    /// Is direct mode enabled. See [`Framebuffer::direct_mode`]
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    /// Set [`BufferStrategy`] used for drawing and flushing. Default is
+    /// [`BufferStrategy::Single`]. Returns strategy which is really used:
+    /// [`BufferStrategy::TripleFlip`] falls back to [`BufferStrategy::DoubleFlip`] if virtual
+    /// screen has no room for three pages, which falls back to [`BufferStrategy::DoubleCopy`] if
+    /// framebuffer can't pan to the second half of virtual screen.
+    ///
+    /// Contents of the buffer being drawn into are preserved when switching strategies. With
+    /// page flipping this is not true for flushes: after flush you'll draw over an older frame, so
+    /// the whole frame should be redrawn.
+    ///
+    /// ```
+    /// # use linfb::{BufferStrategy, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(100, 100);
+    /// let mut single = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// let mut double = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert_eq!(
+    ///     double.set_buffer_strategy(BufferStrategy::DoubleCopy),
+    ///     BufferStrategy::DoubleCopy
+    /// );
+    /// for framebuffer in [&mut single, &mut double].iter_mut() {
+    ///     framebuffer.fill_rect(0, 0, 100, 100, (40, 40, 40));
+    ///     framebuffer.flush();
+    ///     framebuffer.fill_rect(10, 20, 30, 30, (255, 0, 0));
+    ///     framebuffer.flush();
+    /// }
+    /// assert_eq!(single.front_buffer(), double.front_buffer());
+    ///
+    /// # let mut flip_info = screen_info;
+    /// # flip_info.yres_virtual = 200;
+    /// let mut flip = Framebuffer::in_memory(flip_info).unwrap();
+    /// assert_eq!(
+    ///     flip.set_buffer_strategy(BufferStrategy::DoubleFlip),
+    ///     BufferStrategy::DoubleFlip
+    /// );
+    /// for _ in 0..3 {
+    ///     // Every frame is drawn from scratch
+    ///     flip.fill_rect(0, 0, 100, 100, (40, 40, 40));
+    ///     flip.fill_rect(10, 20, 30, 30, (255, 0, 0));
+    ///     flip.flush();
+    /// }
+    /// assert_eq!(single.front_buffer(), flip.front_buffer());
+    /// ```
+    pub fn set_buffer_strategy(&mut self, strategy: BufferStrategy) -> BufferStrategy {
+        self.explicit_strategy = true;
+        self.use_strategy(strategy)
+    }
+
+    /// Switch to strategy or the one it falls back to, see [`Framebuffer::set_buffer_strategy`]
+    fn use_strategy(&mut self, strategy: BufferStrategy) -> BufferStrategy {
+        let strategy = match strategy {
+            BufferStrategy::TripleFlip if self.triple_rows().is_none() => {
+                BufferStrategy::DoubleFlip
+            }
+            strategy => strategy,
+        };
+        let strategy = match strategy {
+            BufferStrategy::DoubleFlip | BufferStrategy::TripleFlip if !self.can_flip() => {
+                BufferStrategy::DoubleCopy
+            }
+            strategy => strategy,
+        };
+        self.switch_buffers(self.direct, strategy);
+        strategy
+    }
+
+    /// Counters of frames queued by page flipping strategies and of frames which were probably
+    /// never shown, since the next one replaced them less than one screen refresh later. Drivers
+    /// don't tell when the queued page is really displayed, so refresh period is computed from
+    /// timings of mode, or assumed to be of 60 Hz if driver doesn't report them:
+    /// ```
+    /// # use std::time::Duration;
+    /// # use linfb::{BufferStrategy, FlipStats, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// screen_info.yres_virtual = 12;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert_eq!(framebuffer.set_buffer_strategy(BufferStrategy::TripleFlip), BufferStrategy::TripleFlip);
+    ///
+    /// // Drawing never goes to the displayed page or the one displayed before it
+    /// let mut origins = Vec::new();
+    /// for &color in &[(255, 0, 0), (0, 255, 0)] {
+    ///     framebuffer.fill_rect(0, 0, 4, 4, color);
+    ///     framebuffer.flush();
+    ///     origins.push(framebuffer.visible_origin().1);
+    /// }
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// framebuffer.fill_rect(0, 0, 4, 4, (0, 0, 255));
+    /// framebuffer.flush();
+    /// origins.push(framebuffer.visible_origin().1);
+    /// assert_eq!(origins, vec![4, 8, 0]);
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[255, 0, 0, 255]);
+    /// // The second frame replaced the first one right away
+    /// assert_eq!(framebuffer.flip_stats(), FlipStats { presented: 3, dropped: 1 });
+    ///
+    /// // Virtual screen with only two pages falls back to double buffering
+    /// screen_info.yres_virtual = 8;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.set_buffer_strategy(BufferStrategy::TripleFlip), BufferStrategy::DoubleFlip);
+    /// ```
+    pub fn flip_stats(&self) -> FlipStats {
+        self.flip_stats
+    }
+
+    /// Currently used [`BufferStrategy`]
+    pub fn buffer_strategy(&self) -> BufferStrategy {
+        self.strategy
+    }
+
+    /// Can flushes pan to the second half of virtual screen instead of copying, i.e. can
+    /// [`BufferStrategy::DoubleFlip`] be used. Requires virtual screen to be at least twice as
+    /// high as the visible one and driver to support panning. [`BufferStrategy::TripleFlip`]
+    /// also needs room for the third page
+    pub fn supports_page_flip(&self) -> bool {
+        self.can_flip()
+    }
+
+    /// Show everything drawn since the last swap by flipping pages. On the first call switches to
+    /// [`BufferStrategy::TripleFlip`], or [`BufferStrategy::DoubleFlip`] if there's no room for
+    /// three pages, keeping what's already drawn, so drawing goes to an offscreen page from then
+    /// on. Strategy chosen with [`Framebuffer::set_buffer_strategy`] is kept as is. Works like
+    /// [`Framebuffer::flush`] if page flipping is not
+    /// [supported](Framebuffer::supports_page_flip):
+    /// ```
+    /// # use linfb::{BufferStrategy, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut copying = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert!(!copying.supports_page_flip());
+    ///
+    /// screen_info.yres_virtual = 8;
+    /// let mut flipping = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert!(flipping.supports_page_flip());
+    ///
+    /// for framebuffer in [&mut copying, &mut flipping].iter_mut() {
+    ///     framebuffer.fill_rect(0, 0, 4, 4, (255, 0, 0));
+    ///     framebuffer.swap_buffers();
+    ///     assert_eq!(&framebuffer.front_buffer()[..4], &[0, 0, 255, 255]);
+    ///     framebuffer.fill_rect(0, 0, 4, 4, (0, 255, 0));
+    ///     framebuffer.swap_buffers();
+    ///     assert_eq!(&framebuffer.front_buffer()[..4], &[0, 255, 0, 255]);
+    /// }
+    /// assert_eq!(copying.buffer_strategy(), BufferStrategy::Single);
+    /// assert_eq!(flipping.buffer_strategy(), BufferStrategy::DoubleFlip);
+    ///
+    /// // Triple buffering is used when there's room for it, unless strategy is chosen by caller
+    /// screen_info.yres_virtual = 12;
+    /// let mut automatic = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// automatic.swap_buffers();
+    /// assert_eq!(automatic.buffer_strategy(), BufferStrategy::TripleFlip);
+    /// for &strategy in &[BufferStrategy::DoubleCopy, BufferStrategy::DoubleFlip] {
+    ///     let mut chosen = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     chosen.set_buffer_strategy(strategy);
+    ///     chosen.swap_buffers();
+    ///     assert_eq!(chosen.buffer_strategy(), strategy);
+    /// }
+    /// ```
+    pub fn swap_buffers(&mut self) {
+        if !self.explicit_strategy && !self.flips() && self.supports_page_flip() {
+            self.use_strategy(BufferStrategy::TripleFlip);
+        }
+        self.flush();
+    }
+
+    /// Is one of page flipping strategies used
+    fn flips(&self) -> bool {
+        self.strategy == BufferStrategy::DoubleFlip || self.strategy == BufferStrategy::TripleFlip
+    }
+
+    fn can_flip(&self) -> bool {
+        // Back page would have to be written to device on every flip, so there's no point
+        if self.back_row().is_none() || self.transport == Transport::Write {
+            return false;
+        }
+        match &self.file {
+            // Check that driver supports panning by panning to the current position
+            Some(file) => unsafe { pan_display(file.as_raw_fd(), &self.screen_info).is_ok() },
+            None => true,
+        }
+    }
+
+    fn switch_buffers(&mut self, direct: bool, strategy: BufferStrategy) {
+        if direct == self.direct && strategy == self.strategy {
+            return;
+        }
+        if direct && self.direct {
+            // Drawing goes to the real framebuffer either way, there are no buffers to fill
+            self.strategy = strategy;
+            self.previous_row = self.origin.1;
+            return;
+        }
+        let contents = self.buffer().to_vec();
+        self.direct = direct;
+        self.strategy = strategy;
+        self.previous_row = self.origin.1;
+
+        self.screen = Vec::new();
+        self.front = Vec::new();
+        if !direct {
+            match strategy {
+                BufferStrategy::Single => self.screen = vec![0u8; contents.len()],
+                BufferStrategy::DoubleCopy => {
+                    self.screen = vec![0u8; contents.len()];
+                    self.front = self.front_buffer().to_vec();
+                }
+                BufferStrategy::DoubleFlip | BufferStrategy::TripleFlip => (),
+            }
+        }
+        self.buffer_mut().copy_from_slice(&contents);
+    }
+
+    /// Memory of rows of visible screen height starting at given row of virtual screen
+    fn page(&self, row: usize) -> std::ops::Range<usize> {
+        let start = row * self.stride;
+        start..start + self.fix_info.page_size(&self.screen_info)
+    }
+
+    /// First row of offscreen page used by page flipping. With [`BufferStrategy::DoubleFlip`]
+    /// it's the top of virtual screen if it doesn't overlap the visible page, or the rows right
+    /// below it
+    fn back_row(&self) -> Option<usize> {
+        if self.strategy == BufferStrategy::TripleFlip {
+            return self.triple_rows().and_then(|rows| {
+                rows.iter()
+                    .copied()
+                    .find(|&row| row != self.origin.1 && row != self.previous_row)
+            });
+        }
+        let height = self.screen_info.yres as usize;
+        let visible_row = self.origin.1;
+        let row = if visible_row >= height {
+            0
+        } else {
+            visible_row + height
+        };
+        let rows = self.virtual_height() as usize;
+        Some(row).filter(|&row| height != 0 && row + height <= rows)
+    }
+
+    /// First rows of three pages of [`BufferStrategy::TripleFlip`], if they fit into virtual
+    /// screen and the visible page is one of them
+    fn triple_rows(&self) -> Option<[usize; 3]> {
+        let height = self.screen_info.yres as usize;
+        let rows = [0, height, height * 2];
+        Some(rows).filter(|rows| {
+            height != 0
+                && height * 3 <= self.virtual_height() as usize
+                && rows.contains(&self.origin.1)
+        })
+    }
+
+    /// Would there still be a back page for the current page flipping strategy if visible screen
+    /// started at given row
+    fn can_flip_at(&mut self, row: usize) -> bool {
+        let visible_row = std::mem::replace(&mut self.origin.1, row);
+        let back_row = self.back_row();
+        self.origin.1 = visible_row;
+        back_row.is_some()
+    }
+
+    /// Duration of one refresh of screen, computed from timings of mode. Drivers which don't
+    /// report them are assumed to refresh at 60 Hz
+    fn refresh_period(&self) -> std::time::Duration {
+        match self.refresh_rate() {
+            Some(rate) => std::time::Duration::from_secs_f64(1.0 / rate),
+            None => std::time::Duration::from_micros(16_667),
+        }
+    }
+
+    /// Number of rows of virtual screen which can be displayed by [`Framebuffer::pan`]:
+    /// `yres_virtual` of [`Framebuffer::screen_info`], but not more than mapped memory holds
+    pub fn virtual_height(&self) -> u32 {
+        let rows = self.framebuffer.map().len() / usize::max(self.stride, 1);
+        usize::min(self.screen_info.yres_virtual as usize, rows) as u32
+    }
+
+    /// Display part of virtual screen with top left corner at given offsets, e.g. to scroll
+    /// content which was rendered once with [`Framebuffer::virtual_screen`]. Drawing and
+    /// [`Framebuffer::flush`] go to the new visible part afterwards. Returns
+    /// [`Error::OutOfBounds`](crate::Error::OutOfBounds) if visible screen doesn't fit into
+    /// virtual one at these offsets and [`Error::Ioctl`](crate::Error::Ioctl) if driver rejects
+    /// panning, offsets are not changed then.
+    ///
+    /// Internal buffer is not changed by panning, so flushing it puts the same contents on screen
+    /// again. Use direct mode or just don't flush while scrolling prerendered content.
+    /// Page flipping pans on every flush itself, so panning doesn't make sense with it. Panning
+    /// to a row where page flipping has no back page switches to
+    /// [`BufferStrategy::DoubleCopy`]:
+    /// ```
+    /// # use linfb::{BufferStrategy, Error, Framebuffer, RenderTarget};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// screen_info.yres_virtual = 12;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.virtual_height(), 12);
+    ///
+    /// // Render tall page once, every 4 rows have their own color
+    /// let mut page = framebuffer.virtual_screen();
+    /// assert_eq!((page.width(), page.height()), (4, 12));
+    /// for (i, &color) in [(255, 0, 0), (0, 255, 0), (0, 0, 255)].iter().enumerate() {
+    ///     for y in i * 4..i * 4 + 4 {
+    ///         page.draw_span(0, y, 4, color.into());
+    ///     }
+    /// }
+    /// drop(page);
+    ///
+    /// framebuffer.pan(0, 6).unwrap();
+    /// assert_eq!(framebuffer.visible_origin(), (0, 6));
+    /// assert_eq!(framebuffer.screen_info.yoffset, 6);
+    /// // Green rows 6 and 7 on top, blue rows 8 and 9 below, bytes are in BGRA order
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[0, 255, 0, 255]);
+    /// assert_eq!(&framebuffer.front_buffer()[12 * 4..13 * 4], &[255, 0, 0, 255]);
+    ///
+    /// assert!(matches!(framebuffer.pan(0, 9), Err(Error::OutOfBounds(0, 9))));
+    /// assert!(framebuffer.pan(1, 0).is_err());
+    /// assert_eq!(framebuffer.visible_origin(), (0, 6));
+    ///
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// screen_info.yres_virtual = 8;
+    /// let mut flipping = Framebuffer::in_memory(screen_info).unwrap();
+    /// flipping.set_buffer_strategy(BufferStrategy::DoubleFlip);
+    /// flipping.fill_rect(0, 0, 4, 4, (255, 0, 0));
+    /// flipping.pan(0, 1).unwrap();
+    /// assert_eq!(flipping.buffer_strategy(), BufferStrategy::DoubleCopy);
+    /// flipping.fill_rect(0, 0, 4, 4, (0, 255, 0));
+    /// flipping.flush();
+    /// assert_eq!(&flipping.front_buffer()[..4], &[0, 255, 0, 255]);
+    /// ```
+    pub fn pan(&mut self, xoffset: u32, yoffset: u32) -> Result<()> {
+        let (xres, yres) = (self.screen_info.xres as u64, self.screen_info.yres as u64);
+        let width = u64::min(
+            u32::max(self.screen_info.xres_virtual, self.screen_info.xres) as u64,
+            (self.stride / self.pixel_size) as u64,
+        );
+        if xoffset as u64 + xres > width || yoffset as u64 + yres > self.virtual_height() as u64 {
+            return Err(OutOfBounds(xoffset, yoffset));
+        }
+        let mut screen_info = self.screen_info.clone();
+        screen_info.xoffset = xoffset;
+        screen_info.yoffset = yoffset;
+        if let Some(file) = &self.file {
+            unsafe { pan_display(file.as_raw_fd(), &screen_info).map_err(Ioctl)? };
+        }
+        if self.flips() && !self.can_flip_at(yoffset as usize) {
+            // Page flipping needs the visible page to be one of its pages
+            self.switch_buffers(self.direct, BufferStrategy::DoubleCopy);
+        }
+        self.screen_info = screen_info;
+        self.origin = (xoffset as usize, yoffset as usize);
+        if self.strategy == BufferStrategy::DoubleCopy && !self.direct {
+            // Shadow copy must match what's displayed now
+            let page = self.page(self.origin.1);
+            self.front.copy_from_slice(&self.framebuffer.map()[page]);
+        }
+        Ok(())
+    }
+
+    /// Draw on the whole virtual screen, e.g. to prerender content below the visible area and
+    /// display it with [`Framebuffer::pan`] later. Returned target writes straight into the real
+    /// framebuffer, ignoring buffering and rotation, coordinates are relative to the top left
+    /// corner of virtual screen. Devices which can't be mapped get changed rows written when the
+    /// target is dropped
+    pub fn virtual_screen(&mut self) -> VirtualScreen<'_> {
+        VirtualScreen {
+            framebuffer: self,
+            rows: None,
+        }
+    }
+
+    /// Position of the top left corner of visible screen in virtual screen, as given by
+    /// `xoffset` and `yoffset` of [`Framebuffer::screen_info`]. All coordinates used for drawing
+    /// are relative to it:
+    /// ```
+    /// # use linfb::{Framebuffer, RenderTarget};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 2);
+    /// screen_info.xres_virtual = 16;
+    /// screen_info.yres_virtual = 20;
+    /// screen_info.xoffset = 8;
+    /// screen_info.yoffset = 16;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert_eq!(framebuffer.visible_origin(), (8, 16));
+    /// framebuffer.set_pixel(0, 0, (255, 255, 255));
+    /// framebuffer.fill_rect(3, 1, 10, 10, (255, 0, 0));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.get_pixel(0, 0), (255, 255, 255).into());
+    ///
+    /// // Visible screen starts at row 16, so rows are counted from there
+    /// let front = framebuffer.front_buffer();
+    /// let pixel = |x: usize, y: usize| &front[(y * 16 + x) * 4..(y * 16 + x + 1) * 4];
+    /// assert_eq!(pixel(8, 0), &[255, 255, 255, 255]);
+    /// assert_eq!(pixel(11, 1), &[0, 0, 255, 255]);
+    /// // Nothing is drawn outside of visible screen
+    /// assert_eq!(pixel(0, 0), &[0, 0, 0, 0]);
+    /// assert_eq!(pixel(12, 1), &[0, 0, 0, 0]);
+    /// assert_eq!(pixel(11, 0), &[0, 0, 0, 0]);
+    ///
+    /// // Visible screen must fit into virtual one
+    /// screen_info.yoffset = 19;
+    /// assert!(Framebuffer::in_memory(screen_info).is_err());
+    /// ```
+    pub fn visible_origin(&self) -> (u32, u32) {
+        (self.origin.0 as u32, self.origin.1 as u32)
+    }
+
+    /// Rotate everything drawn clockwise by given angle, e.g. for displays mounted in portrait
+    /// orientation. Coordinates of all drawing methods, [`RenderTarget::width`],
+    /// [`RenderTarget::height`] and [`Framebuffer::compositor`] are upright, so application
+    /// doesn't need to know about rotation. Contents of screen are not changed, only pixels drawn
+    /// after the call are rotated. Raw memory access with [`Framebuffer::buffer_mut`] and
+    /// [`Framebuffer::rows_mut`] is not rotated.
+    /// ```
+    /// # use linfb::{Framebuffer, RenderTarget, Rotation};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 3);
+    /// let physical = |framebuffer: &Framebuffer, x: usize, y: usize| {
+    ///     let start = (y * 4 + x) * 4;
+    ///     framebuffer.front_buffer()[start..start + 4].to_vec()
+    /// };
+    /// let white = vec![255; 4];
+    /// let cases = [
+    ///     (Rotation::Deg0, (0, 0), (3, 2)),
+    ///     (Rotation::Deg90, (3, 0), (0, 2)),
+    ///     (Rotation::Deg180, (3, 2), (0, 0)),
+    ///     (Rotation::Deg270, (0, 2), (3, 0)),
+    /// ];
+    /// for &(rotation, top_left, bottom_right) in &cases {
+    ///     let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     framebuffer.set_rotation(rotation);
+    ///     assert_eq!(framebuffer.rotation(), rotation);
+    ///     let (width, height) = (framebuffer.width(), framebuffer.height());
+    ///     assert_eq!((width, height), if rotation == Rotation::Deg90 || rotation == Rotation::Deg270 {
+    ///         (3, 4)
+    ///     } else {
+    ///         (4, 3)
+    ///     });
+    ///     framebuffer.set_pixel(0, 0, (255, 255, 255));
+    ///     framebuffer.set_pixel(width as u32 - 1, height as u32 - 1, (255, 255, 255));
+    ///     framebuffer.flush();
+    ///     assert_eq!(physical(&framebuffer, top_left.0, top_left.1), white);
+    ///     assert_eq!(physical(&framebuffer, bottom_right.0, bottom_right.1), white);
+    ///     assert_eq!(framebuffer.get_pixel(0, 0), (255, 255, 255).into());
+    /// }
+    /// ```
+    ///
+    /// Fast paths like [`Framebuffer::fill_rect`] and [`Framebuffer::blit`] are rotated as well,
+    /// so they draw the same as shapes:
+    /// ```
+    /// # use linfb::{Framebuffer, Rotation};
+    /// # use linfb::shape::{Color, Image, Rectangle, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(12, 8);
+    /// let rgba: Vec<u8> = (0..5 * 3 * 4).map(|i| (i * 37 % 256) as u8).collect();
+    /// let rectangle = Rectangle::builder()
+    ///     .width(5)
+    ///     .height(3)
+    ///     .border_width(0)
+    ///     .fill_color((200, 100, 50))
+    ///     .build()
+    ///     .unwrap();
+    /// for &rotation in &[Rotation::Deg0, Rotation::Deg90, Rotation::Deg180, Rotation::Deg270] {
+    ///     let mut fast = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     let mut slow = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     fast.set_rotation(rotation);
+    ///     slow.set_rotation(rotation);
+    ///     fast.fill_rect(1, 2, 5, 3, (200, 100, 50));
+    ///     slow.draw(1, 2, &rectangle);
+    ///     fast.blit(6, 4, 5, 3, &rgba);
+    ///     for y in 0..3 {
+    ///         for x in 0..5 {
+    ///             let i = ((y * 5 + x) * 4) as usize;
+    ///             let color = (rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]);
+    ///             slow.set_pixel(6 + x, 4 + y, color);
+    ///         }
+    ///     }
+    ///     fast.flush();
+    ///     slow.flush();
+    ///     assert_eq!(fast.front_buffer(), slow.front_buffer());
+    ///     assert_eq!(fast.read_region(0, 0, 20, 20), slow.read_region(0, 0, 20, 20));
+    /// }
+    /// ```
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Get current rotation, see [`Framebuffer::set_rotation`]
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Set [`Palette`] which is used to resolve [`PaletteRef`](crate::shape::PaletteRef)s of
+    /// shapes drawn onto framebuffer, replacing the previous one. Compositors with their own
+    /// palette use it instead. Without palette, parts of shapes painted with palette colors are
+    /// not drawn:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Palette, PaletteRef, Rectangle};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let square = Rectangle::builder()
+    ///     .width(2)
+    ///     .height(2)
+    ///     .border_width(0)
+    ///     .fill_color_ref(PaletteRef::new("accent"))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// framebuffer.draw(0, 0, &square);
+    /// assert_eq!(framebuffer.get_pixel(0, 0), (0, 0, 0, 0).into());
+    ///
+    /// framebuffer.set_palette(Palette::dark());
+    /// framebuffer.draw(0, 0, &square);
+    /// assert_eq!(framebuffer.get_pixel(0, 0), Palette::dark().resolve("accent").unwrap());
+    /// ```
+    pub fn set_palette(&mut self, palette: Palette) -> &mut Self {
+        self.palette = Some(std::sync::Arc::new(palette));
+        self
+    }
+
+    /// Currently set [`Palette`], see [`Framebuffer::set_palette`]
+    pub fn palette(&self) -> Option<&Palette> {
+        self.palette.as_deref()
+    }
+
+    /// Layout of pixels in framebuffer memory, as described by [`Framebuffer::screen_info`].
+    /// Useful to prepare pixels with [`Canvas::as_bytes`] in advance:
+    /// ```
+    /// # use linfb::{Canvas, Framebuffer, PixelFormat};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 2);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.pixel_format(), PixelFormat::ARGB8888);
+    ///
+    /// let canvas = Canvas::new(4, 2, (10, 20, 30).into());
+    /// framebuffer.fill_rect(0, 0, 4, 2, (10, 20, 30));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.front_buffer(), &canvas.as_bytes(framebuffer.pixel_format())[..]);
+    /// ```
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.codec.format
+    }
+
+    /// Well-known layout of pixel, to choose fast paths for raw pixel data. Grayscale
+    /// framebuffers and framebuffers with reversed bits in channels (`msb_right`) always have
+    /// [`PixelLayout::Other`] layout, since their pixels can't be just swizzled:
+    /// ```
+    /// # use linfb::{Framebuffer, PixelLayout, RenderTarget};
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let screen_info = |bits_per_pixel, fields: [(u32, u32); 4]| {
+    ///     let [red, green, blue, transp] = fields.map(|(offset, length)| fb_bitfield::new(offset, length));
+    ///     fb_var_screeninfo::new(1, 1, bits_per_pixel, red, green, blue, transp)
+    /// };
+    /// let layouts = [
+    ///     (32, [(16, 8), (8, 8), (0, 8), (0, 0)], PixelLayout::Xrgb8888, 0x00_11_22_33),
+    ///     (32, [(16, 8), (8, 8), (0, 8), (24, 8)], PixelLayout::Argb8888, 0x44_11_22_33),
+    ///     (32, [(0, 8), (8, 8), (16, 8), (0, 0)], PixelLayout::Xbgr8888, 0x00_33_22_11),
+    ///     (32, [(0, 8), (8, 8), (16, 8), (24, 8)], PixelLayout::Abgr8888, 0x44_33_22_11),
+    ///     (32, [(24, 8), (16, 8), (8, 8), (0, 8)], PixelLayout::Rgba8888, 0x11_22_33_44),
+    ///     (32, [(8, 8), (16, 8), (24, 8), (0, 8)], PixelLayout::Bgra8888, 0x33_22_11_44),
+    ///     (16, [(11, 5), (5, 6), (0, 5), (0, 0)], PixelLayout::Rgb565, 2 << 11 | 8 << 5 | 6),
+    /// ];
+    /// for &(bits_per_pixel, fields, layout, pixel) in &layouts {
+    ///     let mut framebuffer = Framebuffer::in_memory(screen_info(bits_per_pixel, fields)).unwrap();
+    ///     assert_eq!(framebuffer.pixel_layout(), layout);
+    ///     framebuffer.set_pixel(0, 0, (0x11, 0x22, 0x33, 0x44));
+    ///     framebuffer.flush();
+    ///     let size = bits_per_pixel as usize / 8;
+    ///     assert_eq!(framebuffer.front_buffer(), &(pixel as u32).to_ne_bytes()[..size]);
+    /// }
+    ///
+    /// // Blue channel with reversed bits
+    /// let mut reversed = screen_info(16, [(11, 5), (5, 6), (0, 5), (0, 0)]);
+    /// reversed.blue.msb_right = 1;
+    /// let mut framebuffer = Framebuffer::in_memory(reversed).unwrap();
+    /// assert!(matches!(framebuffer.pixel_layout(), PixelLayout::Other(_)));
+    /// framebuffer.set_pixel(0, 0, (0, 0, 0x33));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.front_buffer(), &0b01100u16.to_ne_bytes());
+    /// assert_eq!(framebuffer.get_pixel(0, 0), (0, 0, 49).into());
+    /// ```
+    pub fn pixel_layout(&self) -> PixelLayout {
+        if self.codec.is_plain() {
+            self.codec.format.layout()
+        } else {
+            PixelLayout::Other(self.codec.format)
+        }
+    }
+
+    /// Raw contents of the real framebuffer page which is currently displayed
+    pub fn front_buffer(&self) -> &[u8] {
+        &self.framebuffer.map()[self.page(self.origin.1)]
+    }
+
+    fn buffer(&self) -> &[u8] {
+        match self.drawn_page() {
+            Some(page) => &self.framebuffer.map()[page],
+            None => &self.screen,
+        }
+    }
+
+    /// Page of the real framebuffer which drawing goes to, [`None`] if it goes to internal buffer.
+    /// Page flipping draws into the back page, or into the visible one if there's none
+    fn drawn_page(&self) -> Option<std::ops::Range<usize>> {
+        if self.direct {
+            Some(self.page(self.origin.1))
+        } else if self.flips() {
+            Some(self.page(self.back_row().unwrap_or(self.origin.1)))
+        } else {
+            None
+        }
+    }
+
+    /// Raw memory of the buffer being drawn into, for custom rendering. Pixels are laid out as
+    /// described by [`Framebuffer::pixel_format`], rows are [`Framebuffer::stride`] bytes long,
+    /// so [`Framebuffer::rows_mut`] is usually easier to use. Buffer covers the visible page
+    /// only, i.e. `yres` rows of [`Framebuffer::screen_info`], use
+    /// [`Framebuffer::virtual_screen`] to draw outside of it. Everything written here is
+    /// displayed by [`Framebuffer::flush`] as usual
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        match self.drawn_page() {
+            Some(page) => &mut self.framebuffer.map_mut()[page],
+            None => &mut self.screen,
+        }
+    }
+
+    /// Length of one row of [`Framebuffer::buffer_mut`] in bytes, including padding
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Information about framebuffer memory layout, as reported by driver. It's read-only, because
+    /// drawing relies on stride and pixel size computed from it
+    pub fn fix_info(&self) -> &fb_fix_screeninfo {
+        &self.fix_info
+    }
+
+    /// Length of one row in bytes as reported by driver in [`Framebuffer::fix_info`], or 0 if it
+    /// isn't reported. [`Framebuffer::stride`] falls back to unpadded rows then
+    pub fn line_length(&self) -> u32 {
+        self.fix_info.line_length
+    }
+
+    /// Identification string of device from [`Framebuffer::fix_info`], e.g. `"EFI VGA"`, without
+    /// trailing zeroes. Empty for in-memory framebuffers:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert_eq!(framebuffer.device_id(), "");
+    /// assert_eq!(framebuffer.line_length(), 16);
+    ///
+    /// let mut fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// fix_info.id[..7].copy_from_slice(b"EFI VGA");
+    /// let framebuffer = Framebuffer::in_memory_with_layout(screen_info, fix_info).unwrap();
+    /// assert_eq!(framebuffer.device_id(), "EFI VGA");
+    /// ```
+    pub fn device_id(&self) -> String {
+        let id = &self.fix_info.id;
+        let length = id.iter().position(|&byte| byte == 0).unwrap_or(id.len());
+        String::from_utf8_lossy(&id[..length]).into_owned()
+    }
+
+    /// Visible rows of the buffer being drawn into, from top to bottom. Every row holds exactly
+    /// [`Framebuffer::screen_info`]`.xres` pixels encoded with [`Framebuffer::pixel_format`], so
+    /// other rasterizers can draw straight into framebuffer:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::argb8888(64, 48);
+    /// // Rows are padded, padding is skipped
+    /// let mut fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// fix_info.line_length = 320;
+    /// fix_info.smem_len = 320 * 48;
+    /// let mut framebuffer = Framebuffer::in_memory_with_layout(screen_info, fix_info).unwrap();
+    ///
+    /// let plasma = |x: usize, y: usize| {
+    ///     let value = (x as f32 / 4.0).sin() + (y as f32 / 3.0).cos() + (x + y) as f32 / 16.0;
+    ///     let value = (value * 40.0) as u8;
+    ///     (value, 255 - value, 128).into()
+    /// };
+    /// let format = framebuffer.pixel_format();
+    /// for (y, row) in framebuffer.rows_mut().enumerate() {
+    ///     assert_eq!(row.len(), 64 * 4);
+    ///     for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+    ///         pixel.copy_from_slice(&format.encode(plasma(x, y)).to_ne_bytes());
+    ///     }
+    /// }
+    /// framebuffer.flush();
+    /// for &(x, y) in &[(0, 0), (10, 20), (63, 47)] {
+    ///     assert_eq!(framebuffer.get_pixel(x, y), plasma(x as usize, y as usize));
+    /// }
+    /// assert_eq!(framebuffer.rows_mut().count(), 48);
+    /// assert_eq!(&framebuffer.front_buffer()[256..320], &[0; 64][..]);
+    /// ```
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> + '_ {
+        let start = self.physical_offset(0, 0);
+        let range = start..start + self.screen_info.xres as usize * self.pixel_size;
+        let height = self.screen_info.yres as usize;
+        let stride = usize::max(self.stride, 1);
+        self.buffer_mut()
+            .chunks_mut(stride)
+            .take(height)
+            .map(move |row| &mut row[range.clone()])
+    }
+
+    /// Flush internal buffer contents to the real framebuffer device. Does nothing in direct mode,
+    /// unless device memory can't be mapped, see [`Framebuffer::transport`]
+    pub fn flush(&mut self) {
+        self.record_frame();
+        if self.direct {
+            self.present_page();
+            return;
+        }
+        let visible = self.page(self.origin.1);
+        match self.strategy {
+            BufferStrategy::Single => {
+                self.framebuffer.map_mut()[visible].copy_from_slice(self.screen.as_slice());
+            }
+            BufferStrategy::DoubleCopy => {
+                let changed = copy_diff(
+                    &self.screen,
+                    &mut self.front,
+                    &mut self.framebuffer.map_mut()[visible.clone()],
+                    usize::max(self.stride, 1),
+                );
+                self.present_ranges(visible.start, changed);
+                return;
+            }
+            BufferStrategy::DoubleFlip | BufferStrategy::TripleFlip => {
+                let back_row = match self.back_row() {
+                    Some(row) => row,
+                    // Drawing went to the visible page, it only needs to get to device
+                    None => {
+                        self.present_page();
+                        return;
+                    }
+                };
+                let mut screen_info = self.screen_info.clone();
+                screen_info.yoffset = back_row as u32;
+                if self.strategy == BufferStrategy::TripleFlip {
+                    // Queue page for the next vertical blank instead of waiting for it
+                    screen_info.activate = FB_ACTIVATE_VBL;
+                }
+                let panned = match &self.file {
+                    Some(file) => unsafe { pan_display(file.as_raw_fd(), &screen_info).is_ok() },
+                    None => true,
+                };
+                if panned {
+                    let now = std::time::Instant::now();
+                    if let Some(last_flip) = self.last_flip {
+                        if now - last_flip < self.refresh_period() {
+                            self.flip_stats.dropped += 1;
+                        }
+                    }
+                    self.last_flip = Some(now);
+                    self.flip_stats.presented += 1;
+                    self.previous_row = self.origin.1;
+                    self.screen_info.yoffset = screen_info.yoffset;
+                    self.origin.1 = back_row;
+                } else {
+                    let back = self.page(back_row);
+                    self.framebuffer.map_mut().copy_within(back, visible.start);
+                }
+            }
+        }
+        self.present_page();
+    }
+
+    /// Flush only bytes which changed since the last flush, by comparing internal buffer with a
+    /// shadow copy of what was flushed. Switches to [`BufferStrategy::DoubleCopy`] to keep the
+    /// shadow copy, which doubles memory used by internal buffers, and copies everything on this
+    /// first call. Useful when only small parts of screen change between frames, like clocks.
+    ///
+    /// Returns number of bytes copied to the real framebuffer. Nothing is copied in direct mode
+    /// and with page flipping, which is kept and just flushed:
+    /// ```
+    /// # use linfb::{BufferStrategy, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(16, 16);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// framebuffer.fill_rect(0, 0, 16, 16, (40, 40, 40));
+    /// assert_eq!(framebuffer.flush_dirty(), 16 * 16 * 4);
+    /// assert_eq!(framebuffer.buffer_strategy(), BufferStrategy::DoubleCopy);
+    /// assert_eq!(framebuffer.flush_dirty(), 0);
+    ///
+    /// framebuffer.fill_rect(2, 3, 4, 2, (255, 255, 255));
+    /// // Runs of changed bytes are copied, alpha of the last pixel in row is the same
+    /// assert_eq!(framebuffer.flush_dirty(), 2 * (4 * 4 - 1));
+    ///
+    /// let mut expected = Framebuffer::in_memory(screen_info).unwrap();
+    /// expected.fill_rect(0, 0, 16, 16, (40, 40, 40));
+    /// expected.fill_rect(2, 3, 4, 2, (255, 255, 255));
+    /// expected.flush();
+    /// assert_eq!(framebuffer.front_buffer(), expected.front_buffer());
+    /// ```
+    pub fn flush_dirty(&mut self) -> usize {
+        if self.direct {
+            return 0;
+        }
+        if !self.flips() {
+            self.record_frame();
+        }
+        let visible = self.page(self.origin.1);
+        match self.strategy {
+            BufferStrategy::Single => {
+                self.use_strategy(BufferStrategy::DoubleCopy);
+                self.framebuffer.map_mut()[visible].copy_from_slice(&self.screen);
+                self.front.copy_from_slice(&self.screen);
+                self.present_page();
+                self.screen.len()
+            }
+            BufferStrategy::DoubleCopy => {
+                let changed = copy_diff(
+                    &self.screen,
+                    &mut self.front,
+                    &mut self.framebuffer.map_mut()[visible.clone()],
+                    usize::max(self.stride, 1),
+                );
+                let copied = changed.iter().map(|range| range.len()).sum();
+                self.present_ranges(visible.start, changed);
+                copied
+            }
+            BufferStrategy::DoubleFlip | BufferStrategy::TripleFlip => {
+                self.flush();
+                0
+            }
+        }
+    }
+
+    /// Get visible page of the real framebuffer to device, see `present`
+    fn present_page(&mut self) {
+        let height = self.screen_info.yres as usize;
+        let width = self.screen_info.xres as usize;
+        self.present(
+            self.origin.1..self.origin.1 + height,
+            self.origin.0..self.origin.0 + width,
+        );
+    }
+
+    /// Get pixels of the real framebuffer in given rows and columns of virtual screen to device
+    /// after they're changed. Backend gets them flushed row by row since rows can be padded. DRM
+    /// driver is told that the framebuffer changed, since drivers which don't scan out memory
+    /// directly, like ones of USB displays, need it to update screen
+    fn present(&mut self, rows: std::ops::Range<usize>, columns: std::ops::Range<usize>) {
+        let (stride, pixel_size) = (self.stride, self.pixel_size);
+        let ranges = rows.map(|row| {
+            row * stride + columns.start * pixel_size..row * stride + columns.end * pixel_size
+        });
+        self.present_ranges(0, ranges);
+    }
+
+    /// Get given byte ranges of the real framebuffer, relative to `offset`, to device. DRM driver
+    /// is only told that the framebuffer changed if there are any
+    fn present_ranges<I>(&mut self, offset: usize, ranges: I)
+    where
+        I: IntoIterator<Item = std::ops::Range<usize>>,
+    {
+        let mut changed = false;
+        for range in ranges {
+            changed = true;
+            // Flushing can't fail, just like copying into mapped memory
+            let _ = self
+                .framebuffer
+                .flush(offset + range.start..offset + range.end);
+        }
+        #[cfg(feature = "drm")]
+        {
+            if let (true, Some(file), Some(fb_id)) = (changed, &self.file, self.drm_fb) {
+                crate::drm::mark_dirty(file, fb_id);
+            }
+        }
+        #[cfg(not(feature = "drm"))]
+        let _ = changed;
+    }
+
+    /// Blank screen or power it down, e.g. at night. Drawing and flushing still work while screen
+    /// is blanked, so everything drawn in the meantime is displayed after
+    /// [`Framebuffer::unblank`]. Returns [`Error::Ioctl`](crate::Error::Ioctl) if driver doesn't
+    /// support blanking, in-memory framebuffers accept any mode:
+    /// ```
+    /// # use linfb::{BlankMode, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.blank(BlankMode::Powerdown).unwrap();
+    /// framebuffer.fill_rect(0, 0, 4, 4, (255, 255, 255));
+    /// framebuffer.flush();
+    /// framebuffer.unblank().unwrap();
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[255, 255, 255, 255]);
+    /// ```
+    pub fn blank(&mut self, mode: BlankMode) -> Result<()> {
+        if let Some(file) = &self.file {
+            unsafe { blank(file.as_raw_fd(), mode as i32).map_err(Ioctl)? };
+        }
+        Ok(())
+    }
+
+    /// Turn screen back on after [`Framebuffer::blank`]
+    pub fn unblank(&mut self) -> Result<()> {
+        self.blank(BlankMode::Unblank)
+    }
+
+    /// Load gamma ramps of red, green and blue channels into color map of device, e.g. to
+    /// calibrate a panel. Every ramp maps channel values spread evenly from 0 to the maximum into
+    /// output intensity from 0 to 65535, ramps must have the same length, usually 256 entries.
+    ///
+    /// Returns [`Error::Ioctl`](crate::Error::Ioctl) if driver doesn't support color maps, which
+    /// is common for truecolor framebuffers. In-memory framebuffers just keep ramps, so they can
+    /// be read back with [`Framebuffer::gamma`]:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let (red, green, blue) = framebuffer.gamma().unwrap();
+    /// assert_eq!(red.len(), 256);
+    /// assert_eq!((red[0], red[128], red[255]), (0, 128 * 257, 65535));
+    /// assert!(red == green && green == blue);
+    ///
+    /// framebuffer.set_gamma_curve(2.2).unwrap();
+    /// let (curved, _, _) = framebuffer.gamma().unwrap();
+    /// assert_eq!((curved[0], curved[255]), (0, 65535));
+    /// assert!(curved[128] < red[128] / 2);
+    ///
+    /// // Restore saved ramps
+    /// framebuffer.set_gamma(&red, &green, &blue).unwrap();
+    /// assert_eq!(framebuffer.gamma().unwrap().0, red);
+    ///
+    /// assert!(matches!(framebuffer.set_gamma(&red, &green, &blue[1..]), Err(Error::InvalidGamma(_))));
+    /// assert!(matches!(framebuffer.set_gamma_curve(-1.0), Err(Error::InvalidGamma(_))));
+    /// ```
+    pub fn set_gamma(&mut self, red: &[u16], green: &[u16], blue: &[u16]) -> Result<()> {
+        if red.len() != green.len() || green.len() != blue.len() {
+            return Err(InvalidGamma(format!(
+                "ramps have different lengths: {}, {} and {}",
+                red.len(),
+                green.len(),
+                blue.len()
+            )));
+        }
+        match &self.file {
+            Some(file) => {
+                // Driver only reads arrays
+                let cmap = fb_cmap {
+                    start: 0,
+                    len: red.len() as u32,
+                    red: red.as_ptr() as *mut u16,
+                    green: green.as_ptr() as *mut u16,
+                    blue: blue.as_ptr() as *mut u16,
+                    transp: std::ptr::null_mut(),
+                };
+                unsafe { put_cmap(file.as_raw_fd(), &cmap).map_err(Ioctl)? };
+            }
+            None => self.gamma = (red.to_vec(), green.to_vec(), blue.to_vec()),
+        }
+        Ok(())
+    }
+
+    /// Load 256-entry gamma ramp `intensity = value ^ exponent` into all channels, see
+    /// [`Framebuffer::set_gamma`]. Exponent 1 is linear, exponents above 1 darken midtones and
+    /// below 1 brighten them. Returns [`Error::InvalidGamma`](crate::Error::InvalidGamma) if
+    /// exponent is not a positive number
+    pub fn set_gamma_curve(&mut self, exponent: f64) -> Result<()> {
+        if !(exponent > 0f64 && exponent.is_finite()) {
+            return Err(InvalidGamma(format!(
+                "exponent must be positive, got {}",
+                exponent
+            )));
+        }
+        let ramp: Vec<u16> = (0..GAMMA_SIZE)
+            .map(|i| {
+                let value = i as f64 / (GAMMA_SIZE - 1) as f64;
+                (value.powf(exponent) * 65535f64).round() as u16
+            })
+            .collect();
+        self.set_gamma(&ramp, &ramp, &ramp)
+    }
+
+    /// Get current 256-entry gamma ramps of red, green and blue channels from device, e.g. to
+    /// restore them with [`Framebuffer::set_gamma`] on exit. Returns
+    /// [`Error::Ioctl`](crate::Error::Ioctl) if driver doesn't support color maps. In-memory
+    /// framebuffers start with linear ramps
+    pub fn gamma(&self) -> Result<(Vec<u16>, Vec<u16>, Vec<u16>)> {
+        match &self.file {
+            Some(file) => {
+                let mut ramps = (
+                    vec![0u16; GAMMA_SIZE],
+                    vec![0u16; GAMMA_SIZE],
+                    vec![0u16; GAMMA_SIZE],
+                );
+                let mut cmap = fb_cmap {
+                    start: 0,
+                    len: GAMMA_SIZE as u32,
+                    red: ramps.0.as_mut_ptr(),
+                    green: ramps.1.as_mut_ptr(),
+                    blue: ramps.2.as_mut_ptr(),
+                    transp: std::ptr::null_mut(),
+                };
+                unsafe { get_cmap(file.as_raw_fd(), &mut cmap).map_err(Ioctl)? };
+                Ok(ramps)
+            }
+            None if self.gamma.0.is_empty() => {
+                let ramp: Vec<u16> = (0..GAMMA_SIZE)
+                    .map(|i| (i * 65535 / (GAMMA_SIZE - 1)) as u16)
+                    .collect();
+                Ok((ramp.clone(), ramp.clone(), ramp))
+            }
+            None => Ok(self.gamma.clone()),
+        }
+    }
+
+    /// Wait for the next vertical blank, then flush, so the copy doesn't race the scanout and
+    /// the frame doesn't tear. Returns whether vsync was really waited for: drivers which don't
+    /// support it and in-memory framebuffers are just flushed, so applications can pace frames
+    /// themselves, e.g. with [`Framebuffer::flush_paced`]:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 4, 4, (255, 255, 255));
+    /// assert!(!framebuffer.flush_vsync());
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[255, 255, 255, 255]);
+    /// ```
+    pub fn flush_vsync(&mut self) -> bool {
+        let synced = match &self.file {
+            // Drivers without vsync support answer with ENOTTY or EINVAL
+            Some(file) => unsafe { wait_for_vsync(file.as_raw_fd(), &0).is_ok() },
+            None => false,
+        };
+        self.flush();
+        synced
+    }
+
+    /// Refresh rate of screen in Hz, computed from timings of mode, see
+    /// [`fb_var_screeninfo::refresh_rate_hz`]. Returns [`None`] if driver doesn't report timings,
+    /// which is always the case for in-memory framebuffers unless timings are set in their
+    /// screen info:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// assert_eq!(framebuffer.refresh_rate(), None);
+    ///
+    /// // 10^12 / (10^9 * 4 * 4) = 62.5
+    /// screen_info.pixclock = 1_000_000_000;
+    /// let framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.refresh_rate(), Some(62.5));
+    /// ```
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.screen_info.refresh_rate_hz()
+    }
+
+    /// Current state of blanking reported by driver, e.g. number of vertical blanks since boot.
+    /// Fields which are valid are marked by `flags` of [`fb_vblank`], see `FB_VBLANK_*`
+    /// constants in [`sys`](crate::sys):
+    /// ```no_run
+    /// # use linfb::Framebuffer;
+    /// use linfb::sys::FB_VBLANK_HAVE_COUNT;
+    /// let framebuffer = Framebuffer::open().unwrap();
+    /// let vblank = framebuffer.vblank_info().unwrap();
+    /// if vblank.flags & FB_VBLANK_HAVE_COUNT != 0 {
+    ///     println!("{} vertical blanks since boot", vblank.count);
+    /// }
+    /// ```
+    ///
+    /// Returns [`Error::Ioctl`](crate::Error::Ioctl) if driver doesn't support querying
+    /// blanking. In-memory and DRM framebuffers report no valid fields:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert_eq!(framebuffer.vblank_info().unwrap().flags, 0);
+    /// ```
+    pub fn vblank_info(&self) -> Result<fb_vblank> {
+        let mut vblank = fb_vblank::default();
+        #[cfg(feature = "drm")]
+        {
+            if self.drm_fb.is_some() {
+                return Ok(vblank);
+            }
+        }
+        if let Some(file) = &self.file {
+            unsafe { get_vblank(file.as_raw_fd(), &mut vblank).map_err(Ioctl)? };
+        }
+        Ok(vblank)
+    }
+
+    /// Flush only given region of internal buffer, e.g. one returned by [`Compositor::diff`].
+    /// Parts of region outside of screen are skipped. Does nothing in direct mode and falls back to
+    /// full [`Framebuffer::flush`] with page flipping, since the whole page is
+    /// switched there anyway.
+    /// ```
+    /// # use linfb::{Framebuffer, Rect};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(8, 8);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 8, 8, (255, 255, 255));
+    /// framebuffer.flush_region(Rect::new(2, 2, 4, 10));
+    ///
+    /// let front = framebuffer.front_buffer();
+    /// let pixel = |x: usize, y: usize| &front[(y * 8 + x) * 4..(y * 8 + x + 1) * 4];
+    /// assert_eq!(pixel(2, 2), &[255, 255, 255, 255]);
+    /// assert_eq!(pixel(5, 7), &[255, 255, 255, 255]);
+    /// assert_eq!(pixel(1, 2), &[0, 0, 0, 0]);
+    /// assert_eq!(pixel(6, 7), &[0, 0, 0, 0]);
+    /// ```
+    ///
+    /// Rows are addressed with length of row reported by driver, so padded rows are not sheared
+    /// with any strategy:
+    /// ```
+    /// # use linfb::{BufferStrategy, Framebuffer, Rect};
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let mut screen_info = fb_var_screeninfo::argb8888(3, 2);
+    /// screen_info.yres_virtual = 4;
+    /// let mut fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// fix_info.line_length = 16;
+    /// fix_info.smem_len = 64;
+    /// for &strategy in &[BufferStrategy::Single, BufferStrategy::DoubleCopy, BufferStrategy::DoubleFlip] {
+    ///     let mut framebuffer =
+    ///         Framebuffer::in_memory_with_layout(screen_info.clone(), fix_info.clone()).unwrap();
+    ///     framebuffer.set_buffer_strategy(strategy);
+    ///     framebuffer.set_pixel(2, 1, (255, 255, 255));
+    ///     framebuffer.flush_region(Rect::new(2, 1, 1, 1));
+    ///     let front = framebuffer.front_buffer();
+    ///     assert_eq!(&front[24..28], &[255, 255, 255, 255]);
+    ///     assert!(front[..24].iter().chain(&front[28..]).all(|&byte| byte == 0));
+    /// }
+    /// ```
+    pub fn flush_region(&mut self, region: Rect) {
+        if self.direct {
+            self.flush();
+            return;
+        }
+        if self.flips() {
+            self.flush();
+            return;
+        }
+        self.record_frame();
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let region = match region.intersection(&screen) {
+            Some(region) => self.physical_rect(region),
+            None => return,
+        };
+        let visible = self.page(self.origin.1);
+        let target = &mut self.framebuffer.map_mut()[visible];
+        for y in region.y..region.y + region.height {
+            let start = y * self.stride + (self.origin.0 + region.x) * self.pixel_size;
+            let range = start..start + region.width * self.pixel_size;
+            target[range.clone()].copy_from_slice(&self.screen[range.clone()]);
+            if self.strategy == BufferStrategy::DoubleCopy {
+                self.front[range.clone()].copy_from_slice(&self.screen[range]);
+            }
+        }
+        let (x, y) = (self.origin.0 + region.x, self.origin.1 + region.y);
+        self.present(y..y + region.height, x..x + region.width);
+    }
+
+    /// Wait for the start of next frame using [`FrameLimiter`], then flush. Returns statistics
+    /// of the frame that just ended
+    pub fn flush_paced(&mut self, limiter: &mut FrameLimiter) -> FrameStats {
+        let stats = limiter.tick();
+        self.flush();
+        stats
+    }
+
+    /// Run frame loop at given frame rate: call `frame` to draw, flush with
+    /// [`Framebuffer::flush_vsync`], so frames don't tear where vsync is supported, and sleep for
+    /// the rest of frame budget like [`FrameLimiter`] does, without sleeping if drawing is behind.
+    /// Loop stops after the frame for which `frame` returns [`ControlFlow::Break`], that frame is
+    /// still flushed:
+    /// ```
+    /// # use std::ops::ControlFlow;
+    /// # use std::time::Duration;
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let mut frames = Vec::new();
+    /// framebuffer.run(100.0, |framebuffer, info| {
+    ///     framebuffer.fill_rect(0, 0, 4, 4, (info.index as u8, 0, 0));
+    ///     frames.push(info);
+    ///     if info.index == 4 {
+    ///         ControlFlow::Break(())
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// assert_eq!(frames.len(), 5);
+    /// assert_eq!(frames[0].dt, Duration::default());
+    /// assert!(frames[1].dt >= Duration::from_millis(9));
+    /// assert!(frames[4].elapsed >= Duration::from_millis(39));
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[0, 0, 4, 255]);
+    /// ```
+    ///
+    /// Panics if `target_fps` is not positive
+    pub fn run<F>(&mut self, target_fps: f32, mut frame: F)
+    where
+        F: FnMut(&mut Framebuffer, FrameInfo) -> ControlFlow<()>,
+    {
+        let mut limiter = FrameLimiter::new(target_fps);
+        // The first tick only starts counting, so the first frame gets the whole budget
+        limiter.tick();
+        let start = std::time::Instant::now();
+        let mut previous = None;
+        for index in 0.. {
+            let now = std::time::Instant::now();
+            let info = FrameInfo {
+                index,
+                elapsed: now - start,
+                dt: previous.map_or_else(Default::default, |previous| now - previous),
+            };
+            previous = Some(now);
+            let flow = frame(self, info);
+            self.flush_vsync();
+            if let ControlFlow::Break(()) = flow {
+                break;
+            }
+            limiter.tick();
+        }
+    }
+
+    /// Set pixel at x, y to color.
+    /// Alpha value of color is probably will be ignored, as it doesn't makes sense in this context.
+    /// Pixels outside of the screen are skipped, use [`Framebuffer::try_set_pixel`] to know about
+    /// them
+    ///
+    /// Every channel is scaled from `[0-255]` to the length of corresponding field, so devices with
+    /// any channel lengths are supported. Channels with zero length are skipped:
+    /// ```
+    /// # use std::convert::TryInto;
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let packed = |fields: [(u32, u32); 4], color: (u8, u8, u8, u8)| {
+    ///     let [red, green, blue, transp] = fields.map(|(offset, length)| fb_bitfield::new(offset, length));
+    ///     let screen_info = fb_var_screeninfo::new(1, 1, 32, red, green, blue, transp);
+    ///     let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    ///     framebuffer.set_pixel(0, 0, color);
+    ///     framebuffer.flush();
+    ///     u32::from_ne_bytes(framebuffer.front_buffer().try_into().unwrap())
+    /// };
+    /// let color = (255, 128, 64, 255);
+    /// // ARGB8888
+    /// assert_eq!(packed([(16, 8), (8, 8), (0, 8), (24, 8)], color), 0xff_ff_80_40);
+    /// // A2R10G10B10
+    /// assert_eq!(
+    ///     packed([(20, 10), (10, 10), (0, 10), (30, 2)], color),
+    ///     3 << 30 | 1023 << 20 | 514 << 10 | 257,
+    /// );
+    /// // RGB666 without alpha
+    /// assert_eq!(packed([(12, 6), (6, 6), (0, 6), (0, 0)], color), 63 << 12 | 32 << 6 | 16);
+    /// // RGB555 without alpha
+    /// assert_eq!(packed([(10, 5), (5, 5), (0, 5), (0, 0)], color), 31 << 10 | 16 << 5 | 8);
+    /// ```
+    ///
+    /// Pixels of 24-bit framebuffers take 3 bytes, so writing the last pixel of a row never
+    /// touches the next one:
+    /// ```
+    /// # use linfb::{Framebuffer, RenderTarget};
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::new(
+    ///     3,
+    ///     2,
+    ///     24,
+    ///     fb_bitfield::new(16, 8),
+    ///     fb_bitfield::new(8, 8),
+    ///     fb_bitfield::new(0, 8),
+    ///     fb_bitfield::new(0, 0),
+    /// );
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.clear((1, 2, 3));
+    /// framebuffer.set_pixel(2, 0, (255, 255, 255));
+    /// framebuffer.fill_rect(2, 0, 5, 1, (255, 255, 255));
+    /// framebuffer.draw_span(1, 0, 10, (255, 255, 255).into());
+    /// framebuffer.blit(2, 0, 1, 1, &[255, 255, 255, 255]);
+    /// framebuffer.flush();
+    /// let front = framebuffer.front_buffer();
+    /// assert_eq!(front.len(), 18);
+    /// assert_eq!(&front[..3], &[3, 2, 1]);
+    /// assert_eq!(&front[3..9], &[255; 6]);
+    /// assert_eq!(&front[9..], [3, 2, 1].repeat(3).as_slice());
+    /// assert_eq!(framebuffer.get_pixel(2, 0), (255, 255, 255).into());
+    /// assert_eq!(framebuffer.get_pixel(0, 1), (1, 2, 3).into());
+    /// ```
+    ///
+    /// Grayscale framebuffers get luminance of color, packed into red channel:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let mut screen_info = fb_var_screeninfo::new(
+    ///     4,
+    ///     1,
+    ///     16,
+    ///     fb_bitfield::new(4, 8),
+    ///     fb_bitfield::new(0, 0),
+    ///     fb_bitfield::new(0, 0),
+    ///     fb_bitfield::new(0, 0),
+    /// );
+    /// screen_info.grayscale = 1;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.set_pixel(0, 0, (255, 255, 255));
+    /// framebuffer.set_pixel(1, 0, (0, 0, 0));
+    /// framebuffer.set_pixel(2, 0, (128, 128, 128));
+    /// framebuffer.set_pixel(3, 0, (255, 0, 0));
+    /// framebuffer.flush();
+    /// let front = framebuffer.front_buffer();
+    /// let pixel = |x: usize| u16::from_ne_bytes([front[x * 2], front[x * 2 + 1]]);
+    /// assert_eq!(pixel(0), 255 << 4);
+    /// assert_eq!(pixel(1), 0);
+    /// assert_eq!(pixel(2), 128 << 4);
+    /// assert_eq!(pixel(3), 76 << 4);
+    /// assert_eq!(framebuffer.get_pixel(3, 0), (76, 76, 76).into());
+    /// ```
+    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
+        let _ = self.try_set_pixel(x, y, color);
+    }
+
+    /// Same as [`Framebuffer::set_pixel`], but returns [`Error::OutOfBounds`] instead of
+    /// skipping pixels outside of the screen. Nothing is written then, in particular pixels past
+    /// the end of row don't wrap to the next one:
+    /// ```
+    /// # use linfb::{Error, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert!(framebuffer.try_set_pixel(3, 3, (255, 255, 255)).is_ok());
+    /// assert!(matches!(
+    ///     framebuffer.try_set_pixel(4, 0, (255, 255, 255)),
+    ///     Err(Error::OutOfBounds(4, 0))
+    /// ));
+    /// assert!(framebuffer.try_set_pixel(0, 100, (255, 255, 255)).is_err());
+    /// framebuffer.set_pixel(4, 0, (255, 255, 255));
+    /// framebuffer.set_pixel(u32::MAX, u32::MAX, (255, 255, 255));
+    /// assert_eq!(framebuffer.get_pixel(0, 1), (0, 0, 0, 0).into());
+    /// ```
+    pub fn try_set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) -> Result<()> {
+        if x as usize >= self.width() || y as usize >= self.height() {
+            return Err(OutOfBounds(x, y));
+        }
+        let pixel_pos = self.offset(x as usize, y as usize);
+        let pixel = self.pack(color.into());
+        let pixel = &pixel[..self.pixel_size];
+        self.buffer_mut()[pixel_pos..pixel_pos + pixel.len()].copy_from_slice(pixel);
+        Ok(())
+    }
+
+    /// Get color of pixel at x, y from the buffer being drawn into. Channels shorter than 8 bits
+    /// are scaled back to `[0-255]`, alpha is 255 if framebuffer has no alpha channel.
+    ///
+    /// Panics if x, y is out of screen
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        let pixel_pos = self.offset(x as usize, y as usize);
+        self.unpack(&self.buffer()[pixel_pos..pixel_pos + self.pixel_size])
+    }
+
+    /// Read colors of region with top left angle at x, y from the buffer being drawn into, row by
+    /// row. Parts of region outside of the screen are skipped. Can be used to save what's under a
+    /// cursor and draw it back with [`RenderTarget::draw_colors`]:
+    /// ```
+    /// # use linfb::{Framebuffer, RenderTarget};
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let layouts = [
+    ///     // RGBA
+    ///     [(0, 8), (8, 8), (16, 8), (24, 8)],
+    ///     // BGRA
+    ///     [(16, 8), (8, 8), (0, 8), (24, 8)],
+    ///     // BGR565, channels are scaled back up
+    ///     [(11, 5), (5, 6), (0, 5), (0, 0)],
+    /// ];
+    /// for fields in layouts.iter() {
+    ///     let [red, green, blue, transp] = fields.map(|(offset, length)| fb_bitfield::new(offset, length));
+    ///     let screen_info = fb_var_screeninfo::new(8, 6, 32, red, green, blue, transp);
+    ///     let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    ///     for &color in &[(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255), (0, 0, 0)] {
+    ///         framebuffer.set_pixel(3, 2, color);
+    ///         assert_eq!(framebuffer.get_pixel(3, 2), color.into());
+    ///     }
+    ///
+    ///     framebuffer.fill_rect(0, 0, 8, 6, (255, 0, 0));
+    ///     framebuffer.set_pixel(7, 5, (0, 0, 255));
+    ///     let under_cursor = framebuffer.read_region(6, 4, 4, 4);
+    ///     assert_eq!(under_cursor.len(), 2);
+    ///     assert_eq!(under_cursor[1], vec![(255, 0, 0).into(), (0, 0, 255).into()]);
+    ///
+    ///     framebuffer.fill_rect(6, 4, 2, 2, (255, 255, 255));
+    ///     for (inner_y, row) in under_cursor.iter().enumerate() {
+    ///         framebuffer.draw_colors(6, 4 + inner_y, row);
+    ///     }
+    ///     assert_eq!(framebuffer.get_pixel(7, 5), (0, 0, 255).into());
+    ///     assert_eq!(framebuffer.get_pixel(6, 4), (255, 0, 0).into());
+    /// }
+    /// ```
+    pub fn read_region(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<Vec<Color>> {
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let region = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let region = match region.intersection(&screen) {
+            Some(region) => region,
+            None => return Vec::new(),
+        };
+        let buffer = self.buffer();
+        (region.y..region.y + region.height)
+            .map(|y| {
+                (region.x..region.x + region.width)
+                    .map(|x| {
+                        let start = self.offset(x, y);
+                        self.unpack(&buffer[start..start + self.pixel_size])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Position of pixel at rotated coordinates x, y in buffers
+    fn offset(&self, x: usize, y: usize) -> usize {
+        let (width, height) = (
+            self.screen_info.xres as usize,
+            self.screen_info.yres as usize,
+        );
+        let (x, y) = self.rotation.to_physical(x, y, width, height);
+        self.physical_offset(x, y)
+    }
+
+    /// Position of pixel at physical coordinates x, y in buffers. Buffers start at the first
+    /// visible row, but span the whole width of virtual screen
+    fn physical_offset(&self, x: usize, y: usize) -> usize {
+        y * self.stride + (self.origin.0 + x) * self.pixel_size
+    }
+
+    /// Physical rectangle which is covered by rectangle in rotated coordinates. Rectangle must be
+    /// inside of the screen
+    fn physical_rect(&self, rect: Rect) -> Rect {
+        let (width, height) = (
+            self.screen_info.xres as usize,
+            self.screen_info.yres as usize,
+        );
+        match self.rotation {
+            Rotation::Deg0 => rect,
+            Rotation::Deg90 => Rect::new(
+                width - rect.y - rect.height,
+                rect.x,
+                rect.height,
+                rect.width,
+            ),
+            Rotation::Deg180 => Rect::new(
+                width - rect.x - rect.width,
+                height - rect.y - rect.height,
+                rect.width,
+                rect.height,
+            ),
+            Rotation::Deg270 => Rect::new(
+                rect.y,
+                height - rect.x - rect.width,
+                rect.height,
+                rect.width,
+            ),
+        }
+    }
+
+    /// Call `f` with index and bytes of every pixel of row of `len` pixels, which starts at
+    /// rotated coordinates x, y. Whole row must be inside of the screen
+    fn for_row_mut(&mut self, x: usize, y: usize, len: usize, mut f: impl FnMut(usize, &mut [u8])) {
+        let start = self.offset(x, y);
+        let pixel_size = self.pixel_size;
+        // Distance to the next pixel of row in memory, rows of rotated screen are columns or go
+        // backwards
+        let step = match self.rotation {
+            Rotation::Deg0 => pixel_size as isize,
+            Rotation::Deg90 => self.stride as isize,
+            Rotation::Deg180 => -(pixel_size as isize),
+            Rotation::Deg270 => -(self.stride as isize),
+        };
+        let buffer = self.buffer_mut();
+        if step == pixel_size as isize {
+            let pixels = buffer[start..start + len * pixel_size].chunks_exact_mut(pixel_size);
+            for (i, pixel) in pixels.enumerate() {
+                f(i, pixel);
+            }
+        } else {
+            for i in 0..len {
+                let pixel_start = (start as isize + i as isize * step) as usize;
+                f(i, &mut buffer[pixel_start..pixel_start + pixel_size]);
+            }
+        }
+    }
+
+    fn encode(&self, color: Color) -> u32 {
+        self.codec.encode(color)
+    }
+
+    /// Encoded pixel as it's stored in memory, only the first bytes of pixel size are used
+    fn pack(&self, color: Color) -> [u8; 4] {
+        to_bytes(self.encode(color), self.pixel_size)
+    }
+
+    /// Decode pixel stored in memory
+    fn unpack(&self, bytes: &[u8]) -> Color {
+        self.codec.decode(from_bytes(bytes))
+    }
+
+    /// Fill the whole screen with color. Pixel is encoded once and copied into every row, padding
+    /// at the ends of rows is not touched:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::argb8888(3, 2);
+    /// let mut fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// fix_info.line_length = 16;
+    /// fix_info.smem_len = 32;
+    /// let mut framebuffer = Framebuffer::in_memory_with_layout(screen_info, fix_info).unwrap();
+    /// framebuffer.clear((10, 20, 30));
+    /// framebuffer.flush();
+    /// for row in framebuffer.front_buffer().chunks(16) {
+    ///     assert_eq!(&row[..12], [30, 20, 10, 255].repeat(3).as_slice());
+    ///     assert_eq!(&row[12..], &[0; 4]);
+    /// }
+    /// ```
+    pub fn clear<C: Into<Color>>(&mut self, color: C) {
+        let row = self.pack(color.into())[..self.pixel_size].repeat(self.screen_info.xres as usize);
+        let stride = usize::max(self.stride, 1);
+        let start = self.physical_offset(0, 0);
+        for target in self.buffer_mut().chunks_mut(stride) {
+            target[start..start + row.len()].copy_from_slice(&row);
+        }
+    }
+
+    /// Fill rectangle with top left angle at x, y with color. Works like drawing an opaque
+    /// [`Rectangle`](crate::shape::Rectangle) without border, but much faster: pixel is encoded
+    /// once and copied into every row as a whole. Parts of rectangle outside of the screen are
+    /// skipped. Color is written as is, translucent colors are not blended:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(16, 12);
+    /// let mut fast = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    /// let mut slow = Framebuffer::in_memory(screen_info).unwrap();
+    /// for &(x, y, width, height) in &[(2, 3, 5, 4), (10, 8, 20, 20), (0, 0, 16, 1), (20, 0, 5, 5)] {
+    ///     fast.fill_rect(x, y, width, height, (200, 100, 50));
+    ///     let rectangle = Rectangle::builder()
+    ///         .width(width as usize)
+    ///         .height(height as usize)
+    ///         .border_width(0)
+    ///         .fill_color((200, 100, 50))
+    ///         .build()
+    ///         .unwrap();
+    ///     slow.draw(x, y, &rectangle);
+    /// }
+    /// fast.flush();
+    /// slow.flush();
+    /// assert_eq!(fast.front_buffer(), slow.front_buffer());
+    /// ```
+    pub fn fill_rect<C: Into<Color>>(&mut self, x: u32, y: u32, width: u32, height: u32, color: C) {
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let rect = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let rect = match rect.intersection(&screen) {
+            Some(rect) => self.physical_rect(rect),
+            None => return,
+        };
+        let row = self.pack(color.into())[..self.pixel_size].repeat(rect.width);
+        for y in rect.y..rect.y + rect.height {
+            let start = self.physical_offset(rect.x, y);
+            self.buffer_mut()[start..start + row.len()].copy_from_slice(&row);
+        }
+    }
+
+    /// Copy pixels from tightly packed RGBA buffer into rectangle with top left angle at x, y,
+    /// e.g. a decoded video frame. Source has 4 bytes per pixel in R, G, B, A order, rows go one
+    /// after another without padding. Parts of rectangle outside of the screen are skipped, pixels
+    /// are written as is, without blending.
+    ///
+    /// Devices with 8-bit channels get bytes of every row reordered, or copied directly if the
+    /// order is the same, other formats are encoded pixel by pixel. Result is the same either way:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let (width, height) = (5, 4);
+    /// let rgba: Vec<u8> = (0..width * height * 4).map(|i| (i * 37 % 256) as u8).collect();
+    /// let layouts = [
+    ///     [(0, 8), (8, 8), (16, 8), (24, 8)],
+    ///     [(16, 8), (8, 8), (0, 8), (24, 8)],
+    ///     [(16, 8), (8, 8), (0, 8), (0, 0)],
+    ///     [(20, 10), (10, 10), (0, 10), (30, 2)],
+    /// ];
+    /// for fields in layouts.iter() {
+    ///     let [red, green, blue, transp] = fields.map(|(offset, length)| fb_bitfield::new(offset, length));
+    ///     let screen_info = fb_var_screeninfo::new(8, 6, 32, red, green, blue, transp);
+    ///     let mut blitted = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     let mut expected = Framebuffer::in_memory(screen_info).unwrap();
+    ///     // Right column and bottom row are clipped
+    ///     blitted.blit(4, 3, width, height, &rgba);
+    ///     for y in 0..3 {
+    ///         for x in 0..4 {
+    ///             let i = ((y * width + x) * 4) as usize;
+    ///             let color = (rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]);
+    ///             expected.set_pixel(4 + x, 3 + y, color);
+    ///         }
+    ///     }
+    ///     blitted.flush();
+    ///     expected.flush();
+    ///     assert_eq!(blitted.front_buffer(), expected.front_buffer());
+    /// }
+    /// ```
+    ///
+    /// Panics if `rgba` is shorter than `width * height * 4` bytes
+    pub fn blit(&mut self, x: u32, y: u32, width: u32, height: u32, rgba: &[u8]) {
+        let (width, height) = (width as usize, height as usize);
+        assert!(
+            rgba.len() >= width * height * 4,
+            "buffer of {} bytes can't hold {}x{} pixels",
+            rgba.len(),
+            width,
+            height
+        );
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let region = match Rect::new(x as usize, y as usize, width, height).intersection(&screen) {
+            Some(region) => region,
+            None => return,
+        };
+        let codec = self.codec;
+        let pixel_size = self.pixel_size;
+        let bytes = match pixel_size {
+            4 if codec.is_plain() => byte_positions(&codec.format),
+            _ => None,
+        };
+        for inner_y in 0..region.height {
+            let source_start = inner_y * width * 4;
+            let source = &rgba[source_start..source_start + region.width * 4];
+            let (x, y) = (region.x, region.y + inner_y);
+            match bytes {
+                Some([Some(0), Some(1), Some(2), Some(3)]) if self.rotation == Rotation::Deg0 => {
+                    let start = self.offset(x, y);
+                    self.buffer_mut()[start..start + source.len()].copy_from_slice(source);
+                }
+                Some(bytes) => self.for_row_mut(x, y, region.width, |i, pixel| {
+                    pixel.copy_from_slice(&[0; 4]);
+                    for (byte, value) in bytes.iter().zip(&source[i * 4..i * 4 + 4]) {
+                        if let Some(byte) = byte {
+                            pixel[*byte] = *value;
+                        }
+                    }
+                }),
+                None => self.for_row_mut(x, y, region.width, |i, pixel| {
+                    let source = &source[i * 4..i * 4 + 4];
+                    let color = Color::from((source[0], source[1], source[2], source[3]));
+                    pixel.copy_from_slice(&to_bytes(codec.encode(color), pixel_size)[..pixel_size]);
+                }),
+            }
+        }
+    }
+
+    /// Draw shape on the buffer being drawn into. Pixels of shape are written as is, without blending, and
+    /// pixels outside of the screen are skipped, so shapes can hang off any edge:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::Rectangle;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(32, 32);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let rectangle = Rectangle::builder()
+    ///     .width(20)
+    ///     .height(20)
+    ///     .border_width(0)
+    ///     .fill_color((255, 255, 255))
+    ///     .build()
+    ///     .unwrap();
+    /// framebuffer.draw(22, 22, &rectangle);
+    /// assert_eq!(framebuffer.get_pixel(31, 31), (255, 255, 255).into());
+    /// // Nothing wraps around to the first row or column
+    /// for i in 0..32 {
+    ///     assert_eq!(framebuffer.get_pixel(i, 0), (0, 0, 0, 0).into());
+    ///     assert_eq!(framebuffer.get_pixel(0, i), (0, 0, 0, 0).into());
+    /// }
+    /// ```
+    ///
+    /// [`Compositor`]s are rendered into internal buffer which is reused between calls, so
+    /// drawing a compositor on every frame doesn't allocate.
+    pub fn draw<T: Shape + ?Sized>(&mut self, x: u32, y: u32, shape: &T) {
+        self.draw_clipped(x as i64, y as i64, shape);
+    }
+
+    /// Same as [`Framebuffer::draw`], but shape can be placed at negative coordinates, e.g. to
+    /// slide it in from the left or top edge. Only the part of shape inside of the screen is
+    /// drawn, shapes which are entirely outside of it are not even rendered:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Color, Rectangle, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(32, 32);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let rectangle = Rectangle::builder()
+    ///     .width(60)
+    ///     .height(60)
+    ///     .border_width(0)
+    ///     .fill_color((255, 255, 255))
+    ///     .build()
+    ///     .unwrap();
+    /// let white: Color = (255, 255, 255).into();
+    /// let black: Color = (0, 0, 0, 0).into();
+    ///
+    /// framebuffer.draw_clipped(-50, -50, &rectangle);
+    /// assert_eq!(framebuffer.get_pixel(0, 0), white);
+    /// assert_eq!(framebuffer.get_pixel(9, 9), white);
+    /// assert_eq!(framebuffer.get_pixel(10, 9), black);
+    /// assert_eq!(framebuffer.get_pixel(9, 10), black);
+    ///
+    /// // Hanging off the bottom right corner
+    /// framebuffer.draw_clipped(30, 20, &rectangle);
+    /// assert_eq!(framebuffer.get_pixel(31, 31), white);
+    /// assert_eq!(framebuffer.get_pixel(30, 20), white);
+    /// assert_eq!(framebuffer.get_pixel(29, 20), black);
+    /// assert_eq!(framebuffer.get_pixel(30, 19), black);
+    /// for x in 10..32 {
+    ///     assert_eq!(framebuffer.get_pixel(x, 0), black);
+    /// }
+    ///
+    /// #[derive(Clone)]
+    /// struct Expensive;
+    ///
+    /// impl Shape for Expensive {
+    ///     fn render(&self) -> Vec<Vec<Option<Color>>> {
+    ///         unreachable!("shape outside of screen is rendered")
+    ///     }
+    ///
+    ///     fn dimensions(&self) -> (usize, usize) {
+    ///         (10, 10)
+    ///     }
+    /// }
+    /// for &(x, y) in &[(-10, 0), (0, -10), (32, 0), (0, 32), (-1000, 1000)] {
+    ///     framebuffer.draw_clipped(x, y, &Expensive);
+    /// }
+    /// ```
+    pub fn draw_clipped<T: Shape + ?Sized>(&mut self, x: i64, y: i64, shape: &T) {
+        if x >= self.width() as i64 || y >= self.height() as i64 {
+            return;
+        }
+        if x < 0 || y < 0 {
+            let (width, height) = shape.dimensions();
+            if x + width as i64 <= 0 || y + height as i64 <= 0 {
+                return;
+            }
+        }
+        if let Some(compositor) = shape.as_any().downcast_ref::<Compositor>() {
+            let mut scratch = std::mem::take(&mut self.scratch);
+            compositor.render_themed(&mut scratch, self.palette());
+            let mut target = Shifted {
+                target: self,
+                dx: x,
+                dy: y,
+            };
+            for (inner_y, row) in scratch.rows().enumerate() {
+                target.draw_colors(0, inner_y, row);
+            }
+            self.scratch = scratch;
+        } else {
+            let mut target = Shifted {
+                target: self,
+                dx: x,
+                dy: y,
+            };
+            shape.render_into(&mut target, 0, 0);
+        }
+    }
+
+    /// Draw [`PositionedShape`] at its position, e.g. one taken from [`Compositor`]. Hidden shapes
+    /// are skipped and translucent ones are blended over current contents. Anchors are applied
+    /// to the whole screen. Parts of shape outside of the screen are skipped like with
+    /// [`Framebuffer::draw_clipped`]:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Anchor, Rectangle, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(16, 16);
+    /// let square = |color: (u8, u8, u8)| {
+    ///     Rectangle::builder()
+    ///         .width(4)
+    ///         .height(4)
+    ///         .border_width(0)
+    ///         .fill_color(color)
+    ///         .build()
+    ///         .unwrap()
+    /// };
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 16, 16, (0, 0, 0));
+    /// let mut hidden = square((0, 255, 0)).at(0, 0);
+    /// hidden.visible = false;
+    /// let mut faded = square((200, 100, 0)).at(14, 14);
+    /// faded.opacity = 0.5;
+    /// let shapes = vec![
+    ///     square((255, 0, 0)).at(2, 2),
+    ///     square((0, 0, 255)).at(1, 1).anchored(Anchor::BottomRight),
+    ///     hidden,
+    ///     faded,
+    /// ];
+    /// framebuffer.draw_all(&shapes);
+    /// assert_eq!(framebuffer.get_pixel(2, 2), (255, 0, 0).into());
+    /// assert_eq!(framebuffer.get_pixel(0, 0), (0, 0, 0).into());
+    /// assert_eq!(framebuffer.get_pixel(11, 11), (0, 0, 255).into());
+    /// assert_eq!(framebuffer.get_pixel(15, 15), (100, 50, 0).into());
+    /// // Nothing wraps around to the next row
+    /// assert_eq!(framebuffer.get_pixel(0, 15), (0, 0, 0).into());
+    /// ```
+    pub fn draw_positioned(&mut self, shape: &PositionedShape) {
+        if !shape.visible || shape.opacity <= 0f32 {
+            return;
+        }
+        let (x, y) = match shape.anchor {
+            None => (shape.x, shape.y),
+            Some(anchor) => {
+                let (width, height) = shape.shape.dimensions();
+                let (horizontal, vertical) = anchor.align();
+                (
+                    horizontal.place(0, self.width(), width, shape.x),
+                    vertical.place(0, self.height(), height, shape.y),
+                )
+            }
+        };
+        if shape.opacity >= 1f32 {
+            self.draw_clipped(x as i64, y as i64, &*shape.shape);
+        } else if x < self.width() && y < self.height() {
+            let mut target = Faded {
+                target: &mut Blended(self),
+                opacity: shape.opacity,
+            };
+            shape.shape.render_into(&mut target, x, y);
+        }
+    }
+
+    /// Draw every [`PositionedShape`] in order, see [`Framebuffer::draw_positioned`]
+    pub fn draw_all<'a>(&mut self, shapes: impl IntoIterator<Item = &'a PositionedShape>) {
+        for shape in shapes {
+            self.draw_positioned(shape);
+        }
+    }
+
+    /// Same as [`Framebuffer::draw`], but translucent pixels of shape are alpha-blended over
+    /// current contents of the buffer being drawn into instead of overwriting them, e.g. for
+    /// translucent captions drawn without [`Compositor`]. Opaque pixels are written as is:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::Rectangle;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(8, 8);
+    /// let rectangle = Rectangle::builder()
+    ///     .width(4)
+    ///     .height(4)
+    ///     .border_width(0)
+    ///     .fill_color((0, 200, 0, 128))
+    ///     .build()
+    ///     .unwrap();
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 8, 8, (200, 0, 0));
+    /// framebuffer.draw_blended(2, 2, &rectangle);
+    /// assert_eq!(framebuffer.get_pixel(3, 3), (99, 100, 0).into());
+    /// assert_eq!(framebuffer.get_pixel(1, 1), (200, 0, 0).into());
+    ///
+    /// framebuffer.draw(2, 2, &rectangle);
+    /// assert_eq!(framebuffer.get_pixel(3, 3), (0, 200, 0, 128).into());
+    /// ```
+    pub fn draw_blended<T: Shape + ?Sized>(&mut self, x: u32, y: u32, shape: &T) {
+        if (x as usize) < self.width() && (y as usize) < self.height() {
+            shape.render_into(&mut Blended(self), x as usize, y as usize);
+        }
+    }
+
+    /// Same as [`Framebuffer::draw_blended`], but colors of shape are combined with current
+    /// contents of the buffer being drawn into using given [`BlendMode`]. Alpha of shape scales
+    /// the effect, transparent pixels are skipped:
+    /// ```
+    /// # use linfb::{BlendMode, Framebuffer};
+    /// # use linfb::shape::Rectangle;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let square = |color| {
+    ///     Rectangle::builder()
+    ///         .width(1)
+    ///         .height(1)
+    ///         .border_width(0)
+    ///         .fill_color(color)
+    ///         .build()
+    ///         .unwrap()
+    /// };
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let mut blend = |mode, color| {
+    ///     framebuffer.fill_rect(0, 0, 1, 1, (100, 50, 200));
+    ///     framebuffer.draw_with(0, 0, &square(color), mode);
+    ///     framebuffer.get_pixel(0, 0)
+    /// };
+    /// // Both red and blue saturate
+    /// assert_eq!(blend(BlendMode::Add, (200, 100, 240, 255)), (255, 150, 255).into());
+    /// // 100 * 200 / 255 = 78.4, 50 * 100 / 255 = 19.6, 200 * 40 / 255 = 31.4
+    /// assert_eq!(blend(BlendMode::Multiply, (200, 100, 40, 255)), (78, 20, 31).into());
+    /// // 255 - 155 * 55 / 255 = 221.6, 255 - 205 * 155 / 255 = 130.4, 255 - 55 * 215 / 255 = 208.6
+    /// assert_eq!(blend(BlendMode::Screen, (200, 100, 40, 255)), (222, 130, 209).into());
+    /// assert_eq!(blend(BlendMode::Normal, (200, 100, 40, 255)), (200, 100, 40).into());
+    ///
+    /// // Half of the effect: 100 + 155 * 0.502 = 177.8, 50 + 100 * 0.502 = 100.2, 200 + 40 * 0.502 = 220.1
+    /// assert_eq!(blend(BlendMode::Add, (200, 100, 40, 128)), (178, 100, 220).into());
+    /// assert_eq!(blend(BlendMode::Multiply, (200, 100, 40, 0)), (100, 50, 200).into());
+    /// ```
+    pub fn draw_with<T: Shape + ?Sized>(&mut self, x: u32, y: u32, shape: &T, mode: BlendMode) {
+        if (x as usize) < self.width() && (y as usize) < self.height() {
+            shape.render_into(&mut Mixed(self, mode), x as usize, y as usize);
+        }
+    }
+
+    /// Draw shape by XOR-ing packed values of its opaque pixels with the buffer being drawn into.
+    /// Translucent and transparent pixels are skipped. Drawing the same shape at the same position
+    /// again restores previous contents exactly, so it's useful for cursors and selection
+    /// rectangles which are drawn over content without saving it. Pixels are changed in the same
+    /// buffer as with [`Framebuffer::draw`], so they're picked up by every flush:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(20, 20);
+    /// # use linfb::BufferStrategy;
+    /// // Outline only, inside of selection is transparent
+    /// let selection = Rectangle::builder()
+    ///     .width(10)
+    ///     .height(8)
+    ///     .border_color((255, 255, 255))
+    ///     .build()
+    ///     .unwrap();
+    /// for &strategy in &[BufferStrategy::Single, BufferStrategy::DoubleCopy] {
+    ///     let mut framebuffer = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     framebuffer.set_buffer_strategy(strategy);
+    ///     framebuffer.fill_rect(0, 0, 20, 20, (10, 100, 200));
+    ///     framebuffer.fill_rect(5, 5, 5, 5, (255, 255, 0));
+    ///     framebuffer.flush();
+    ///     let original = framebuffer.front_buffer().to_vec();
+    ///
+    ///     framebuffer.draw_xor(3, 4, &selection);
+    ///     framebuffer.fill_rect_xor(0, 15, 20, 1, (255, 255, 255));
+    ///     framebuffer.flush();
+    ///     assert_eq!(framebuffer.get_pixel(3, 4), (245, 155, 55).into());
+    ///     assert_eq!(framebuffer.get_pixel(5, 15), (245, 155, 55).into());
+    ///     assert_eq!(framebuffer.get_pixel(6, 6), (255, 255, 0).into());
+    ///     assert_ne!(framebuffer.front_buffer(), &original[..]);
+    ///
+    ///     framebuffer.draw_xor(3, 4, &selection);
+    ///     framebuffer.fill_rect_xor(0, 15, 20, 1, (255, 255, 255));
+    ///     framebuffer.flush();
+    ///     assert_eq!(framebuffer.front_buffer(), &original[..]);
+    ///
+    ///     // Shapes behind pointers work too, rectangles are clipped to screen before drawing
+    ///     let boxed: Box<dyn Shape> = Box::new(selection.clone());
+    ///     framebuffer.draw_xor(3, 4, &*boxed);
+    ///     framebuffer.draw_xor(3, 4, &*boxed);
+    ///     framebuffer.fill_rect_xor(0, u32::MAX, 20, 2, (255, 255, 255));
+    ///     framebuffer.fill_rect_xor(0, 0, u32::MAX, u32::MAX, (255, 255, 255));
+    ///     framebuffer.fill_rect_xor(0, 0, u32::MAX, u32::MAX, (255, 255, 255));
+    ///     framebuffer.flush();
+    ///     assert_eq!(framebuffer.front_buffer(), &original[..]);
+    /// }
+    /// ```
+    pub fn draw_xor<T: Shape + ?Sized>(&mut self, x: u32, y: u32, shape: &T) {
+        shape.render_into(&mut Xored(self), x as usize, y as usize);
+    }
+
+    /// Fill rectangle by XOR-ing its color with the buffer being drawn into. See
+    /// [`Framebuffer::draw_xor`]
+    pub fn fill_rect_xor<C: Into<Color>>(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        color: C,
+    ) {
+        let color = color.into();
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let rect = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let rect = match rect.intersection(&screen) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let mut target = Xored(self);
+        for y in rect.y..rect.y + rect.height {
+            target.draw_span(rect.x, y, rect.width, color);
+        }
+    }
+
+    /// Draw text with top left angle at x, y using default sans-serif system font. Text is
+    /// alpha-blended over the current contents and clipped at screen edges. This is a shortcut
+    /// for simple cases, use [`Caption`](shape::Caption) for more control.
+    /// ```no_run
+    /// # use linfb::Framebuffer;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut framebuffer = Framebuffer::open()?;
+    /// framebuffer.draw_text(10, 10, "Hello, framebuffer!", 32, (255, 255, 255).into())?;
+    /// framebuffer.flush();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "text")]
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, size: u32, color: Color) -> Result<()> {
+        let caption = shape::Caption::builder()
+            .text(text.into())
+            .size(size)
+            .font(crate::text::default_font()?)
+            .color(color)
+            .build()?;
+        caption.render_into(&mut Blended(self), x as usize, y as usize);
+        Ok(())
+    }
+
+    /// Load image from file and draw it with top left angle at x, y. Image is alpha-blended over
+    /// the current contents and clipped at screen edges. Errors are returned if image can't be
+    /// loaded:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(16, 16);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// assert!(framebuffer.draw_image_file(0, 0, "/does/not/exist.png").is_err());
+    /// ```
+    #[cfg(feature = "images")]
+    pub fn draw_image_file<P: AsRef<Path>>(&mut self, x: u32, y: u32, path: P) -> Result<()> {
+        let image = shape::Image::from_path(path)?;
+        image.render_into(&mut Blended(self), x as usize, y as usize);
+        Ok(())
+    }
+
+    /// Capture the buffer being drawn into as [`Image`](shape::Image), i.e. what will be on
+    /// screen after the next flush. Pixels are decoded with channel layout of framebuffer, alpha
+    /// is 255 if framebuffer has no alpha channel:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::Shape;
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// // Blue in the highest byte, no alpha
+    /// let screen_info = fb_var_screeninfo::new(
+    ///     4,
+    ///     4,
+    ///     32,
+    ///     fb_bitfield::new(8, 8),
+    ///     fb_bitfield::new(16, 8),
+    ///     fb_bitfield::new(24, 8),
+    ///     fb_bitfield::new(0, 0),
+    /// );
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 2, 4, (255, 0, 0));
+    /// let snapshot = framebuffer.snapshot();
+    /// assert_eq!(snapshot.render()[0][0], Some((255, 0, 0, 255).into()));
+    /// assert_eq!(snapshot.render()[3][3], Some((0, 0, 0, 255).into()));
+    ///
+    /// // Nothing is flushed yet
+    /// let live = framebuffer.snapshot_live();
+    /// assert_eq!(live.render()[0][0], Some((0, 0, 0, 255).into()));
+    /// framebuffer.flush();
+    /// assert_eq!(framebuffer.snapshot_live().render(), snapshot.render());
+    /// ```
+    #[cfg(feature = "images")]
+    pub fn snapshot(&self) -> shape::Image {
+        self.capture(self.buffer()).into()
+    }
+
+    /// Same as [`Framebuffer::snapshot`], but captures the real framebuffer page which is
+    /// currently displayed, including anything drawn there by other processes
+    #[cfg(feature = "images")]
+    pub fn snapshot_live(&self) -> shape::Image {
+        self.capture(self.front_buffer()).into()
+    }
+
+    /// Save [`Framebuffer::snapshot`] to file. Format is chosen by extension of path:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Image, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(1, 1, 2, 2, (10, 20, 30));
+    /// let path = std::env::temp_dir().join("linfb-screenshot.png");
+    /// framebuffer.save_screenshot(&path).unwrap();
+    /// let saved = Image::from_path(&path).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert_eq!(saved.render(), framebuffer.snapshot().render());
+    /// assert!(framebuffer.save_screenshot("/does/not/exist.png").is_err());
+    /// ```
+    #[cfg(feature = "images")]
+    pub fn save_screenshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.capture(self.buffer()).save(path)?;
+        Ok(())
+    }
+
+    /// Start saving every flushed frame into directory as `frame_000001.png`, `frame_000002.png`
+    /// and so on, creating directory if needed. [`Framebuffer::flush`],
+    /// [`Framebuffer::flush_region`] and [`Framebuffer::flush_dirty`] capture the whole buffer
+    /// being flushed. Recording which was already running is stopped:
+    /// ```
+    /// # use linfb::Framebuffer;
+    /// # use linfb::shape::{Image, Shape};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let directory = std::env::temp_dir().join("linfb-recording");
+    /// # let _ = std::fs::remove_dir_all(&directory);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let recording = framebuffer.start_recording(&directory).unwrap();
+    /// recording.skip_identical(true);
+    /// framebuffer.fill_rect(0, 0, 2, 2, (255, 0, 0));
+    /// framebuffer.flush();
+    /// // Nothing changed, so this frame is skipped
+    /// framebuffer.flush();
+    /// framebuffer.fill_rect(2, 2, 2, 2, (0, 0, 255));
+    /// framebuffer.flush();
+    /// framebuffer.stop_recording();
+    /// // Not recorded anymore
+    /// framebuffer.flush();
+    /// assert_eq!(recording.finish().unwrap(), 2);
+    ///
+    /// let last = Image::from_path(directory.join("frame_000002.png")).unwrap();
+    /// assert_eq!(last.render(), framebuffer.snapshot().render());
+    /// assert!(!directory.join("frame_000003.png").exists());
+    /// std::fs::remove_dir_all(&directory).unwrap();
+    /// ```
+    #[cfg(feature = "images")]
+    pub fn start_recording<P: AsRef<Path>>(&mut self, directory: P) -> Result<RecordingHandle> {
+        let (recorder, handle) = Recorder::start(directory.as_ref())?;
+        self.recording = Some(recorder);
+        Ok(handle)
+    }
+
+    /// Stop saving flushed frames started by [`Framebuffer::start_recording`]. Frames which are
+    /// already captured are still saved, use [`RecordingHandle::finish`] to wait for them
+    #[cfg(feature = "images")]
+    pub fn stop_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// Capture the buffer being flushed if recording
+    fn record_frame(&mut self) {
+        #[cfg(feature = "images")]
+        {
+            if self.recording.is_some() {
+                let frame = self.capture(self.buffer());
+                if let Some(recording) = &mut self.recording {
+                    recording.record(frame);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "images")]
+    fn capture(&self, buffer: &[u8]) -> image::RgbaImage {
+        image::RgbaImage::from_fn(self.width() as u32, self.height() as u32, |x, y| {
+            let start = self.offset(x as usize, y as usize);
+            let color = self.unpack(&buffer[start..start + self.pixel_size]);
+            image::Rgba([color.red, color.green, color.blue, color.alpha])
+        })
+    }
+
+    /// Save what's currently displayed and restore it when returned guard is dropped, even on
+    /// panic, so the console is not left with output of application after it exits. Guard
+    /// dereferences to framebuffer, so everything is drawn through it as usual. Use
+    /// [`RestoreGuard::commit`] to leave output on screen instead:
+    /// ```
+    /// # use linfb::{BufferStrategy, Framebuffer};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// # screen_info.yres_virtual = 8;
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// // Console contents
+    /// framebuffer.fill_rect(0, 0, 4, 4, (0, 0, 255));
+    /// framebuffer.flush();
+    /// let console = framebuffer.front_buffer().to_vec();
+    ///
+    /// {
+    ///     let mut guard = framebuffer.with_restore();
+    ///     guard.set_buffer_strategy(BufferStrategy::DoubleFlip);
+    ///     guard.fill_rect(0, 0, 4, 4, (255, 0, 0));
+    ///     guard.flush();
+    ///     assert_ne!(guard.front_buffer(), &console[..]);
+    /// }
+    /// assert_eq!(framebuffer.front_buffer(), &console[..]);
+    ///
+    /// let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    ///     let mut guard = framebuffer.with_restore();
+    ///     guard.fill_rect(0, 0, 4, 4, (255, 0, 0));
+    ///     guard.flush();
+    ///     panic!("application crashed");
+    /// }));
+    /// assert!(result.is_err());
+    /// assert_eq!(framebuffer.front_buffer(), &console[..]);
+    ///
+    /// let mut guard = framebuffer.with_restore();
+    /// guard.fill_rect(0, 0, 4, 4, (255, 0, 0));
+    /// guard.flush();
+    /// guard.commit();
+    /// assert_eq!(&framebuffer.front_buffer()[..4], &[0, 0, 255, 255]);
+    /// ```
+    pub fn with_restore(&mut self) -> RestoreGuard<'_> {
+        let saved = (self.origin.1, self.front_buffer().to_vec());
+        RestoreGuard {
+            framebuffer: self,
+            saved: Some(saved),
+        }
+    }
+
+    /// Display memory starting at given row of virtual screen again and write contents there
+    fn restore_page(&mut self, row: usize, contents: &[u8]) {
+        if self.origin.1 != row {
+            let mut screen_info = self.screen_info.clone();
+            screen_info.yoffset = row as u32;
+            if let Some(file) = &self.file {
+                let _ = unsafe { pan_display(file.as_raw_fd(), &screen_info) };
+            }
+            self.screen_info.yoffset = screen_info.yoffset;
+            self.origin.1 = row;
+        }
+        let page = self.page(row);
+        // Mode was changed, so contents don't fit anymore
+        if page.len() != contents.len() || page.end > self.framebuffer.map().len() {
+            return;
+        }
+        self.framebuffer.map_mut()[page].copy_from_slice(contents);
+        if self.strategy == BufferStrategy::DoubleCopy && !self.direct {
+            self.front.copy_from_slice(contents);
+        }
+        self.present_page();
+    }
+
+    /// Split the buffer being drawn into between writers for given rectangles of screen, so they
+    /// can draw from several threads at once. Every [`RegionWriter`] has its own coordinates
+    /// starting at the top left angle of its rectangle and skips pixels outside of it. Parts of
+    /// rectangles outside of the screen are dropped. Framebuffer is borrowed until all writers
+    /// are dropped, then it can be flushed as usual:
+    /// ```
+    /// # use linfb::{Framebuffer, Rect, RenderTarget};
+    /// # use linfb::shape::Rectangle;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(8, 8);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let quadrants = [
+    ///     Rect::new(0, 0, 4, 4),
+    ///     Rect::new(4, 0, 4, 4),
+    ///     Rect::new(0, 4, 4, 4),
+    ///     Rect::new(4, 4, 4, 4),
+    /// ];
+    /// let colors = [(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)];
+    /// let writers = framebuffer.split_regions(&quadrants).unwrap();
+    /// std::thread::scope(|scope| {
+    ///     for (mut writer, &color) in writers.into_iter().zip(&colors) {
+    ///         scope.spawn(move || {
+    ///             let rectangle = Rectangle::builder()
+    ///                 .width(6)
+    ///                 .height(6)
+    ///                 .border_width(0)
+    ///                 .fill_color(color)
+    ///                 .build()
+    ///                 .unwrap();
+    ///             // Everything outside of the quadrant is clipped
+    ///             writer.draw(1, 1, &rectangle);
+    ///             writer.set_pixel(0, 0, (0, 0, 0));
+    ///             writer.fill_rect(3, 0, 5, 1, (128, 128, 128));
+    ///         });
+    ///     }
+    /// });
+    /// framebuffer.flush();
+    /// for (rect, &color) in quadrants.iter().zip(&colors) {
+    ///     let (x, y) = (rect.x as u32, rect.y as u32);
+    ///     assert_eq!(framebuffer.get_pixel(x, y), (0, 0, 0).into());
+    ///     assert_eq!(framebuffer.get_pixel(x + 3, y), (128, 128, 128).into());
+    ///     assert_eq!(framebuffer.get_pixel(x + 1, y + 1), color.into());
+    ///     assert_eq!(framebuffer.get_pixel(x + 3, y + 3), color.into());
+    /// }
+    /// ```
+    ///
+    /// Rectangles are in rotated coordinates, like everything else, see
+    /// [`Framebuffer::set_rotation`]. Overlapping rectangles are rejected with
+    /// [`Error::OverlappingRegions`](crate::Error::OverlappingRegions):
+    /// ```
+    /// # use linfb::{Error, Framebuffer, Rect, Rotation};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(8, 6);
+    /// for &rotation in &[Rotation::Deg0, Rotation::Deg90, Rotation::Deg180, Rotation::Deg270] {
+    ///     let mut split = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     let mut whole = Framebuffer::in_memory(screen_info.clone()).unwrap();
+    ///     split.set_rotation(rotation);
+    ///     whole.set_rotation(rotation);
+    ///     let mut writers = split
+    ///         .split_regions(&[Rect::new(1, 0, 2, 3), Rect::new(3, 1, 10, 2)])
+    ///         .unwrap();
+    ///     writers[0].fill_rect(0, 0, 2, 3, (255, 0, 0));
+    ///     writers[1].fill_rect(0, 0, 10, 2, (0, 255, 0));
+    ///     writers[1].set_pixel(1, 1, (0, 0, 255));
+    ///     // Parts outside of region are clipped
+    ///     writers[1].fill_rect(0, u32::MAX, 10, 2, (255, 255, 255));
+    ///     writers[1].fill_rect(u32::MAX, 0, 1, u32::MAX, (255, 255, 255));
+    ///     assert_eq!(writers[1].get_pixel(1, 1), (0, 0, 255).into());
+    ///     drop(writers);
+    ///     whole.fill_rect(1, 0, 2, 3, (255, 0, 0));
+    ///     whole.fill_rect(3, 1, 10, 2, (0, 255, 0));
+    ///     whole.set_pixel(4, 2, (0, 0, 255));
+    ///     assert_eq!(split.read_region(0, 0, 8, 8), whole.read_region(0, 0, 8, 8));
+    /// }
+    ///
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// let result = framebuffer.split_regions(&[Rect::new(0, 0, 4, 4), Rect::new(3, 3, 2, 2)]);
+    /// assert!(matches!(result, Err(Error::OverlappingRegions(_, _))));
+    /// // Rectangles which only touch are fine
+    /// assert!(framebuffer
+    ///     .split_regions(&[Rect::new(0, 0, 4, 4), Rect::new(4, 0, 4, 4)])
+    ///     .is_ok());
+    /// ```
+    pub fn split_regions(&mut self, rects: &[Rect]) -> Result<Vec<RegionWriter<'_>>> {
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let regions: Vec<Rect> = rects
+            .iter()
+            .map(|rect| {
+                rect.intersection(&screen)
+                    .unwrap_or_else(|| Rect::new(rect.x, rect.y, 0, 0))
+            })
+            .collect();
+        for (i, first) in regions.iter().enumerate() {
+            for (j, second) in regions.iter().enumerate().skip(i + 1) {
+                if first.intersection(second).is_some() {
+                    return Err(OverlappingRegions(rects[i], rects[j]));
+                }
+            }
+        }
+
+        let physical: Vec<Rect> = regions
+            .iter()
+            .map(|&region| {
+                if region.is_empty() {
+                    Rect::default()
+                } else {
+                    self.physical_rect(region)
+                }
+            })
+            .collect();
+        let mut rows: Vec<Vec<&mut [u8]>> = regions.iter().map(|_| Vec::new()).collect();
+        let (codec, rotation, pixel_size) = (self.codec, self.rotation, self.pixel_size);
+        let palette = self.palette.clone();
+        let size = (
+            self.screen_info.xres as usize,
+            self.screen_info.yres as usize,
+        );
+        let start = self.physical_offset(0, 0);
+        let stride = usize::max(self.stride, 1);
+        // Physical rectangles are disjoint too, so every row is cut into parts of rectangles
+        // crossing it from left to right
+        for (y, row) in self
+            .buffer_mut()
+            .chunks_mut(stride)
+            .take(size.1)
+            .enumerate()
+        {
+            let mut crossing: Vec<usize> = (0..physical.len())
+                .filter(|&i| {
+                    let rect = physical[i];
+                    !rect.is_empty() && (rect.y..rect.y + rect.height).contains(&y)
+                })
+                .collect();
+            crossing.sort_by_key(|&i| physical[i].x);
+            let mut rest = &mut row[start..];
+            let mut position = 0;
+            for i in crossing {
+                let rect = physical[i];
+                let (_, tail) =
+                    std::mem::take(&mut rest).split_at_mut((rect.x - position) * pixel_size);
+                let (part, tail) = tail.split_at_mut(rect.width * pixel_size);
+                rows[i].push(part);
+                rest = tail;
+                position = rect.x + rect.width;
+            }
+        }
+
+        Ok(rows
+            .into_iter()
+            .zip(regions)
+            .zip(physical)
+            .map(|((rows, region), physical)| RegionWriter {
+                rows,
+                region,
+                physical,
+                size,
+                rotation,
+                codec,
+                pixel_size,
+                palette: palette.clone(),
+            })
+            .collect())
+    }
+
+    /// Create [Compositor] object with size of a screen and given background color
+    pub fn compositor(&self, background: Color) -> Compositor {
+        Compositor::new(self.width(), self.height(), background)
+    }
+}
+
+/// Guard which restores contents of screen when dropped, see [`Framebuffer::with_restore`]
+pub struct RestoreGuard<'a> {
+    framebuffer: &'a mut Framebuffer,
+    /// First row of page which was displayed and its contents
+    saved: Option<(usize, Vec<u8>)>,
+}
+
+impl RestoreGuard<'_> {
+    /// Leave current contents of screen visible instead of restoring them
+    pub fn commit(mut self) {
+        self.saved = None;
+    }
+}
+
+impl std::ops::Deref for RestoreGuard<'_> {
+    type Target = Framebuffer;
+
+    fn deref(&self) -> &Framebuffer {
+        self.framebuffer
+    }
+}
+
+impl std::ops::DerefMut for RestoreGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Framebuffer {
+        self.framebuffer
+    }
+}
+
+impl Drop for RestoreGuard<'_> {
+    fn drop(&mut self) {
+        if let Some((row, contents)) = self.saved.take() {
+            self.framebuffer.restore_page(row, &contents);
+        }
+    }
+}
+
+impl RenderTarget for Framebuffer {
+    fn width(&self) -> usize {
+        if self.rotation.is_transposed() {
+            self.screen_info.yres as usize
+        } else {
+            self.screen_info.xres as usize
+        }
+    }
+
+    fn height(&self) -> usize {
+        if self.rotation.is_transposed() {
+            self.screen_info.xres as usize
+        } else {
+            self.screen_info.yres as usize
+        }
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width() && y < self.height() {
+            self.set_pixel(x as u32, y as u32, color);
+        }
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let len = usize::min(len, self.width() - x);
+        let pixel = self.pack(color);
+        let pixel = &pixel[..self.pixel_size];
+        self.for_row_mut(x, y, len, |_, chunk| chunk.copy_from_slice(pixel));
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let row = &row[..usize::min(row.len(), self.width() - x)];
+        let codec = self.codec;
+        let pixel_size = self.pixel_size;
+        self.for_row_mut(x, y, row.len(), |i, chunk| {
+            let pixel = codec.encode(row[i]);
+            chunk.copy_from_slice(&to_bytes(pixel, pixel_size)[..pixel_size]);
+        });
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.palette.as_deref()
+    }
+}
+
+/// Everything needed to convert colors to pixels of framebuffer and back
+#[derive(Clone, Copy)]
+struct Codec {
+    format: PixelFormat,
+    /// Colors are written as luminance
+    grayscale: bool,
+    /// Channels, in red, green, blue, alpha order, which have the most significant bit at the
+    /// right
+    msb_right: [bool; 4],
+}
+
+impl Codec {
+    fn new(screen_info: &fb_var_screeninfo) -> Self {
+        Self {
+            format: PixelFormat::from(screen_info),
+            grayscale: screen_info.grayscale != 0,
+            msb_right: [
+                screen_info.red.msb_right != 0,
+                screen_info.green.msb_right != 0,
+                screen_info.blue.msb_right != 0,
+                screen_info.transp.msb_right != 0,
+            ],
+        }
+    }
+
+    /// Are pixels encoded only by [`PixelFormat`], so they can be swizzled or copied directly
+    fn is_plain(&self) -> bool {
+        !self.grayscale && !self.msb_right.contains(&true)
+    }
+
+    /// Pack color into pixel. Grayscale devices get luminance of color in every channel, usually
+    /// only red one is used there
+    fn encode(&self, color: Color) -> u32 {
+        let color = if self.grayscale {
+            let luminance = color.luminance();
+            Color {
+                red: luminance,
+                green: luminance,
+                blue: luminance,
+                ..color
+            }
+        } else {
+            color
+        };
+        self.reverse(self.format.encode(color))
+    }
+
+    fn decode(&self, pixel: u32) -> Color {
+        let color = self.format.decode(self.reverse(pixel));
+        if self.grayscale {
+            Color {
+                green: color.red,
+                blue: color.red,
+                ..color
+            }
+        } else {
+            color
+        }
+    }
+
+    /// Reverse order of bits in channels with `msb_right`. Reversing twice gives the same pixel
+    fn reverse(&self, pixel: u32) -> u32 {
+        let format = &self.format;
+        let channels = [format.red, format.green, format.blue, format.alpha];
+        let mut pixel = pixel;
+        for (&(offset, length), _) in channels
+            .iter()
+            .zip(&self.msb_right)
+            .filter(|(&(_, length), &reversed)| reversed && length != 0)
+        {
+            let mask = (u32::MAX >> (32 - length)) << offset;
+            let value = (pixel & mask) >> offset;
+            let reversed = value.reverse_bits() >> (32 - length);
+            pixel = (pixel & !mask) | (reversed << offset);
+        }
+        pixel
+    }
+}
+
+/// Positions of red, green, blue and alpha bytes in memory of pixel, if all channels are whole
+/// bytes. Channels with zero length have no position
+fn byte_positions(format: &PixelFormat) -> Option<[Option<usize>; 4]> {
+    let position = |(offset, length): (u32, u32)| match length {
+        0 => Some(None),
+        8 if offset % 8 == 0 && offset < 32 => {
+            let byte = (offset / 8) as usize;
+            Some(Some(if cfg!(target_endian = "little") {
+                byte
+            } else {
+                3 - byte
+            }))
+        }
+        _ => None,
+    };
+    Some([
+        position(format.red)?,
+        position(format.green)?,
+        position(format.blue)?,
+        position(format.alpha)?,
+    ])
+}
+
+/// Bytes of pixel as it's stored in memory of framebuffer with given pixel size, as native-endian
+/// number. Only the first `pixel_size` bytes are used
+fn to_bytes(pixel: u32, pixel_size: usize) -> [u8; 4] {
+    let mut bytes = pixel.to_ne_bytes();
+    if cfg!(target_endian = "big") {
+        bytes.rotate_left(4 - pixel_size);
+    }
+    bytes
+}
+
+/// Inverse of [`to_bytes`], takes bytes of one pixel
+fn from_bytes(pixel: &[u8]) -> u32 {
+    let mut bytes = [0u8; 4];
+    if cfg!(target_endian = "big") {
+        bytes[4 - pixel.len()..].copy_from_slice(pixel);
+    } else {
+        bytes[..pixel.len()].copy_from_slice(pixel);
+    }
+    u32::from_ne_bytes(bytes)
+}
+
+/// linfb supports framebuffers with 16, 24 or 32 bits per pixel
+pub(crate) fn check_pixel_format(screen_info: &fb_var_screeninfo) -> Result<()> {
+    if ![16, 24, 32].contains(&screen_info.bits_per_pixel) {
+        return Err(UnsupportedPixelFormat(screen_info.bits_per_pixel));
+    }
+    Ok(())
+}
+
+impl From<&fb_var_screeninfo> for PixelFormat {
+    fn from(screen_info: &fb_var_screeninfo) -> Self {
+        let channel = |channel: &fb_bitfield| (channel.offset, channel.length);
+        Self::new(
+            channel(&screen_info.red),
+            channel(&screen_info.green),
+            channel(&screen_info.blue),
+            channel(&screen_info.transp),
+        )
+    }
+}
+
+/// Target which moves pixels by dx, dy, possibly negative. Pixels which end up at negative
+/// coordinates are skipped
+struct Shifted<'a> {
+    target: &'a mut dyn RenderTarget,
+    dx: i64,
+    dy: i64,
+}
+
+impl Shifted<'_> {
+    /// Part of row with given length at x, y which has nonnegative coordinates after shift, as
+    /// offset into row and shifted position
+    fn shift(&self, x: usize, y: usize, len: usize) -> Option<(usize, usize, usize)> {
+        let y = y as i64 + self.dy;
+        let x = x as i64 + self.dx;
+        let offset = (-x).max(0) as usize;
+        if y < 0 || offset >= len {
+            return None;
+        }
+        Some((offset, (x + offset as i64) as usize, y as usize))
+    }
+}
+
+impl RenderTarget for Shifted<'_> {
+    fn width(&self) -> usize {
+        (self.target.width() as i64 - self.dx).max(0) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.target.height() as i64 - self.dy).max(0) as usize
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if let Some((_, x, y)) = self.shift(x, y, 1) {
+            self.target.draw_pixel(x, y, color);
+        }
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if let Some((offset, x, y)) = self.shift(x, y, len) {
+            self.target.draw_span(x, y, len - offset, color);
+        }
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        if let Some((offset, x, y)) = self.shift(x, y, row.len()) {
+            self.target.draw_row(x, y, &row[offset..]);
+        }
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        if let Some((offset, x, y)) = self.shift(x, y, row.len()) {
+            self.target.draw_colors(x, y, &row[offset..]);
+        }
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.target.palette()
+    }
+}
+
+/// Whole virtual screen of framebuffer as render target, see [`Framebuffer::virtual_screen`]
+pub struct VirtualScreen<'a> {
+    framebuffer: &'a mut Framebuffer,
+    /// First and last changed rows, which are written to devices which can't be mapped on drop
+    rows: Option<(usize, usize)>,
+}
+
+impl RenderTarget for VirtualScreen<'_> {
+    fn width(&self) -> usize {
+        let framebuffer = &self.framebuffer;
+        usize::min(
+            u32::max(
+                framebuffer.screen_info.xres_virtual,
+                framebuffer.screen_info.xres,
+            ) as usize,
+            framebuffer.stride / framebuffer.pixel_size,
+        )
+    }
+
+    fn height(&self) -> usize {
+        self.framebuffer.virtual_height() as usize
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let framebuffer = &mut self.framebuffer;
+        let start = y * framebuffer.stride + x * framebuffer.pixel_size;
+        let pixel = framebuffer.pack(color);
+        let pixel = &pixel[..framebuffer.pixel_size];
+        framebuffer.framebuffer.map_mut()[start..start + pixel.len()].copy_from_slice(pixel);
+        self.rows = match self.rows {
+            Some((first, last)) => Some((usize::min(first, y), usize::max(last, y))),
+            None => Some((y, y)),
+        };
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.framebuffer.palette()
+    }
+}
+
+impl Drop for VirtualScreen<'_> {
+    fn drop(&mut self) {
+        if let Some((first, last)) = self.rows {
+            let width = self.width();
+            self.framebuffer.present(first..last + 1, 0..width);
+        }
+    }
+}
+
+/// Part of the buffer being drawn into which can be drawn on independently of other parts, e.g.
+/// from another thread, see [`Framebuffer::split_regions`]. Coordinates start at the top left
+/// angle of region, pixels outside of it are skipped
+pub struct RegionWriter<'a> {
+    /// Parts of physical rows covered by region, from top to bottom
+    rows: Vec<&'a mut [u8]>,
+    region: Rect,
+    /// Physical rectangle covered by region
+    physical: Rect,
+    /// Physical size of screen
+    size: (usize, usize),
+    rotation: Rotation,
+    codec: Codec,
+    pixel_size: usize,
+    palette: Option<std::sync::Arc<Palette>>,
+}
+
+impl RegionWriter<'_> {
+    /// Rectangle of screen covered by writer, clipped to the screen
+    pub fn region(&self) -> Rect {
+        self.region
+    }
+
+    /// Set pixel at x, y of region. Pixels outside of region are skipped
+    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
+        self.draw_pixel(x as usize, y as usize, color.into());
+    }
+
+    /// Get color of pixel at x, y of region, see [`Framebuffer::get_pixel`].
+    ///
+    /// Panics if x, y is outside of region
+    pub fn get_pixel(&mut self, x: u32, y: u32) -> Color {
+        assert!((x as usize) < self.width() && (y as usize) < self.height());
+        let codec = self.codec;
+        codec.decode(from_bytes(self.pixel_mut(x as usize, y as usize)))
+    }
+
+    /// Fill rectangle of region with color, see [`Framebuffer::fill_rect`]
+    pub fn fill_rect<C: Into<Color>>(&mut self, x: u32, y: u32, width: u32, height: u32, color: C) {
+        let color = color.into();
+        let region = Rect::new(0, 0, self.width(), self.height());
+        let rect = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let rect = match rect.intersection(&region) {
+            Some(rect) => rect,
+            None => return,
+        };
+        for y in rect.y..rect.y + rect.height {
+            self.draw_span(rect.x, y, rect.width, color);
+        }
+    }
+
+    /// Draw shape at x, y of region, see [`Framebuffer::draw`]
+    pub fn draw<T: Shape + ?Sized>(&mut self, x: u32, y: u32, shape: &T) {
+        if (x as usize) < self.width() && (y as usize) < self.height() {
+            shape.render_into(self, x as usize, y as usize);
+        }
+    }
+
+    /// Bytes of pixel at x, y of region, which must be inside of it
+    fn pixel_mut(&mut self, x: usize, y: usize) -> &mut [u8] {
+        let (x, y) = self.rotation.to_physical(
+            self.region.x + x,
+            self.region.y + y,
+            self.size.0,
+            self.size.1,
+        );
+        let start = (x - self.physical.x) * self.pixel_size;
+        &mut self.rows[y - self.physical.y][start..start + self.pixel_size]
+    }
+}
+
+impl RenderTarget for RegionWriter<'_> {
+    fn width(&self) -> usize {
+        self.region.width
+    }
+
+    fn height(&self) -> usize {
+        self.region.height
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.draw_span(x, y, 1, color);
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let pixel = to_bytes(self.codec.encode(color), self.pixel_size);
+        let pixel = &pixel[..self.pixel_size];
+        for inner_x in x..usize::min(x + len, self.width()) {
+            self.pixel_mut(inner_x, y).copy_from_slice(pixel);
+        }
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let row = &row[..usize::min(row.len(), self.width() - x)];
+        let pixel_size = self.pixel_size;
+        for (i, &color) in row.iter().enumerate() {
+            let pixel = to_bytes(self.codec.encode(color), pixel_size);
+            self.pixel_mut(x + i, y)
+                .copy_from_slice(&pixel[..pixel_size]);
+        }
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.palette.as_deref()
+    }
+}
+
+/// Target which alpha-blends pixels over current contents of framebuffer
+struct Blended<'a>(&'a mut Framebuffer);
+
+impl RenderTarget for Blended<'_> {
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width() || y >= self.height() || color.alpha == 0 {
+            return;
+        }
+        let color = if color.alpha == 255 {
+            color
+        } else {
+            canvas::blend(self.0.get_pixel(x as u32, y as u32), color)
+        };
+        self.0.set_pixel(x as u32, y as u32, color);
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.0.palette()
+    }
+}
+
+/// Target which combines pixels with current contents of framebuffer using [`BlendMode`]
+struct Mixed<'a>(&'a mut Framebuffer, BlendMode);
+
+impl RenderTarget for Mixed<'_> {
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width() || y >= self.height() || color.alpha == 0 {
+            return;
+        }
+        let previous = self.0.get_pixel(x as u32, y as u32);
+        self.0
+            .set_pixel(x as u32, y as u32, self.1.apply(previous, color));
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.0.palette()
+    }
+}
+
+/// Target which XORs opaque pixels with current contents of framebuffer
+struct Xored<'a>(&'a mut Framebuffer);
+
+impl RenderTarget for Xored<'_> {
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width() || y >= self.height() || color.alpha != 255 {
+            return;
+        }
+        // Alpha channel of framebuffer is kept intact
+        let pixel = self.0.pack(Color { alpha: 0, ..color });
+        let start = self.0.offset(x, y);
+        let pixel_size = self.0.pixel_size;
+        let chunk = &mut self.0.buffer_mut()[start..start + pixel_size];
+        for (byte, value) in chunk.iter_mut().zip(pixel.iter()) {
+            *byte ^= value;
+        }
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.0.palette()
+    }
+}
+
+/// Copy every changed byte range from `source` into both `shadow` and `target`, assuming that
+/// `shadow` has the same contents as `target`. Returns copied ranges, one per changed row
+fn copy_diff(
+    source: &[u8],
+    shadow: &mut [u8],
+    target: &mut [u8],
+    row_size: usize,
+) -> Vec<std::ops::Range<usize>> {
+    let mut copied = Vec::new();
+    let rows = source
+        .chunks(row_size)
+        .zip(shadow.chunks_mut(row_size))
+        .zip(target.chunks_mut(row_size));
+    for (row, ((source_row, shadow_row), target_row)) in rows.enumerate() {
+        if source_row == shadow_row {
+            continue;
+        }
+        // Can unwrap here because rows are not equal
+        let start = first_difference(source_row, shadow_row).unwrap();
+        let end = last_difference(source_row, shadow_row).unwrap() + 1;
+        shadow_row[start..end].copy_from_slice(&source_row[start..end]);
+        target_row[start..end].copy_from_slice(&source_row[start..end]);
+        copied.push(row * row_size + start..row * row_size + end);
+    }
+    copied
+}
+
+/// Length of chunks which slices are compared by before looking for the exact byte, so that the
+/// comparison is done with `memcmp` instead of byte by byte
+const DIFF_CHUNK: usize = 64;
+
+/// Index of the first byte which differs in slices of equal length
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    let chunk = a
+        .chunks(DIFF_CHUNK)
+        .zip(b.chunks(DIFF_CHUNK))
+        .position(|(a, b)| a != b)?;
+    let offset = chunk * DIFF_CHUNK;
+    let end = usize::min(offset + DIFF_CHUNK, a.len());
+    a[offset..end]
+        .iter()
+        .zip(&b[offset..end])
+        .position(|(a, b)| a != b)
+        .map(|index| offset + index)
+}
+
+/// Index of the last byte which differs in slices of equal length
+fn last_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    let chunk = a
+        .rchunks(DIFF_CHUNK)
+        .zip(b.rchunks(DIFF_CHUNK))
+        .position(|(a, b)| a != b)?;
+    let end = a.len() - chunk * DIFF_CHUNK;
+    let offset = end.saturating_sub(DIFF_CHUNK);
+    a[offset..end]
+        .iter()
+        .zip(&b[offset..end])
+        .rposition(|(a, b)| a != b)
+        .map(|index| offset + index)
+}
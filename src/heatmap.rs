@@ -0,0 +1,321 @@
+use std::sync::OnceLock;
+
+use crate::shape::{hash_of, Color, Shape};
+
+/// Mapping of values from 0 to 1 to colors, used by [`Heatmap`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorMap {
+    /// Perceptually uniform ramp from dark purple through teal to yellow, similar to Viridis
+    Viridis,
+    /// Ramp from black to white
+    Grayscale,
+    /// Linear gradient through given stops as (position, color). Positions should be from 0 to 1,
+    /// values before the first and after the last stop get colors of these stops
+    Gradient(Vec<(f32, Color)>),
+}
+
+/// Stops of [`ColorMap::Viridis`]
+const VIRIDIS: [(f32, (u8, u8, u8)); 5] = [
+    (0.0, (68, 1, 84)),
+    (0.25, (59, 82, 139)),
+    (0.5, (33, 145, 140)),
+    (0.75, (94, 201, 98)),
+    (1.0, (253, 231, 37)),
+];
+
+impl ColorMap {
+    /// Color of value `t`, clamped to `[0, 1]`:
+    /// ```
+    /// # use linfb::shape::{Color, ColorMap};
+    /// assert_eq!(ColorMap::Grayscale.color(0.0), (0, 0, 0).into());
+    /// assert_eq!(ColorMap::Grayscale.color(0.5), (128, 128, 128).into());
+    /// assert_eq!(ColorMap::Viridis.color(2.0), (253, 231, 37).into());
+    /// let map = ColorMap::Gradient(vec![
+    ///     (0.25, (255, 0, 0).into()),
+    ///     (0.75, (0, 0, 255).into()),
+    /// ]);
+    /// assert_eq!(map.color(0.0), (255, 0, 0).into());
+    /// assert_eq!(map.color(0.5), (128, 0, 128).into());
+    /// assert_eq!(map.color(1.0), (0, 0, 255).into());
+    /// ```
+    pub fn color(&self, t: f32) -> Color {
+        let t = if t.is_nan() {
+            0f32
+        } else {
+            t.clamp(0f32, 1f32)
+        };
+        match self {
+            ColorMap::Viridis => {
+                let stops: Vec<_> = VIRIDIS
+                    .iter()
+                    .map(|&(position, color)| (position, color.into()))
+                    .collect();
+                gradient(&stops, t)
+            }
+            ColorMap::Grayscale => {
+                let value = (t * 255f32).round() as u8;
+                (value, value, value).into()
+            }
+            ColorMap::Gradient(stops) => gradient(stops, t),
+        }
+    }
+}
+
+fn gradient(stops: &[(f32, Color)], t: f32) -> Color {
+    let mut stops = stops.to_vec();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return (0, 0, 0, 0).into(),
+    };
+    if t <= first.0 {
+        return first.1;
+    }
+    for pair in stops.windows(2) {
+        let ((start, from), (end, to)) = (pair[0], pair[1]);
+        if t <= end {
+            let fraction = if end > start {
+                (t - start) / (end - start)
+            } else {
+                1f32
+            };
+            let channel = |from: u8, to: u8| {
+                (from as f32 + (to as f32 - from as f32) * fraction).round() as u8
+            };
+            return Color {
+                red: channel(from.red, to.red),
+                green: channel(from.green, to.green),
+                blue: channel(from.blue, to.blue),
+                alpha: channel(from.alpha, to.alpha),
+            };
+        }
+    }
+    last.1
+}
+
+/// How [`Heatmap`] fills pixels between cell centers when upscaled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    /// Every cell is a solid block
+    #[default]
+    Nearest,
+    /// Values are linearly interpolated between neighbouring cells
+    Bilinear,
+}
+
+/// Grid of values drawn through [`ColorMap`], e.g. frames of thermal camera. Every row of values
+/// is a row of cells, NaN cells and cells missing from short rows are transparent. By default
+/// every cell is one pixel and values are scaled from the smallest to the largest one:
+/// ```
+/// # use linfb::shape::{ColorMap, Heatmap, Shape};
+/// let map = ColorMap::Gradient(vec![(0.0, (0, 0, 0).into()), (1.0, (255, 255, 255).into())]);
+/// let mut heatmap = Heatmap::new(vec![vec![0.0, 5.0, 10.0], vec![10.0, f32::NAN]])
+///     .color_map(map);
+/// let rendered = heatmap.render();
+/// assert_eq!(rendered[0][0], Some((0, 0, 0).into()));
+/// assert_eq!(rendered[0][1], Some((128, 128, 128).into()));
+/// assert_eq!(rendered[0][2], Some((255, 255, 255).into()));
+/// assert_eq!(rendered[1][1], None);
+/// assert_eq!(rendered[1][2], None);
+///
+/// heatmap.set_values(vec![vec![0.0, 20.0]]);
+/// let heatmap = heatmap.range(0.0, 40.0);
+/// assert_eq!(heatmap.render(), vec![vec![Some((0, 0, 0).into()), Some((128, 128, 128).into())]]);
+/// ```
+///
+/// Heatmap can be upscaled to given size in pixels:
+/// ```
+/// # use linfb::shape::{ColorMap, Heatmap, Interpolation, Shape};
+/// let heatmap = Heatmap::new(vec![vec![0.0, 10.0], vec![10.0, 20.0]])
+///     .color_map(ColorMap::Grayscale);
+/// let nearest = heatmap.clone().upscale(4, 4, Interpolation::Nearest).render();
+/// assert_eq!(nearest[1][1], Some((0, 0, 0).into()));
+/// assert_eq!(nearest[1][2], Some((128, 128, 128).into()));
+///
+/// let bilinear = heatmap.upscale(3, 3, Interpolation::Bilinear);
+/// assert_eq!(bilinear.dimensions(), (3, 3));
+/// let rendered = bilinear.render();
+/// assert_eq!(rendered[0][0], Some((0, 0, 0).into()));
+/// assert_eq!(rendered[2][2], Some((255, 255, 255).into()));
+/// // Center is the average of all four cells
+/// assert_eq!(rendered[1][1], Some((128, 128, 128).into()));
+/// assert_eq!(rendered[0][1], Some((64, 64, 64).into()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+    values: Vec<Vec<f32>>,
+    color_map: ColorMap,
+    range: Option<(f32, f32)>,
+    size: Option<(usize, usize)>,
+    interpolation: Interpolation,
+    rows: OnceLock<Vec<Vec<Option<Color>>>>,
+}
+
+impl Heatmap {
+    /// Create heatmap from rows of values, drawn with [`ColorMap::Viridis`]
+    pub fn new(values: Vec<Vec<f32>>) -> Self {
+        Self {
+            values,
+            color_map: ColorMap::Viridis,
+            range: None,
+            size: None,
+            interpolation: Interpolation::default(),
+            rows: OnceLock::new(),
+        }
+    }
+
+    /// Set color map
+    pub fn color_map(mut self, color_map: ColorMap) -> Self {
+        self.color_map = color_map;
+        self.rows = OnceLock::new();
+        self
+    }
+
+    /// Map values from `min` to `max` to the whole color map instead of the actual range of
+    /// values. Values outside of the range get colors of the ends of color map
+    pub fn range(mut self, min: f32, max: f32) -> Self {
+        self.range = Some((min, max));
+        self.rows = OnceLock::new();
+        self
+    }
+
+    /// Draw heatmap with given size in pixels instead of one pixel per cell
+    pub fn upscale(mut self, width: usize, height: usize, interpolation: Interpolation) -> Self {
+        self.size = Some((width, height));
+        self.interpolation = interpolation;
+        self.rows = OnceLock::new();
+        self
+    }
+
+    /// Replace values, e.g. with the next frame. Size of grid can change
+    pub fn set_values(&mut self, values: Vec<Vec<f32>>) {
+        self.values = values;
+        self.rows = OnceLock::new();
+    }
+
+    /// Rows of values
+    pub fn values(&self) -> &[Vec<f32>] {
+        &self.values
+    }
+
+    /// Number of cells as (columns, rows)
+    fn cells(&self) -> (usize, usize) {
+        let columns = self.values.iter().map(Vec::len).max().unwrap_or(0);
+        (columns, self.values.len())
+    }
+
+    fn value(&self, column: usize, row: usize) -> f32 {
+        self.values[row].get(column).copied().unwrap_or(f32::NAN)
+    }
+
+    /// Range of values mapped to the whole color map
+    fn value_range(&self) -> (f32, f32) {
+        self.range.unwrap_or_else(|| {
+            let finite = self.values.iter().flatten().filter(|v| v.is_finite());
+            let (min, max) = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+                (f32::min(min, v), f32::max(max, v))
+            });
+            if min <= max {
+                (min, max)
+            } else {
+                (0f32, 0f32)
+            }
+        })
+    }
+
+    /// Value at given pixel of upscaled heatmap
+    fn sample(&self, x: usize, y: usize, (width, height): (usize, usize)) -> f32 {
+        let (columns, rows) = self.cells();
+        // Position of pixel center in cell coordinates
+        let u = (x as f32 + 0.5) * columns as f32 / width as f32;
+        let v = (y as f32 + 0.5) * rows as f32 / height as f32;
+        let nearest = || {
+            self.value(
+                usize::min(u as usize, columns - 1),
+                usize::min(v as usize, rows - 1),
+            )
+        };
+        if self.interpolation == Interpolation::Nearest {
+            return nearest();
+        }
+        let u = (u - 0.5).clamp(0f32, (columns - 1) as f32);
+        let v = (v - 0.5).clamp(0f32, (rows - 1) as f32);
+        let (left, top) = (u.floor() as usize, v.floor() as usize);
+        let (right, bottom) = (
+            usize::min(left + 1, columns - 1),
+            usize::min(top + 1, rows - 1),
+        );
+        let (dx, dy) = (u - left as f32, v - top as f32);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let value = lerp(
+            lerp(self.value(left, top), self.value(right, top), dx),
+            lerp(self.value(left, bottom), self.value(right, bottom), dx),
+            dy,
+        );
+        // Interpolation next to missing cells falls back to the nearest cell
+        if value.is_nan() {
+            nearest()
+        } else {
+            value
+        }
+    }
+}
+
+impl Shape for Heatmap {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        let (width, height) = self.dimensions();
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+        self.rows
+            .get_or_init(|| {
+                let (min, max) = self.value_range();
+                (0..height)
+                    .map(|y| {
+                        (0..width)
+                            .map(|x| {
+                                let value = self.sample(x, y, (width, height));
+                                if value.is_nan() {
+                                    return None;
+                                }
+                                let t = if max > min {
+                                    (value - min) / (max - min)
+                                } else {
+                                    0f32
+                                };
+                                Some(self.color_map.color(t))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .clone()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        match self.size {
+            Some(size) if self.cells().0 > 0 => size,
+            _ => self.cells(),
+        }
+    }
+
+    fn content_hash(&self) -> u64 {
+        let values: Vec<Vec<u32>> = self
+            .values
+            .iter()
+            .map(|row| row.iter().map(|v| v.to_bits()).collect())
+            .collect();
+        let stops: Vec<(u32, Color)> = match &self.color_map {
+            ColorMap::Gradient(stops) => stops.iter().map(|(p, c)| (p.to_bits(), *c)).collect(),
+            _ => Vec::new(),
+        };
+        hash_of(&(
+            values,
+            std::mem::discriminant(&self.color_map),
+            stops,
+            self.range.map(|(min, max)| (min.to_bits(), max.to_bits())),
+            self.size,
+            self.interpolation,
+        ))
+    }
+}
@@ -0,0 +1,279 @@
+use std::sync::OnceLock;
+
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+
+/// How [`Histogram`] splits range of samples into bins
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binning {
+    /// Fixed number of bins of equal width
+    Count(usize),
+    /// Bins of fixed width, number of bins depends on range of samples
+    Width(f32),
+}
+
+/// Histogram of samples, e.g. response times. Range of samples is split into bins and every bin
+/// is drawn as a bar with height proportional to the number of samples in it. Bins are computed
+/// lazily on the first render after samples change:
+/// ```
+/// # use linfb::shape::{Binning, Histogram, Shape};
+/// let mut histogram = Histogram::new(40, 21)
+///     .binning(Binning::Count(4))
+///     .bar_color((255, 0, 0))
+///     .axis_color((255, 255, 255));
+/// histogram.set_samples(&[1.0, 1.5, 2.2, 2.4, 2.6, 2.8, 3.1, 3.2, 4.0]);
+/// assert_eq!(histogram.bins(), &[2, 2, 4, 1]);
+///
+/// // Tallest bar takes the whole height above the axis
+/// let rendered = histogram.render();
+/// let bar_height = |x: usize| {
+///     rendered.iter().filter(|row| row[x] == Some((255, 0, 0).into())).count()
+/// };
+/// assert_eq!(bar_height(25), 20);
+/// assert_eq!(bar_height(5), 10);
+/// assert_eq!(bar_height(15), 10);
+/// assert_eq!(bar_height(35), 5);
+/// assert_eq!(rendered[20][25], Some((255, 255, 255).into()));
+/// assert_eq!(rendered[0][0], Some((255, 255, 255).into()));
+/// ```
+///
+/// Samples outside of clamp range are counted in the edge bins instead of stretching the scale:
+/// ```
+/// # use linfb::shape::{Binning, Histogram, Shape};
+/// let mut histogram = Histogram::new(100, 50)
+///     .binning(Binning::Width(10.0))
+///     .clamp(0.0, 100.0);
+/// let mut samples: Vec<f32> = (0..100).map(|i| i as f32).collect();
+/// samples.extend(&[-1000.0, 5000.0, 5000.0]);
+/// histogram.set_samples(&samples);
+/// assert_eq!(histogram.bins(), &[11, 10, 10, 10, 10, 10, 10, 10, 10, 12]);
+/// assert_eq!(histogram.bar_height(9), 50);
+/// assert_eq!(histogram.bar_height(1), 42);
+///
+/// // Logarithmic scale makes small bins more visible
+/// let histogram = histogram.log_scale(true);
+/// assert!(histogram.bar_height(1) > 42);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    width: usize,
+    height: usize,
+    samples: Vec<f32>,
+    binning: Binning,
+    clamp: Option<(f32, f32)>,
+    log_scale: bool,
+    bar_color: Paint,
+    axis_color: Option<Paint>,
+    grid: Option<(usize, Paint)>,
+    bins: OnceLock<Vec<usize>>,
+}
+
+impl Histogram {
+    /// Create histogram without samples, with 10 black bars and no axes
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            samples: Vec::new(),
+            binning: Binning::Count(10),
+            clamp: None,
+            log_scale: false,
+            bar_color: (0, 0, 0).into(),
+            axis_color: None,
+            grid: None,
+            bins: OnceLock::new(),
+        }
+    }
+
+    /// Replace samples. Non-finite samples are ignored
+    pub fn set_samples(&mut self, samples: &[f32]) {
+        self.samples = samples.iter().copied().filter(|s| s.is_finite()).collect();
+        self.bins = OnceLock::new();
+    }
+
+    /// Set how range of samples is split into bins. Default is 10 bins
+    pub fn binning(mut self, binning: Binning) -> Self {
+        self.binning = binning;
+        self.bins = OnceLock::new();
+        self
+    }
+
+    /// Use fixed range of samples instead of range from the smallest to the largest one. Samples
+    /// outside of it are counted in the first or the last bin
+    pub fn clamp(mut self, min: f32, max: f32) -> Self {
+        self.clamp = Some((min, max));
+        self.bins = OnceLock::new();
+        self
+    }
+
+    /// Enable logarithmic scale of bar heights
+    pub fn log_scale(mut self, enabled: bool) -> Self {
+        self.log_scale = enabled;
+        self
+    }
+
+    /// Set color of bars. Default is black
+    pub fn bar_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.bar_color = color.into();
+        self
+    }
+
+    /// Draw axes at the left and bottom edges with given color. Bars are drawn above and to the
+    /// right of them
+    pub fn axis_color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.axis_color = Some(color.into());
+        self
+    }
+
+    /// Draw given number of evenly spaced horizontal gridlines behind bars
+    pub fn gridlines<P: Into<Paint>>(mut self, count: usize, color: P) -> Self {
+        self.grid = Some((count, color.into()));
+        self
+    }
+
+    /// Range of samples split into bins
+    pub fn range(&self) -> (f32, f32) {
+        self.clamp.unwrap_or_else(|| {
+            let min = self.samples.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = self
+                .samples
+                .iter()
+                .copied()
+                .fold(f32::NEG_INFINITY, f32::max);
+            if min <= max {
+                (min, max)
+            } else {
+                (0f32, 0f32)
+            }
+        })
+    }
+
+    /// Number of samples in every bin
+    pub fn bins(&self) -> &[usize] {
+        self.bins.get_or_init(|| {
+            let (min, max) = self.range();
+            let span = max - min;
+            let (count, bin_width) = match self.binning {
+                Binning::Count(count) => (usize::max(count, 1), span / usize::max(count, 1) as f32),
+                Binning::Width(width) if width > 0f32 && span > 0f32 => {
+                    (usize::max((span / width).ceil() as usize, 1), width)
+                }
+                Binning::Width(width) => (1, width),
+            };
+            let mut bins = vec![0; count];
+            for sample in &self.samples {
+                let index = if bin_width > 0f32 {
+                    ((sample - min) / bin_width).floor().max(0f32) as usize
+                } else {
+                    0
+                };
+                bins[usize::min(index, count - 1)] += 1;
+            }
+            bins
+        })
+    }
+
+    /// Height of plot area above the axis
+    fn plot_height(&self) -> usize {
+        match self.axis_color {
+            Some(_) => self.height.saturating_sub(1),
+            None => self.height,
+        }
+    }
+
+    /// Height of bar of given bin in px
+    pub fn bar_height(&self, bin: usize) -> usize {
+        let bins = self.bins();
+        let max = bins.iter().copied().max().unwrap_or(0);
+        let count = bins.get(bin).copied().unwrap_or(0);
+        if max == 0 {
+            return 0;
+        }
+        let fraction = if self.log_scale {
+            (count as f32).ln_1p() / (max as f32).ln_1p()
+        } else {
+            count as f32 / max as f32
+        };
+        (fraction * self.plot_height() as f32).round() as usize
+    }
+}
+
+impl Shape for Histogram {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        let axis_color = self
+            .axis_color
+            .as_ref()
+            .and_then(|paint| paint.resolve(palette));
+        let bar_color = self.bar_color.resolve(palette);
+        let plot_height = self.plot_height();
+        let plot_x = if self.axis_color.is_some() { 1 } else { 0 };
+        let plot_width = self.width.saturating_sub(plot_x);
+        let mut grid = vec![vec![None; self.width]; self.height];
+
+        if let Some((count, color)) = &self.grid {
+            for line in 1..=*count {
+                let y = plot_height - plot_height * line / (count + 1);
+                if let Some(row) = grid.get_mut(y) {
+                    for pixel in row[plot_x..].iter_mut() {
+                        *pixel = color.resolve(palette);
+                    }
+                }
+            }
+        }
+
+        let bins = self.bins().len();
+        for bin in 0..bins {
+            let (start, end) = (
+                plot_x + plot_width * bin / bins,
+                plot_x + plot_width * (bin + 1) / bins,
+            );
+            let top = plot_height - usize::min(self.bar_height(bin), plot_height);
+            for row in grid[top..plot_height].iter_mut() {
+                for pixel in row[start..end].iter_mut() {
+                    *pixel = bar_color;
+                }
+            }
+        }
+
+        if let Some(color) = axis_color {
+            for row in grid.iter_mut() {
+                row[0] = Some(color);
+            }
+            if let Some(row) = grid.last_mut() {
+                for pixel in row.iter_mut() {
+                    *pixel = Some(color);
+                }
+            }
+        }
+        grid
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        let samples: Vec<u32> = self.samples.iter().map(|s| s.to_bits()).collect();
+        let binning = match self.binning {
+            Binning::Count(count) => (0, count as u64),
+            Binning::Width(width) => (1, width.to_bits() as u64),
+        };
+        hash_of(&(
+            self.width,
+            self.height,
+            samples,
+            binning,
+            self.clamp.map(|(min, max)| (min.to_bits(), max.to_bits())),
+            self.log_scale,
+            &self.bar_color,
+            &self.axis_color,
+            &self.grid,
+        ))
+    }
+}
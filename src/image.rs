@@ -1,46 +1,224 @@
+use std::fmt;
 #[cfg(feature = "images")]
 use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
 use crate::error::Result;
-use crate::shape::{Color, Shape};
-use image;
+use crate::shape::{hash_of, Color, Shape};
+use crate::{Rect, RenderTarget};
 
-/// Image shape. Can be created from any file, [`image`] crate can parse. Supports transparency
+/// Image shape. Can be created from any file, [`image`] crate can parse. Supports transparency.
+///
+/// Drawing image copies its rows directly from the underlying buffer, fully opaque rows are
+/// copied as a whole:
+/// ```
+/// # use linfb::{Canvas, RenderTarget};
+/// # use linfb::shape::{Image, Shape};
+/// let buffer = image::RgbaImage::from_fn(5, 4, |x, y| {
+///     image::Rgba([x as u8 * 50, y as u8 * 60, 100, [0, 128, 255][(x + y) as usize % 3]])
+/// });
+/// let image = Image::from(buffer);
+///
+/// let mut canvas = Canvas::new(8, 8, (10, 20, 30).into());
+/// image.render_into(&mut canvas, 2, 1);
+///
+/// let mut expected = Canvas::new(8, 8, (10, 20, 30).into());
+/// for (y, row) in image.render().iter().enumerate() {
+///     expected.draw_row(2, 1 + y, row);
+/// }
+/// assert_eq!(canvas, expected);
+/// assert_eq!(image.render()[0][0], None);
+/// assert_eq!(image.render()[1][1], Some((50, 60, 100, 255).into()));
+/// ```
+///
+/// Fully transparent pixels don't belong to image when hit testing:
+/// ```
+/// # use linfb::shape::{Image, Shape};
+/// let mut buffer = image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 255, 255, 255]));
+/// buffer.put_pixel(0, 0, image::Rgba([255, 255, 255, 0]));
+/// let image = Image::from(buffer).at(10, 10);
+/// assert!(!image.hit_test(10, 10));
+/// assert!(image.hit_test(11, 10));
+/// assert!(image.hit_test(13, 13));
+/// assert!(!image.hit_test(14, 13));
+/// ```
+///
+/// Image can't be changed after creation, so clones share pixel data and rows converted by
+/// [`Shape::render`]
+#[derive(Clone)]
 pub struct Image {
-    image: image::RgbaImage,
+    image: Arc<image::RgbaImage>,
+    rows: Arc<OnceLock<Vec<Vec<Option<Color>>>>>,
+    /// Hash of pixels, computed once since images can be large
+    hash: Arc<OnceLock<u64>>,
 }
 
 impl Image {
     /// Create [`Image`] from file
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Ok(Self {
-            image: image::open(path)?.to_rgba(),
-        })
+        Ok(image::open(path)?.to_rgba().into())
     }
 
     /// Create [`Image`] from in-memory buffer
     pub fn from_buffer(buffer: &[u8]) -> Result<Self> {
-        Ok(Self {
-            image: image::load_from_memory(buffer)?.to_rgba(),
+        Ok(image::load_from_memory(buffer)?.to_rgba().into())
+    }
+
+    /// Render only given region of image, reading pixels directly from the underlying buffer.
+    /// Result always has the size of region, parts of region outside of image are [`None`]:
+    /// ```
+    /// # use linfb::Rect;
+    /// # use linfb::shape::{Image, Shape};
+    /// let buffer = image::RgbaImage::from_fn(4, 4, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+    /// let image = Image::from(buffer);
+    /// let view = image.render_region(Rect::new(2, 1, 3, 2));
+    /// assert_eq!(view[0][0], Some((2, 1, 0, 255).into()));
+    /// assert_eq!(view[1][1], Some((3, 2, 0, 255).into()));
+    /// assert_eq!(view[1][2], None);
+    /// assert_eq!(image.render_region(Rect::new(0, 0, 4, 4)), image.render());
+    /// ```
+    pub fn render_region(&self, region: Rect) -> Vec<Vec<Option<Color>>> {
+        if region.width == 0 {
+            return Vec::new();
+        }
+        let (width, height) = self.dimensions();
+        (region.y..region.y + region.height)
+            .map(|y| {
+                (region.x..region.x + region.width)
+                    .map(|x| {
+                        if x < width && y < height {
+                            pixel_color(self.image.get_pixel(x as u32, y as u32).0)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Same as [`Shape::render`], but borrows rows instead of copying them. Pixels are converted
+    /// on the first call and kept:
+    /// ```
+    /// # use linfb::shape::{Image, Shape};
+    /// let buffer = image::RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+    /// let image = Image::from(buffer);
+    /// assert_eq!(image.rows()[1][2], Some((2, 1, 0, 255).into()));
+    /// assert_eq!(image.rows(), &image.render()[..]);
+    /// // Clones share converted rows
+    /// assert!(std::ptr::eq(image.clone().rows(), image.rows()));
+    /// ```
+    pub fn rows(&self) -> &[Vec<Option<Color>>] {
+        if self.image.width() == 0 {
+            return &[];
+        }
+        self.rows.get_or_init(|| {
+            self.image
+                .rows()
+                .map(|row| row.map(|rgba| pixel_color(rgba.0)).collect())
+                .collect()
         })
     }
+
+    /// Draw only given region of image onto target, with top left angle of region at x, y
+    pub fn render_region_into(
+        &self,
+        region: Rect,
+        target: &mut dyn RenderTarget,
+        x: usize,
+        y: usize,
+    ) {
+        let (width, height) = self.dimensions();
+        let region = match region.intersection(&Rect::new(0, 0, width, height)) {
+            Some(region) => region,
+            None => return,
+        };
+        let row_size = self.image.width() as usize * 4;
+        let rows = self
+            .image
+            .chunks(row_size)
+            .skip(region.y)
+            .take(region.height);
+        for (inner_y, row) in rows.enumerate() {
+            let row = &as_colors(row)[region.x..region.x + region.width];
+            // Fully transparent pixels are skipped, everything else is drawn in runs
+            let mut start = 0;
+            while start < row.len() {
+                let visible = row[start].alpha != 0;
+                let len = row[start..]
+                    .iter()
+                    .position(|color| (color.alpha != 0) != visible)
+                    .unwrap_or(row.len() - start);
+                if visible {
+                    target.draw_colors(x + start, y + inner_y, &row[start..start + len]);
+                }
+                start += len;
+            }
+        }
+    }
+}
+
+/// Convert RGBA pixel to color, fully transparent pixels are [`None`]
+fn pixel_color([r, g, b, a]: [u8; 4]) -> Option<Color> {
+    if a == 0 {
+        None
+    } else {
+        Some((r, g, b, a).into())
+    }
+}
+
+/// Pixels are not printed, only size of image
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.image.width())
+            .field("height", &self.image.height())
+            .finish()
+    }
+}
+
+impl From<image::RgbaImage> for Image {
+    fn from(image: image::RgbaImage) -> Self {
+        Self {
+            image: Arc::new(image),
+            rows: Arc::default(),
+            hash: Arc::default(),
+        }
+    }
+}
+
+/// View RGBA bytes as colors
+fn as_colors(bytes: &[u8]) -> &[Color] {
+    // Can do it safely, because Color is #[repr(C)] struct of four u8's in RGBA order
+    unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const Color, bytes.len() / 4) }
 }
 
 impl Shape for Image {
+    /// Pixels are converted only once, subsequent calls just copy them. Use [`Image::rows`] to
+    /// avoid copying
     fn render(&self) -> Vec<Vec<Option<Color>>> {
-        self.image
-            .rows()
-            .map(|row| {
-                row.map(|rgba| {
-                    let [r, g, b, a] = rgba.0;
-                    if a == 0 {
-                        None
-                    } else {
-                        Some((r, g, b, a).into())
-                    }
-                })
-                .collect()
-            })
-            .collect()
+        self.rows().to_vec()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.image.width() as usize, self.image.height() as usize)
+    }
+
+    /// Pixels are hashed only once, subsequent calls and clones reuse the hash
+    fn content_hash(&self) -> u64 {
+        *self.hash.get_or_init(|| {
+            let bytes: &[u8] = &self.image;
+            hash_of(&(self.image.width(), self.image.height(), bytes))
+        })
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let (width, height) = self.dimensions();
+        x < width && y < height && self.image.get_pixel(x as u32, y as u32).0[3] != 0
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        let (width, height) = self.dimensions();
+        self.render_region_into(Rect::new(0, 0, width, height), target, x, y);
     }
 }
@@ -1,20 +1,29 @@
 //! linfb is a drawing library that uses Linux' `/dev/fb0` device as it's backend. For most
 //! tasks you probably want to use OpenGL or Vulkan backed library. `/dev/fb0` is deprecated but
-//! still useful for some specific cases. This library supports framebuffers that use 32 bits per
-//! pixel, so (theoretically) most modern systems.
+//! still useful for some specific cases. This library supports framebuffers that use 32, 24 or 16
+//! bits per pixel, so (theoretically) most modern, old and embedded systems.
 //!
 //! Before drawing on framebuffer you should allocate a virtual terminal and switch to it. I
-//! recommend using [vt](https://crates.io/crates/vt) crates for this task. You should never draw
-//! on virtual terminal used by X.org/Wayland server, this is unsafe and can lead to panics.
+//! recommend using [vt](https://crates.io/crates/vt) crates for this task, or enabling `vt` feature
+//! and using `Framebuffer::open_on_new_vt`, which also switches terminal to graphics mode. You
+//! should never draw on virtual terminal used by X.org/Wayland server, this is unsafe and can lead
+//! to panics.
 //!
 //! By default linfb includes text and images drawing capabilities, which brings additional
 //! dependencies. You can disable these features if you only need low-level framebuffer
-//! interactions and [`Shape`] trait.
+//! interactions and [`Shape`](shape::Shape) trait.
 //!
-//! Basic usage can look like this:
-//! ```ignore
-//! use linfb::Framebuffer;
-//! use linfb::shape::{Color, Shape, Rectangle, Caption, Image, FontBuilder, Alignment};
+//! Everything besides the framebuffer device itself is usable without it: with only
+//! `render-core` feature enabled linfb doesn't depend on `/dev/fb0`, mmap or ioctls. Shapes and
+//! compositors are rendered into a [`Canvas`], and [`Canvas::as_bytes`] packs its pixels for
+//! memory managed by caller. [`Framebuffer`] is enabled with `framebuffer` feature, which is on
+//! by default.
+//!
+//! Shapes are imported from [`shape`], everything else from the root of the crate. Most
+//! programs only need [`prelude`], basic usage can look like this:
+//! ```no_run
+//! # #[cfg(all(feature = "text", feature = "images"))] {
+//! use linfb::prelude::*;
 //! let mut framebuffer = Framebuffer::open()
 //!     .expect("Failed to open framebuffer");
 //! let mut compositor = framebuffer.compositor((255, 255, 255).into());
@@ -54,116 +63,164 @@
 //! framebuffer.draw(0, 0, &compositor);
 //! // Really changing screen contents
 //! framebuffer.flush();
+//! # }
+//! ```
+//!
+//! Code drawing on framebuffer can be tested without a real device and root privileges:
+//! [`Framebuffer::in_memory`] creates framebuffer with any resolution and channel layout, backed
+//! by ordinary memory. Everything works the same way on it, so tests can check pixels. Pixels can
+//! also be sent anywhere else by implementing [`FramebufferBackend`], see
+//! [`Framebuffer::with_backend`]:
+//! ```
+//! # #[cfg(feature = "framebuffer")] {
+//! # use linfb::prelude::*;
+//! use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+//! let screen_info = fb_var_screeninfo::new(
+//!     64,
+//!     48,
+//!     16,
+//!     fb_bitfield::new(11, 5),
+//!     fb_bitfield::new(5, 6),
+//!     fb_bitfield::new(0, 5),
+//!     fb_bitfield::new(0, 0),
+//! );
+//! let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+//! let mut compositor = framebuffer.compositor((0, 0, 0).into());
+//! compositor.add("rect", Rectangle::builder()
+//!     .width(10)
+//!     .height(10)
+//!     .border_width(0)
+//!     .fill_color((255, 0, 0))
+//!     .build()
+//!     .unwrap()
+//!     .at(5, 5));
+//! framebuffer.draw(0, 0, &compositor);
+//! framebuffer.flush();
+//! assert_eq!(framebuffer.get_pixel(5, 5), (255, 0, 0).into());
+//! assert_eq!(framebuffer.get_pixel(4, 5), (0, 0, 0).into());
+//! # }
 //! ```
 
-use std::fs::OpenOptions;
-use std::io;
-use std::os::unix::io::AsRawFd;
-
-use memmap::{MmapMut, MmapOptions};
-
+#[cfg(feature = "framebuffer")]
 pub mod sys;
-use sys::fb_var_screeninfo;
-use sys::get_var_screeninfo;
+
+#[cfg(feature = "drm")]
+mod drm;
+#[cfg(feature = "vt")]
+mod vt;
+#[cfg(feature = "vt")]
+pub use vt::VtGuard;
+#[cfg(feature = "framebuffer")]
+mod backend;
+#[cfg(feature = "framebuffer")]
+mod framebuffer;
+#[cfg(feature = "framebuffer")]
+pub use backend::{FbdevBackend, FramebufferBackend, MemoryFramebuffer};
+#[cfg(feature = "framebuffer")]
+pub use framebuffer::{
+    BlankMode, BlendMode, BufferStrategy, FlipStats, Framebuffer, ModeActivation, ModeChange,
+    RegionWriter, RestoreGuard, Rotation, Transport, VirtualScreen,
+};
 
 mod error;
 pub use error::{Error, Result};
 
+#[cfg(feature = "render-core")]
 pub mod shape;
-use shape::{Color, Shape};
 
+#[cfg(feature = "render-core")]
+pub mod animation;
+
+#[cfg(feature = "render-core")]
+pub mod prelude;
+
+#[cfg(feature = "render-core")]
+mod palette;
+
+#[cfg(feature = "render-core")]
+mod canvas;
+#[cfg(feature = "render-core")]
+pub use canvas::{Canvas, Rect, RenderTarget};
+
+#[cfg(feature = "render-core")]
+mod pixel_format;
+#[cfg(feature = "render-core")]
+pub use pixel_format::{PixelFormat, PixelLayout};
+
+#[cfg(feature = "render-core")]
 mod compositor;
-pub use compositor::{Compositor, CompositorBuilder};
+#[cfg(feature = "render-core")]
+pub use compositor::{Compositor, CompositorBuilder, ShapeHandle};
+
+#[cfg(feature = "render-core")]
+mod limiter;
+#[cfg(feature = "render-core")]
+pub use limiter::{FrameInfo, FrameLimiter, FrameStats};
+
+#[cfg(feature = "framebuffer")]
+mod mirror;
+#[cfg(feature = "framebuffer")]
+pub use mirror::{MirrorPolicy, MirroredFramebuffer};
+
+#[cfg(feature = "framebuffer")]
+mod span;
+#[cfg(feature = "framebuffer")]
+pub use span::MultiFramebuffer;
+
+#[cfg(feature = "render-core")]
+mod transition;
+#[cfg(feature = "render-core")]
+pub use transition::{Direction, Transition};
 
 #[cfg(feature = "text")]
 mod text;
 
+#[cfg(feature = "text")]
+mod marquee;
+
+#[cfg(feature = "text")]
+mod log_view;
+
+#[cfg(feature = "render-core")]
+mod clock;
+
+#[cfg(feature = "render-core")]
+mod status_bar;
+
+#[cfg(feature = "render-core")]
+mod spinner;
+
+#[cfg(feature = "render-core")]
+mod line;
+
+#[cfg(feature = "render-core")]
+mod arc;
+
+#[cfg(feature = "render-core")]
+mod path;
+
+#[cfg(feature = "render-core")]
+mod histogram;
+
+#[cfg(feature = "render-core")]
+mod heatmap;
+
+#[cfg(feature = "render-core")]
+mod table;
+
+#[cfg(feature = "barcode")]
+mod barcode;
+
 #[cfg(feature = "images")]
 mod image;
 
-/// Basic object used to manipulate framebuffer.
-/// You should normally use [Shape] and [Compositor] to draw on it
-pub struct Framebuffer {
-    screen: Vec<u8>,
-    /// Information about framebuffer
-    pub screen_info: fb_var_screeninfo,
-    framebuffer: MmapMut,
-}
-
-impl Framebuffer {
-    /// Try to open `/dev/fb0` and create Framebuffer object.
-    /// It requires root privileges on most systems.
-    /// This method will panic if `/dev/fb0` is not a framebuffer or it's pixel size is not 32 bits
-    pub fn open() -> io::Result<Self> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(false)
-            .open("/dev/fb0")?;
-        let mut screen_info: fb_var_screeninfo = Default::default();
-        unsafe {
-            get_var_screeninfo(file.as_raw_fd(), &mut screen_info)
-                .expect("Failed to get var_screeninfo")
-        };
-
-        if screen_info.bits_per_pixel != 32 {
-            panic!("Size of one pixel must be 32 bits for linfb to work");
-        }
-
-        let framebuffer = unsafe {
-            MmapOptions::new()
-                .len(screen_info.overall_size())
-                .map_mut(&file)?
-        };
-        let screen = vec![0u8; framebuffer.len()];
-
-        Ok(Self {
-            screen,
-            framebuffer,
-            screen_info,
-        })
-    }
-
-    /// Flush internal buffer contents to the real framebuffer device
-    pub fn flush(&mut self) {
-        self.framebuffer.copy_from_slice(self.screen.as_slice());
-    }
-
-    /// Set pixel at x, y to color.
-    /// Alpha value of color is probably will be ignored, as it doesn't makes sense in this context
-    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
-        let color: Color = color.into();
-        let pixel_pos = ((y * self.screen_info.xres + x) * 4) as usize;
-
-        let mut pixel = 0u32;
-        pixel |=
-            (color.red as u32) >> (8 - self.screen_info.red.length) << self.screen_info.red.offset;
-        pixel |= (color.green as u32) >> (8 - self.screen_info.green.length)
-            << self.screen_info.green.offset;
-        pixel |= (color.blue as u32) >> (8 - self.screen_info.blue.length)
-            << self.screen_info.blue.offset;
-        pixel |= (color.alpha as u32) >> (8 - self.screen_info.transp.length)
-            << self.screen_info.transp.offset;
-        self.screen[pixel_pos..pixel_pos + 4].copy_from_slice(&pixel.to_ne_bytes());
-    }
-
-    /// Draw shape on internal buffer
-    pub fn draw<T: Shape>(&mut self, x: u32, y: u32, shape: &T) {
-        for (inner_y, row) in shape.render().iter().enumerate() {
-            for (inner_x, color) in row.iter().enumerate() {
-                if let Some(color) = color {
-                    self.set_pixel(x + (inner_x as u32), y + (inner_y as u32), *color);
-                }
-            }
-        }
-    }
-
-    /// Create [Compositor] object with size of a screen and given background color
-    pub fn compositor(&self, background: Color) -> Compositor {
-        Compositor::new(
-            self.screen_info.xres as usize,
-            self.screen_info.yres as usize,
-            background,
-        )
-    }
-}
+#[cfg(feature = "images")]
+mod sprite;
+
+#[cfg(all(feature = "framebuffer", feature = "images"))]
+mod recording;
+#[cfg(all(feature = "framebuffer", feature = "images"))]
+pub use recording::RecordingHandle;
+
+#[cfg(feature = "images")]
+pub mod testing;
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of frames used to compute [`FrameStats::fps`]
+const FPS_WINDOW: usize = 30;
+
+/// Statistics reported by [`FrameLimiter::tick`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameStats {
+    /// Real duration of the last frame, including rendering and sleeping. Zero for the first
+    /// frame
+    pub frame_time: Duration,
+    /// Overall number of frames which were skipped because rendering took too long
+    pub dropped_frames: u64,
+    /// Average frames per second over the last few frames
+    pub fps: f32,
+}
+
+/// Helper which keeps animation at the constant frame rate. Call [`FrameLimiter::tick`] once per
+/// frame and it will sleep for the remaining part of frame. Frames are scheduled against a
+/// monotonic clock, so oversleeping in one frame is compensated in the next one and errors don't
+/// accumulate:
+/// ```
+/// # use std::thread;
+/// # use std::time::{Duration, Instant};
+/// # use linfb::FrameLimiter;
+/// let mut limiter = FrameLimiter::new(100.0);
+/// let start = Instant::now();
+/// let mut stats = limiter.tick();
+/// for _ in 0..20 {
+///     // Rendering takes some time
+///     thread::sleep(Duration::from_millis(3));
+///     stats = limiter.tick();
+/// }
+/// // 20 frames after the first one at 100 fps, none of them missed
+/// assert!(start.elapsed() >= Duration::from_millis(200));
+/// assert_eq!(stats.dropped_frames, 0);
+///
+/// // Frame which takes two frame budgets is reported as dropped
+/// thread::sleep(Duration::from_millis(25));
+/// let stats = limiter.tick();
+/// assert!(stats.dropped_frames >= 1);
+/// assert!(stats.frame_time >= Duration::from_millis(25));
+///
+/// // Frame period is at least one nanosecond, however high frame rate is
+/// assert_eq!(FrameLimiter::new(1e12).period(), Duration::from_nanos(1));
+/// ```
+#[derive(Debug)]
+pub struct FrameLimiter {
+    period: Duration,
+    next_deadline: Option<Instant>,
+    last_tick: Option<Instant>,
+    dropped_frames: u64,
+    frame_times: VecDeque<Duration>,
+}
+
+impl FrameLimiter {
+    /// Create limiter with given target frame rate. Panics if `target_fps` is not positive
+    pub fn new(target_fps: f32) -> Self {
+        assert!(target_fps > 0f32, "target_fps must be positive");
+        Self {
+            period: Duration::max(
+                Duration::from_secs_f32(1f32 / target_fps),
+                Duration::from_nanos(1),
+            ),
+            next_deadline: None,
+            last_tick: None,
+            dropped_frames: 0,
+            frame_times: VecDeque::with_capacity(FPS_WINDOW),
+        }
+    }
+
+    /// Duration of one frame at target frame rate
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Sleep until the start of next frame and report statistics of the frame that just ended.
+    /// First call doesn't sleep. If rendering took longer than frame budget, missed frames are
+    /// skipped and no sleeping is done.
+    pub fn tick(&mut self) -> FrameStats {
+        let now = Instant::now();
+        self.next_deadline = Some(match self.next_deadline {
+            None => now + self.period,
+            Some(deadline) if now < deadline => {
+                thread::sleep(deadline - now);
+                deadline + self.period
+            }
+            Some(deadline) => {
+                let period = self.period.as_nanos();
+                let missed = (now - deadline).as_nanos() / period;
+                self.dropped_frames += missed as u64;
+                deadline + Duration::from_nanos(((missed + 1) * period) as u64)
+            }
+        });
+
+        let now = Instant::now();
+        let frame_time = match self.last_tick {
+            Some(last_tick) => now - last_tick,
+            None => Duration::default(),
+        };
+        self.last_tick = Some(now);
+        if self.frame_times.len() == FPS_WINDOW {
+            self.frame_times.pop_front();
+        }
+        if frame_time > Duration::default() {
+            self.frame_times.push_back(frame_time);
+        }
+
+        let total: Duration = self.frame_times.iter().sum();
+        FrameStats {
+            frame_time,
+            dropped_frames: self.dropped_frames,
+            fps: if total > Duration::default() {
+                self.frame_times.len() as f32 / total.as_secs_f32()
+            } else {
+                0f32
+            },
+        }
+    }
+}
+
+/// Timing of frame passed to callback of [`Framebuffer::run`](crate::Framebuffer::run)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameInfo {
+    /// Number of frame, starting from 0
+    pub index: u64,
+    /// Time since the start of the first frame
+    pub elapsed: Duration,
+    /// Time since the start of the previous frame, zero for the first frame
+    pub dt: Duration,
+}
@@ -0,0 +1,399 @@
+use derive_builder::Builder;
+
+use crate::error::{Error::*, Result};
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+use crate::RenderTarget;
+
+/// Straight segment between two points. Shape covers the bounding box of stroke, so its top left
+/// angle is not at `from` in general: [`Line::origin`] tells where to place the shape so its ends
+/// land at `from` and `to`. Everything outside of stroke is [`None`]:
+/// ```
+/// # use linfb::Compositor;
+/// # use linfb::shape::{Line, Shape};
+/// let line = Line::builder()
+///     .from(5, 3)
+///     .to(1, 1)
+///     .color((255, 0, 0))
+///     .build()
+///     .unwrap();
+/// assert_eq!(line.dimensions(), (5, 3));
+/// assert_eq!(line.origin(), (1, 1));
+/// let (x, y) = line.origin();
+///
+/// let mut compositor = Compositor::new(7, 5, (0, 0, 0).into());
+/// compositor.add("line", line.at(x as usize, y as usize));
+/// let rendered = compositor.render();
+/// let red = Some((255, 0, 0).into());
+/// assert_eq!(rendered[3][5], red);
+/// assert_eq!(rendered[1][1], red);
+/// let stroked: Vec<_> = (0..5)
+///     .flat_map(|y| (0..7).map(move |x| (x, y)))
+///     .filter(|&(x, y)| rendered[y][x] == red)
+///     .collect();
+/// assert_eq!(stroked, vec![(1, 1), (2, 2), (3, 2), (4, 3), (5, 3)]);
+/// ```
+///
+/// Thick lines are expanded perpendicular to their direction into a parallelogram, with ends cut
+/// along the shorter axis. Odd thickness is centered on the segment, the extra pixel of even
+/// thickness goes down or right. Horizontal and vertical lines are rectangles and a single
+/// point is a square:
+/// ```
+/// # use linfb::shape::{Line, Shape};
+/// let line = |from: (usize, usize), to: (usize, usize), thickness| {
+///     Line::builder()
+///         .from(from.0, from.1)
+///         .to(to.0, to.1)
+///         .color((255, 255, 255))
+///         .thickness(thickness)
+///         .build()
+///         .unwrap()
+/// };
+/// let white = Some((255, 255, 255).into());
+///
+/// let horizontal = line((10, 10), (15, 10), 3);
+/// assert_eq!(horizontal.origin(), (10, 9));
+/// assert_eq!(horizontal.render(), vec![vec![white; 6]; 3]);
+///
+/// let vertical = line((10, 15), (10, 10), 2);
+/// assert_eq!(vertical.origin(), (10, 10));
+/// assert_eq!(vertical.render(), vec![vec![white; 2]; 6]);
+///
+/// let point = line((0, 0), (0, 0), 3);
+/// assert_eq!(point.origin(), (-1, -1));
+/// assert_eq!(point.render(), vec![vec![white; 3]; 3]);
+/// assert_eq!(line((4, 4), (4, 4), 1).render(), vec![vec![white]]);
+///
+/// // Runs of diagonal are 3 * sqrt(2) = 4px long, so it's 3px thick perpendicular to itself
+/// let diagonal = line((0, 0), (9, 9), 3);
+/// assert_eq!(diagonal.origin(), (0, -1));
+/// assert_eq!(diagonal.dimensions(), (10, 13));
+/// for (y, row) in diagonal.render().iter().enumerate() {
+///     let stroked = row.iter().filter(|pixel| pixel.is_some()).count();
+///     assert!(stroked >= 1 && stroked <= 4);
+/// }
+/// for x in 0..10 {
+///     assert!(diagonal.contains(x, x + 2));
+/// }
+///
+/// # use linfb::Canvas;
+/// // Streaming rendering draws the same pixels
+/// for &(to, thickness) in &[((9, 2), 1), ((2, 9), 2), ((0, 7), 3), ((9, 0), 5)] {
+///     let line = line((4, 4), to, thickness);
+///     let (width, height) = line.dimensions();
+///     let mut canvas = Canvas::new(width, height, (0, 0, 0, 0).into());
+///     line.render_into(&mut canvas, 0, 0);
+///     let rendered: Vec<Vec<_>> = line
+///         .render()
+///         .iter()
+///         .map(|row| row.iter().map(|pixel| pixel.or(Some((0, 0, 0, 0).into()))).collect())
+///         .collect();
+///     assert_eq!(canvas.to_rows(), rendered);
+/// }
+/// ```
+///
+/// Antialiased lines express coverage of pixels by alpha of color, so 45° line is solid, while
+/// shallow line is split between two pixels in every column:
+/// ```
+/// # use linfb::shape::{Line, Shape};
+/// let line = |to: (usize, usize), thickness| {
+///     Line::builder()
+///         .from(0, 0)
+///         .to(to.0, to.1)
+///         .color((255, 255, 255))
+///         .thickness(thickness)
+///         .antialiased(true)
+///         .build()
+///         .unwrap()
+/// };
+/// let alpha = |line: &Line, x: usize, y: usize| line.render()[y][x].map_or(0, |color| color.alpha);
+///
+/// let diagonal = line((6, 6), 1);
+/// for i in 0..=6 {
+///     assert_eq!(alpha(&diagonal, i, i), 255);
+/// }
+/// assert_eq!(diagonal.render().iter().flatten().filter(|pixel| pixel.is_some()).count(), 7);
+///
+/// // Segment goes through y = x / 4, e.g. y = 0.25 at x = 1 and y = 0.5 at x = 2
+/// let shallow = line((8, 2), 1);
+/// assert_eq!(shallow.dimensions(), (9, 3));
+/// assert_eq!((alpha(&shallow, 1, 0), alpha(&shallow, 1, 1)), (191, 64));
+/// assert_eq!((alpha(&shallow, 2, 0), alpha(&shallow, 2, 1)), (128, 128));
+/// assert_eq!((alpha(&shallow, 3, 0), alpha(&shallow, 3, 1)), (64, 191));
+/// for x in 0..9 {
+///     let total: u32 = (0..3).map(|y| alpha(&shallow, x, y) as u32).sum();
+///     assert!((254..=256).contains(&total));
+/// }
+///
+/// // Thick strokes are solid inside and soft only at the edges
+/// let thick = line((8, 2), 3);
+/// assert_eq!(thick.dimensions(), (9, 5));
+/// let column: Vec<_> = (0..5).map(|y| alpha(&thick, 2, y)).collect();
+/// assert_eq!(column, vec![128, 255, 255, 128, 0]);
+/// ```
+///
+/// Thickness must be at least 1:
+/// ```
+/// # use linfb::shape::Line;
+/// let error = Line::builder()
+///     .from(0, 0)
+///     .to(5, 5)
+///     .color((0, 0, 0))
+///     .thickness(0)
+///     .build()
+///     .unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Line: thickness must be at least 1");
+/// let error = Line::builder().from(0, 0).color((0, 0, 0)).build().unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Line: `to` must be initialized");
+/// ```
+#[derive(Clone, Debug, Builder)]
+#[builder(build_fn(name = "build_unvalidated"))]
+pub struct Line {
+    /// First end of line, as x, y
+    #[builder(private, setter(name = "from_point"))]
+    pub from: (usize, usize),
+    /// Second end of line, as x, y
+    #[builder(private, setter(name = "to_point"))]
+    pub to: (usize, usize),
+    /// Color of line
+    #[builder(setter(into))]
+    pub color: Paint,
+    /// Width of stroke in px. Builder default is 1
+    #[builder(default = "1")]
+    pub thickness: usize,
+    /// Smooth edges of stroke with Xiaolin Wu's algorithm: pixels partially covered by stroke
+    /// get partially transparent color, so they're blended with whatever is underneath. Builder
+    /// default is `false`
+    #[builder(default)]
+    pub antialiased: bool,
+}
+
+impl Line {
+    /// Create a default [`LineBuilder`]
+    pub fn builder() -> LineBuilder {
+        LineBuilder::default()
+    }
+
+    /// Position of top left angle of shape in coordinates of `from` and `to`, i.e. where it
+    /// should be drawn. It's negative for thick lines which reach over the top or left edge, they
+    /// can be drawn with [`Framebuffer::draw_clipped`](crate::Framebuffer::draw_clipped)
+    pub fn origin(&self) -> (i64, i64) {
+        let (before, _) = self.padding();
+        let x = usize::min(self.from.0, self.to.0) as i64;
+        let y = usize::min(self.from.1, self.to.1) as i64;
+        let before = before as i64;
+        if self.from == self.to {
+            (x - before, y - before)
+        } else if self.is_steep() {
+            (x - before, y)
+        } else {
+            (x, y - before)
+        }
+    }
+
+    /// Whether line goes along y axis more than along x axis
+    fn is_steep(&self) -> bool {
+        let (dx, dy) = self.delta();
+        dy.unsigned_abs() > dx.unsigned_abs()
+    }
+
+    fn delta(&self) -> (i64, i64) {
+        (
+            self.to.0 as i64 - self.from.0 as i64,
+            self.to.1 as i64 - self.from.1 as i64,
+        )
+    }
+
+    /// Difference between ends along the longer and the shorter axes
+    fn axes(&self) -> (i64, i64) {
+        let (dx, dy) = self.delta();
+        if self.is_steep() {
+            (dy, dx)
+        } else {
+            (dx, dy)
+        }
+    }
+
+    /// Length of run of pixels across the longer axis, so stroke has the requested thickness
+    /// perpendicular to line
+    fn run(&self) -> usize {
+        let (major, minor) = self.axes();
+        if major == 0 {
+            return self.thickness;
+        }
+        let stretch = (major as f64).hypot(minor as f64) / major.unsigned_abs() as f64;
+        usize::max((self.thickness as f64 * stretch).round() as usize, 1)
+    }
+
+    /// Number of pixels of run before and after the pixel on segment
+    fn padding(&self) -> (usize, usize) {
+        let run = self.run();
+        let before = (run - 1) / 2;
+        (before, run - 1 - before)
+    }
+
+    /// Call `f` with start of every run of stroke relative to the top left angle of shape, as
+    /// position along the longer axis, position along the shorter one rounded to the nearest
+    /// pixel and exact position along the shorter one
+    fn for_each_run(&self, mut f: impl FnMut(usize, usize, f64)) {
+        let (major, minor) = self.axes();
+        // Runs go from the end with the smaller coordinate along the longer axis
+        let minor = if major < 0 { -minor } else { minor };
+        let (major_len, minor_len) = (major.unsigned_abs(), minor.unsigned_abs());
+        for i in 0..=major_len {
+            // Position of segment at i, rounded to the nearest pixel
+            let offset = (2 * minor_len * i + major_len)
+                .checked_div(2 * major_len)
+                .unwrap_or(0);
+            let exact = if major_len == 0 {
+                0f64
+            } else {
+                (minor_len * i) as f64 / major_len as f64
+            };
+            if minor < 0 {
+                f(
+                    i as usize,
+                    (minor_len - offset) as usize,
+                    minor_len as f64 - exact,
+                );
+            } else {
+                f(i as usize, offset as usize, exact);
+            }
+        }
+        // A point is a square, so its single run is repeated across the longer axis
+        if major_len == 0 {
+            for i in 1..self.thickness {
+                f(i, 0, 0f64);
+            }
+        }
+    }
+
+    /// Call `f` with x, y relative to the top left angle of shape and coverage from 0 to 1 of
+    /// every pixel of stroke. Without antialiasing every pixel is fully covered, otherwise run
+    /// starts at the exact position, so pixels at its ends are covered partially
+    fn for_each_pixel(&self, mut f: impl FnMut(usize, usize, f64)) {
+        let (run, steep) = (self.run(), self.is_steep());
+        let mut emit = |major, minor, coverage| {
+            if steep {
+                f(minor, major, coverage);
+            } else {
+                f(major, minor, coverage);
+            }
+        };
+        self.for_each_run(|major, position, exact| {
+            if !self.antialiased {
+                for minor in position..position + run {
+                    emit(major, minor, 1f64);
+                }
+                return;
+            }
+            let end = exact + run as f64;
+            for minor in exact.floor() as usize..end.ceil() as usize {
+                let coverage = f64::min(minor as f64 + 1f64, end) - f64::max(minor as f64, exact);
+                if coverage > 0f64 {
+                    emit(major, minor, coverage);
+                }
+            }
+        });
+    }
+
+    /// Color of pixel with given coverage
+    fn covered(color: Color, coverage: f64) -> Color {
+        Color {
+            alpha: (color.alpha as f64 * coverage).round() as u8,
+            ..color
+        }
+    }
+
+    /// Size of shape along the longer and the shorter axes
+    fn extent(&self) -> (usize, usize) {
+        let (major, minor) = self.axes();
+        let major = if major == 0 {
+            self.thickness
+        } else {
+            major.unsigned_abs() as usize + 1
+        };
+        (major, minor.unsigned_abs() as usize + self.run())
+    }
+}
+
+impl LineBuilder {
+    /// Set first end of line
+    pub fn from(&mut self, x: usize, y: usize) -> &mut Self {
+        self.from_point((x, y))
+    }
+
+    /// Set second end of line
+    pub fn to(&mut self, x: usize, y: usize) -> &mut Self {
+        self.to_point((x, y))
+    }
+
+    /// Build [`Line`]. Returns [`Error::InvalidShape`](crate::Error::InvalidShape) if required
+    /// fields are not set or thickness is 0
+    pub fn build(&self) -> Result<Line> {
+        let line = self.build_unvalidated().map_err(|reason| InvalidShape {
+            shape: "Line",
+            reason,
+        })?;
+        if line.thickness == 0 {
+            return Err(InvalidShape {
+                shape: "Line",
+                reason: "thickness must be at least 1".into(),
+            });
+        }
+        Ok(line)
+    }
+}
+
+impl Shape for Line {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let (width, height) = self.dimensions();
+        let mut rows = vec![vec![None; width]; height];
+        if let Some(color) = self.color.resolve(palette) {
+            self.for_each_pixel(|x, y, coverage| {
+                rows[y][x] = Some(Self::covered(color, coverage));
+            });
+        }
+        rows
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        let color = match self.color.resolve(target.palette()) {
+            Some(color) => color,
+            None => return,
+        };
+        self.for_each_pixel(|inner_x, inner_y, coverage| {
+            target.draw_pixel(x + inner_x, y + inner_y, Self::covered(color, coverage));
+        });
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let (major, minor) = self.extent();
+        if self.is_steep() {
+            (minor, major)
+        } else {
+            (major, minor)
+        }
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let mut found = false;
+        self.for_each_pixel(|inner_x, inner_y, _| {
+            found |= (inner_x, inner_y) == (x, y);
+        });
+        found
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.from,
+            self.to,
+            self.thickness,
+            self.antialiased,
+            &self.color,
+        ))
+    }
+}
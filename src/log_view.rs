@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use rusttype::Font;
+
+use crate::canvas::over;
+use crate::shape::{hash_of, Caption, Color, Shape};
+
+/// Default number of lines kept by [`LogView`]
+const DEFAULT_SCROLLBACK: usize = 1000;
+
+/// On-screen log panel. Lines are word-wrapped to the width of panel and rasterized once when
+/// they're pushed, so rendering only picks already rasterized rows. New lines scroll the view to
+/// the bottom, oldest lines are dropped when there are more of them than scrollback limit:
+/// ```
+/// # use linfb::Compositor;
+/// # use linfb::shape::{FontBuilder, LogView, Shape};
+/// let font = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// let mut compositor = Compositor::new(200, 100, (0, 0, 0).into());
+/// compositor.add(
+///     "log",
+///     LogView::new(200, 100, font, 16)
+///         .scrollback(3)
+///         .background((0, 0, 0, 128))
+///         .at(0, 0),
+/// );
+///
+/// let log: &mut LogView = compositor.get("log").unwrap();
+/// for i in 0..5 {
+///     log.push_line(&format!("line {}", i), (255, 255, 255));
+/// }
+/// assert_eq!(log.lines().collect::<Vec<_>>(), vec!["line 2", "line 3", "line 4"]);
+/// assert_eq!(log.render().len(), 100);
+/// assert!(log.render().iter().all(|row| row.len() == 200));
+///
+/// // Long line is wrapped to several rows
+/// log.push_line(&"word ".repeat(30), (255, 0, 0));
+/// assert_eq!(log.lines().count(), 3);
+/// assert!(log.total_rows() > 3);
+/// ```
+///
+/// Scrolling goes back in history and is clamped at both ends:
+/// ```
+/// # use linfb::shape::{FontBuilder, LogView, Shape};
+/// # let font = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// let mut log = LogView::new(200, 100, font, 16);
+/// for i in 0..20 {
+///     log.push_line(&format!("line {}", i), (255, 255, 255));
+/// }
+/// let bottom = log.render();
+/// let max_scroll = log.total_rows() - log.visible_rows();
+///
+/// log.scroll(3);
+/// assert_eq!(log.scroll_offset(), 3);
+/// assert_ne!(log.render(), bottom);
+/// log.scroll(1000);
+/// assert_eq!(log.scroll_offset(), max_scroll);
+/// log.scroll(-1000);
+/// assert_eq!(log.scroll_offset(), 0);
+/// assert_eq!(log.render(), bottom);
+///
+/// // New line scrolls to the bottom
+/// log.scroll(5);
+/// log.push_line("new", (255, 255, 255));
+/// assert_eq!(log.scroll_offset(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogView {
+    width: usize,
+    height: usize,
+    font: Arc<Font<'static>>,
+    size: u32,
+    line_height: usize,
+    background: Option<Color>,
+    scrollback: usize,
+    entries: VecDeque<Entry>,
+    /// Number of rows between the bottom of log and the bottom of view
+    scroll: usize,
+    /// Number of lines pushed over the whole lifetime, identifies content for hashing
+    pushed: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    text: String,
+    /// Rasterized and wrapped line, every row is `line_height` pixels high
+    pixels: Vec<Vec<Option<Color>>>,
+}
+
+impl LogView {
+    /// Create empty log panel with given size, drawing text with given font and size in px
+    pub fn new<F: Into<Arc<Font<'static>>>>(
+        width: usize,
+        height: usize,
+        font: F,
+        size: u32,
+    ) -> Self {
+        let font = font.into();
+        let line_height = Caption::builder()
+            .text(String::new())
+            .size(size)
+            .font(font.clone())
+            .build()
+            .map(|caption| caption.line_height())
+            .unwrap_or(size as usize);
+        Self {
+            width,
+            height,
+            font,
+            size,
+            line_height: usize::max(line_height, 1),
+            background: None,
+            scrollback: DEFAULT_SCROLLBACK,
+            entries: VecDeque::new(),
+            scroll: 0,
+            pushed: 0,
+        }
+    }
+
+    /// Fill panel with given color, can be translucent. By default background is transparent
+    pub fn background<C: Into<Color>>(mut self, color: C) -> Self {
+        self.background = Some(color.into());
+        self
+    }
+
+    /// Set maximum number of kept lines. Default is 1000
+    pub fn scrollback(mut self, limit: usize) -> Self {
+        self.scrollback = limit;
+        self.trim();
+        self
+    }
+
+    /// Add line to the bottom of log and scroll to it
+    pub fn push_line<C: Into<Color>>(&mut self, text: &str, color: C) {
+        let caption = Caption::builder()
+            .text(text.into())
+            .size(self.size)
+            .font(self.font.clone())
+            .color(color.into())
+            .max_width(self.width)
+            .build();
+        // Zero font size or width can't be rendered, such lines are empty
+        let mut pixels = caption.map(|caption| caption.render()).unwrap_or_default();
+        if pixels.is_empty() {
+            pixels = vec![vec![None; self.width]; self.line_height];
+        }
+        self.entries.push_back(Entry {
+            text: text.into(),
+            pixels,
+        });
+        self.pushed += 1;
+        self.scroll = 0;
+        self.trim();
+    }
+
+    /// Texts of kept lines, from the oldest one
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.text.as_str())
+    }
+
+    /// Scroll view by given number of rows. Positive numbers scroll back to older lines,
+    /// negative ones scroll forward. Scrolling is clamped between the top and the bottom of log
+    pub fn scroll(&mut self, rows: i32) {
+        let scroll = self.scroll as i64 + rows as i64;
+        self.scroll = usize::min(scroll.max(0) as usize, self.max_scroll());
+    }
+
+    /// Current scroll position in rows from the bottom of log
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll
+    }
+
+    /// Number of text rows of all kept lines, after wrapping
+    pub fn total_rows(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| entry.pixels.len() / self.line_height)
+            .sum()
+    }
+
+    /// Number of text rows which fit into panel
+    pub fn visible_rows(&self) -> usize {
+        self.height / self.line_height
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.total_rows().saturating_sub(self.visible_rows())
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.scrollback {
+            self.entries.pop_front();
+        }
+        self.scroll = usize::min(self.scroll, self.max_scroll());
+    }
+}
+
+impl Shape for LogView {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        let total_rows = self.total_rows();
+        let first_row = total_rows.saturating_sub(self.visible_rows() + self.scroll);
+        let mut result: Vec<Vec<Option<Color>>> = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.pixels.iter())
+            .skip(first_row * self.line_height)
+            .take(self.height)
+            .map(|row| match self.background {
+                Some(background) => row
+                    .iter()
+                    .map(|color| Some(color.map_or(background, |color| over(color, background))))
+                    .collect(),
+                None => row.clone(),
+            })
+            .collect();
+        result.resize(self.height, vec![self.background; self.width]);
+        result
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.width,
+            self.height,
+            self.background,
+            self.pushed,
+            self.entries.len(),
+            self.scroll,
+        ))
+    }
+}
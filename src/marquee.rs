@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use crate::shape::{hash_of, Caption, Color, Shape};
+
+/// Single line of text scrolling to the left inside of fixed-width window. Caption is rasterized
+/// once on creation, every frame only crops it. After the end of text and a gap the text starts
+/// again, and every cycle begins with a pause at the initial position:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::shape::{Caption, FontBuilder, Marquee, Shape};
+/// let caption = Caption::builder()
+///     .text("Now playing: some very long title".into())
+///     .size(16)
+///     .font(FontBuilder::default().family("sans-serif").build_shared().unwrap())
+///     .build()
+///     .unwrap();
+/// let text = caption.render();
+/// let text_width = text[0].len();
+///
+/// let mut marquee = Marquee::new(caption, 100, 100.0)
+///     .gap(20)
+///     .dwell(Duration::from_millis(500));
+/// assert!(marquee.scrolls());
+/// assert_eq!(marquee.render(), text.iter().map(|row| row[..100].to_vec()).collect::<Vec<_>>());
+///
+/// // Pause at the start
+/// marquee.advance(Duration::from_millis(400));
+/// assert_eq!(marquee.offset(), 0);
+/// marquee.advance(Duration::from_millis(200));
+/// assert_eq!(marquee.offset(), 10);
+///
+/// // End of text, then gap, then the text again
+/// let offset = text_width - 30;
+/// marquee.advance(Duration::from_secs_f32((offset - 10) as f32 / 100.0));
+/// assert_eq!(marquee.offset(), offset);
+/// let rendered = marquee.render();
+/// for (row, text_row) in rendered.iter().zip(text.iter()) {
+///     assert_eq!(row.len(), 100);
+///     assert_eq!(&row[..30], &text_row[offset..]);
+///     assert!(row[30..50].iter().all(Option::is_none));
+///     assert_eq!(&row[50..], &text_row[..50]);
+/// }
+///
+/// // After the whole cycle scrolling starts over
+/// marquee.advance(Duration::from_secs_f32(50.0 / 100.0));
+/// assert_eq!(marquee.offset(), 0);
+/// assert_eq!(marquee.render(), text.iter().map(|row| row[..100].to_vec()).collect::<Vec<_>>());
+/// ```
+///
+/// Text which fits into window is not scrolled at all:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::shape::{Caption, FontBuilder, Marquee, Shape};
+/// let caption = Caption::builder()
+///     .text("Short".into())
+///     .size(16)
+///     .font(FontBuilder::default().family("sans-serif").build_shared().unwrap())
+///     .build()
+///     .unwrap();
+/// let text = caption.render();
+/// let mut marquee = Marquee::new(caption, 200, 100.0);
+/// marquee.advance(Duration::from_secs(3));
+/// assert!(!marquee.scrolls());
+/// assert_eq!(marquee.offset(), 0);
+/// for (row, text_row) in marquee.render().iter().zip(text.iter()) {
+///     assert_eq!(row.len(), 200);
+///     assert_eq!(&row[..text_row.len()], &text_row[..]);
+///     assert!(row[text_row.len()..].iter().all(Option::is_none));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Marquee {
+    text: Vec<Vec<Option<Color>>>,
+    text_width: usize,
+    text_hash: u64,
+    window_width: usize,
+    speed: f32,
+    gap: usize,
+    dwell: Duration,
+    phase: f32,
+}
+
+impl Marquee {
+    /// Create marquee showing `window_width` px of caption and scrolling it by `speed` px per
+    /// second. Gap between end of text and its next start equals to the window width by default
+    pub fn new(caption: Caption, window_width: usize, speed: f32) -> Self {
+        let text = caption.render();
+        Self {
+            text_width: text.first().map(Vec::len).unwrap_or(0),
+            text_hash: caption.content_hash(),
+            text,
+            window_width,
+            speed,
+            gap: window_width,
+            dwell: Duration::default(),
+            phase: 0f32,
+        }
+    }
+
+    /// Set gap between end of text and its next start in px
+    pub fn gap(mut self, gap: usize) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Set time text stays at its initial position before every scroll
+    pub fn dwell(mut self, dwell: Duration) -> Self {
+        self.dwell = dwell;
+        self
+    }
+
+    /// Does text need scrolling, i.e. it doesn't fit into window
+    pub fn scrolls(&self) -> bool {
+        self.text_width > self.window_width && self.speed > 0f32
+    }
+
+    /// Move scrolling forward in time
+    pub fn advance(&mut self, elapsed: Duration) {
+        if self.scrolls() {
+            self.phase = (self.phase + elapsed.as_secs_f32()) % self.cycle();
+        }
+    }
+
+    /// First column of text shown at the left edge of window
+    pub fn offset(&self) -> usize {
+        if !self.scrolls() {
+            return 0;
+        }
+        let scroll_time = self.phase - self.dwell.as_secs_f32();
+        if scroll_time <= 0f32 {
+            0
+        } else {
+            (scroll_time * self.speed).round() as usize % self.period()
+        }
+    }
+
+    /// Width of text with the following gap
+    fn period(&self) -> usize {
+        self.text_width + self.gap
+    }
+
+    /// Duration of one full cycle in seconds
+    fn cycle(&self) -> f32 {
+        self.dwell.as_secs_f32() + self.period() as f32 / self.speed
+    }
+}
+
+impl Shape for Marquee {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        if self.window_width == 0 {
+            return Vec::new();
+        }
+        let (offset, period) = (self.offset(), self.period());
+        self.text
+            .iter()
+            .map(|row| {
+                (0..self.window_width)
+                    .map(|x| {
+                        let source = if self.scrolls() {
+                            (offset + x) % period
+                        } else {
+                            x
+                        };
+                        row.get(source).copied().flatten()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.window_width, self.text.len())
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.text_hash,
+            self.window_width,
+            self.period(),
+            self.offset(),
+        ))
+    }
+}
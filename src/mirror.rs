@@ -0,0 +1,235 @@
+use crate::canvas::{Canvas, Clipped, Rect, RenderTarget};
+use crate::error::{Error::*, Result};
+use crate::shape::{Color, Palette, Shape};
+use crate::{Compositor, Framebuffer};
+
+/// What [`MirroredFramebuffer`] does when framebuffers have different resolutions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MirrorPolicy {
+    /// Refuse to mirror framebuffers with different resolutions
+    Exact,
+    /// Draw onto the area of the smallest resolution, centered on every framebuffer
+    Letterbox,
+}
+
+/// Group of framebuffers showing the same picture. Drawing methods mirror ones of
+/// [`Framebuffer`] and are applied to every framebuffer, so framebuffers with different pixel
+/// formats show the same colors:
+/// ```
+/// # use linfb::{Framebuffer, MirrorPolicy, MirroredFramebuffer};
+/// # use linfb::shape::{Rectangle, Shape};
+/// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+/// let info = |red, blue| {
+///     fb_var_screeninfo::new(
+///         8,
+///         8,
+///         32,
+///         fb_bitfield::new(red, 8),
+///         fb_bitfield::new(8, 8),
+///         fb_bitfield::new(blue, 8),
+///         fb_bitfield::new(24, 8),
+///     )
+/// };
+/// let bgra = Framebuffer::in_memory(info(16, 0)).unwrap();
+/// let rgba = Framebuffer::in_memory(info(0, 16)).unwrap();
+/// let mut mirror = MirroredFramebuffer::new(vec![bgra, rgba], MirrorPolicy::Exact).unwrap();
+///
+/// let rectangle = Rectangle::builder()
+///     .width(4)
+///     .height(4)
+///     .fill_color((255, 0, 0))
+///     .border_color((0, 0, 255))
+///     .build()
+///     .unwrap();
+/// mirror.draw(2, 2, &rectangle);
+/// mirror.set_pixel(0, 0, (0, 255, 0));
+/// // Rectangles are clipped to the mirrored area
+/// mirror.fill_rect(6, 6, u32::MAX, u32::MAX, (0, 255, 0));
+/// mirror.flush();
+///
+/// let [bgra, rgba] = &mirror.framebuffers()[..] else { unreachable!() };
+/// assert_ne!(bgra.front_buffer(), rgba.front_buffer());
+/// for y in 0..8 {
+///     for x in 0..8 {
+///         assert_eq!(bgra.get_pixel(x, y), rgba.get_pixel(x, y));
+///     }
+/// }
+/// assert_eq!(rgba.get_pixel(3, 3), (255, 0, 0).into());
+/// assert_eq!(rgba.get_pixel(7, 7), (0, 255, 0).into());
+/// ```
+pub struct MirroredFramebuffer {
+    framebuffers: Vec<Framebuffer>,
+    width: usize,
+    height: usize,
+    scratch: Canvas,
+}
+
+impl MirroredFramebuffer {
+    /// Mirror given framebuffers. Returns an error if there are no framebuffers or if their
+    /// resolutions differ with [`MirrorPolicy::Exact`]:
+    /// ```
+    /// # use linfb::{Framebuffer, MirrorPolicy, MirroredFramebuffer, RenderTarget};
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let info = |width| fb_var_screeninfo::argb8888(width, 6);
+    /// let devices = || {
+    ///     vec![
+    ///         Framebuffer::in_memory(info(10)).unwrap(),
+    ///         Framebuffer::in_memory(info(6)).unwrap(),
+    ///     ]
+    /// };
+    /// assert!(MirroredFramebuffer::new(devices(), MirrorPolicy::Exact).is_err());
+    ///
+    /// let mut mirror = MirroredFramebuffer::new(devices(), MirrorPolicy::Letterbox).unwrap();
+    /// assert_eq!((mirror.width(), mirror.height()), (6, 6));
+    /// mirror.set_pixel(0, 0, (255, 255, 255));
+    /// assert_eq!(mirror.framebuffers()[0].get_pixel(2, 0), (255, 255, 255).into());
+    /// assert_eq!(mirror.framebuffers()[1].get_pixel(0, 0), (255, 255, 255).into());
+    /// ```
+    pub fn new(framebuffers: Vec<Framebuffer>, policy: MirrorPolicy) -> Result<Self> {
+        let sizes: Vec<_> = framebuffers
+            .iter()
+            .map(|framebuffer| (framebuffer.width(), framebuffer.height()))
+            .collect();
+        let (first_width, first_height) = *sizes.first().ok_or(NoFramebuffers)?;
+        if policy == MirrorPolicy::Exact {
+            if let Some(&size) = sizes
+                .iter()
+                .find(|&&size| size != (first_width, first_height))
+            {
+                return Err(ResolutionMismatch((first_width, first_height), size));
+            }
+        }
+        Ok(Self {
+            width: sizes.iter().map(|size| size.0).min().unwrap_or(0),
+            height: sizes.iter().map(|size| size.1).min().unwrap_or(0),
+            framebuffers,
+            scratch: Canvas::default(),
+        })
+    }
+
+    /// Mirrored framebuffers, in the order they were passed to [`MirroredFramebuffer::new`]
+    pub fn framebuffers(&self) -> &[Framebuffer] {
+        &self.framebuffers
+    }
+
+    /// Get mirrored framebuffers back
+    pub fn into_framebuffers(self) -> Vec<Framebuffer> {
+        self.framebuffers
+    }
+
+    /// Set pixel at x, y on every framebuffer. Pixels outside of the mirrored area are skipped
+    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
+        self.draw_pixel(x as usize, y as usize, color.into());
+    }
+
+    /// Fill rectangle on every framebuffer, see [`Framebuffer::fill_rect`]
+    pub fn fill_rect<C: Into<Color>>(&mut self, x: u32, y: u32, width: u32, height: u32, color: C) {
+        let color = color.into();
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let rect = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let rect = match rect.intersection(&screen) {
+            Some(rect) => rect,
+            None => return,
+        };
+        for y in rect.y..rect.y + rect.height {
+            self.draw_span(rect.x, y, rect.width, color);
+        }
+    }
+
+    /// Draw shape on every framebuffer, see [`Framebuffer::draw`]. Shape is rendered only once
+    pub fn draw<T: Shape>(&mut self, x: u32, y: u32, shape: &T) {
+        if let Some(compositor) = shape.as_any().downcast_ref::<Compositor>() {
+            let mut scratch = std::mem::take(&mut self.scratch);
+            compositor.render_themed(&mut scratch, self.palette());
+            for (inner_y, row) in scratch.rows().enumerate() {
+                self.draw_colors(x as usize, y as usize + inner_y, row);
+            }
+            self.scratch = scratch;
+        } else {
+            shape.render_into(self, x as usize, y as usize);
+        }
+    }
+
+    /// Flush all framebuffers one right after another, so they stay in sync
+    pub fn flush(&mut self) {
+        for framebuffer in &mut self.framebuffers {
+            framebuffer.flush();
+        }
+    }
+
+    /// Flush given region of all framebuffers, see [`Framebuffer::flush_region`]
+    pub fn flush_region(&mut self, region: Rect) {
+        let (width, height) = (self.width, self.height);
+        for framebuffer in &mut self.framebuffers {
+            let (x, y) = letterbox_offset(framebuffer, width, height);
+            framebuffer.flush_region(Rect::new(
+                region.x + x,
+                region.y + y,
+                region.width,
+                region.height,
+            ));
+        }
+    }
+
+    /// Create compositor with the size of mirrored area
+    pub fn compositor(&self, background: Color) -> Compositor {
+        Compositor::new(self.width, self.height, background)
+    }
+
+    /// Call `f` for every framebuffer with mirrored area of it and coordinates shifted into it
+    fn for_each(
+        &mut self,
+        x: usize,
+        y: usize,
+        mut f: impl FnMut(&mut dyn RenderTarget, usize, usize),
+    ) {
+        let (width, height) = (self.width, self.height);
+        for framebuffer in &mut self.framebuffers {
+            let (offset_x, offset_y) = letterbox_offset(framebuffer, width, height);
+            let mut target = Clipped {
+                target: framebuffer,
+                region: Rect::new(offset_x, offset_y, width, height),
+            };
+            f(&mut target, x + offset_x, y + offset_y);
+        }
+    }
+}
+
+/// Position of mirrored area with given size centered on framebuffer
+fn letterbox_offset(framebuffer: &Framebuffer, width: usize, height: usize) -> (usize, usize) {
+    (
+        (framebuffer.width() - width) / 2,
+        (framebuffer.height() - height) / 2,
+    )
+}
+
+impl RenderTarget for MirroredFramebuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.for_each(x, y, |target, x, y| target.draw_pixel(x, y, color));
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        self.for_each(x, y, |target, x, y| target.draw_span(x, y, len, color));
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        self.for_each(x, y, |target, x, y| target.draw_row(x, y, row));
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        self.for_each(x, y, |target, x, y| target.draw_colors(x, y, row));
+    }
+
+    /// Palette of the first framebuffer, see [`Framebuffer::set_palette`]
+    fn palette(&self) -> Option<&Palette> {
+        self.framebuffers.first().and_then(Framebuffer::palette)
+    }
+}
@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::error::{Error::*, Result};
+use crate::shape::Color;
+
+/// Set of named colors used for theming. Shapes can refer to palette colors with
+/// [`PaletteRef`], which are resolved at render time against palette of
+/// [`RenderTarget`](crate::RenderTarget) shape is drawn onto. [`Compositor`](crate::Compositor)
+/// provides its palette to its shapes, so replacing palette re-themes the whole scene:
+/// ```
+/// # use linfb::{Compositor, Rect};
+/// # use linfb::shape::{Palette, PaletteRef, Rectangle, Shape};
+/// let mut compositor = Compositor::new(10, 10, (0, 0, 0).into());
+/// compositor.add("button", Rectangle::builder()
+///     .width(10)
+///     .height(10)
+///     .border_width(0)
+///     .fill_color_ref(PaletteRef::new("accent"))
+///     .build()
+///     .unwrap()
+///     .at(0, 0));
+///
+/// compositor.set_palette(Palette::light());
+/// let light = compositor.render()[5][5];
+/// let before = compositor.clone();
+/// compositor.set_palette(Palette::dark());
+/// let dark = compositor.render()[5][5];
+/// assert_eq!(light, Some(Palette::light().resolve("accent").unwrap()));
+/// assert_eq!(dark, Some(Palette::dark().resolve("accent").unwrap()));
+/// // Shapes are hashed with unresolved palette colors, so replacing palette damages everything
+/// assert_eq!(compositor.diff(&before), vec![Rect::new(0, 0, 10, 10)]);
+///
+/// // Nested compositor without palette uses palette of its parent
+/// let mut child = Compositor::new(10, 10, (0, 0, 0).into());
+/// child.add("button", compositor.get_positioned("button").unwrap().clone());
+/// assert_eq!(child.render()[5][5], Some((0, 0, 0).into()));
+/// let mut parent = Compositor::new(10, 10, (0, 0, 0).into());
+/// parent.set_palette(Palette::dark());
+/// parent.add("child", child.at(0, 0));
+/// assert_eq!(parent.render()[5][5], dark);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Palette {
+    colors: HashMap<String, Color>,
+}
+
+impl Palette {
+    /// Create empty palette
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Palette with light `"background"`, `"surface"`, `"accent"` and dark `"text"` colors
+    pub fn light() -> Self {
+        let mut palette = Self::new();
+        palette
+            .set("background", (250, 250, 250))
+            .set("surface", (255, 255, 255))
+            .set("accent", (25, 118, 210))
+            .set("text", (33, 33, 33));
+        palette
+    }
+
+    /// Palette with dark `"background"`, `"surface"`, `"accent"` and light `"text"` colors
+    pub fn dark() -> Self {
+        let mut palette = Self::new();
+        palette
+            .set("background", (18, 18, 18))
+            .set("surface", (40, 40, 40))
+            .set("accent", (144, 202, 249))
+            .set("text", (238, 238, 238));
+        palette
+    }
+
+    /// Set color with given name, replacing the previous one
+    pub fn set<C: Into<Color>>(&mut self, name: &str, color: C) -> &mut Self {
+        self.colors.insert(name.into(), color.into());
+        self
+    }
+
+    /// Get color by name. Will return [`None`] if there are no color with this name
+    pub fn get(&self, name: &str) -> Option<Color> {
+        self.colors.get(name).copied()
+    }
+
+    /// Get color by name or return an error if there are no color with this name
+    pub fn resolve(&self, name: &str) -> Result<Color> {
+        self.get(name)
+            .ok_or_else(|| UnknownPaletteColor(name.into()))
+    }
+}
+
+/// Colors are hashed in order of their names, so palettes which are equal have the same hash
+impl Hash for Palette {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut colors: Vec<_> = self.colors.iter().collect();
+        colors.sort_unstable_by_key(|(name, _color)| *name);
+        colors.hash(state);
+    }
+}
+
+/// Reference to a color of [`Palette`] by it's name. Name can be both a literal and a string
+/// built at runtime:
+/// ```
+/// # use linfb::shape::{Palette, PaletteRef, Paint};
+/// let mut palette = Palette::new();
+/// palette.set("series-1", (255, 0, 0));
+/// let literal = Paint::from(PaletteRef::new("series-1"));
+/// let runtime = Paint::from(PaletteRef::new(format!("series-{}", 1)));
+/// assert_eq!(literal, runtime);
+/// assert_eq!(runtime.resolve(Some(&palette)), Some((255, 0, 0).into()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PaletteRef(pub Cow<'static, str>);
+
+impl PaletteRef {
+    /// Create reference to color with given name
+    pub fn new<N: Into<Cow<'static, str>>>(name: N) -> Self {
+        Self(name.into())
+    }
+
+    /// Name of referenced color
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Color of some part of shape: either fixed [`Color`] or [`PaletteRef`]. Shape builders accept
+/// anything convertible to it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Paint {
+    Fixed(Color),
+    Palette(PaletteRef),
+}
+
+impl Paint {
+    /// Get actual color. Palette references are resolved against given palette, [`None`] is
+    /// returned when there is no palette or no such color in it. Shapes get the palette from
+    /// [`RenderTarget::palette`](crate::RenderTarget::palette) they're drawn onto
+    pub fn resolve(&self, palette: Option<&Palette>) -> Option<Color> {
+        match self {
+            Paint::Fixed(color) => Some(*color),
+            Paint::Palette(reference) => palette.and_then(|palette| palette.get(reference.name())),
+        }
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Self::Fixed(color)
+    }
+}
+
+impl From<(u8, u8, u8)> for Paint {
+    fn from(rgb: (u8, u8, u8)) -> Self {
+        Self::Fixed(rgb.into())
+    }
+}
+
+impl From<(u8, u8, u8, u8)> for Paint {
+    fn from(rgba: (u8, u8, u8, u8)) -> Self {
+        Self::Fixed(rgba.into())
+    }
+}
+
+impl From<PaletteRef> for Paint {
+    fn from(reference: PaletteRef) -> Self {
+        Self::Palette(reference)
+    }
+}
@@ -0,0 +1,512 @@
+use derive_builder::Builder;
+
+use crate::error::{Error::*, Result};
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+use crate::RenderTarget;
+
+/// Miter joins longer than this many halves of thickness, measured from the vertex, are drawn as
+/// bevel joins
+const MITER_LIMIT: f64 = 4f64;
+
+/// Look of open ends of [`Path`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Cap {
+    /// Stroke ends exactly at the end point
+    #[default]
+    Butt,
+    /// Half of a disc with diameter of thickness is added at the end
+    Round,
+    /// Stroke is extended by half of thickness past the end
+    Square,
+}
+
+/// Look of corners of [`Path`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Join {
+    /// Outer edges are extended until they meet in a sharp corner. Corners sharper than about
+    /// 29° would make very long spikes, so they're beveled instead
+    #[default]
+    Miter,
+    /// Corner is rounded with a disc with diameter of thickness
+    Round,
+    /// Corner is cut straight between outer edges
+    Bevel,
+}
+
+/// Part of stroke which covers some pixels
+#[derive(Clone, Debug)]
+enum Piece {
+    /// Body of segment, possibly extended past its ends
+    Segment {
+        start: (f64, f64),
+        /// Unit vector from start to end
+        direction: (f64, f64),
+        length: f64,
+        /// Extension before start and after end
+        extend: (f64, f64),
+    },
+    /// Disc with diameter of thickness
+    Disc((f64, f64)),
+    /// Convex polygon
+    Polygon(Vec<(f64, f64)>),
+}
+
+/// Stroke going through several points, e.g. a route or a chart. Path is rasterized as a whole,
+/// so every pixel is covered once, even where segments overlap at corners: semi-transparent
+/// strokes don't get darker spots. Shape covers the bounding box of stroke, [`Path::origin`]
+/// tells where to place it so points land at their coordinates. Everything outside of stroke is
+/// [`None`]:
+/// ```
+/// # use linfb::Canvas;
+/// # use linfb::shape::{Join, Path, Shape};
+/// let mut builder = Path::new(vec![(0, 0), (10, 0), (10, 10)]);
+/// builder.stroke_color((255, 0, 0, 128)).thickness(5);
+/// let path = builder.build().unwrap();
+/// assert_eq!(path.origin(), (0, -2));
+/// assert_eq!(path.dimensions(), (13, 13));
+///
+/// // Miter join fills the whole outer corner, there are no holes
+/// for x in 0..13 {
+///     for y in 0..5 {
+///         assert!(path.contains(x, y));
+///     }
+/// }
+/// for y in 0..13 {
+///     for x in 8..13 {
+///         assert!(path.contains(x, y));
+///     }
+/// }
+/// assert!(!path.contains(7, 5));
+///
+/// // Every pixel is blended once
+/// let mut canvas = Canvas::new(13, 13, (0, 0, 0).into());
+/// path.render_into(&mut canvas, 0, 0);
+/// let mut colors: Vec<_> = canvas.to_rows().into_iter().flatten().collect();
+/// colors.sort_by_key(|color| color.map(|color| color.red));
+/// colors.dedup();
+/// assert_eq!(colors.len(), 2);
+///
+/// // Other joins cut the outer corner
+/// let bevel = builder.clone().join(Join::Bevel).build().unwrap();
+/// assert!(!bevel.contains(12, 0));
+/// assert!(!bevel.contains(12, 1));
+/// assert!(bevel.contains(11, 1));
+/// let round = builder.clone().join(Join::Round).build().unwrap();
+/// assert!(!round.contains(12, 0));
+/// assert!(round.contains(12, 1));
+///
+/// // Sharp corners don't make long spikes
+/// let sharp = Path::new(vec![(0, 0), (20, 0), (0, 3)]).thickness(5).build().unwrap();
+/// assert_eq!(sharp.dimensions(), (21, 8));
+/// ```
+///
+/// Round caps and joins make a stroke which covers exactly pixels not farther than half of
+/// thickness from the line through points:
+/// ```
+/// # use linfb::shape::{Cap, Join, Path, Shape};
+/// let points = vec![(0, 0), (12, 3), (14, 15), (2, 9)];
+/// let path = Path::new(points.clone())
+///     .stroke_color((0, 0, 255))
+///     .thickness(5)
+///     .cap(Cap::Round)
+///     .join(Join::Round)
+///     .build()
+///     .unwrap();
+/// let distance = |x: f64, y: f64, from: (i32, i32), to: (i32, i32)| {
+///     let (ax, ay) = (from.0 as f64, from.1 as f64);
+///     let (dx, dy) = (to.0 as f64 - ax, to.1 as f64 - ay);
+///     let t = (((x - ax) * dx + (y - ay) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+///     (x - ax - t * dx).hypot(y - ay - t * dy)
+/// };
+/// let (left, top) = path.origin();
+/// let (width, height) = path.dimensions();
+/// assert_eq!((left, top, width, height), (-2, -2, 19, 20));
+/// for (y, row) in path.render().iter().enumerate() {
+///     for (x, pixel) in row.iter().enumerate() {
+///         let (x, y) = ((x as i64 + left) as f64, (y as i64 + top) as f64);
+///         let near = points.windows(2).any(|ends| distance(x, y, ends[0], ends[1]) <= 2.5);
+///         assert_eq!(pixel.is_some(), near);
+///     }
+/// }
+/// ```
+///
+/// Open ends look the same as in [`Line`](crate::shape::Line) by default, caps can extend them.
+/// Closed paths have no ends, the last point is joined with the first one:
+/// ```
+/// # use linfb::shape::{Cap, Path, Shape};
+/// let segment = |cap| Path::new(vec![(0, 0), (10, 0)]).thickness(5).cap(cap).build().unwrap();
+/// assert_eq!(segment(Cap::Butt).origin(), (0, -2));
+/// assert_eq!(segment(Cap::Butt).dimensions(), (11, 5));
+/// assert_eq!(segment(Cap::Square).origin(), (-2, -2));
+/// assert_eq!(segment(Cap::Square).dimensions(), (15, 5));
+/// assert!(segment(Cap::Square).contains(0, 0));
+/// assert_eq!(segment(Cap::Round).dimensions(), (15, 5));
+/// assert!(!segment(Cap::Round).contains(0, 0));
+/// assert!(segment(Cap::Round).contains(0, 2));
+///
+/// let square = vec![(0, 0), (10, 0), (10, 10), (0, 10)];
+/// let closed = Path::new(square.clone()).closed(true).build().unwrap();
+/// assert_eq!(closed.dimensions(), (11, 11));
+/// let open = Path::new(square).build().unwrap();
+/// for y in 0..11 {
+///     for x in 0..11 {
+///         let border = x == 0 || y == 0 || x == 10 || y == 10;
+///         assert_eq!(closed.contains(x, y), border);
+///         assert_eq!(open.contains(x, y), border && (x != 0 || y == 0 || y == 10));
+///     }
+/// }
+///
+/// // Single point is visible with round or square caps only
+/// let dot = |cap| Path::new(vec![(3, 3)]).thickness(3).cap(cap).build().unwrap();
+/// assert_eq!(dot(Cap::Butt).dimensions(), (0, 0));
+/// assert_eq!(dot(Cap::Square).origin(), (2, 2));
+/// assert_eq!(dot(Cap::Square).dimensions(), (3, 3));
+/// ```
+///
+/// Thickness must be at least 1:
+/// ```
+/// # use linfb::shape::Path;
+/// let error = Path::new(vec![(0, 0), (5, 5)]).thickness(0).build().unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Path: thickness must be at least 1");
+/// let error = Path::builder().thickness(2).build().unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Path: `points` must be initialized");
+/// ```
+#[derive(Clone, Debug, Builder)]
+#[builder(build_fn(name = "build_unvalidated"))]
+pub struct Path {
+    /// Points path goes through, as x, y
+    points: Vec<(i32, i32)>,
+    /// Color of stroke. Builder default is black
+    #[builder(setter(into), default = "(0, 0, 0).into()")]
+    stroke_color: Paint,
+    /// Width of stroke in px. Builder default is 1. Odd thickness is centered on points, the
+    /// extra pixel of even thickness goes down or right
+    #[builder(default = "1")]
+    thickness: usize,
+    /// Look of open ends. Builder default is [`Cap::Butt`]
+    #[builder(default)]
+    cap: Cap,
+    /// Look of corners. Builder default is [`Join::Miter`]
+    #[builder(default)]
+    join: Join,
+    /// Join the last point with the first one. Closed path has no caps. Builder default is
+    /// `false`
+    #[builder(default)]
+    closed: bool,
+    /// Pieces of stroke, computed once on build
+    #[builder(setter(skip))]
+    pieces: Vec<Piece>,
+    /// Covered pixels as left, top, width and height in coordinates of points
+    #[builder(setter(skip))]
+    bounds: (i64, i64, usize, usize),
+}
+
+impl Path {
+    /// Create a default [`PathBuilder`]
+    pub fn builder() -> PathBuilder {
+        PathBuilder::default()
+    }
+
+    /// Create [`PathBuilder`] for path through given points, shortcut for
+    /// `Path::builder().points(points)`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(points: Vec<(i32, i32)>) -> PathBuilder {
+        let mut builder = Self::builder();
+        builder.points(points);
+        builder
+    }
+
+    /// Points path goes through
+    pub fn points(&self) -> &[(i32, i32)] {
+        &self.points
+    }
+
+    /// Position of top left angle of shape in coordinates of points, i.e. where it should be
+    /// drawn. It's negative for paths which reach over the top or left edge, they can be drawn
+    /// with [`Framebuffer::draw_clipped`](crate::Framebuffer::draw_clipped)
+    pub fn origin(&self) -> (i64, i64) {
+        (self.bounds.0, self.bounds.1)
+    }
+
+    fn half(&self) -> f64 {
+        self.thickness as f64 / 2f64
+    }
+
+    /// Points without repeats, in continuous coordinates where pixel x, y covers square from x, y
+    /// to x + 1, y + 1
+    fn vertices(&self) -> Vec<(f64, f64)> {
+        // Centered on pixel for odd thickness and on its bottom right angle for even one
+        let shift = if self.thickness % 2 == 0 { 1f64 } else { 0.5 };
+        let mut vertices: Vec<_> = self
+            .points
+            .iter()
+            .map(|&(x, y)| (x as f64 + shift, y as f64 + shift))
+            .collect();
+        vertices.dedup();
+        if self.closed && vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        vertices
+    }
+
+    /// Split stroke into simple pieces, which together cover it
+    fn pieces(&self) -> Vec<Piece> {
+        let vertices = self.vertices();
+        let half = self.half();
+        let mut pieces = Vec::new();
+        if vertices.len() == 1 {
+            match self.cap {
+                Cap::Butt => {}
+                Cap::Round => pieces.push(Piece::Disc(vertices[0])),
+                Cap::Square => pieces.push(Piece::Segment {
+                    start: vertices[0],
+                    direction: (1f64, 0f64),
+                    length: 0f64,
+                    extend: (half, half),
+                }),
+            }
+            return pieces;
+        }
+
+        let segments = if self.closed {
+            vertices.len()
+        } else {
+            vertices.len().saturating_sub(1)
+        };
+        let segment = |i: usize| {
+            let start = vertices[i];
+            let end = vertices[(i + 1) % vertices.len()];
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let length = dx.hypot(dy);
+            (start, (dx / length, dy / length), length)
+        };
+        for i in 0..segments {
+            let (start, direction, length) = segment(i);
+            let cap = |is_end| match self.cap {
+                Cap::Square if is_end && !self.closed => half,
+                _ => 0f64,
+            };
+            pieces.push(Piece::Segment {
+                start,
+                direction,
+                length,
+                extend: (cap(i == 0), cap(i + 1 == segments)),
+            });
+            if !self.closed && self.cap == Cap::Round {
+                if i == 0 {
+                    pieces.push(Piece::Disc(start));
+                }
+                if i + 1 == segments {
+                    pieces.push(Piece::Disc(vertices[i + 1]));
+                }
+            }
+        }
+
+        let joins = if self.closed {
+            0..segments
+        } else {
+            1..segments
+        };
+        for i in joins {
+            let (_, incoming, _) = segment((i + segments - 1) % segments);
+            let (vertex, outgoing, _) = segment(i);
+            if let Some(piece) = self.join_piece(vertex, incoming, outgoing) {
+                pieces.push(piece);
+            }
+        }
+        pieces
+    }
+
+    /// Piece filling the outer side of corner between segments with given directions
+    fn join_piece(
+        &self,
+        vertex: (f64, f64),
+        incoming: (f64, f64),
+        outgoing: (f64, f64),
+    ) -> Option<Piece> {
+        if incoming == outgoing {
+            return None;
+        }
+        if self.join == Join::Round {
+            return Some(Piece::Disc(vertex));
+        }
+        let half = self.half();
+        let normal = |(dx, dy): (f64, f64)| (-dy, dx);
+        let (first, second) = (normal(incoming), normal(outgoing));
+        // Outer side is the one segments turn away from
+        let side = if first.0 * outgoing.0 + first.1 * outgoing.1 > 0f64 {
+            -half
+        } else {
+            half
+        };
+        let offset = |(x, y): (f64, f64), scale: f64| (vertex.0 + x * scale, vertex.1 + y * scale);
+        let bevel = vec![vertex, offset(first, side), offset(second, side)];
+        let bisector = (first.0 + second.0, first.1 + second.1);
+        let squared = bisector.0 * bisector.0 + bisector.1 * bisector.1;
+        // Distance to the tip is 2 / |bisector| halves of thickness
+        if self.join == Join::Bevel || squared * MITER_LIMIT * MITER_LIMIT < 4f64 {
+            return Some(Piece::Polygon(bevel));
+        }
+        let tip = offset(bisector, 2f64 * side / squared);
+        Some(Piece::Polygon(vec![bevel[0], bevel[1], tip, bevel[2]]))
+    }
+
+    /// Covered pixels as left, top, width and height in coordinates of points
+    fn bounds_of(&self, pieces: &[Piece]) -> (i64, i64, usize, usize) {
+        let half = self.half();
+        let mut corners = Vec::new();
+        for piece in pieces {
+            match piece {
+                Piece::Segment {
+                    start,
+                    direction: (dx, dy),
+                    length,
+                    extend: (before, after),
+                } => {
+                    for &along in &[-before, length + after] {
+                        for &across in &[-half, half] {
+                            corners.push((
+                                start.0 + dx * along - dy * across,
+                                start.1 + dy * along + dx * across,
+                            ));
+                        }
+                    }
+                }
+                Piece::Disc((x, y)) => {
+                    corners.push((x - half, y - half));
+                    corners.push((x + half, y + half));
+                }
+                Piece::Polygon(points) => corners.extend(points),
+            }
+        }
+        if corners.is_empty() {
+            return (0, 0, 0, 0);
+        }
+        let fold = |axis: fn(&(f64, f64)) -> f64| {
+            let min = corners.iter().map(axis).fold(f64::INFINITY, f64::min);
+            let max = corners.iter().map(axis).fold(f64::NEG_INFINITY, f64::max);
+            // Pixels whose centers are inside
+            let (first, last) = ((min - 0.5).ceil() as i64, (max - 0.5).floor() as i64);
+            (first, (last - first + 1).max(0) as usize)
+        };
+        let (left, width) = fold(|&(x, _)| x);
+        let (top, height) = fold(|&(_, y)| y);
+        (left, top, width, height)
+    }
+
+    /// Whether center of pixel at x, y in coordinates of points is covered by any piece
+    fn covers(&self, x: i64, y: i64) -> bool {
+        let half = self.half();
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+        self.pieces.iter().any(|piece| match piece {
+            Piece::Segment {
+                start,
+                direction: (dx, dy),
+                length,
+                extend: (before, after),
+            } => {
+                let (px, py) = (x - start.0, y - start.1);
+                let along = px * dx + py * dy;
+                let across = py * dx - px * dy;
+                along >= -before && along <= length + after && across.abs() <= half
+            }
+            Piece::Disc((cx, cy)) => (x - cx).hypot(y - cy) <= half,
+            Piece::Polygon(points) => {
+                let signs = points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .map(|(&(ax, ay), &(bx, by))| (bx - ax) * (y - ay) - (by - ay) * (x - ax));
+                let (mut positive, mut negative) = (false, false);
+                for sign in signs {
+                    positive |= sign > 0f64;
+                    negative |= sign < 0f64;
+                }
+                !(positive && negative)
+            }
+        })
+    }
+
+    /// Call `f` with every run of covered pixels relative to the top left angle of shape, as x,
+    /// y and length
+    fn for_each_run(&self, mut f: impl FnMut(usize, usize, usize)) {
+        let (left, top, width, height) = self.bounds;
+        for y in 0..height {
+            let mut start = None;
+            for x in 0..=width {
+                let covered = x < width && self.covers(left + x as i64, top + y as i64);
+                match (covered, start) {
+                    (true, None) => start = Some(x),
+                    (false, Some(from)) => {
+                        f(from, y, x - from);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl PathBuilder {
+    /// Build [`Path`]. Returns [`Error::InvalidShape`](crate::Error::InvalidShape) if points are
+    /// not set or thickness is 0
+    pub fn build(&self) -> Result<Path> {
+        let mut path = self.build_unvalidated().map_err(|reason| InvalidShape {
+            shape: "Path",
+            reason,
+        })?;
+        if path.thickness == 0 {
+            return Err(InvalidShape {
+                shape: "Path",
+                reason: "thickness must be at least 1".into(),
+            });
+        }
+        path.pieces = path.pieces();
+        path.bounds = path.bounds_of(&path.pieces);
+        Ok(path)
+    }
+}
+
+impl Shape for Path {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let (width, height) = self.dimensions();
+        let mut rows = vec![vec![None; width]; height];
+        if let Some(color) = self.stroke_color.resolve(palette) {
+            self.for_each_run(|x, y, len| rows[y][x..x + len].fill(Some(color)));
+        }
+        rows
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        if let Some(color) = self.stroke_color.resolve(target.palette()) {
+            self.for_each_run(|inner_x, inner_y, len| {
+                target.draw_span(x + inner_x, y + inner_y, len, color)
+            });
+        }
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let (_, _, width, height) = self.bounds;
+        (width, height)
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let (left, top, width, height) = self.bounds;
+        x < width && y < height && self.covers(left + x as i64, top + y as i64)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            &self.points,
+            self.thickness,
+            self.cap,
+            self.join,
+            self.closed,
+            &self.stroke_color,
+        ))
+    }
+}
@@ -0,0 +1,180 @@
+use crate::shape::Color;
+
+/// Layout of 32-bit pixel: offset and length in bits of every channel, counted from the least
+/// significant bit. Packed pixels are stored as native-endian [`u32`], just like in framebuffer
+/// memory. Channels with zero length are dropped when encoding, missing alpha is decoded as
+/// opaque:
+/// ```
+/// # use linfb::PixelFormat;
+/// let format = PixelFormat::XRGB8888;
+/// let pixel = format.encode((255, 128, 0, 10).into());
+/// assert_eq!(pixel, 0x00ff8000);
+/// assert_eq!(format.decode(pixel), (255, 128, 0).into());
+///
+/// // Channels of other lengths are scaled, e.g. for deep color framebuffers
+/// let format = PixelFormat::new((20, 10), (10, 10), (0, 10), (30, 2));
+/// assert_eq!(format.encode((255, 0, 0).into()), 0xfff00000);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PixelFormat {
+    /// Offset and length of red channel
+    pub red: (u32, u32),
+    /// Offset and length of green channel
+    pub green: (u32, u32),
+    /// Offset and length of blue channel
+    pub blue: (u32, u32),
+    /// Offset and length of alpha channel
+    pub alpha: (u32, u32),
+}
+
+impl PixelFormat {
+    /// Blue in the lowest byte, alpha is ignored. The most common framebuffer format
+    pub const XRGB8888: Self = Self::new((16, 8), (8, 8), (0, 8), (0, 0));
+    /// Blue in the lowest byte, alpha in the highest one
+    pub const ARGB8888: Self = Self::new((16, 8), (8, 8), (0, 8), (24, 8));
+    /// Red in the lowest byte, alpha is ignored
+    pub const XBGR8888: Self = Self::new((0, 8), (8, 8), (16, 8), (0, 0));
+    /// Red in the lowest byte, alpha in the highest one. On little-endian machines bytes are in
+    /// the same order as channels of [`Color`]
+    pub const ABGR8888: Self = Self::new((0, 8), (8, 8), (16, 8), (24, 8));
+    /// Alpha in the lowest byte, red in the highest one
+    pub const RGBA8888: Self = Self::new((24, 8), (16, 8), (8, 8), (0, 8));
+    /// Alpha in the lowest byte, blue in the highest one
+    pub const BGRA8888: Self = Self::new((8, 8), (16, 8), (24, 8), (0, 8));
+    /// 16-bit pixel without alpha, green gets one bit more than red and blue
+    pub const RGB565: Self = Self::new((11, 5), (5, 6), (0, 5), (0, 0));
+
+    /// Create format from (offset, length) pairs of channels
+    pub const fn new(
+        red: (u32, u32),
+        green: (u32, u32),
+        blue: (u32, u32),
+        alpha: (u32, u32),
+    ) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Well-known layout this format has, if any:
+    /// ```
+    /// # use linfb::{PixelFormat, PixelLayout};
+    /// assert_eq!(PixelFormat::XRGB8888.layout(), PixelLayout::Xrgb8888);
+    /// let deep = PixelFormat::new((20, 10), (10, 10), (0, 10), (30, 2));
+    /// assert_eq!(deep.layout(), PixelLayout::Other(deep));
+    /// ```
+    pub fn layout(&self) -> PixelLayout {
+        [
+            (Self::XRGB8888, PixelLayout::Xrgb8888),
+            (Self::ARGB8888, PixelLayout::Argb8888),
+            (Self::XBGR8888, PixelLayout::Xbgr8888),
+            (Self::ABGR8888, PixelLayout::Abgr8888),
+            (Self::RGBA8888, PixelLayout::Rgba8888),
+            (Self::BGRA8888, PixelLayout::Bgra8888),
+            (Self::RGB565, PixelLayout::Rgb565),
+        ]
+        .iter()
+        .find(|(format, _)| format == self)
+        .map_or(PixelLayout::Other(*self), |(_, layout)| *layout)
+    }
+
+    /// Number of bytes pixel of this format takes in memory: 2 if every channel fits into the
+    /// lowest 16 bits, 4 otherwise. Packed 24-bit pixels look the same as 32-bit ones by layout,
+    /// so they are never guessed:
+    /// ```
+    /// # use linfb::PixelFormat;
+    /// assert_eq!(PixelFormat::RGB565.pixel_size(), 2);
+    /// assert_eq!(PixelFormat::XRGB8888.pixel_size(), 4);
+    /// ```
+    pub fn pixel_size(&self) -> usize {
+        let bits = [self.red, self.green, self.blue, self.alpha]
+            .iter()
+            .filter(|(_, length)| *length != 0)
+            .map(|(offset, length)| offset + length)
+            .max()
+            .unwrap_or(0);
+        if bits <= 16 {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// Pack color into pixel. Channels missing from format are not written at all, channels of
+    /// 8 bits are just shifted and others are scaled:
+    /// ```
+    /// # use linfb::PixelFormat;
+    /// let color = (255, 128, 64, 32).into();
+    /// assert_eq!(PixelFormat::XRGB8888.encode(color), 0x00_ff_80_40);
+    /// assert_eq!(PixelFormat::ARGB8888.encode(color), 0x20_ff_80_40);
+    /// assert_eq!(PixelFormat::BGRA8888.encode(color), 0x40_80_ff_20);
+    /// assert_eq!(PixelFormat::RGB565.encode(color), 31 << 11 | 32 << 5 | 8);
+    /// for format in &[PixelFormat::ARGB8888, PixelFormat::BGRA8888] {
+    ///     assert_eq!(format.decode(format.encode(color)), color);
+    /// }
+    /// // Missing alpha is decoded as opaque
+    /// let pixel = PixelFormat::XRGB8888.encode(color);
+    /// assert_eq!(PixelFormat::XRGB8888.decode(pixel), (255, 128, 64).into());
+    /// ```
+    pub fn encode(&self, color: Color) -> u32 {
+        let encode_channel = |value: u8, (offset, length): (u32, u32)| match length {
+            0 => 0,
+            8 => (value as u32) << offset,
+            _ => {
+                // Scaling with rounding: 0 and 255 are mapped to the minimum and maximum of field
+                let max = (1u64 << length) - 1;
+                let value = (value as u64 * max + 127) / 255;
+                (value << offset) as u32
+            }
+        };
+        encode_channel(color.red, self.red)
+            | encode_channel(color.green, self.green)
+            | encode_channel(color.blue, self.blue)
+            | encode_channel(color.alpha, self.alpha)
+    }
+
+    /// Unpack color from pixel
+    pub fn decode(&self, pixel: u32) -> Color {
+        let decode_channel = |(offset, length): (u32, u32)| {
+            if length == 0 {
+                return None;
+            }
+            let max = (1u64 << length) - 1;
+            let value = (pixel as u64 >> offset) & max;
+            Some(((value * 255 + max / 2) / max) as u8)
+        };
+        Color {
+            red: decode_channel(self.red).unwrap_or(0),
+            green: decode_channel(self.green).unwrap_or(0),
+            blue: decode_channel(self.blue).unwrap_or(0),
+            alpha: decode_channel(self.alpha).unwrap_or(255),
+        }
+    }
+}
+
+/// Well-known layouts of pixel, named by channels from the most significant bits to the least
+/// significant ones, like constants of [`PixelFormat`]. Useful to choose fast paths for raw
+/// pixel data, e.g. [`Bgra8888`](PixelLayout::Bgra8888) pixels are stored in memory of
+/// little-endian machines as alpha, red, green and blue bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PixelLayout {
+    /// See [`PixelFormat::XRGB8888`]
+    Xrgb8888,
+    /// See [`PixelFormat::ARGB8888`]
+    Argb8888,
+    /// See [`PixelFormat::XBGR8888`]
+    Xbgr8888,
+    /// See [`PixelFormat::ABGR8888`]
+    Abgr8888,
+    /// See [`PixelFormat::RGBA8888`]
+    Rgba8888,
+    /// See [`PixelFormat::BGRA8888`]
+    Bgra8888,
+    /// See [`PixelFormat::RGB565`]
+    Rgb565,
+    /// Any other layout, described by channels
+    Other(PixelFormat),
+}
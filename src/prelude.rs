@@ -0,0 +1,58 @@
+//! Commonly used types, so a program can start with a single glob import:
+//! ```no_run
+//! # #[cfg(feature = "framebuffer")] {
+//! use linfb::prelude::*;
+//!
+//! let mut framebuffer = Framebuffer::open().expect("Failed to open framebuffer");
+//! let mut compositor = framebuffer.compositor((255, 255, 255).into());
+//! compositor.add("rect", Rectangle::builder()
+//!     .width(100)
+//!     .height(100)
+//!     .fill_color(Color::hex("#ff000099").unwrap())
+//!     .build()
+//!     .unwrap()
+//!     .at(100, 100));
+//! # #[cfg(feature = "text")]
+//! compositor.add("text", Caption::builder()
+//!     .text("Hello".into())
+//!     .size(56)
+//!     .color((0, 0, 0))
+//!     .font(FontBuilder::default().family("monospace").build().unwrap())
+//!     .alignment(Alignment::Center)
+//!     .build()
+//!     .unwrap()
+//!     .at(100, 300));
+//! framebuffer.draw(0, 0, &compositor);
+//! framebuffer.flush();
+//! # }
+//! ```
+//!
+//! Types are still defined in their usual places: root of the crate holds framebuffer and
+//! rendering machinery, everything which can be drawn lives in [`shape`](crate::shape). Less common
+//! types, like configuration enums of particular shapes, are not included here.
+
+pub use crate::animation::{Animator, Easing};
+pub use crate::{
+    Canvas, Compositor, Direction, FrameLimiter, PixelFormat, Rect, RenderTarget, ShapeHandle,
+    Transition,
+};
+
+#[cfg(feature = "framebuffer")]
+pub use crate::animation::AnimationLoop;
+
+#[cfg(feature = "framebuffer")]
+pub use crate::{BlankMode, BufferStrategy, Framebuffer, ModeActivation};
+
+pub use crate::shape::{
+    Anchor, Clock, Color, FnShape, Heatmap, Histogram, Line, Paint, Palette, PaletteRef,
+    PositionedShape, Push, Rectangle, Shape, Spinner, StatusBar, Table, Wipe,
+};
+
+#[cfg(feature = "text")]
+pub use crate::shape::{Alignment, Caption, FontBuilder, LogView, Marquee};
+
+#[cfg(feature = "images")]
+pub use crate::shape::{Image, SpriteAnimation};
+
+#[cfg(feature = "barcode")]
+pub use crate::shape::Barcode;
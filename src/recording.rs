@@ -0,0 +1,94 @@
+//! Saving flushed frames to numbered PNG files, see
+//! [`Framebuffer::start_recording`](crate::Framebuffer::start_recording)
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::error::Result;
+
+/// Number of captured frames which can wait for being saved before flushes block
+const QUEUE_LENGTH: usize = 2;
+
+/// Recording state kept by framebuffer while recording
+pub(crate) struct Recorder {
+    sender: SyncSender<(u64, image::RgbaImage)>,
+    skip_identical: Arc<AtomicBool>,
+    index: u64,
+    /// Pixels of the last sent frame, only kept when identical frames are skipped
+    last: Option<Vec<u8>>,
+}
+
+impl Recorder {
+    /// Create directory and start thread saving frames into it
+    pub(crate) fn start(directory: &Path) -> Result<(Self, RecordingHandle)> {
+        std::fs::create_dir_all(directory)?;
+        let directory = directory.to_path_buf();
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let thread = thread::spawn(move || save_frames(directory, receiver));
+        let skip_identical = Arc::new(AtomicBool::new(false));
+        let recorder = Self {
+            sender,
+            skip_identical: Arc::clone(&skip_identical),
+            index: 0,
+            last: None,
+        };
+        let handle = RecordingHandle {
+            skip_identical,
+            thread,
+        };
+        Ok((recorder, handle))
+    }
+
+    /// Send captured frame to be saved, unless it's the same as the previous one and identical
+    /// frames are skipped
+    pub(crate) fn record(&mut self, frame: image::RgbaImage) {
+        let skip = self.skip_identical.load(Ordering::Relaxed);
+        if skip && self.last.as_deref() == Some(&*frame) {
+            return;
+        }
+        self.index += 1;
+        if skip {
+            self.last = Some(frame.to_vec());
+        }
+        // Thread only stops by itself after failing to save frame, error is reported by handle
+        let _ = self.sender.send((self.index, frame));
+    }
+}
+
+/// Handle of frame recording started by
+/// [`Framebuffer::start_recording`](crate::Framebuffer::start_recording). Frames are saved by a
+/// separate thread, so flushes only block if it falls behind by more than a couple of frames
+pub struct RecordingHandle {
+    skip_identical: Arc<AtomicBool>,
+    thread: JoinHandle<Result<u64>>,
+}
+
+impl RecordingHandle {
+    /// Don't save frame if it's the same as the previous saved one, e.g. when nothing changed
+    /// between flushes. Disabled by default, so every flush is saved
+    pub fn skip_identical(&self, skip: bool) {
+        self.skip_identical.store(skip, Ordering::Relaxed);
+    }
+
+    /// Wait until all frames are saved and return number of saved frames, or the first error
+    /// of saving them. Recording must be stopped with
+    /// [`Framebuffer::stop_recording`](crate::Framebuffer::stop_recording) first,
+    /// otherwise this blocks until framebuffer is dropped
+    pub fn finish(self) -> Result<u64> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+fn save_frames(directory: PathBuf, frames: Receiver<(u64, image::RgbaImage)>) -> Result<u64> {
+    let mut saved = 0;
+    for (index, frame) in frames {
+        frame.save(directory.join(format!("frame_{:06}.png", index)))?;
+        saved += 1;
+    }
+    Ok(saved)
+}
@@ -1,22 +1,50 @@
 //! Various drawing primitives
 
+use std::collections::hash_map::DefaultHasher;
 use std::convert::TryFrom;
-use std::ops::{Mul, MulAssign};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Mul, MulAssign, Range};
 
 use derive_builder::Builder;
 use downcast_rs::{impl_downcast, Downcast};
 
 use crate::{
     Error::{self, *},
-    Result,
+    RenderTarget, Result,
 };
 
 #[cfg(feature = "text")]
-pub use crate::text::{Alignment, Caption, CaptionBuilder, FontBuilder};
+pub use crate::text::{
+    clear_font_cache, Alignment, Caption, CaptionBuilder, FontBuilder, GlyphColor, GlyphInfo,
+};
+
+#[cfg(feature = "text")]
+pub use crate::marquee::Marquee;
+
+#[cfg(feature = "text")]
+pub use crate::log_view::LogView;
 
 #[cfg(feature = "images")]
 pub use crate::image::Image;
 
+#[cfg(feature = "images")]
+pub use crate::sprite::{Playback, SpriteAnimation};
+
+pub use crate::arc::{Arc, ArcBuilder};
+#[cfg(feature = "barcode")]
+pub use crate::barcode::Barcode;
+pub use crate::clock::Clock;
+pub use crate::heatmap::{ColorMap, Heatmap, Interpolation};
+pub use crate::histogram::{Binning, Histogram};
+pub use crate::line::{Line, LineBuilder};
+pub use crate::palette::{Paint, Palette, PaletteRef};
+pub use crate::path::{Cap, Join, Path};
+pub use crate::spinner::{Spinner, SpinnerStyle};
+pub use crate::status_bar::{Slot, StatusBar};
+pub use crate::table::{ColumnWidth, Table};
+pub use crate::transition::{Push, Wipe};
+
 /// RGBA color used in many places in the library. Alpha channel is `[0-255]`, not `[0-1]`.
 ///
 /// Can be created from 4-tuple of [`u8`], 3-tuple of [`u8`] (assuming `255` in alpha channel) and hex
@@ -34,13 +62,28 @@ pub use crate::image::Image;
 /// # assert_eq!(c3, c4);
 /// ```
 ///
-/// Can be multiplied to `[0, 1]` [`f32`] coefficient, which affects every channel besides alpha:
+/// Can be multiplied to [`f32`] coefficient, which affects every channel besides alpha. Resulting
+/// channels are clamped to `[0-255]`, so coefficients above 1 saturate and negative coefficients
+/// give black. Use [`Color::scaled`] to reject coefficients outside of `[0, 1]` instead:
 /// ```
 /// # use linfb::shape::Color;
 /// let color: Color = (128, 128, 128, 128).into(); // All channels set to 128
 /// assert_eq!(color * 0.5, (64, 64, 64, 128).into());
+/// assert_eq!(color * -0.5, (0, 0, 0, 128).into());
+/// assert_eq!(color * 0.0, (0, 0, 0, 128).into());
+/// assert_eq!(color * 1.0, color);
+/// assert_eq!(color * 1.5, (192, 192, 192, 128).into());
+/// assert_eq!(color * 300.0, (255, 255, 255, 128).into());
+///
+/// let mut color = color;
+/// color *= 300.0;
+/// assert_eq!(color, (255, 255, 255, 128).into());
+/// color *= -0.5;
+/// assert_eq!(color, (0, 0, 0, 128).into());
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
@@ -52,10 +95,11 @@ impl Mul<f32> for Color {
     type Output = Self;
 
     fn mul(self, coeff: f32) -> Self {
+        let scale = |channel: u8| (channel as f32 * coeff).clamp(0f32, 255f32) as u8;
         Self {
-            red: (self.red as f32 * coeff) as u8,
-            green: (self.green as f32 * coeff) as u8,
-            blue: (self.blue as f32 * coeff) as u8,
+            red: scale(self.red),
+            green: scale(self.green),
+            blue: scale(self.blue),
             alpha: self.alpha,
         }
     }
@@ -63,13 +107,41 @@ impl Mul<f32> for Color {
 
 impl MulAssign<f32> for Color {
     fn mul_assign(&mut self, coeff: f32) {
-        self.red = ((self.red as f32) * coeff) as u8;
-        self.green = ((self.green as f32) * coeff) as u8;
-        self.blue = ((self.blue as f32) * coeff) as u8;
+        *self = *self * coeff;
     }
 }
 
 impl Color {
+    /// Multiply every channel besides alpha by coefficient, which must be in `[0, 1]`:
+    /// ```
+    /// # use linfb::shape::Color;
+    /// let color: Color = (128, 128, 128).into();
+    /// assert_eq!(color.scaled(0.5).unwrap(), (64, 64, 64).into());
+    /// assert!(color.scaled(1.5).is_err());
+    /// assert!(color.scaled(-0.5).is_err());
+    /// assert!(color.scaled(f32::NAN).is_err());
+    /// ```
+    pub fn scaled(self, coeff: f32) -> Result<Self> {
+        if (0f32..=1f32).contains(&coeff) {
+            Ok(self * coeff)
+        } else {
+            Err(InvalidCoefficient(coeff))
+        }
+    }
+
+    /// Perceived brightness of color by Rec. 601 coefficients, alpha is ignored:
+    /// ```
+    /// # use linfb::shape::Color;
+    /// assert_eq!(Color::from((255, 255, 255)).luminance(), 255);
+    /// assert_eq!(Color::from((255, 0, 0)).luminance(), 76);
+    /// assert_eq!(Color::from((0, 255, 0)).luminance(), 150);
+    /// assert_eq!(Color::from((0, 0, 255)).luminance(), 29);
+    /// ```
+    pub fn luminance(self) -> u8 {
+        (0.299 * self.red as f32 + 0.587 * self.green as f32 + 0.114 * self.blue as f32).round()
+            as u8
+    }
+
     /// Create [`Color`] object from hex string.
     /// Equivalent to `.try_into()` on string slice:
     /// ```
@@ -89,7 +161,7 @@ impl Color {
                 "length must be 7 or 9",
             ));
         }
-        if color_string.chars().next() != Some('#') {
+        if !color_string.starts_with('#') {
             return Err(InvalidColorString(
                 color_string.into(),
                 "first char must be #",
@@ -145,34 +217,354 @@ impl TryFrom<&str> for Color {
     }
 }
 
-/// Something you can draw on framebuffer
-pub trait Shape: Downcast {
-    /// Create a two-dimensional array of pixels. Every row must have the same length.
+/// Something you can draw on framebuffer. Shapes must be [`Clone`], see [`CloneShape`]
+pub trait Shape: Downcast + CloneShape {
+    /// Create a two-dimensional array of pixels. Every row should have the same length. Rows
+    /// which are shorter than the longest one are treated as padded with [`None`], so ragged
+    /// output is drawn as is, without shifting the following rows:
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{Color, Shape};
+    /// #[derive(Clone)]
+    /// struct Ragged;
+    ///
+    /// impl Shape for Ragged {
+    ///     fn render(&self) -> Vec<Vec<Option<Color>>> {
+    ///         let white = Some((255, 255, 255).into());
+    ///         vec![vec![white; 3], vec![white], vec![], vec![white; 2]]
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Ragged.dimensions(), (3, 4));
+    ///
+    /// let mut compositor = Compositor::new(4, 4, (0, 0, 0).into());
+    /// compositor.add("ragged", Ragged.at(1, 0));
+    /// let black = Some((0, 0, 0).into());
+    /// let white = Some((255, 255, 255).into());
+    /// assert_eq!(
+    ///     compositor.render(),
+    ///     vec![
+    ///         vec![black, white, white, white],
+    ///         vec![black, white, black, black],
+    ///         vec![black, black, black, black],
+    ///         vec![black, white, white, black],
+    ///     ],
+    /// );
+    ///
+    /// # #[cfg(feature = "framebuffer")] {
+    /// # use linfb::Framebuffer;
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let screen_info = fb_var_screeninfo::argb8888(4, 4);
+    /// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+    /// framebuffer.fill_rect(0, 0, 4, 4, (0, 0, 0));
+    /// framebuffer.draw(1, 0, &Ragged);
+    /// for (y, row) in compositor.render().iter().enumerate() {
+    ///     for (x, color) in row.iter().enumerate() {
+    ///         assert_eq!(Some(framebuffer.get_pixel(x as u32, y as u32)), *color);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// Shape with zero width or height renders an empty grid, i.e. `Vec::new()`:
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{FnShape, Rectangle, Shape};
+    /// let rectangle = |width, height| {
+    ///     Rectangle::builder()
+    ///         .width(width)
+    ///         .height(height)
+    ///         .fill_color((255, 0, 0))
+    ///         .build()
+    ///         .unwrap()
+    /// };
+    /// let mut shapes: Vec<Box<dyn Shape>> = vec![
+    ///     Box::new(rectangle(0, 0)),
+    ///     Box::new(rectangle(0, 5)),
+    ///     Box::new(rectangle(5, 0)),
+    ///     Box::new(FnShape::new(0, 5, |_, _| unreachable!())),
+    ///     Box::new(FnShape::new(5, 0, |_, _| unreachable!())),
+    ///     Box::new(Compositor::new(0, 5, (0, 0, 0).into())),
+    ///     Box::new(Compositor::new(5, 0, (0, 0, 0).into())),
+    /// ];
+    /// # #[cfg(feature = "images")]
+    /// # shapes.extend([0, 5].iter().map(|&width| -> Box<dyn Shape> {
+    /// #     Box::new(linfb::shape::Image::from(image::RgbaImage::new(width, 5 - width)))
+    /// # }));
+    /// # #[cfg(feature = "text")]
+    /// # {
+    /// #     let font = linfb::shape::FontBuilder::default()
+    /// #         .family("sans-serif")
+    /// #         .build_shared()
+    /// #         .unwrap();
+    /// #     let caption = |max_width| {
+    /// #         let mut builder = linfb::shape::Caption::builder();
+    /// #         builder.text("".into()).size(16).font(font.clone());
+    /// #         if let Some(max_width) = max_width {
+    /// #             builder.max_width(max_width);
+    /// #         }
+    /// #         builder.build().unwrap()
+    /// #     };
+    /// #     shapes.push(Box::new(caption(None)));
+    /// #     shapes.push(Box::new(caption(Some(10))));
+    /// # }
+    /// let mut compositor = Compositor::new(5, 5, (0, 0, 0).into());
+    /// for (i, shape) in shapes.into_iter().enumerate() {
+    ///     assert_eq!(shape.render(), Vec::<Vec<_>>::new());
+    ///     assert_eq!(shape.dimensions().0 * shape.dimensions().1, 0);
+    ///     compositor.add(&i.to_string(), linfb::shape::PositionedShape {
+    ///         x: 2,
+    ///         y: 2,
+    ///         shape,
+    ///         visible: true,
+    ///         opacity: 1.0,
+    ///         anchor: None,
+    ///     });
+    /// }
+    /// assert_eq!(compositor.render(), vec![vec![Some((0, 0, 0).into()); 5]; 5]);
+    /// ```
     ///
     /// [`None`] means "no pixel at all" and semantically equivalent to `(0, 0, 0, 0).into()`, but
     /// can have better performance.
     fn render(&self) -> Vec<Vec<Option<Color>>>;
 
+    /// Same as [`Shape::render`], but [`PaletteRef`]s are resolved against given palette. Shapes
+    /// which are painted with [`Paint`] should override it and make [`Shape::render`] call it
+    /// with [`None`]. Default implementation ignores palette
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let _ = palette;
+        self.render()
+    }
+
+    /// Draw shape onto [`RenderTarget`] with top left angle at x, y. Default implementation just
+    /// draws result of [`Shape::render_with_palette`] with palette of target, shapes which pixels
+    /// are cheap to compute should override it to avoid allocating the intermediate grid.
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        let rows = self.render_with_palette(target.palette());
+        for (inner_y, row) in rows.iter().enumerate() {
+            target.draw_row(x, y + inner_y, row);
+        }
+    }
+
+    /// Width and height of shape in pixels. Default implementation renders the shape, so shapes
+    /// should override it if they know their size. Width is the length of the longest row
+    fn dimensions(&self) -> (usize, usize) {
+        let rows = self.render();
+        (rows.iter().map(Vec::len).max().unwrap_or(0), rows.len())
+    }
+
+    /// Check if point at x, y relative to the top left angle of shape belongs to the shape.
+    /// Default implementation treats the whole bounding box from [`Shape::dimensions`] as shape,
+    /// shapes with transparent areas can override it with precise check:
+    /// ```
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let frame = Rectangle::builder()
+    ///     .width(10)
+    ///     .height(10)
+    ///     .border_color((255, 0, 0))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(frame.contains(0, 5));
+    /// assert!(!frame.contains(5, 5)); // Fill is transparent
+    /// assert!(!frame.contains(10, 5));
+    /// ```
+    fn contains(&self, x: usize, y: usize) -> bool {
+        let (width, height) = self.dimensions();
+        x < width && y < height
+    }
+
+    /// Fingerprint of shape contents: shapes which would render differently should have different
+    /// hashes. Used by [`Compositor::diff`](super::Compositor::diff). Default implementation
+    /// hashes the rendered pixels, so shapes should override it with something cheaper, like a
+    /// hash of their fields
+    fn content_hash(&self) -> u64 {
+        hash_of(&self.render())
+    }
+
+    /// Name of shape used in [`Debug`](fmt::Debug) output of `dyn Shape` and in
+    /// [`Display`](fmt::Display) output of [`Compositor`](super::Compositor). Default
+    /// implementation returns the type name without module path
+    /// ```
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let rect: Box<dyn Shape> = Box::new(Rectangle::builder()
+    ///     .width(3)
+    ///     .height(2)
+    ///     .build()
+    ///     .unwrap());
+    /// assert_eq!(rect.debug_name(), "Rectangle");
+    /// assert_eq!(format!("{:?}", rect), "Rectangle(3x2)");
+    /// ```
+    fn debug_name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let path = name.split('<').next().unwrap_or(name);
+        &name[path.rfind("::").map_or(0, |i| i + 2)..]
+    }
+
     /// Convert self into [`PositionedShape`], saving position info. Needed for
     /// [`Compositor`](super::Compositor).
     fn at(self, x: usize, y: usize) -> PositionedShape
     where
         Self: Sized + 'static,
     {
-        PositionedShape {
-            x,
-            y,
-            shape: Box::new(self),
-        }
+        PositionedShape::new(x, y, self)
     }
 }
 impl_downcast!(Shape);
 
+/// Helper trait which allows to clone boxed shapes. It's implemented for every [`Clone`] type, so
+/// shapes should just derive [`Clone`]:
+/// ```
+/// # use linfb::shape::{Rectangle, Shape};
+/// let original: Box<dyn Shape> = Box::new(Rectangle::builder()
+///     .width(3)
+///     .height(2)
+///     .build()
+///     .unwrap());
+/// let mut copy = original.clone();
+/// copy.downcast_mut::<Rectangle>().unwrap().width = 5;
+/// assert_eq!(original.dimensions(), (3, 2));
+/// assert_eq!(copy.dimensions(), (5, 2));
+/// ```
+pub trait CloneShape {
+    /// Clone shape into a new box
+    fn clone_box(&self) -> Box<dyn Shape>;
+}
+
+impl<T: Shape + Clone> CloneShape for T {
+    fn clone_box(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Shape> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Shapes are not required to implement [`Debug`](fmt::Debug), so only their name and size are
+/// printed
+impl fmt::Debug for dyn Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.dimensions();
+        write!(f, "{}({}x{})", self.debug_name(), width, height)
+    }
+}
+
+/// Hash value with the default hasher
+pub(crate) fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Point of [`Compositor`](super::Compositor) safe area which anchored [`PositionedShape`] is
+/// attached to. See [`PositionedShape::anchored`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Placement of anchored shape along one axis
+#[derive(Clone, Copy)]
+pub(crate) enum Align {
+    Start,
+    Middle,
+    End,
+}
+
+impl Align {
+    /// Position of shape with given size and offset from anchor inside of span starting at
+    /// `start`. Shape is kept inside of span if it fits
+    pub(crate) fn place(self, start: usize, span: usize, size: usize, offset: usize) -> usize {
+        let free = span.saturating_sub(size);
+        start
+            + match self {
+                Align::Start => usize::min(offset, free),
+                Align::Middle => usize::min(free / 2 + offset, free),
+                Align::End => free - usize::min(offset, free),
+            }
+    }
+}
+
+impl Anchor {
+    /// Horizontal and vertical placement
+    pub(crate) fn align(self) -> (Align, Align) {
+        use Align::*;
+        match self {
+            Anchor::TopLeft => (Start, Start),
+            Anchor::Top => (Middle, Start),
+            Anchor::TopRight => (End, Start),
+            Anchor::Left => (Start, Middle),
+            Anchor::Center => (Middle, Middle),
+            Anchor::Right => (End, Middle),
+            Anchor::BottomLeft => (Start, End),
+            Anchor::Bottom => (Middle, End),
+            Anchor::BottomRight => (End, End),
+        }
+    }
+}
+
 /// [`Shape`], positioned for placing onto [`Compositor`](super::Compositor)
+///
+/// Cloning copies the inner shape too, so template shape can be placed many times:
+/// ```
+/// # use linfb::Compositor;
+/// # use linfb::shape::{Rectangle, Shape};
+/// let card = Rectangle::builder()
+///     .width(20)
+///     .height(10)
+///     .fill_color((40, 40, 40))
+///     .build()
+///     .unwrap()
+///     .at(0, 0);
+/// let mut compositor = Compositor::new(100, 100, (0, 0, 0).into());
+/// for i in 0..5 {
+///     let mut copy = card.clone();
+///     copy.y = i * 15;
+///     copy.inner_mut::<Rectangle>().unwrap().width = 20 + i * 10;
+///     compositor.add("card", copy);
+/// }
+/// assert_eq!((card.x, card.y), (0, 0));
+/// assert_eq!(card.shape.dimensions(), (20, 10));
+/// assert_eq!(compositor.shapes_at(55, 62), vec!["card"]);
+/// ```
+#[derive(Clone, Debug)]
 pub struct PositionedShape {
     pub x: usize,
     pub y: usize,
     pub shape: Box<dyn Shape + 'static>,
+    /// Hidden shapes are not drawn and can't be picked. Default is `true`
+    pub visible: bool,
+    /// Opacity of shape from 0 (fully transparent) to 1 (as is), multiplied into alpha of every
+    /// pixel. Default is `1.0`
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{Rectangle, Shape};
+    /// let mut compositor = Compositor::new(4, 4, (0, 0, 0).into());
+    /// compositor.add("rect", Rectangle::builder()
+    ///     .width(4)
+    ///     .height(4)
+    ///     .border_width(0)
+    ///     .fill_color((200, 100, 0))
+    ///     .build()
+    ///     .unwrap()
+    ///     .at(0, 0));
+    /// compositor.get_positioned("rect").unwrap().opacity = 0.5;
+    /// assert_eq!(compositor.render()[1][1], Some((100, 50, 0).into()));
+    /// ```
+    pub opacity: f32,
+    /// If set, `x` and `y` are offsets from the anchor point towards the center of compositor
+    /// safe area instead of absolute coordinates. Default is [`None`]
+    pub anchor: Option<Anchor>,
 }
 
 impl PositionedShape {
@@ -182,9 +574,19 @@ impl PositionedShape {
             x,
             y,
             shape: Box::new(shape),
+            visible: true,
+            opacity: 1.0,
+            anchor: None,
         }
     }
 
+    /// Attach shape to given point of compositor safe area. Anchored shapes are kept inside of
+    /// safe area when they fit in it. See [`Compositor::set_safe_area`](super::Compositor::set_safe_area)
+    pub fn anchored(mut self, anchor: Anchor) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
     /// Get shared reference to inner [`Shape`] if it's type matches `T`
     pub fn inner<T: Shape + 'static>(&self) -> Option<&T> {
         self.shape.downcast_ref()
@@ -194,16 +596,175 @@ impl PositionedShape {
     pub fn inner_mut<T: Shape + 'static>(&mut self) -> Option<&mut T> {
         self.shape.downcast_mut()
     }
+
+    /// Check if point at x, y in the coordinates of parent belongs to the shape. See
+    /// [`Shape::contains`]
+    pub fn hit_test(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && self.shape.contains(x - self.x, y - self.y)
+    }
 }
 
-/// Simplest of all shapes, just a rectangle
-#[derive(Debug, Builder)]
+/// Simplest of all shapes, just a rectangle. Border can be at most half of rectangle wide,
+/// rounded up: such borders meet in the middle, so rectangle is filled with border color
+/// completely. Wider borders are rejected by builder:
+/// ```
+/// # use linfb::Canvas;
+/// # use linfb::shape::{Color, Rectangle, Shape};
+/// let border: Color = (255, 0, 0).into();
+/// let fill: Color = (0, 255, 0).into();
+/// for width in 0..7 {
+///     for height in 0..7 {
+///         for border_width in 0..9 {
+///             let rectangle = Rectangle::builder()
+///                 .width(width)
+///                 .height(height)
+///                 .border_width(border_width)
+///                 .border_color(border)
+///                 .fill_color(fill)
+///                 .build();
+///             let size = usize::min(width, height);
+///             if size != 0 && border_width > size.div_ceil(2) {
+///                 assert!(rectangle.is_err());
+///                 continue;
+///             }
+///             let rectangle = rectangle.unwrap();
+///             let rendered = rectangle.render();
+///             let count = |color| rendered.iter().flatten().filter(|&&c| c == Some(color)).count();
+///             let fill_count = width.saturating_sub(2 * border_width)
+///                 * height.saturating_sub(2 * border_width);
+///             assert_eq!(count(fill), fill_count);
+///             assert_eq!(count(border), width * height - fill_count);
+///
+///             let mut canvas = Canvas::new(width, height, (0, 0, 0).into());
+///             rectangle.render_into(&mut canvas, 0, 0);
+///             assert_eq!(canvas.to_rows(), rendered);
+///         }
+///     }
+/// }
+/// ```
+///
+/// Corners can be rounded. Pixels outside of rounded outline are [`None`], border follows the
+/// curve and fill stops at its inner edge:
+/// ```
+/// # use linfb::shape::{Color, Rectangle, Shape};
+/// let rounded = |width, height, radius, border_width| {
+///     Rectangle::builder()
+///         .width(width)
+///         .height(height)
+///         .corner_radius(radius)
+///         .border_width(border_width)
+///         .border_color((255, 0, 0))
+///         .build()
+///         .unwrap()
+/// };
+/// let picture = |rectangle: &Rectangle| -> Vec<String> {
+///     rectangle
+///         .render()
+///         .iter()
+///         .map(|row| row.iter().map(|pixel| if pixel.is_some() { '#' } else { '.' }).collect())
+///         .collect()
+/// };
+/// // Fill is transparent, so only the border is visible
+/// assert_eq!(
+///     picture(&rounded(12, 9, 4, 1)),
+///     vec![
+///         "..########..",
+///         ".#........#.",
+///         "#..........#",
+///         "#..........#",
+///         "#..........#",
+///         "#..........#",
+///         "#..........#",
+///         ".#........#.",
+///         "..########..",
+///     ],
+/// );
+/// // Radius larger than half of height makes a capsule
+/// assert_eq!(
+///     picture(&rounded(8, 4, 100, 0)),
+///     picture(&rounded(8, 4, 2, 0)),
+/// );
+///
+/// // Every corner can have its own radius, e.g. only the top ones are rounded for a tab
+/// let tab = |radii| {
+///     Rectangle::builder()
+///         .width(12)
+///         .height(6)
+///         .corner_radii(radii)
+///         .border_color((255, 0, 0))
+///         .build()
+///         .unwrap()
+/// };
+/// assert_eq!(
+///     picture(&tab((4, 4, 0, 0))),
+///     vec![
+///         "..########..",
+///         ".#........#.",
+///         "#..........#",
+///         "#..........#",
+///         "#..........#",
+///         "############",
+///     ],
+/// );
+/// // Radii of adjacent corners which don't fit in the side are scaled down proportionally
+/// assert_eq!(picture(&tab((12, 4, 0, 0))), picture(&tab((6, 2, 0, 0))));
+///
+/// # use linfb::Canvas;
+/// // Drawing and hit testing agree with rendered pixels
+/// let black = Some((0, 0, 0).into());
+/// for radii in [(0, 0, 0, 0), (2, 2, 2, 2), (7, 7, 7, 7), (5, 0, 3, 9), (0, 6, 1, 0)] {
+///     for border_width in 0..4 {
+///         let rectangle = Rectangle::builder()
+///             .width(11)
+///             .height(8)
+///             .corner_radii(radii)
+///             .border_width(border_width)
+///             .border_color((255, 0, 0))
+///             .fill_color((0, 0, 255))
+///             .build()
+///             .unwrap();
+///         let rendered = rectangle.render();
+///         let mut canvas = Canvas::new(11, 8, (0, 0, 0).into());
+///         rectangle.render_into(&mut canvas, 0, 0);
+///         let expected: Vec<Vec<_>> = rendered
+///             .iter()
+///             .map(|row| row.iter().map(|pixel| pixel.or(black)).collect())
+///             .collect();
+///         assert_eq!(canvas.to_rows(), expected);
+///         for (y, row) in rendered.iter().enumerate() {
+///             for (x, pixel) in row.iter().enumerate() {
+///                 assert_eq!(rectangle.contains(x, y), pixel.is_some());
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// Builder errors name the field which is wrong:
+/// ```
+/// # use linfb::shape::Rectangle;
+/// let error = Rectangle::builder().width(10).build().unwrap_err();
+/// assert_eq!(error.to_string(), "invalid Rectangle: `height` must be initialized");
+/// let error = Rectangle::builder()
+///     .width(10)
+///     .height(4)
+///     .border_width(3)
+///     .build()
+///     .unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "invalid Rectangle: border_width 3 is more than half of size 10x4",
+/// );
+/// ```
+#[derive(Clone, Debug, Builder)]
+#[builder(build_fn(name = "build_unvalidated"))]
 pub struct Rectangle {
     /// Width of rectangle including border
     pub width: usize,
     /// Height of rectangle including border
     pub height: usize,
-    /// Border width. Builder default is 1, set to 0 to disable borders
+    /// Border width. Builder default is 1, set to 0 to disable borders. Border can be at most half
+    /// of the smaller side wide, rounded up: wider one is rejected by [`RectangleBuilder::build`]
     #[builder(default = "1")]
     pub border_width: usize,
     /// Border color. Builder default is [`None`] (fully transparent)
@@ -212,6 +773,21 @@ pub struct Rectangle {
     /// Fill color. Builder default is [`None`] (fully transparent)
     #[builder(setter(into, strip_option), default)]
     pub fill_color: Option<Color>,
+    /// Palette color of border. If palette rectangle is rendered with has it, it's used instead of
+    /// [`Rectangle::border_color`]. Builder default is [`None`]
+    #[builder(setter(into, strip_option), default)]
+    pub border_color_ref: Option<PaletteRef>,
+    /// Palette color of fill. If palette rectangle is rendered with has it, it's used instead of
+    /// [`Rectangle::fill_color`]. Builder default is [`None`]
+    #[builder(setter(into, strip_option), default)]
+    pub fill_color_ref: Option<PaletteRef>,
+    /// Radii of rounded corners, clockwise from top left: top left, top right, bottom right,
+    /// bottom left. Builder default is 0 for all of them, i.e. sharp corners. If radii of two
+    /// corners on one side don't fit in it, all radii are scaled down proportionally, so
+    /// rectangle with huge equal radii becomes a capsule. Builder also has
+    /// [`RectangleBuilder::corner_radius`] setting all of them at once
+    #[builder(default)]
+    pub corner_radii: (usize, usize, usize, usize),
 }
 
 impl Rectangle {
@@ -219,26 +795,317 @@ impl Rectangle {
     pub fn builder() -> RectangleBuilder {
         RectangleBuilder::default()
     }
+
+    /// Border and fill colors, with palette colors taking precedence over fixed ones
+    fn colors(&self, palette: Option<&Palette>) -> (Option<Color>, Option<Color>) {
+        let pick = |reference: &Option<PaletteRef>, color: Option<Color>| {
+            reference
+                .as_ref()
+                .and_then(|reference| palette?.get(reference.name()))
+                .or(color)
+        };
+        (
+            pick(&self.border_color_ref, self.border_color),
+            pick(&self.fill_color_ref, self.fill_color),
+        )
+    }
+
+    /// Covered part of row y as range of columns, which is split into parts of border on the
+    /// sides and fill in the middle. Fill is empty if borders meet
+    fn row(&self, y: usize) -> Option<(Range<usize>, Range<usize>)> {
+        let (left, right) = self.row_inset(y, 0)?;
+        if left + right >= self.width {
+            return None;
+        }
+        let outer = left..self.width - right;
+        let inner = match self.row_inset(y, self.border_width) {
+            Some((left, right)) if left + right < self.width => left..self.width - right,
+            _ => {
+                let middle = (outer.start + outer.end) / 2;
+                middle..middle
+            }
+        };
+        Some((outer, inner))
+    }
+
+    /// Corner radii in the same order as [`Rectangle::corner_radii`], scaled down so that radii
+    /// of adjacent corners fit in the side between them
+    fn radii(&self) -> [f64; 4] {
+        let (top_left, top_right, bottom_right, bottom_left) = self.corner_radii;
+        let radii = [top_left, top_right, bottom_right, bottom_left].map(|radius| radius as f64);
+        let (width, height) = (self.width as f64, self.height as f64);
+        let scale = [
+            (width, radii[0] + radii[1]),
+            (height, radii[1] + radii[2]),
+            (width, radii[2] + radii[3]),
+            (height, radii[3] + radii[0]),
+        ]
+        .iter()
+        .filter(|&&(_, sum)| sum > 0f64)
+        .fold(1f64, |scale, &(side, sum)| scale.min(side / sum));
+        radii.map(|radius| radius * scale)
+    }
+
+    /// Number of pixels on the left and on the right of row y which are outside of rounded
+    /// rectangle shrunk by `inset` from every side, or [`None`] if row is outside of it. Pixel is
+    /// inside if its center is
+    fn row_inset(&self, y: usize, inset: usize) -> Option<(usize, usize)> {
+        let height = self.height as f64 - 2f64 * inset as f64;
+        let center_y = y as f64 + 0.5 - inset as f64;
+        if 2 * inset >= self.width || height <= 0f64 || center_y < 0f64 || center_y > height {
+            return None;
+        }
+        let [top_left, top_right, bottom_right, bottom_left] =
+            self.radii().map(|radius| (radius - inset as f64).max(0f64));
+        let side = |top: f64, bottom: f64| {
+            // Corner circle row crosses and distance from row to its center
+            let (radius, dy) = if center_y < top {
+                (top, top - center_y)
+            } else if center_y > height - bottom {
+                (bottom, center_y - (height - bottom))
+            } else {
+                return inset;
+            };
+            let dx = (radius * radius - dy * dy).max(0f64).sqrt();
+            inset + (radius - dx - 0.5).ceil().max(0f64) as usize
+        };
+        Some((side(top_left, bottom_left), side(top_right, bottom_right)))
+    }
+}
+
+impl RectangleBuilder {
+    /// Set the same radius for all corners, see [`Rectangle::corner_radii`]
+    pub fn corner_radius(&mut self, radius: usize) -> &mut Self {
+        self.corner_radii((radius, radius, radius, radius))
+    }
+
+    /// Build [`Rectangle`]. Returns [`Error::InvalidShape`] if required fields are not set or
+    /// border is too wide
+    pub fn build(&self) -> Result<Rectangle> {
+        let rectangle = self.build_unvalidated().map_err(|reason| InvalidShape {
+            shape: "Rectangle",
+            reason,
+        })?;
+        let size = usize::min(rectangle.width, rectangle.height);
+        if size != 0 && rectangle.border_width > size.div_ceil(2) {
+            return Err(InvalidShape {
+                shape: "Rectangle",
+                reason: format!(
+                    "border_width {} is more than half of size {}x{}",
+                    rectangle.border_width, rectangle.width, rectangle.height
+                ),
+            });
+        }
+        Ok(rectangle)
+    }
 }
 
 impl Shape for Rectangle {
     fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        let (border_color, fill_color) = self.colors(palette);
         (0..self.height)
             .map(|y| {
-                (0..self.width)
-                    .map(|x| {
-                        if x < self.border_width
-                            || x >= self.width - self.border_width
-                            || y < self.border_width
-                            || y >= self.height - self.border_width
-                        {
-                            self.border_color
+                let mut row = vec![None; self.width];
+                if let Some((outer, inner)) = self.row(y) {
+                    for x in outer {
+                        row[x] = if inner.contains(&x) {
+                            fill_color
                         } else {
-                            self.fill_color
-                        }
-                    })
-                    .collect()
+                            border_color
+                        };
+                    }
+                }
+                row
             })
             .collect()
     }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.width,
+            self.height,
+            self.border_width,
+            self.corner_radii,
+            self.border_color,
+            self.fill_color,
+            &self.border_color_ref,
+            &self.fill_color_ref,
+        ))
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        match self.row(y) {
+            Some((outer, _)) if !outer.contains(&x) => false,
+            Some((_, inner)) if inner.contains(&x) => {
+                self.fill_color.is_some() || self.fill_color_ref.is_some()
+            }
+            Some(_) => self.border_color.is_some() || self.border_color_ref.is_some(),
+            None => false,
+        }
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        let (border_color, fill_color) = self.colors(target.palette());
+        let draw_span = |target: &mut dyn RenderTarget, x, y, len, color: Option<Color>| {
+            if let (Some(color), true) = (color, len > 0) {
+                target.draw_span(x, y, len, color);
+            }
+        };
+
+        for inner_y in 0..self.height {
+            let (outer, inner) = match self.row(inner_y) {
+                Some(row) => row,
+                None => continue,
+            };
+            let y = y + inner_y;
+            draw_span(
+                target,
+                x + outer.start,
+                y,
+                inner.start - outer.start,
+                border_color,
+            );
+            draw_span(target, x + inner.start, y, inner.len(), fill_color);
+            draw_span(
+                target,
+                x + inner.end,
+                y,
+                outer.end - inner.end,
+                border_color,
+            );
+        }
+    }
+}
+
+/// Procedural shape, which gets color of every pixel from a function of it's coordinates. Function
+/// is only called for pixels inside of declared size, [`None`] means transparent pixel:
+/// ```
+/// # use linfb::shape::{FnShape, Shape};
+/// let pattern = FnShape::new(16, 16, |x, y| {
+///     let value = ((x ^ y) * 16) as u8;
+///     Some((value, value, value).into())
+/// });
+/// let rendered = pattern.render();
+/// assert_eq!(rendered.len(), 16);
+/// assert!(rendered.iter().all(|row| row.len() == 16));
+/// assert_eq!(rendered[3][5], Some((96, 96, 96).into()));
+///
+/// # use std::sync::{Arc, Mutex};
+/// # use linfb::Canvas;
+/// // Function is never called outside of declared size
+/// let calls = Arc::new(Mutex::new(Vec::new()));
+/// let recorder = calls.clone();
+/// let shape = FnShape::new(3, 2, move |x, y| {
+///     recorder.lock().unwrap().push((x, y));
+///     None
+/// });
+/// shape.render_into(&mut Canvas::new(10, 10, (0, 0, 0).into()), 5, 5);
+/// let seen = calls.lock().unwrap();
+/// assert_eq!(seen.len(), 6);
+/// assert!(seen.iter().all(|&(x, y)| x < 3 && y < 2));
+/// ```
+///
+/// Function must be `Send` and `Sync` like other shapes, clones share it
+#[derive(Clone)]
+pub struct FnShape {
+    width: usize,
+    height: usize,
+    time: f32,
+    function: std::sync::Arc<dyn Fn(usize, usize, f32) -> Option<Color> + Send + Sync>,
+}
+
+impl FnShape {
+    /// Create shape with given size from function of pixel coordinates
+    pub fn new<F>(width: usize, height: usize, function: F) -> Self
+    where
+        F: Fn(usize, usize) -> Option<Color> + Send + Sync + 'static,
+    {
+        Self::animated(width, height, move |x, y, _time| function(x, y))
+    }
+
+    /// Create shape with given size from function of pixel coordinates and time parameter. Time
+    /// starts at zero and can be changed with [`FnShape::set_time`], e.g. on every frame:
+    /// ```
+    /// # use linfb::Compositor;
+    /// # use linfb::shape::{FnShape, Shape};
+    /// let mut compositor = Compositor::new(4, 4, (0, 0, 0).into());
+    /// compositor.add("plasma", FnShape::animated(4, 4, |x, _y, time| {
+    ///     Some(((x as f32 * 10.0 + time) as u8, 0, 0).into())
+    /// }).at(0, 0));
+    ///
+    /// assert_eq!(compositor.render()[0][1], Some((10, 0, 0).into()));
+    /// compositor.get::<FnShape>("plasma").unwrap().set_time(5.0);
+    /// assert_eq!(compositor.render()[0][1], Some((15, 0, 0).into()));
+    /// ```
+    pub fn animated<F>(width: usize, height: usize, function: F) -> Self
+    where
+        F: Fn(usize, usize, f32) -> Option<Color> + Send + Sync + 'static,
+    {
+        Self {
+            width,
+            height,
+            time: 0f32,
+            function: std::sync::Arc::new(function),
+        }
+    }
+
+    /// Current value of time parameter
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Set time parameter passed to the function
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    fn row(&self, y: usize) -> Vec<Option<Color>> {
+        (0..self.width)
+            .map(|x| (self.function)(x, y, self.time))
+            .collect()
+    }
+}
+
+impl fmt::Debug for FnShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnShape")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+impl Shape for FnShape {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        (0..self.height).map(|y| self.row(y)).collect()
+    }
+
+    /// Rows are evaluated one at a time, so the whole shape is never stored in memory
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        for inner_y in 0..self.height {
+            target.draw_row(x, y + inner_y, &self.row(inner_y));
+        }
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
 }
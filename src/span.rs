@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use crate::canvas::{Canvas, Rect, RenderTarget};
+use crate::error::{Error::*, Result};
+use crate::shape::{Color, Palette, Shape};
+use crate::{Compositor, Framebuffer};
+
+/// Group of framebuffers placed side by side and used as one large screen, e.g. two displays of
+/// the same machine. Every framebuffer covers the area of logical screen starting at its offset,
+/// and drawing methods mirror ones of [`Framebuffer`], so shapes crossing the seam are split
+/// between framebuffers. Framebuffers keep their own pixel formats:
+/// ```
+/// # use linfb::{Framebuffer, MultiFramebuffer, RenderTarget};
+/// # use linfb::shape::Rectangle;
+/// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+/// let bgra = fb_var_screeninfo::argb8888(6, 4);
+/// let rgb565 = fb_var_screeninfo::new(
+///     4,
+///     6,
+///     16,
+///     fb_bitfield::new(11, 5),
+///     fb_bitfield::new(5, 6),
+///     fb_bitfield::new(0, 5),
+///     fb_bitfield::new(0, 0),
+/// );
+/// let mut screen = MultiFramebuffer::new(vec![
+///     (Framebuffer::in_memory(bgra).unwrap(), (0, 0)),
+///     (Framebuffer::in_memory(rgb565).unwrap(), (6, 0)),
+/// ])
+/// .unwrap();
+/// assert_eq!((screen.width(), screen.height()), (10, 6));
+///
+/// let rectangle = Rectangle::builder()
+///     .width(4)
+///     .height(2)
+///     .border_width(0)
+///     .fill_color((255, 0, 0))
+///     .build()
+///     .unwrap();
+/// screen.draw(4, 1, &rectangle);
+/// // Rectangles are clipped to logical screen
+/// screen.fill_rect(8, 4, u32::MAX, u32::MAX, (0, 255, 0));
+/// screen.set_pixel(9, 5, (0, 0, 255));
+/// screen.flush();
+///
+/// let [left, right] = &screen.framebuffers()[..] else { unreachable!() };
+/// assert_eq!(left.get_pixel(4, 1), (255, 0, 0).into());
+/// assert_eq!(left.get_pixel(5, 2), (255, 0, 0).into());
+/// assert_eq!(right.get_pixel(0, 1), (255, 0, 0).into());
+/// assert_eq!(right.get_pixel(1, 2), (255, 0, 0).into());
+/// assert_eq!(right.get_pixel(2, 1), (0, 0, 0).into());
+/// assert_eq!(right.get_pixel(3, 5), (0, 0, 255).into());
+/// assert_eq!(right.get_pixel(2, 4), (0, 255, 0).into());
+/// assert_eq!(screen.get_pixel(7, 2), Some((255, 0, 0).into()));
+/// // Below the shorter framebuffer nothing is shown
+/// assert_eq!(screen.get_pixel(0, 5), None);
+/// ```
+pub struct MultiFramebuffer {
+    framebuffers: Vec<Framebuffer>,
+    /// Area of logical screen covered by every framebuffer, in the same order
+    areas: Vec<Rect>,
+    width: usize,
+    height: usize,
+    scratch: Canvas,
+}
+
+impl MultiFramebuffer {
+    /// Combine given framebuffers, each with position of its top left angle on the logical
+    /// screen. Logical screen is just large enough to hold all of them. Returns an error if there
+    /// are no framebuffers. Areas of framebuffers may overlap, then pixels are drawn on all of them
+    pub fn new(framebuffers: Vec<(Framebuffer, (u32, u32))>) -> Result<Self> {
+        if framebuffers.is_empty() {
+            return Err(NoFramebuffers);
+        }
+        let (framebuffers, areas): (Vec<_>, Vec<_>) = framebuffers
+            .into_iter()
+            .map(|(framebuffer, (x, y))| {
+                let area = Rect::new(
+                    x as usize,
+                    y as usize,
+                    framebuffer.width(),
+                    framebuffer.height(),
+                );
+                (framebuffer, area)
+            })
+            .unzip();
+        Ok(Self {
+            width: areas
+                .iter()
+                .map(|area| area.x + area.width)
+                .max()
+                .unwrap_or(0),
+            height: areas
+                .iter()
+                .map(|area| area.y + area.height)
+                .max()
+                .unwrap_or(0),
+            framebuffers,
+            areas,
+            scratch: Canvas::default(),
+        })
+    }
+
+    /// Open framebuffer devices, e.g. `/dev/fb0` and `/dev/fb1`, and combine them, see
+    /// [`MultiFramebuffer::new`]. Returns the first error of [`Framebuffer::open_path`]
+    /// ```no_run
+    /// # use linfb::MultiFramebuffer;
+    /// use std::path::Path;
+    /// let screen = MultiFramebuffer::open(&[
+    ///     (Path::new("/dev/fb0"), (0, 0)),
+    ///     (Path::new("/dev/fb1"), (1920, 0)),
+    /// ])
+    /// .unwrap();
+    /// ```
+    pub fn open(devices: &[(&Path, (u32, u32))]) -> Result<Self> {
+        let framebuffers = devices
+            .iter()
+            .map(|&(path, offset)| Ok((Framebuffer::open_path(path)?, offset)))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(framebuffers)
+    }
+
+    /// Combined framebuffers, in the order they were passed to [`MultiFramebuffer::new`]
+    pub fn framebuffers(&self) -> &[Framebuffer] {
+        &self.framebuffers
+    }
+
+    /// Get combined framebuffers back
+    pub fn into_framebuffers(self) -> Vec<Framebuffer> {
+        self.framebuffers
+    }
+
+    /// Set pixel at x, y of logical screen. Pixels not covered by any framebuffer are skipped
+    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
+        self.draw_pixel(x as usize, y as usize, color.into());
+    }
+
+    /// Get color of pixel at x, y of logical screen from the first framebuffer covering it, see
+    /// [`Framebuffer::get_pixel`]. Returns [`None`] if no framebuffer covers it
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Color> {
+        let (x, y) = (x as usize, y as usize);
+        self.framebuffers
+            .iter()
+            .zip(&self.areas)
+            .find(|(_, area)| area.contains(x, y))
+            .map(|(framebuffer, area)| {
+                framebuffer.get_pixel((x - area.x) as u32, (y - area.y) as u32)
+            })
+    }
+
+    /// Fill rectangle of logical screen, see [`Framebuffer::fill_rect`]
+    pub fn fill_rect<C: Into<Color>>(&mut self, x: u32, y: u32, width: u32, height: u32, color: C) {
+        let color = color.into();
+        let span = Rect::new(0, 0, self.width(), self.height());
+        let rect = Rect::new(x as usize, y as usize, width as usize, height as usize);
+        let rect = match rect.intersection(&span) {
+            Some(rect) => rect,
+            None => return,
+        };
+        for y in rect.y..rect.y + rect.height {
+            self.draw_span(rect.x, y, rect.width, color);
+        }
+    }
+
+    /// Draw shape on logical screen, see [`Framebuffer::draw`]. Shape is rendered only once, even
+    /// if it crosses several framebuffers
+    pub fn draw<T: Shape>(&mut self, x: u32, y: u32, shape: &T) {
+        if let Some(compositor) = shape.as_any().downcast_ref::<Compositor>() {
+            let mut scratch = std::mem::take(&mut self.scratch);
+            compositor.render_themed(&mut scratch, self.palette());
+            for (inner_y, row) in scratch.rows().enumerate() {
+                self.draw_colors(x as usize, y as usize + inner_y, row);
+            }
+            self.scratch = scratch;
+        } else {
+            shape.render_into(self, x as usize, y as usize);
+        }
+    }
+
+    /// Flush all framebuffers one right after another, so they stay in sync
+    pub fn flush(&mut self) {
+        for framebuffer in &mut self.framebuffers {
+            framebuffer.flush();
+        }
+    }
+
+    /// Flush given region of logical screen, see [`Framebuffer::flush_region`]. Framebuffers
+    /// which don't intersect region are not touched
+    pub fn flush_region(&mut self, region: Rect) {
+        for (framebuffer, area) in self.framebuffers.iter_mut().zip(&self.areas) {
+            if let Some(part) = region.intersection(area) {
+                framebuffer.flush_region(Rect::new(
+                    part.x - area.x,
+                    part.y - area.y,
+                    part.width,
+                    part.height,
+                ));
+            }
+        }
+    }
+
+    /// Create compositor with the size of logical screen
+    pub fn compositor(&self, background: Color) -> Compositor {
+        Compositor::new(self.width, self.height, background)
+    }
+
+    /// Call `f` for every framebuffer which row of given length at x, y of logical screen
+    /// crosses, with offset of its part into row, position of the part on framebuffer and its
+    /// length
+    fn for_each(
+        &mut self,
+        x: usize,
+        y: usize,
+        len: usize,
+        mut f: impl FnMut(&mut Framebuffer, usize, usize, usize, usize),
+    ) {
+        let row = Rect::new(x, y, len, 1);
+        for (framebuffer, area) in self.framebuffers.iter_mut().zip(&self.areas) {
+            if let Some(part) = row.intersection(area) {
+                f(
+                    framebuffer,
+                    part.x - x,
+                    part.x - area.x,
+                    y - area.y,
+                    part.width,
+                );
+            }
+        }
+    }
+}
+
+impl RenderTarget for MultiFramebuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.for_each(x, y, 1, |target, _, x, y, _| target.draw_pixel(x, y, color));
+    }
+
+    fn draw_span(&mut self, x: usize, y: usize, len: usize, color: Color) {
+        self.for_each(x, y, len, |target, _, x, y, len| {
+            target.draw_span(x, y, len, color)
+        });
+    }
+
+    fn draw_row(&mut self, x: usize, y: usize, row: &[Option<Color>]) {
+        self.for_each(x, y, row.len(), |target, offset, x, y, len| {
+            target.draw_row(x, y, &row[offset..offset + len])
+        });
+    }
+
+    fn draw_colors(&mut self, x: usize, y: usize, row: &[Color]) {
+        self.for_each(x, y, row.len(), |target, offset, x, y, len| {
+            target.draw_colors(x, y, &row[offset..offset + len])
+        });
+    }
+
+    /// Palette of the first framebuffer, see [`Framebuffer::set_palette`]
+    fn palette(&self) -> Option<&Palette> {
+        self.framebuffers.first().and_then(Framebuffer::palette)
+    }
+}
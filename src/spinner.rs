@@ -0,0 +1,196 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use crate::shape::{hash_of, Color, Paint, Palette, Shape};
+
+/// Look of [`Spinner`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SpinnerStyle {
+    /// Quarter of a ring rotating around the center
+    #[default]
+    Arc,
+    /// Ring of dots with a bright head and fading tail
+    Dots,
+    /// Ring expanding from the center and fading out
+    Pulse,
+}
+
+/// Indeterminate loading indicator. Spinner is a square with side `2 * radius + 1` regardless
+/// of its phase, so it never shifts the layout:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::shape::{Shape, Spinner, SpinnerStyle};
+/// for &style in &[SpinnerStyle::Arc, SpinnerStyle::Dots, SpinnerStyle::Pulse] {
+///     let mut spinner = Spinner::new(10)
+///         .style(style)
+///         .color((255, 255, 255))
+///         .stroke_width(3)
+///         .dots(8)
+///         .speed(1.0);
+///     let initial = spinner.render();
+///     let mut previous = initial.clone();
+///     for step in 1..=8 {
+///         spinner.advance(Duration::from_millis(125));
+///         let rendered = spinner.render();
+///         assert_eq!(rendered.len(), 21);
+///         assert!(rendered.iter().all(|row| row.len() == 21));
+///         assert_eq!(spinner.dimensions(), (21, 21));
+///         if step < 8 {
+///             assert_ne!(rendered, previous);
+///         }
+///         previous = rendered;
+///     }
+///     // Full period brings spinner back to its initial look
+///     assert_eq!(spinner.phase(), 0.0);
+///     assert_eq!(previous, initial);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Spinner {
+    radius: usize,
+    color: Paint,
+    stroke_width: usize,
+    dots: usize,
+    speed: f64,
+    style: SpinnerStyle,
+    /// Part of full period which has passed, from 0 to 1
+    phase: f64,
+}
+
+impl Spinner {
+    /// Create black arc spinner with given radius, doing one revolution per second
+    pub fn new(radius: usize) -> Self {
+        Self {
+            radius,
+            color: (0, 0, 0).into(),
+            stroke_width: usize::max(radius / 4, 1),
+            dots: 8,
+            speed: 1f64,
+            style: SpinnerStyle::default(),
+            phase: 0f64,
+        }
+    }
+
+    /// Set look of spinner
+    pub fn style(mut self, style: SpinnerStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set color of spinner
+    pub fn color<P: Into<Paint>>(mut self, color: P) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    /// Set width of ring and diameter of dots in px. Default is a quarter of radius
+    pub fn stroke_width(mut self, width: usize) -> Self {
+        self.stroke_width = usize::max(width, 1);
+        self
+    }
+
+    /// Set number of dots of [`SpinnerStyle::Dots`]. Default is 8
+    pub fn dots(mut self, dots: usize) -> Self {
+        self.dots = usize::max(dots, 1);
+        self
+    }
+
+    /// Set number of periods per second. Default is 1
+    pub fn speed(mut self, periods_per_second: f32) -> Self {
+        self.speed = periods_per_second as f64;
+        self
+    }
+
+    /// Move animation forward in time
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.phase = (self.phase + elapsed.as_secs_f64() * self.speed).rem_euclid(1f64);
+    }
+
+    /// Part of period which has passed, from 0 to 1
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Color of pixel with given offset from center, or [`None`] if it's not covered
+    fn pixel(&self, color: Color, dx: f64, dy: f64) -> Option<Color> {
+        let radius = self.radius as f64 + 0.5;
+        let stroke = self.stroke_width as f64;
+        let distance = (dx * dx + dy * dy).sqrt();
+        // Angle clockwise from 12, from 0 to 1
+        let angle = (dx.atan2(-dy) / (2f64 * PI)).rem_euclid(1f64);
+        let with_alpha = |alpha: f64| {
+            Some(Color {
+                alpha: (color.alpha as f64 * alpha).round() as u8,
+                ..color
+            })
+        };
+        match self.style {
+            SpinnerStyle::Arc => {
+                let in_ring = distance <= radius && distance >= radius - stroke;
+                let from_head = (angle - self.phase).rem_euclid(1f64);
+                Some(color).filter(|_| in_ring && from_head < 0.25)
+            }
+            SpinnerStyle::Dots => {
+                let dot_radius = stroke / 2f64;
+                let dots = self.dots as f64;
+                let head = (self.phase * dots).floor();
+                (0..self.dots).find_map(|i| {
+                    let dot_angle = i as f64 / dots * 2f64 * PI;
+                    let center = radius - dot_radius;
+                    let (x, y) = (center * dot_angle.sin(), -center * dot_angle.cos());
+                    if (dx - x).powi(2) + (dy - y).powi(2) > dot_radius * dot_radius {
+                        return None;
+                    }
+                    let behind = (head - i as f64).rem_euclid(dots);
+                    with_alpha(1f64 - behind / dots)
+                })
+            }
+            SpinnerStyle::Pulse => {
+                let ring = self.phase * radius;
+                if (distance - ring).abs() <= stroke / 2f64 {
+                    with_alpha(1f64 - self.phase)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl Shape for Spinner {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        let size = 2 * self.radius + 1;
+        let color = match self.color.resolve(palette) {
+            Some(color) => color,
+            None => return vec![vec![None; size]; size],
+        };
+        let center = self.radius as f64 + 0.5;
+        (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| self.pixel(color, x as f64 + 0.5 - center, y as f64 + 0.5 - center))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let size = 2 * self.radius + 1;
+        (size, size)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            self.radius,
+            &self.color,
+            self.stroke_width,
+            self.dots,
+            self.style,
+            self.phase.to_bits(),
+        ))
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use crate::shape::{hash_of, Color, Image, Shape};
+use crate::{Rect, RenderTarget};
+
+/// What [`SpriteAnimation`] does after its last frame. Default is [`Playback::Loop`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Playback {
+    /// Start again from the first frame
+    #[default]
+    Loop,
+    /// Stay at the last frame and report [`SpriteAnimation::finished`]
+    Once,
+}
+
+/// Animation from sprite sheet: one [`Image`] containing frames of the same size in a grid.
+/// Frames are indexed left-to-right, top-to-bottom, and only the current one is drawn directly
+/// from the sheet:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::shape::{Image, Shape, SpriteAnimation};
+/// // 2x2 grid of 3x2 frames: red, green, blue, white
+/// let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+/// let sheet = image::RgbaImage::from_fn(6, 4, |x, y| {
+///     let [r, g, b] = colors[(y / 2 * 2 + x / 3) as usize];
+///     image::Rgba([r, g, b, 255])
+/// });
+/// let mut animation = SpriteAnimation::new(Image::from(sheet), 3, 2, 4, 10.0);
+/// assert_eq!(animation.dimensions(), (3, 2));
+///
+/// let frame = Duration::from_millis(100);
+/// for &[r, g, b] in colors.iter().chain(colors[..1].iter()) {
+///     assert_eq!(animation.render(), vec![vec![Some((r, g, b).into()); 3]; 2]);
+///     animation.advance(frame);
+/// }
+/// assert_eq!(animation.frame(), 1);
+/// assert!(!animation.finished());
+///
+/// animation.set_frame(3);
+/// assert_eq!(animation.render()[1][2], Some((255, 255, 255).into()));
+/// ```
+///
+/// One-shot animation stops at its last frame:
+/// ```
+/// # use std::time::Duration;
+/// # use linfb::shape::{Image, Playback, Shape, SpriteAnimation};
+/// # let sheet = image::RgbaImage::from_fn(8, 2, |x, _y| image::Rgba([x as u8 / 2, 0, 0, 255]));
+/// let mut animation = SpriteAnimation::new(Image::from(sheet), 2, 2, 4, 10.0)
+///     .playback(Playback::Once);
+/// animation.advance(Duration::from_millis(350));
+/// assert_eq!(animation.frame(), 3);
+/// assert!(!animation.finished());
+/// animation.advance(Duration::from_secs(5));
+/// assert_eq!(animation.frame(), 3);
+/// assert!(animation.finished());
+///
+/// animation.set_frame(0);
+/// assert!(!animation.finished());
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpriteAnimation {
+    sheet: Image,
+    sheet_hash: u64,
+    frame_width: usize,
+    frame_height: usize,
+    frames: usize,
+    fps: f32,
+    playback: Playback,
+    /// Current position measured in frames, fractional part is progress of current frame
+    position: f64,
+}
+
+impl SpriteAnimation {
+    /// Create looping animation of `frames` frames with given size, shown at `fps` frames per
+    /// second
+    pub fn new(
+        sheet: Image,
+        frame_width: usize,
+        frame_height: usize,
+        frames: usize,
+        fps: f32,
+    ) -> Self {
+        Self {
+            sheet_hash: sheet.content_hash(),
+            sheet,
+            frame_width,
+            frame_height,
+            frames,
+            fps,
+            playback: Playback::default(),
+            position: 0f64,
+        }
+    }
+
+    /// Set what happens after the last frame
+    pub fn playback(mut self, playback: Playback) -> Self {
+        self.playback = playback;
+        self
+    }
+
+    /// Move animation forward in time
+    pub fn advance(&mut self, elapsed: Duration) {
+        if self.frames == 0 {
+            return;
+        }
+        self.position += elapsed.as_secs_f64() * self.fps as f64;
+        self.position = match self.playback {
+            Playback::Loop => self.position % self.frames as f64,
+            Playback::Once => f64::min(self.position, self.frames as f64),
+        };
+    }
+
+    /// Index of current frame
+    pub fn frame(&self) -> usize {
+        // Durations which hit the frame boundary exactly shouldn't be lost to rounding errors
+        let frame = (self.position + 1e-9).floor() as usize;
+        match self.playback {
+            Playback::Loop => frame % usize::max(self.frames, 1),
+            Playback::Once => usize::min(frame, self.frames.saturating_sub(1)),
+        }
+    }
+
+    /// Jump to frame with given index. Indices past the last frame are clamped
+    pub fn set_frame(&mut self, frame: usize) {
+        self.position = usize::min(frame, self.frames.saturating_sub(1)) as f64;
+    }
+
+    /// Is one-shot animation done playing. Looping animations never finish
+    pub fn finished(&self) -> bool {
+        self.playback == Playback::Once && self.position + 1e-9 >= self.frames as f64
+    }
+
+    /// Region of sheet with current frame
+    fn frame_rect(&self) -> Rect {
+        let columns = usize::max(
+            self.sheet.dimensions().0 / usize::max(self.frame_width, 1),
+            1,
+        );
+        let frame = self.frame();
+        Rect::new(
+            frame % columns * self.frame_width,
+            frame / columns * self.frame_height,
+            self.frame_width,
+            self.frame_height,
+        )
+    }
+}
+
+impl Shape for SpriteAnimation {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.sheet.render_region(self.frame_rect())
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        self.sheet
+            .render_region_into(self.frame_rect(), target, x, y);
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.frame_width, self.frame_height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(self.sheet_hash, self.frame_rect()))
+    }
+}
@@ -0,0 +1,305 @@
+use std::sync::OnceLock;
+
+use crate::canvas::{Canvas, Clipped};
+use crate::shape::{hash_of, Color, Shape};
+use crate::{Rect, RenderTarget};
+
+/// Default distance between items of one slot in px
+const DEFAULT_SPACING: usize = 4;
+
+/// Part of [`StatusBar`], every slot takes a third of bar width
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// Items start at the left edge
+    Left,
+    /// Items are centered
+    Center,
+    /// Items end at the right edge
+    Right,
+}
+
+impl Slot {
+    fn index(self) -> usize {
+        match self {
+            Slot::Left => 0,
+            Slot::Center => 1,
+            Slot::Right => 2,
+        }
+    }
+}
+
+/// Placement of slot contents computed by [`StatusBar`]
+#[derive(Clone, Debug, Default)]
+struct Layout {
+    /// Bounds of items which fit into their slots, last items of overflowing slots are missing
+    items: [Vec<Rect>; 3],
+    /// Bounds of ellipsis of overflowing slots
+    ellipses: Vec<Rect>,
+}
+
+/// Horizontal bar with left, center and right slots, e.g. for time, wifi and battery indicators.
+/// Items of every slot are placed in a row with spacing between them and centered vertically:
+/// ```
+/// # use linfb::shape::{Rectangle, Shape, Slot, StatusBar};
+/// # let square = |size, color: (u8, u8, u8)| -> Box<dyn Shape> {
+/// #     Box::new(Rectangle::builder()
+/// #         .width(size)
+/// #         .height(size)
+/// #         .border_width(0)
+/// #         .fill_color(color)
+/// #         .build()
+/// #         .unwrap())
+/// # };
+/// let mut bar = StatusBar::new(300, 20, (0, 0, 0).into());
+/// bar.set_slot(Slot::Left, vec![square(10, (255, 0, 0)), square(6, (255, 255, 0))]);
+/// bar.set_slot(Slot::Center, vec![square(10, (0, 255, 0))]);
+/// bar.set_slot(Slot::Right, vec![square(10, (0, 0, 255))]);
+///
+/// let rendered = bar.render();
+/// assert_eq!(rendered[5][0], Some((255, 0, 0).into()));
+/// assert_eq!(rendered[7][14], Some((255, 255, 0).into()));
+/// assert_eq!(rendered[6][14], Some((0, 0, 0).into()));
+/// assert_eq!(rendered[5][145], Some((0, 255, 0).into()));
+/// assert_eq!(rendered[5][144], Some((0, 0, 0).into()));
+/// assert_eq!(rendered[5][299], Some((0, 0, 255).into()));
+/// assert_eq!(rendered[5][289], Some((0, 0, 0).into()));
+/// assert_eq!(bar.item_bounds(Slot::Left)[1].y, 7);
+/// ```
+///
+/// Short icon is centered next to a tall text:
+/// ```
+/// # #[cfg(feature = "text")] {
+/// # use linfb::shape::{Caption, FontBuilder, Rectangle, Shape, Slot, StatusBar};
+/// let icon = Rectangle::builder()
+///     .width(8)
+///     .height(8)
+///     .fill_color((255, 255, 255))
+///     .build()
+///     .unwrap();
+/// let time = Caption::builder()
+///     .text("12:30".into())
+///     .size(24)
+///     .font(FontBuilder::default().family("sans-serif").build_shared().unwrap())
+///     .build()
+///     .unwrap();
+/// let mut bar = StatusBar::new(600, 40, (0, 0, 0).into());
+/// bar.set_slot(Slot::Right, vec![Box::new(icon), Box::new(time)]);
+/// let bounds = bar.item_bounds(Slot::Right);
+/// assert_eq!(bounds[0].y, 16);
+/// assert_eq!(bounds[1].y, (40 - bounds[1].height) / 2);
+/// let center = |rect: linfb::Rect| 2 * rect.y + rect.height;
+/// assert!((center(bounds[0]) as i64 - center(bounds[1]) as i64).abs() <= 1);
+/// assert_eq!(bounds[1].x + bounds[1].width, 600);
+/// assert_eq!(bounds[0].x + bounds[0].width + 4, bounds[1].x);
+/// # }
+/// ```
+///
+/// Items which don't fit into their third of bar are replaced with ellipsis:
+/// ```
+/// # use linfb::shape::{Rectangle, Shape, Slot, StatusBar};
+/// # let square = |size| -> Box<dyn Shape> {
+/// #     Box::new(Rectangle::builder()
+/// #         .width(size)
+/// #         .height(size)
+/// #         .border_width(0)
+/// #         .fill_color((255, 0, 0))
+/// #         .build()
+/// #         .unwrap())
+/// # };
+/// let mut bar = StatusBar::new(300, 20, (0, 0, 0).into()).ellipsis_color((0, 255, 0));
+/// bar.set_slot(Slot::Left, (0..5).map(|_| square(30)).collect());
+/// // 30 + 4 + 30 + 4 + 30 fits, but leaves no space for ellipsis
+/// assert_eq!(bar.item_bounds(Slot::Left).len(), 2);
+/// let rendered = bar.render();
+/// assert_eq!(rendered[10][68], Some((0, 255, 0).into()));
+/// assert!(rendered.iter().all(|row| row[78..].iter().all(|&c| c == Some((0, 0, 0).into()))));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StatusBar {
+    width: usize,
+    height: usize,
+    background: Color,
+    spacing: usize,
+    ellipsis_color: Color,
+    slots: [Vec<Box<dyn Shape>>; 3],
+    layout: OnceLock<Layout>,
+}
+
+impl StatusBar {
+    /// Create bar with empty slots
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        Self {
+            width,
+            height,
+            background,
+            spacing: DEFAULT_SPACING,
+            ellipsis_color: (128, 128, 128).into(),
+            slots: Default::default(),
+            layout: OnceLock::new(),
+        }
+    }
+
+    /// Set distance between items of one slot in px. Default is 4
+    pub fn spacing(mut self, spacing: usize) -> Self {
+        self.spacing = spacing;
+        self.layout = OnceLock::new();
+        self
+    }
+
+    /// Set color of ellipsis shown in overflowing slots. Default is gray
+    pub fn ellipsis_color<C: Into<Color>>(mut self, color: C) -> Self {
+        self.ellipsis_color = color.into();
+        self
+    }
+
+    /// Replace contents of slot. Items are laid out again on the next render
+    pub fn set_slot(&mut self, slot: Slot, items: Vec<Box<dyn Shape>>) {
+        self.slots[slot.index()] = items;
+        self.layout = OnceLock::new();
+    }
+
+    /// Items of slot
+    pub fn slot(&self, slot: Slot) -> &[Box<dyn Shape>] {
+        &self.slots[slot.index()]
+    }
+
+    /// Bounds of items of slot which are shown in bar
+    pub fn item_bounds(&self, slot: Slot) -> Vec<Rect> {
+        self.layout().items[slot.index()].clone()
+    }
+
+    /// Region of bar taken by slot
+    fn region(&self, index: usize) -> Rect {
+        let start = self.width * index / 3;
+        Rect::new(start, 0, self.width * (index + 1) / 3 - start, self.height)
+    }
+
+    /// Size of ellipsis: three square dots with gaps of the same size
+    fn ellipsis_size(&self) -> (usize, usize) {
+        let dot = usize::max(self.height / 10, 1);
+        (5 * dot, dot)
+    }
+
+    fn layout(&self) -> &Layout {
+        self.layout.get_or_init(|| {
+            let mut layout = Layout::default();
+            for (index, items) in self.slots.iter().enumerate() {
+                let region = self.region(index);
+                let sizes: Vec<_> = items.iter().map(|item| item.dimensions()).collect();
+                let row_width = |widths: &mut dyn Iterator<Item = usize>| {
+                    widths.fold(None, |total, width| {
+                        Some(total.map_or(width, |total| total + self.spacing + width))
+                    })
+                };
+
+                let mut shown = sizes.len();
+                let mut ellipsis = None;
+                let full_width = row_width(&mut sizes.iter().map(|size| size.0)).unwrap_or(0);
+                if full_width > region.width {
+                    let (ellipsis_width, _) = self.ellipsis_size();
+                    // Drop items from the end until the rest and ellipsis fit
+                    while shown > 0 {
+                        let items = row_width(&mut sizes[..shown].iter().map(|size| size.0));
+                        if items.unwrap_or(0) + self.spacing + ellipsis_width <= region.width {
+                            break;
+                        }
+                        shown -= 1;
+                    }
+                    ellipsis = Some(ellipsis_width);
+                }
+
+                let mut widths = sizes[..shown].iter().map(|size| size.0);
+                let mut content_width = row_width(&mut widths).unwrap_or(0);
+                if let Some(ellipsis_width) = ellipsis {
+                    content_width = match shown {
+                        0 => ellipsis_width,
+                        _ => content_width + self.spacing + ellipsis_width,
+                    };
+                }
+                // Overflowing slots are filled from the start
+                let mut x = match index {
+                    _ if ellipsis.is_some() => region.x,
+                    0 => region.x,
+                    1 => region.x + region.width.saturating_sub(content_width) / 2,
+                    _ => region.x + region.width.saturating_sub(content_width),
+                };
+                for &(width, height) in &sizes[..shown] {
+                    let y = self.height.saturating_sub(height) / 2;
+                    layout.items[index].push(Rect::new(x, y, width, height));
+                    x += width + self.spacing;
+                }
+                if let Some(ellipsis_width) = ellipsis {
+                    let (_, dot) = self.ellipsis_size();
+                    layout.ellipses.push(Rect::new(
+                        x,
+                        self.height.saturating_sub(dot) / 2,
+                        ellipsis_width,
+                        dot,
+                    ));
+                }
+            }
+            layout
+        })
+    }
+}
+
+impl Shape for StatusBar {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        let mut canvas = Canvas::new(self.width, self.height, self.background);
+        self.render_into(&mut canvas, 0, 0);
+        canvas.to_rows()
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        if self.width == 0 {
+            return;
+        }
+        for inner_y in 0..self.height {
+            target.draw_span(x, y + inner_y, self.width, self.background);
+        }
+        let layout = self.layout();
+        for (index, items) in self.slots.iter().enumerate() {
+            let region = self.region(index);
+            let mut target = Clipped {
+                target: &mut *target,
+                region: Rect::new(x + region.x, y, region.width, self.height),
+            };
+            for (item, bounds) in items.iter().zip(layout.items[index].iter()) {
+                item.render_into(&mut target, x + bounds.x, y + bounds.y);
+            }
+        }
+        for ellipsis in &layout.ellipses {
+            let dot = ellipsis.height;
+            for i in 0..3 {
+                for inner_y in 0..dot {
+                    target.draw_span(
+                        x + ellipsis.x + 2 * i * dot,
+                        y + ellipsis.y + inner_y,
+                        dot,
+                        self.ellipsis_color,
+                    );
+                }
+            }
+        }
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        let items: Vec<Vec<u64>> = self
+            .slots
+            .iter()
+            .map(|items| items.iter().map(|item| item.content_hash()).collect())
+            .collect();
+        hash_of(&(
+            self.width,
+            self.height,
+            self.background,
+            self.spacing,
+            self.ellipsis_color,
+            items,
+        ))
+    }
+}
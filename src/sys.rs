@@ -1,10 +1,15 @@
 //! Low-level utilities to deal with framebuffer file descriptor
 
-use nix::ioctl_read_bad;
+use std::os::raw::c_ulong;
+
+use nix::{
+    ioctl_read, ioctl_read_bad, ioctl_readwrite_bad, ioctl_write_int_bad, ioctl_write_ptr,
+    ioctl_write_ptr_bad,
+};
 
 /// System structure representing one RGB channel parameters
 #[repr(C)]
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct fb_bitfield {
     /// Offset in bits from the right of pixel
     pub offset: u32,
@@ -14,6 +19,17 @@ pub struct fb_bitfield {
     pub msb_right: u32,
 }
 
+impl fb_bitfield {
+    /// Create channel definition with given offset and length
+    pub fn new(offset: u32, length: u32) -> Self {
+        Self {
+            offset,
+            length,
+            msb_right: 0,
+        }
+    }
+}
+
 /// System structure representing variable screen info
 #[repr(C)]
 #[derive(Clone, Default, Debug)]
@@ -26,7 +42,7 @@ pub struct fb_var_screeninfo {
     pub yres_virtual: u32,
     pub xoffset: u32,
     pub yoffset: u32,
-    /// Number of bits per one pixel. Should be 32
+    /// Number of bits per one pixel. Should be 32, 24 or 16
     pub bits_per_pixel: u32,
     /// Is framebuffer grayscale. Should be 0 on modern systems
     pub grayscale: u32,
@@ -60,13 +76,299 @@ pub struct fb_var_screeninfo {
 }
 
 impl fb_var_screeninfo {
-    /// Overall size of framebuffer in bytes
+    /// Create screen info for a synthetic framebuffer, e.g. for
+    /// [`Framebuffer::in_memory`](crate::Framebuffer::in_memory). Virtual resolution is the same
+    /// as the visible one
+    pub fn new(
+        xres: u32,
+        yres: u32,
+        bits_per_pixel: u32,
+        red: fb_bitfield,
+        green: fb_bitfield,
+        blue: fb_bitfield,
+        transp: fb_bitfield,
+    ) -> Self {
+        Self {
+            xres,
+            yres,
+            xres_virtual: xres,
+            yres_virtual: yres,
+            bits_per_pixel,
+            red,
+            green,
+            blue,
+            transp,
+            ..Default::default()
+        }
+    }
+
+    /// Screen info for a synthetic 32 bpp framebuffer with 8 bit channels, alpha in the highest
+    /// byte and blue in the lowest one, the most common layout of real devices:
+    /// ```
+    /// # use linfb::sys::{fb_bitfield, fb_var_screeninfo};
+    /// let screen_info = fb_var_screeninfo::argb8888(640, 480);
+    /// assert_eq!((screen_info.xres, screen_info.yres, screen_info.bits_per_pixel), (640, 480, 32));
+    /// assert_eq!(screen_info.red, fb_bitfield::new(16, 8));
+    /// assert_eq!(screen_info.transp, fb_bitfield::new(24, 8));
+    /// ```
+    pub fn argb8888(xres: u32, yres: u32) -> Self {
+        Self::new(
+            xres,
+            yres,
+            32,
+            fb_bitfield::new(16, 8),
+            fb_bitfield::new(8, 8),
+            fb_bitfield::new(0, 8),
+            fb_bitfield::new(24, 8),
+        )
+    }
+
+    /// Overall size of visible screen in bytes, assuming rows without padding. Real devices can
+    /// pad rows, use [`fb_fix_screeninfo::page_size`] to get the actual size
     pub fn overall_size(&self) -> usize {
         (self.xres * self.yres * self.bits_per_pixel / 8) as usize
     }
+
+    /// Refresh rate of mode in Hz, computed from duration of one pixel in `pixclock` and total
+    /// size of frame including margins and sync pulses. Interlaced modes count fields, not full
+    /// frames. Returns [`None`] if driver doesn't report timings, i.e. `pixclock` is 0. Standard
+    /// 1080p timing at 148.5 MHz gives 60 Hz:
+    /// ```
+    /// # use linfb::sys::fb_var_screeninfo;
+    /// let mut screen_info = fb_var_screeninfo::argb8888(1920, 1080);
+    /// assert_eq!(screen_info.refresh_rate_hz(), None);
+    ///
+    /// // 10^12 / 148.5 MHz = 6734 ps
+    /// screen_info.pixclock = 6734;
+    /// // 1920 + 88 + 44 + 148 = 2200 pixels per row
+    /// screen_info.right_margin = 88;
+    /// screen_info.hsync_len = 44;
+    /// screen_info.left_margin = 148;
+    /// // 1080 + 4 + 5 + 36 = 1125 rows per frame
+    /// screen_info.lower_margin = 4;
+    /// screen_info.vsync_len = 5;
+    /// screen_info.upper_margin = 36;
+    /// // 10^12 / (6734 * 2200 * 1125) = 60.0001
+    /// let rate = screen_info.refresh_rate_hz().unwrap();
+    /// assert!((rate - 60.0).abs() < 0.001);
+    ///
+    /// // Interlaced 1080i60 scans 60 fields of 562.5 rows at half of the clock
+    /// screen_info.pixclock = 13468;
+    /// screen_info.vmode = 1;
+    /// let rate = screen_info.refresh_rate_hz().unwrap();
+    /// assert!((rate - 60.0).abs() < 0.1);
+    /// ```
+    pub fn refresh_rate_hz(&self) -> Option<f64> {
+        if self.pixclock == 0 {
+            return None;
+        }
+        let width = self.left_margin + self.xres + self.right_margin + self.hsync_len;
+        let mut height =
+            (self.upper_margin + self.yres + self.lower_margin + self.vsync_len) as f64;
+        if self.vmode & FB_VMODE_INTERLACED != 0 {
+            height /= 2.0;
+        }
+        if self.vmode & FB_VMODE_DOUBLE != 0 {
+            height *= 2.0;
+        }
+        // Pixel clock is given as duration of one pixel in picoseconds
+        let picoseconds = self.pixclock as f64 * width as f64 * height;
+        Some(1e12 / picoseconds)
+    }
+}
+
+/// Bit of `vmode` of [`fb_var_screeninfo`] set for interlaced modes
+pub const FB_VMODE_INTERLACED: u32 = 1;
+/// Bit of `vmode` of [`fb_var_screeninfo`] set for double scan modes, which show every row twice
+pub const FB_VMODE_DOUBLE: u32 = 2;
+
+/// System structure representing fixed screen info
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+pub struct fb_fix_screeninfo {
+    /// Identification string
+    pub id: [u8; 16],
+    /// Physical address of framebuffer memory
+    pub smem_start: c_ulong,
+    /// Length of framebuffer memory in bytes
+    pub smem_len: u32,
+    pub type_: u32,
+    pub type_aux: u32,
+    pub visual: u32,
+    pub xpanstep: u16,
+    pub ypanstep: u16,
+    pub ywrapstep: u16,
+    /// Length of one row in bytes, including padding
+    pub line_length: u32,
+    pub mmio_start: c_ulong,
+    pub mmio_len: u32,
+    pub accel: u32,
+    pub capabilities: u16,
+    pub reserved: [u16; 2],
+}
+
+impl fb_fix_screeninfo {
+    /// Create fixed screen info for a synthetic framebuffer with given variable screen info: rows
+    /// are not padded and memory holds the whole virtual screen
+    pub fn new(screen_info: &fb_var_screeninfo) -> Self {
+        let line_length = screen_info.xres_virtual * screen_info.bits_per_pixel / 8;
+        Self {
+            line_length,
+            smem_len: line_length * screen_info.yres_virtual,
+            ..Default::default()
+        }
+    }
+
+    /// Length of one row in bytes. Falls back to unpadded rows of virtual screen if driver
+    /// doesn't report it
+    pub fn stride(&self, screen_info: &fb_var_screeninfo) -> usize {
+        if self.line_length != 0 {
+            self.line_length as usize
+        } else {
+            (u32::max(screen_info.xres_virtual, screen_info.xres) * screen_info.bits_per_pixel / 8)
+                as usize
+        }
+    }
+
+    /// Size of visible screen in bytes, including row padding
+    pub fn page_size(&self, screen_info: &fb_var_screeninfo) -> usize {
+        self.stride(screen_info) * screen_info.yres as usize
+    }
+
+    /// Number of bytes which can be mapped: the whole virtual screen, but not more than the
+    /// framebuffer memory reported by driver:
+    /// ```
+    /// # use linfb::sys::{fb_fix_screeninfo, fb_var_screeninfo};
+    /// let mut screen_info = fb_var_screeninfo::argb8888(100, 50);
+    /// let fix_info = fb_fix_screeninfo::new(&screen_info);
+    /// assert_eq!(fix_info.stride(&screen_info), 400);
+    /// assert_eq!(fix_info.map_length(&screen_info), 400 * 50);
+    ///
+    /// // Rows padded to 512 bytes, virtual screen is twice as high as visible one
+    /// screen_info.yres_virtual = 100;
+    /// let mut fix_info = fb_fix_screeninfo::default();
+    /// fix_info.line_length = 512;
+    /// fix_info.smem_len = 1 << 20;
+    /// assert_eq!(fix_info.page_size(&screen_info), 512 * 50);
+    /// assert_eq!(fix_info.map_length(&screen_info), 512 * 100);
+    ///
+    /// // Driver reports less memory than virtual screen needs
+    /// fix_info.smem_len = 512 * 60;
+    /// assert_eq!(fix_info.map_length(&screen_info), 512 * 60);
+    ///
+    /// // Driver doesn't report anything
+    /// let fix_info = fb_fix_screeninfo::default();
+    /// assert_eq!(fix_info.stride(&screen_info), 400);
+    /// assert_eq!(fix_info.map_length(&screen_info), 400 * 100);
+    /// ```
+    pub fn map_length(&self, screen_info: &fb_var_screeninfo) -> usize {
+        let rows = u32::max(screen_info.yres_virtual, screen_info.yres) as usize;
+        let virtual_size = self.stride(screen_info) * rows;
+        if self.smem_len != 0 {
+            usize::min(virtual_size, self.smem_len as usize)
+        } else {
+            virtual_size
+        }
+    }
+}
+
+/// System structure representing state of vertical and horizontal blanking, see [`get_vblank`].
+/// `flags` tell which other fields driver supports, see `FB_VBLANK_*` constants
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+pub struct fb_vblank {
+    pub flags: u32,
+    /// Number of vertical blanks since boot
+    pub count: u32,
+    /// Row which is being scanned out
+    pub vcount: u32,
+    /// Column which is being scanned out
+    pub hcount: u32,
+    pub reserved: [u32; 4],
+}
+
+/// Bit of `flags` of [`fb_vblank`] set while screen is in vertical blank
+pub const FB_VBLANK_VBLANKING: u32 = 0x001;
+/// Bit of `flags` of [`fb_vblank`] set while screen is in horizontal blank
+pub const FB_VBLANK_HBLANKING: u32 = 0x002;
+/// Bit of `flags` of [`fb_vblank`] set if driver reports vertical blanking
+pub const FB_VBLANK_HAVE_VBLANK: u32 = 0x004;
+/// Bit of `flags` of [`fb_vblank`] set if driver reports horizontal blanking
+pub const FB_VBLANK_HAVE_HBLANK: u32 = 0x008;
+/// Bit of `flags` of [`fb_vblank`] set if `count` is valid
+pub const FB_VBLANK_HAVE_COUNT: u32 = 0x010;
+/// Bit of `flags` of [`fb_vblank`] set if `vcount` is valid
+pub const FB_VBLANK_HAVE_VCOUNT: u32 = 0x020;
+/// Bit of `flags` of [`fb_vblank`] set if `hcount` is valid
+pub const FB_VBLANK_HAVE_HCOUNT: u32 = 0x040;
+/// Bit of `flags` of [`fb_vblank`] set while vertical sync pulse is sent
+pub const FB_VBLANK_VSYNCING: u32 = 0x080;
+/// Bit of `flags` of [`fb_vblank`] set if driver reports vertical sync
+pub const FB_VBLANK_HAVE_VSYNC: u32 = 0x100;
+
+/// System structure representing color map: values of red, green, blue and transparency for
+/// pixel values from `start` to `start + len`. For truecolor framebuffers it's a gamma ramp of
+/// every channel. Arrays are owned by the caller and must hold `len` entries, `transp` can be null
+#[repr(C)]
+#[derive(Debug)]
+pub struct fb_cmap {
+    pub start: u32,
+    pub len: u32,
+    pub red: *mut u16,
+    pub green: *mut u16,
+    pub blue: *mut u16,
+    pub transp: *mut u16,
 }
 
 ioctl_read_bad! {
     /// Make a `get_var_screeninfo` ioctl call and return [`fb_var_screeninfo`] struct
     get_var_screeninfo, 0x4600, fb_var_screeninfo
 }
+
+ioctl_read_bad! {
+    /// Make a `get_fix_screeninfo` ioctl call and return [`fb_fix_screeninfo`] struct
+    get_fix_screeninfo, 0x4602, fb_fix_screeninfo
+}
+
+ioctl_readwrite_bad! {
+    /// Make a `put_var_screeninfo` ioctl call, which changes video mode to given
+    /// [`fb_var_screeninfo`], or only checks it if `activate` is `FB_ACTIVATE_TEST`. Driver
+    /// writes the mode it really uses back, e.g. with rounded resolution
+    put_var_screeninfo, 0x4601, fb_var_screeninfo
+}
+
+ioctl_write_ptr_bad! {
+    /// Make a `pan_display` ioctl call, which displays virtual screen starting at `xoffset`,
+    /// `yoffset` of given [`fb_var_screeninfo`]
+    pan_display, 0x4606, fb_var_screeninfo
+}
+
+ioctl_readwrite_bad! {
+    /// Make a `get_cmap` ioctl call, which fills arrays of given [`fb_cmap`] with current color
+    /// map
+    get_cmap, 0x4604, fb_cmap
+}
+
+ioctl_write_ptr_bad! {
+    /// Make a `put_cmap` ioctl call, which loads given [`fb_cmap`] into hardware
+    put_cmap, 0x4605, fb_cmap
+}
+
+ioctl_read! {
+    /// Make a `FBIOGET_VBLANK` ioctl call, which fills [`fb_vblank`] with current state of
+    /// blanking. Request number is `_IOR('F', 0x12, struct fb_vblank)`
+    get_vblank, b'F', 0x12, fb_vblank
+}
+
+ioctl_write_int_bad! {
+    /// Make a `blank` ioctl call with one of VESA blanking levels: 0 turns screen on, 1 blanks it
+    /// keeping everything powered, 2 and 3 also suspend vertical or horizontal sync and 4 powers
+    /// the screen down
+    blank, 0x4611
+}
+
+ioctl_write_ptr! {
+    /// Make a `FBIO_WAITFORVSYNC` ioctl call, which blocks until the next vertical blank of CRTC
+    /// with given index, usually 0. Request number is `_IOW('F', 0x20, u32)`
+    wait_for_vsync, b'F', 0x20, u32
+}
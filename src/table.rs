@@ -0,0 +1,443 @@
+use std::sync::OnceLock;
+
+use crate::canvas::{Canvas, Clipped};
+use crate::shape::{hash_of, Color, Shape};
+use crate::{Rect, RenderTarget};
+
+/// Width of [`Table`] column. Widths include cell padding, but not grid lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColumnWidth {
+    /// Exactly this many px
+    Fixed(usize),
+    /// Fit the widest cell of column
+    Auto,
+    /// Share of width left after fixed and auto columns, proportional to weight
+    Weighted(u32),
+}
+
+/// Column widths and row heights computed by [`Table`]
+#[derive(Clone, Debug, Default)]
+struct Layout {
+    columns: Vec<usize>,
+    rows: Vec<usize>,
+}
+
+/// Grid of cells, e.g. for departure boards. Cells can hold any shape and are drawn at the top
+/// left corner of their cell, inside the padding. Column widths are resolved from
+/// [`ColumnWidth`]s, weighted columns take the rest of table width:
+/// ```
+/// # use linfb::shape::{ColumnWidth, Rectangle, Shape, Table};
+/// # let square = |size| Rectangle::builder()
+/// #     .width(size)
+/// #     .height(size)
+/// #     .border_width(0)
+/// #     .fill_color((255, 255, 255))
+/// #     .build()
+/// #     .unwrap();
+/// let columns = vec![
+///     ColumnWidth::Fixed(30),
+///     ColumnWidth::Auto,
+///     ColumnWidth::Weighted(1),
+///     ColumnWidth::Weighted(3),
+/// ];
+/// let mut table = Table::new(200, columns, (0, 0, 0).into())
+///     .padding(2)
+///     .grid((128, 128, 128), 1);
+/// table.set_cell(0, 1, square(20));
+/// table.set_cell(1, 1, square(26));
+/// // Auto column fits 26 px cell and padding, weighted columns share 200 - 30 - 30 - 5 grid lines
+/// assert_eq!(table.column_widths(), vec![30, 30, 33, 102]);
+/// assert_eq!(table.dimensions(), (200, 1 + 24 + 1 + 30 + 1));
+/// assert_eq!(table.cell_bounds(1, 1), Some(linfb::Rect::new(34, 28, 26, 26)));
+///
+/// let rendered = table.render();
+/// assert_eq!(rendered[0][10], Some((128, 128, 128).into()));
+/// assert_eq!(rendered[10][31], Some((128, 128, 128).into()));
+/// assert_eq!(rendered[28][34], Some((255, 255, 255).into()));
+/// assert_eq!(rendered[28][33], Some((0, 0, 0).into()));
+/// ```
+///
+/// Auto columns fit [`Caption::measure`](crate::shape::Caption::measure) of their captions:
+/// ```
+/// # #[cfg(feature = "text")] {
+/// # use linfb::shape::{Caption, ColumnWidth, FontBuilder, Shape, Table};
+/// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+/// let caption = |text: &str| Caption::builder()
+///     .text(text.into())
+///     .size(16)
+///     .font(font.clone())
+///     .build()
+///     .unwrap();
+/// let mut table = Table::new(400, vec![ColumnWidth::Auto, ColumnWidth::Weighted(1)], (0, 0, 0).into())
+///     .padding(4);
+/// table.set_cell(0, 0, caption("12:30"));
+/// table.set_cell(0, 1, caption("Central station"));
+/// table.set_cell(1, 0, caption("12:45"));
+/// table.set_cell(1, 1, caption("Airport"));
+/// let time_width = caption("12:30").measure().0.max(caption("12:45").measure().0);
+/// assert_eq!(table.column_widths(), vec![time_width + 8, 400 - time_width - 8]);
+/// # }
+/// ```
+///
+/// Body rows can be striped, and the first row can be styled as header. Header is not counted
+/// when striping:
+/// ```
+/// # use linfb::shape::{ColumnWidth, Rectangle, Shape, Table};
+/// # let square = || Rectangle::builder()
+/// #     .width(4)
+/// #     .height(4)
+/// #     .border_width(0)
+/// #     .fill_color((255, 0, 0))
+/// #     .build()
+/// #     .unwrap();
+/// let mut table = Table::new(20, vec![ColumnWidth::Weighted(1)], (0, 0, 0).into())
+///     .padding(1)
+///     .header((0, 0, 255))
+///     .stripes((255, 255, 255), (128, 128, 128));
+/// for row in 0..5 {
+///     table.set_cell(row, 0, square());
+/// }
+/// let rendered = table.render();
+/// let row_color = |row: usize| rendered[row * 6][10];
+/// assert_eq!(rendered[1][1], Some((255, 0, 0).into()));
+/// assert_eq!(row_color(0), Some((0, 0, 255).into()));
+/// assert_eq!(row_color(1), Some((255, 255, 255).into()));
+/// assert_eq!(row_color(2), Some((128, 128, 128).into()));
+/// assert_eq!(row_color(3), Some((255, 255, 255).into()));
+/// assert_eq!(row_color(4), Some((128, 128, 128).into()));
+/// ```
+///
+/// Rows are as tall as their tallest cell, unless it exceeds maximum row height. Then contents
+/// of the row are clipped, leaving padding intact:
+/// ```
+/// # use linfb::shape::{ColumnWidth, Rectangle, Shape, Table};
+/// let tall = Rectangle::builder()
+///     .width(10)
+///     .height(30)
+///     .border_width(0)
+///     .fill_color((255, 0, 0))
+///     .build()
+///     .unwrap();
+/// let mut table = Table::new(20, vec![ColumnWidth::Auto], (0, 0, 0).into())
+///     .padding(2)
+///     .max_row_height(10);
+/// table.set_cell(0, 0, tall);
+/// assert_eq!(table.dimensions(), (20, 10));
+/// let rendered = table.render();
+/// assert_eq!(rendered[7][2], Some((255, 0, 0).into()));
+/// assert_eq!(rendered[8][2], Some((0, 0, 0).into()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Table {
+    width: usize,
+    columns: Vec<ColumnWidth>,
+    background: Color,
+    padding: usize,
+    stripes: Option<(Color, Color)>,
+    header: Option<Color>,
+    grid: Option<(Color, usize)>,
+    max_row_height: Option<usize>,
+    cells: Vec<Vec<Option<Box<dyn Shape>>>>,
+    layout: OnceLock<Layout>,
+}
+
+impl Table {
+    /// Create table without rows
+    pub fn new(width: usize, columns: Vec<ColumnWidth>, background: Color) -> Self {
+        Self {
+            width,
+            columns,
+            background,
+            padding: 0,
+            stripes: None,
+            header: None,
+            grid: None,
+            max_row_height: None,
+            cells: Vec::new(),
+            layout: OnceLock::new(),
+        }
+    }
+
+    /// Set space between cell borders and contents in px. Default is 0
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self.layout = OnceLock::new();
+        self
+    }
+
+    /// Alternate backgrounds of body rows, starting with `even` one
+    pub fn stripes<C: Into<Color>>(mut self, even: C, odd: C) -> Self {
+        self.stripes = Some((even.into(), odd.into()));
+        self
+    }
+
+    /// Treat the first row as header with given background
+    pub fn header<C: Into<Color>>(mut self, background: C) -> Self {
+        self.header = Some(background.into());
+        self
+    }
+
+    /// Draw lines of given color and width around every cell
+    pub fn grid<C: Into<Color>>(mut self, color: C, width: usize) -> Self {
+        self.grid = Some((color.into(), width));
+        self.layout = OnceLock::new();
+        self
+    }
+
+    /// Clip rows taller than given height in px, including padding
+    pub fn max_row_height(mut self, height: usize) -> Self {
+        self.max_row_height = Some(height);
+        self.layout = OnceLock::new();
+        self
+    }
+
+    /// Put shape into cell, adding rows if needed. Table is laid out again on the next render.
+    ///
+    /// Panics if column doesn't exist
+    pub fn set_cell<S: Shape>(&mut self, row: usize, column: usize, cell: S) {
+        assert!(
+            column < self.columns.len(),
+            "column {} doesn't exist, table has {} columns",
+            column,
+            self.columns.len()
+        );
+        if row >= self.cells.len() {
+            let columns = self.columns.len();
+            self.cells
+                .resize_with(row + 1, || (0..columns).map(|_| None).collect());
+        }
+        self.cells[row][column] = Some(Box::new(cell));
+        self.layout = OnceLock::new();
+    }
+
+    /// Remove shape from cell, returning it. Rows are kept even if they become empty
+    pub fn clear_cell(&mut self, row: usize, column: usize) -> Option<Box<dyn Shape>> {
+        let cell = self.cells.get_mut(row)?.get_mut(column)?.take();
+        self.layout = OnceLock::new();
+        cell
+    }
+
+    /// Shape in cell, if any
+    pub fn cell(&self, row: usize, column: usize) -> Option<&dyn Shape> {
+        self.cells.get(row)?.get(column)?.as_deref()
+    }
+
+    /// Number of rows, including header
+    pub fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Resolved widths of columns, including padding
+    pub fn column_widths(&self) -> Vec<usize> {
+        self.layout().columns.clone()
+    }
+
+    /// Region of table where contents of cell are drawn, without padding
+    pub fn cell_bounds(&self, row: usize, column: usize) -> Option<Rect> {
+        let layout = self.layout();
+        let (&width, &height) = (layout.columns.get(column)?, layout.rows.get(row)?);
+        let grid = self.grid_width();
+        let x = grid
+            + layout.columns[..column]
+                .iter()
+                .map(|w| w + grid)
+                .sum::<usize>();
+        let y = grid + layout.rows[..row].iter().map(|h| h + grid).sum::<usize>();
+        Some(Rect::new(
+            x + self.padding,
+            y + self.padding,
+            width.saturating_sub(2 * self.padding),
+            height.saturating_sub(2 * self.padding),
+        ))
+    }
+
+    fn grid_width(&self) -> usize {
+        self.grid.map_or(0, |(_, width)| width)
+    }
+
+    /// Background of row, taking header and stripes into account
+    fn row_background(&self, row: usize) -> Color {
+        let row = match self.header {
+            Some(header) if row == 0 => return header,
+            Some(_) => row - 1,
+            None => row,
+        };
+        match self.stripes {
+            Some((even, _)) if row % 2 == 0 => even,
+            Some((_, odd)) => odd,
+            None => self.background,
+        }
+    }
+
+    fn layout(&self) -> &Layout {
+        self.layout.get_or_init(|| {
+            let padding = 2 * self.padding;
+            let mut columns: Vec<usize> = self
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| match *column {
+                    ColumnWidth::Fixed(width) => width,
+                    ColumnWidth::Auto => {
+                        let content = self
+                            .cells
+                            .iter()
+                            .filter_map(|row| row[index].as_ref())
+                            .map(|cell| cell.dimensions().0)
+                            .max()
+                            .unwrap_or(0);
+                        content + padding
+                    }
+                    ColumnWidth::Weighted(_) => 0,
+                })
+                .collect();
+
+            // Weighted columns split the rest by cumulative weight, so their total is exact
+            let taken = columns.iter().sum::<usize>() + self.grid_width() * (columns.len() + 1);
+            let rest = self.width.saturating_sub(taken) as u64;
+            let weight = |column: &ColumnWidth| match *column {
+                ColumnWidth::Weighted(weight) => u64::from(weight),
+                _ => 0,
+            };
+            let total: u64 = self.columns.iter().map(weight).sum();
+            let mut cumulative = 0;
+            let mut end = 0;
+            for (width, column) in columns.iter_mut().zip(self.columns.iter()) {
+                if let ColumnWidth::Weighted(_) = column {
+                    cumulative += weight(column);
+                    // Columns weighted with 0 are empty, even if all of them are
+                    let next = (rest * cumulative).checked_div(total).unwrap_or(0);
+                    *width = (next - end) as usize;
+                    end = next;
+                }
+            }
+
+            let rows = self
+                .cells
+                .iter()
+                .map(|row| {
+                    let content = row
+                        .iter()
+                        .flatten()
+                        .map(|cell| cell.dimensions().1)
+                        .max()
+                        .unwrap_or(0);
+                    let height = content + padding;
+                    self.max_row_height
+                        .map_or(height, |max_height| height.min(max_height))
+                })
+                .collect();
+            Layout { columns, rows }
+        })
+    }
+}
+
+impl Shape for Table {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        let (width, height) = self.dimensions();
+        let mut canvas = Canvas::new(width, height, self.background);
+        self.render_into(&mut canvas, 0, 0);
+        canvas.to_rows()
+    }
+
+    fn render_into(&self, target: &mut dyn RenderTarget, x: usize, y: usize) {
+        let (width, height) = self.dimensions();
+        if width == 0 {
+            return;
+        }
+        let mut target = Clipped {
+            target,
+            region: Rect::new(x, y, width, height),
+        };
+        let layout = self.layout();
+        let grid = self.grid_width();
+        let grid_color = self.grid.map(|(color, _)| color);
+        let content_width = usize::min(
+            width,
+            layout.columns.iter().map(|w| w + grid).sum::<usize>() + grid,
+        );
+
+        let draw_line = |target: &mut Clipped, y: usize| {
+            if let Some(color) = grid_color {
+                for inner_y in 0..grid {
+                    target.draw_span(x, y + inner_y, content_width, color);
+                }
+            }
+        };
+        let mut row_y = y;
+        for (index, &row_height) in layout.rows.iter().enumerate() {
+            draw_line(&mut target, row_y);
+            row_y += grid;
+            for inner_y in 0..row_height {
+                target.draw_span(x, row_y + inner_y, width, self.row_background(index));
+            }
+            if let Some(color) = grid_color {
+                let mut line_x = x;
+                for &column_width in layout.columns.iter().chain(Some(&0)) {
+                    for inner_y in 0..row_height {
+                        target.draw_span(line_x, row_y + inner_y, grid, color);
+                    }
+                    line_x += grid + column_width;
+                }
+            }
+            row_y += row_height;
+        }
+        if !layout.rows.is_empty() {
+            draw_line(&mut target, row_y);
+        }
+
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                let (cell, bounds) = match (cell, self.cell_bounds(row, column)) {
+                    (Some(cell), Some(bounds)) => (cell, bounds),
+                    _ => continue,
+                };
+                let region = Rect::new(x + bounds.x, y + bounds.y, bounds.width, bounds.height);
+                let region = match region.intersection(&target.region) {
+                    Some(region) => region,
+                    None => continue,
+                };
+                let mut target = Clipped {
+                    target: &mut *target.target,
+                    region,
+                };
+                cell.render_into(&mut target, x + bounds.x, y + bounds.y);
+            }
+        }
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let rows = &self.layout().rows;
+        if rows.is_empty() {
+            return (self.width, 0);
+        }
+        let grid = self.grid_width();
+        (
+            self.width,
+            rows.iter().map(|height| height + grid).sum::<usize>() + grid,
+        )
+    }
+
+    fn content_hash(&self) -> u64 {
+        let cells: Vec<Vec<Option<u64>>> = self
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.as_ref().map(|cell| cell.content_hash()))
+                    .collect()
+            })
+            .collect();
+        hash_of(&(
+            self.width,
+            &self.columns,
+            self.background,
+            self.padding,
+            self.stripes,
+            self.header,
+            self.grid,
+            self.max_row_height,
+            cells,
+        ))
+    }
+}
@@ -0,0 +1,240 @@
+//! Golden-image tests of shapes. [`assert_shape_matches`] renders shape and compares it with a
+//! checked-in PNG fixture, so changes in pixel output are caught without screenshots.
+//!
+//! Fixtures are looked up in `tests/fixtures` of the crate being tested. Run tests with
+//! `LINFB_UPDATE_FIXTURES=1` to write current output as fixtures instead of comparing. On
+//! mismatch actual output and the difference are written to `target/golden`, differing pixels
+//! are red in the difference image:
+//! ```
+//! # use linfb::shape::Rectangle;
+//! # use linfb::testing::assert_shape_matches;
+//! let rectangle = Rectangle::builder()
+//!     .width(16)
+//!     .height(12)
+//!     .border_width(2)
+//!     .border_color((255, 0, 0))
+//!     .fill_color((0, 0, 255, 128))
+//!     .build()
+//!     .unwrap();
+//! assert_shape_matches(&rectangle, "rectangle.png");
+//! ```
+//!
+//! Text with a bundled font, so result doesn't depend on fonts installed in the system:
+//! ```
+//! # #[cfg(feature = "text")] {
+//! # use linfb::shape::Caption;
+//! # use linfb::testing::assert_shape_matches;
+//! let font = include_bytes!(concat!(
+//!     env!("CARGO_MANIFEST_DIR"),
+//!     "/tests/fixtures/fonts/DejaVuSansMono.ttf",
+//! ));
+//! let caption = Caption::builder()
+//!     .text("Golden\nimage".into())
+//!     .size(16)
+//!     .color((20, 200, 100))
+//!     .font(rusttype::Font::from_bytes(&font[..]).unwrap())
+//!     .build()
+//!     .unwrap();
+//! assert_shape_matches(&caption, "caption.png");
+//! # }
+//! ```
+//!
+//! Blending of overlapping translucent shapes:
+//! ```
+//! # use linfb::Compositor;
+//! # use linfb::shape::{Color, Rectangle, Shape};
+//! # use linfb::testing::assert_shape_matches;
+//! let square = |color: &str| {
+//!     Rectangle::builder()
+//!         .width(12)
+//!         .height(12)
+//!         .border_width(0)
+//!         .fill_color(Color::hex(color).unwrap())
+//!         .build()
+//!         .unwrap()
+//! };
+//! let mut compositor = Compositor::new(24, 24, (255, 255, 255).into());
+//! compositor
+//!     .add("red", square("#ff000099").at(2, 2))
+//!     .add("green", square("#00ff0099").at(8, 6))
+//!     .add("blue", square("#0000ff99").at(10, 10));
+//! assert_shape_matches(&compositor, "compositor_blending.png");
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::shape::Shape;
+
+/// Environment variable which makes [`assert_shape_matches`] write fixtures instead of comparing
+pub const UPDATE_FIXTURES_VAR: &str = "LINFB_UPDATE_FIXTURES";
+
+/// How much output can differ from fixture. Default is exact match
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tolerance {
+    /// Maximum difference of any channel, including alpha, for pixels to be considered equal
+    pub channel: u8,
+    /// Number of pixels which are allowed to differ
+    pub pixels: usize,
+}
+
+/// Panic if shape doesn't render exactly to fixture. See [module docs](self)
+/// ```should_panic
+/// # use linfb::shape::Rectangle;
+/// # // Compares with fixture written by another example, so never rewrites it
+/// # std::env::remove_var(linfb::testing::UPDATE_FIXTURES_VAR);
+/// # use linfb::testing::assert_shape_matches;
+/// // Border of fixture is red
+/// let rectangle = Rectangle::builder()
+///     .width(16)
+///     .height(12)
+///     .border_width(2)
+///     .border_color((250, 0, 0))
+///     .fill_color((0, 0, 255, 128))
+///     .build()
+///     .unwrap();
+/// assert_shape_matches(&rectangle, "rectangle.png");
+/// ```
+pub fn assert_shape_matches<T: Shape + ?Sized>(shape: &T, fixture: &str) {
+    assert_shape_matches_with(shape, fixture, Tolerance::default());
+}
+
+/// Panic if shape renders differently from fixture, with given [`Tolerance`]:
+/// ```
+/// # use linfb::shape::Rectangle;
+/// # // Compares with fixture written by another example, so never rewrites it
+/// # std::env::remove_var(linfb::testing::UPDATE_FIXTURES_VAR);
+/// # use linfb::testing::{assert_shape_matches_with, Tolerance};
+/// let rectangle = Rectangle::builder()
+///     .width(16)
+///     .height(12)
+///     .border_width(2)
+///     .border_color((255, 0, 0))
+///     .fill_color((0, 0, 250, 128))
+///     .build()
+///     .unwrap();
+/// let tolerance = Tolerance {
+///     channel: 5,
+///     pixels: 0,
+/// };
+/// assert_shape_matches_with(&rectangle, "rectangle.png", tolerance);
+/// ```
+pub fn assert_shape_matches_with<T: Shape + ?Sized>(
+    shape: &T,
+    fixture: &str,
+    tolerance: Tolerance,
+) {
+    let actual = to_image(shape);
+    let path = crate_dir().join("tests").join("fixtures").join(fixture);
+    if env::var_os(UPDATE_FIXTURES_VAR).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create fixtures directory");
+        }
+        actual.save(&path).expect("Failed to write fixture");
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(expected) => expected.to_rgba(),
+        Err(err) => panic!(
+            "Failed to open fixture {}: {}. Run with {}=1 to create it",
+            path.display(),
+            err,
+            UPDATE_FIXTURES_VAR
+        ),
+    };
+    let mismatch = if expected.dimensions() != actual.dimensions() {
+        Some(format!(
+            "size differs: expected {:?}, got {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        ))
+    } else {
+        let differing = actual
+            .pixels()
+            .zip(expected.pixels())
+            .filter(|(actual, expected)| !similar(actual.0, expected.0, tolerance.channel))
+            .count();
+        if differing > tolerance.pixels {
+            Some(format!(
+                "{} pixels differ, {} allowed",
+                differing, tolerance.pixels
+            ))
+        } else {
+            None
+        }
+    };
+
+    if let Some(mismatch) = mismatch {
+        let output = target_dir().join("golden");
+        fs::create_dir_all(&output).expect("Failed to create output directory");
+        let name = fixture.replace(['/', '\\'], "_");
+        let actual_path = output.join(format!("actual_{}", name));
+        actual
+            .save(&actual_path)
+            .expect("Failed to write actual image");
+        let mut message = format!(
+            "Shape doesn't match fixture {}: {}. Actual image: {}",
+            path.display(),
+            mismatch,
+            actual_path.display()
+        );
+        if expected.dimensions() == actual.dimensions() {
+            let diff_path = output.join(format!("diff_{}", name));
+            diff(&actual, &expected, tolerance.channel)
+                .save(&diff_path)
+                .expect("Failed to write difference image");
+            message += &format!(", difference: {}", diff_path.display());
+        }
+        panic!("{}", message);
+    }
+}
+
+/// Render shape into image, transparent pixels are (0, 0, 0, 0)
+fn to_image<T: Shape + ?Sized>(shape: &T) -> image::RgbaImage {
+    let (width, height) = shape.dimensions();
+    let rows = shape.render();
+    image::RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        let color = rows
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .and_then(|color| *color);
+        match color {
+            Some(color) => image::Rgba([color.red, color.green, color.blue, color.alpha]),
+            None => image::Rgba([0, 0, 0, 0]),
+        }
+    })
+}
+
+fn similar(actual: [u8; 4], expected: [u8; 4], tolerance: u8) -> bool {
+    actual
+        .iter()
+        .zip(expected.iter())
+        .all(|(actual, expected)| actual.max(expected) - actual.min(expected) <= tolerance)
+}
+
+/// Image with differing pixels in red and matching ones dimmed
+fn diff(actual: &image::RgbaImage, expected: &image::RgbaImage, tolerance: u8) -> image::RgbaImage {
+    image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let pixel = actual.get_pixel(x, y).0;
+        if similar(pixel, expected.get_pixel(x, y).0, tolerance) {
+            image::Rgba([pixel[0], pixel[1], pixel[2], pixel[3] / 4])
+        } else {
+            image::Rgba([255, 0, 0, 255])
+        }
+    })
+}
+
+/// Root of crate being tested, falls back to the current directory outside of cargo
+fn crate_dir() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+fn target_dir() -> PathBuf {
+    env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate_dir().join("target"))
+}
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
 #[cfg(feature = "text")]
 use derive_builder::Builder;
 use font_loader::system_fonts::FontPropertyBuilder;
@@ -5,10 +9,40 @@ use rusttype::{point, Font, PositionedGlyph, Scale};
 use xi_unicode::LineBreakIterator;
 
 use crate::error::{Error::*, Result};
-use crate::shape::{Color, Shape};
+use crate::shape::{hash_of, Color, Palette, PaletteRef, Shape};
+
+/// Fonts loaded with [`FontBuilder`], keyed by their properties
+fn font_cache() -> &'static Mutex<HashMap<FontBuilder, Arc<Font<'static>>>> {
+    static FONT_CACHE: OnceLock<Mutex<HashMap<FontBuilder, Arc<Font<'static>>>>> = OnceLock::new();
+    FONT_CACHE.get_or_init(Default::default)
+}
+
+/// Drop all fonts cached by [`FontBuilder`], so the next builds will load them from disk again.
+/// Fonts which are still in use are not freed until all their users are dropped
+pub fn clear_font_cache() {
+    font_cache().lock().unwrap().clear();
+}
 
 /// Builder for [`Font`]. All methods map to corresponding [`FontPropertyBuilder`] methods.
-#[derive(Default)]
+///
+/// Loaded fonts are cached for the whole process, so building a font with the same properties
+/// again doesn't touch the filesystem. Use [`FontBuilder::build_uncached`] to bypass the cache and
+/// [`clear_font_cache`] to empty it:
+/// ```
+/// # use std::sync::Arc;
+/// # use linfb::shape::{clear_font_cache, FontBuilder};
+/// let first = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// let second = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// assert!(Arc::ptr_eq(&first, &second));
+///
+/// let bold = FontBuilder::default().family("monospace").bold().build_shared().unwrap();
+/// assert!(!Arc::ptr_eq(&first, &bold));
+///
+/// clear_font_cache();
+/// let reloaded = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// assert!(!Arc::ptr_eq(&first, &reloaded));
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 pub struct FontBuilder {
     italic: bool,
     oblique: bool,
@@ -43,8 +77,50 @@ impl FontBuilder {
         self
     }
 
-    /// Try to build an owned font with given properties
+    /// Try to build a shared font with given properties. Shared font can be passed to any number
+    /// of [`Caption`]s without copying it:
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use linfb::shape::{Caption, FontBuilder};
+    /// let font = FontBuilder::default().family("monospace").build_shared().unwrap();
+    /// let caption1 = Caption::builder()
+    ///     .text("first".into())
+    ///     .size(16)
+    ///     .font(font.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// let caption2 = Caption::builder()
+    ///     .text("second".into())
+    ///     .size(16)
+    ///     .font(font)
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(Arc::ptr_eq(&caption1.font, &caption2.font));
+    /// ```
+    pub fn build_shared(&self) -> Result<Arc<Font<'static>>> {
+        if let Some(font) = font_cache().lock().unwrap().get(self) {
+            return Ok(font.clone());
+        }
+        // Font is loaded without holding the lock, so loading one font doesn't block users of
+        // already cached ones
+        let font = Arc::new(self.build_uncached()?);
+        Ok(font_cache()
+            .lock()
+            .unwrap()
+            .entry(self.clone())
+            .or_insert(font)
+            .clone())
+    }
+
+    /// Try to build an owned font with given properties. Font data is shared with the cached
+    /// font, so it isn't copied
     pub fn build(&self) -> Result<Font<'static>> {
+        self.build_shared().map(|font| (*font).clone())
+    }
+
+    /// Try to build a font with given properties, always loading it from disk and not touching
+    /// the cache
+    pub fn build_uncached(&self) -> Result<Font<'static>> {
         let mut property_builder = FontPropertyBuilder::new().family(&self.family);
         if self.italic {
             property_builder = property_builder.italic();
@@ -68,38 +144,240 @@ impl FontBuilder {
     }
 }
 
+/// Default sans-serif system font, loaded on the first use
+#[cfg(feature = "framebuffer")]
+pub(crate) fn default_font() -> Result<Arc<Font<'static>>> {
+    FontBuilder::default().family("sans-serif").build_shared()
+}
+
 /// Text alignment for [`Caption`]. Default is [`Alignment::Left`]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Hash)]
 pub enum Alignment {
+    #[default]
     Left,
     Center,
     Right,
 }
 
-impl Default for Alignment {
-    fn default() -> Self {
-        Self::Left
+/// Information about glyph passed to [`Caption::glyph_color`] callback
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphInfo {
+    /// Character this glyph represents
+    pub character: char,
+    /// Index of glyph in the whole caption, counting from zero
+    pub index: usize,
+    /// Index of line containing this glyph, counting from zero
+    pub line: usize,
+    /// Position of glyph center across the full text width, from 0.0 (left edge) to 1.0 (right
+    /// edge)
+    pub fraction: f32,
+}
+
+/// Callback which chooses color of every glyph of [`Caption`]. Any `Fn(GlyphInfo) -> Color`
+/// closure which is `Send` and `Sync` can be converted into it, so captions can be moved across
+/// threads:
+/// ```
+/// # use linfb::shape::{Caption, FontBuilder, Shape};
+/// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+/// let caption = Caption::builder()
+///     .text("HI".into())
+///     .size(24)
+///     .font(font)
+///     .glyph_color(|glyph: linfb::shape::GlyphInfo| {
+///         if glyph.index == 0 {
+///             (255, 0, 0).into()
+///         } else {
+///             (0, 0, 255).into()
+///         }
+///     })
+///     .build()
+///     .unwrap();
+/// let rendered = caption.render();
+/// let pixels = || rendered.iter().flat_map(|row| row.iter().enumerate());
+/// let color_at = |x| pixels().filter(|(i, _)| *i == x).find_map(|(_, c)| *c).unwrap();
+/// let first = pixels().filter(|(_, c)| c.is_some()).map(|(i, _)| i).min().unwrap();
+/// let last = pixels().filter(|(_, c)| c.is_some()).map(|(i, _)| i).max().unwrap();
+/// let (first, last) = (color_at(first), color_at(last));
+/// assert_eq!((first.red, first.green, first.blue), (255, 0, 0));
+/// assert_eq!((last.red, last.green, last.blue), (0, 0, 255));
+///
+/// let rendered_elsewhere = std::thread::spawn(move || caption.render()).join().unwrap();
+/// assert_eq!(rendered_elsewhere, rendered);
+/// ```
+#[derive(Clone)]
+pub struct GlyphColor(Arc<dyn Fn(GlyphInfo) -> Color + Send + Sync>);
+
+impl<F: Fn(GlyphInfo) -> Color + Send + Sync + 'static> From<F> for GlyphColor {
+    fn from(callback: F) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for GlyphColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("GlyphColor(..)")
     }
 }
 
 /// Shape containing single- or multi-line text. Text will be soft wrapped if `max_width` is set.
-#[derive(Debug, Builder)]
+/// Lines which can't be wrapped to fit into `max_width` are clipped according to alignment:
+/// ```
+/// # use linfb::shape::{Alignment, Caption, FontBuilder, Shape};
+/// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+/// let caption = |alignment| {
+///     Caption::builder()
+///         .text("Unbreakable-loooooooong-word".into())
+///         .size(16)
+///         .font(font.clone())
+///         .max_width(20)
+///         .alignment(alignment)
+///         .build()
+///         .unwrap()
+///         .render()
+/// };
+/// for alignment in [Alignment::Left, Alignment::Center, Alignment::Right] {
+///     let rendered = caption(alignment);
+///     assert!(!rendered.is_empty());
+///     assert!(rendered.iter().all(|row| row.len() == 20));
+/// }
+/// assert_ne!(caption(Alignment::Left), caption(Alignment::Right));
+/// ```
+///
+/// Wrapping works with any UTF-8 text:
+/// ```
+/// # use linfb::shape::{Caption, FontBuilder, Shape};
+/// # let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+/// for text in ["naïve café — déjà vu", "wait—what—really", "party 🎉 time 🎉🎉 now", "ü ö ä ß é"] {
+///     let one_line = Caption::builder()
+///         .text(text.into())
+///         .size(16)
+///         .font(font.clone())
+///         .build()
+///         .unwrap()
+///         .render();
+///     for max_width in 1..120 {
+///         let wrapped = Caption::builder()
+///             .text(text.into())
+///             .size(16)
+///             .font(font.clone())
+///             .max_width(max_width)
+///             .build()
+///             .unwrap()
+///             .render();
+///         assert!(wrapped.len() >= one_line.len());
+///     }
+/// }
+/// ```
+///
+/// Whitespace at the end of wrapped lines is not drawn and not taken into account when wrapping or
+/// aligning:
+/// ```
+/// # use linfb::shape::{Alignment, Caption, FontBuilder, Shape};
+/// # let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+/// let caption = |text: &str, max_width, alignment| {
+///     let mut builder = Caption::builder();
+///     builder.text(text.into()).size(16).font(font.clone()).alignment(alignment);
+///     if let Some(max_width) = max_width {
+///         builder.max_width(max_width);
+///     }
+///     builder.build().unwrap().render()
+/// };
+/// let width = |rows: &Vec<Vec<_>>| rows[0].len();
+///
+/// // Words exactly filling max_width are not wrapped early
+/// let line_height = caption("aaa bbb", None, Alignment::Left).len();
+/// let max_width = width(&caption("aaa bbb", None, Alignment::Left));
+/// assert_eq!(caption("aaa bbb ccc", Some(max_width), Alignment::Left).len(), 2 * line_height);
+///
+/// // Ends of right aligned lines are flush
+/// let max_width = width(&caption("cab", None, Alignment::Left));
+/// let rendered = caption("ab cab ab cab", Some(max_width), Alignment::Right);
+/// let mut line_ends = vec![];
+/// let mut line_end = None;
+/// for row in &rendered {
+///     match row.iter().rposition(Option::is_some) {
+///         Some(end) => line_end = Some(usize::max(end, line_end.unwrap_or(0))),
+///         None => line_ends.extend(line_end.take()),
+///     }
+/// }
+/// line_ends.extend(line_end);
+/// assert_eq!(line_ends.len(), 4);
+/// let (min, max) = (line_ends.iter().min().unwrap(), line_ends.iter().max().unwrap());
+/// assert!(max - min <= 1);
+/// ```
+///
+/// Builder errors name the field which is wrong:
+/// ```
+/// # use linfb::shape::{Caption, FontBuilder};
+/// let font = FontBuilder::default().family("monospace").build_shared().unwrap();
+/// let caption = |size, max_width| {
+///     Caption::builder()
+///         .text("Text".into())
+///         .size(size)
+///         .font(font.clone())
+///         .max_width(max_width)
+///         .build()
+/// };
+/// assert!(caption(12, 100).is_ok());
+/// let message = |result: linfb::Result<Caption>| result.unwrap_err().to_string();
+/// assert_eq!(message(caption(0, 100)), "invalid Caption: size must be positive");
+/// assert_eq!(message(caption(12, 0)), "invalid Caption: max_width must be positive");
+/// assert_eq!(
+///     message(Caption::builder().size(12).font(font.clone()).build()),
+///     "invalid Caption: `text` must be initialized",
+/// );
+/// ```
+#[derive(Debug, Clone, Builder)]
+#[builder(build_fn(name = "build_unvalidated"))]
 pub struct Caption {
     /// Caption text
     pub text: String,
     /// Font size in px
     pub size: u32,
-    /// Font object, built with [`FontBuilder`]
-    pub font: Font<'static>,
+    /// Font object, built with [`FontBuilder`]. Font is shared, so cloning a caption doesn't copy
+    /// it. Builder accepts both owned and shared fonts
+    #[builder(setter(into))]
+    pub font: Arc<Font<'static>>,
     /// Font color. Default is black
-    #[builder(default = "Color::from((0, 0, 0))")]
+    #[builder(setter(into), default = "Color::from((0, 0, 0))")]
     pub color: Color,
+    /// Palette font color. If palette caption is rendered with has it, it's used instead of
+    /// `color`. Default is [`None`]
+    #[builder(setter(into, strip_option), default)]
+    pub color_ref: Option<PaletteRef>,
     /// Soft wrap width. If not set, soft wrapping will be disabled
     #[builder(setter(strip_option), default)]
     pub max_width: Option<usize>,
     #[builder(default)]
     /// Text alignment
     pub alignment: Alignment,
+    /// Color of every glyph, overriding `color`. Coverage of anti-aliased pixels is still
+    /// applied to alpha of the returned color
+    #[builder(setter(into, strip_option), default)]
+    pub glyph_color: Option<GlyphColor>,
+    /// Distance between tops of consecutive lines in px. If not set, it's taken from font
+    /// metrics, see [`Caption::line_height`]
+    #[builder(setter(strip_option), default)]
+    pub line_height: Option<usize>,
+}
+
+impl CaptionBuilder {
+    /// Build [`Caption`]. Returns [`Error::InvalidShape`](crate::Error::InvalidShape) if
+    /// required fields are not set, size or wrap width is zero
+    pub fn build(&self) -> Result<Caption> {
+        let invalid = |reason: String| InvalidShape {
+            shape: "Caption",
+            reason,
+        };
+        let caption = self.build_unvalidated().map_err(invalid)?;
+        if caption.size == 0 {
+            return Err(invalid("size must be positive".into()));
+        }
+        if caption.max_width == Some(0) {
+            return Err(invalid("max_width must be positive".into()));
+        }
+        Ok(caption)
+    }
 }
 
 impl Caption {
@@ -108,28 +386,84 @@ impl Caption {
         CaptionBuilder::default()
     }
 
+    /// Distance between tops of consecutive lines in px: `line_height` if it's set, otherwise
+    /// full height of font (ascent and descent, which can be larger than `size`) plus line gap
+    pub fn line_height(&self) -> usize {
+        self.line_height.unwrap_or_else(|| {
+            let v_metrics = self.font.v_metrics(Scale::uniform(self.size as f32));
+            self.glyph_rows() + v_metrics.line_gap.round() as usize
+        })
+    }
+
+    /// Width and height of rendered caption, computed without rendering it:
+    /// ```
+    /// # use linfb::shape::{Caption, FontBuilder, Shape};
+    /// let font = FontBuilder::default().family("sans-serif").build_shared().unwrap();
+    /// let caption = Caption::builder()
+    ///     .text("gypsy\nwaltz quickly".into())
+    ///     .size(32)
+    ///     .font(font.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// let rendered = caption.render();
+    /// assert_eq!(caption.measure(), (rendered[0].len(), rendered.len()));
+    /// assert_eq!(rendered.len(), 2 * caption.line_height());
+    ///
+    /// // Descenders are not clipped
+    /// let v_metrics = font.v_metrics(rusttype::Scale::uniform(32.0));
+    /// let baseline = v_metrics.ascent.round() as usize;
+    /// assert!(rendered[baseline + 2..caption.line_height()]
+    ///     .iter()
+    ///     .flatten()
+    ///     .any(|pixel| pixel.map(|color| color.alpha > 0).unwrap_or(false)));
+    /// ```
+    pub fn measure(&self) -> (usize, usize) {
+        if self.text.is_empty() {
+            return (0, 0);
+        }
+        let lines = self.split_text();
+        let width = match self.max_width {
+            Some(max_width) => max_width,
+            None => lines
+                .iter()
+                .map(|line| self.width(&self.layout(line)).ceil() as usize)
+                .max()
+                .unwrap_or(0),
+        };
+        if width == 0 {
+            return (0, 0);
+        }
+        (width, lines.len() * self.line_height())
+    }
+
+    /// Number of rows glyphs of one line can occupy
+    fn glyph_rows(&self) -> usize {
+        let v_metrics = self.font.v_metrics(Scale::uniform(self.size as f32));
+        (v_metrics.ascent - v_metrics.descent).ceil() as usize
+    }
+
+    /// Characters of text which have glyphs in font, one for every glyph of [`Caption::layout`]
+    fn known_chars<'a>(&'a self, text: &'a str) -> impl Iterator<Item = char> + 'a {
+        text.chars().filter(move |c| {
+            self.font
+                .glyph(*c)
+                .standalone()
+                .get_data()
+                .map(|g| g.id != 0)
+                .unwrap_or(false)
+        })
+    }
+
     fn layout(&self, text: &str) -> Vec<PositionedGlyph<'_>> {
         let scale = Scale::uniform(self.size as f32);
         let offset = point(0f32, self.font.v_metrics(scale).ascent);
-        let text: String = text
-            .chars()
-            .filter(|c| {
-                self.font
-                    .glyph(*c)
-                    .standalone()
-                    .get_data()
-                    .and_then(|g| Some(g.id != 0))
-                    .unwrap_or(false)
-            })
-            .collect();
+        let text: String = self.known_chars(text).collect();
         self.font.layout(&text, scale, offset).collect()
     }
 
     fn width(&self, glyphs: &Vec<PositionedGlyph<'_>>) -> f32 {
-        match glyphs.iter().rev().next() {
-            Some(glyph) => {
-                glyph.position().x as f32 + glyph.unpositioned().h_metrics().advance_width
-            }
+        match glyphs.iter().next_back() {
+            Some(glyph) => glyph.position().x + glyph.unpositioned().h_metrics().advance_width,
             None => 0f32,
         }
     }
@@ -138,17 +472,26 @@ impl Caption {
         self.width(&self.layout(text)).round() as usize
     }
 
-    fn split_text_at_indices(&self, indices: Vec<usize>) -> Vec<&str> {
+    /// Split text into lines. Every index is a pair of byte offsets: end of the current line and
+    /// start of the next one
+    fn split_text_at_indices(&self, indices: Vec<(usize, usize)>) -> Vec<&str> {
         let mut last_split = 0;
         let mut result = Vec::with_capacity(indices.len() + 1);
-        for idx in indices {
-            result.push(&self.text[last_split..idx]);
-            last_split = idx;
+        for (line_end, next_start) in indices {
+            result.push(&self.text[last_split..line_end]);
+            last_split = next_start;
         }
         result.push(&self.text[last_split..]);
         result
     }
 
+    /// End of line between byte offsets `start` and `end`, without trailing whitespace
+    fn line_end(&self, start: usize, end: usize) -> usize {
+        start + self.text[start..end].trim_end().len()
+    }
+
+    /// Find line breaks. All offsets are byte offsets provided by [`LineBreakIterator`], so
+    /// slicing at them is always valid
     fn split_text(&self) -> Vec<&str> {
         let mut prev_offset = 0;
         let mut prev_break = None;
@@ -156,29 +499,23 @@ impl Caption {
 
         for (offset, hard_break) in LineBreakIterator::new(&self.text) {
             if let Some(max_width) = self.max_width {
-                let width = self.str_width(&self.text[prev_offset..offset]);
+                // Whitespace before break is not drawn, so it doesn't count
+                let width =
+                    self.str_width(&self.text[prev_offset..self.line_end(prev_offset, offset)]);
                 if width > max_width {
-                    if let Some(mut prev_break) = prev_break {
+                    if let Some(prev_break) =
+                        prev_break.filter(|&prev_break| prev_break > prev_offset)
+                    {
+                        where_to_break.push((self.line_end(prev_offset, prev_break), prev_break));
                         prev_offset = prev_break;
-
-                        // Stripping space from line end
-                        // Can .unwrap() here because prev_break is line offset
-                        if self
-                            .text
-                            .chars()
-                            .nth(prev_break - 1)
-                            .unwrap()
-                            .is_whitespace()
-                        {
-                            prev_break -= 1;
-                        }
-                        where_to_break.push(prev_break);
                     }
                 }
             }
 
-            if hard_break {
-                where_to_break.push(offset);
+            // Break iterator always reports a hard break at the end of text, which doesn't start a
+            // new line
+            if hard_break && offset < self.text.len() {
+                where_to_break.push((self.line_end(prev_offset, offset), offset));
                 prev_offset = offset;
             }
 
@@ -188,22 +525,46 @@ impl Caption {
         self.split_text_at_indices(where_to_break)
     }
 
-    fn render_line(&self, line: &str) -> Vec<Vec<Option<Color>>> {
+    fn render_line(
+        &self,
+        line: &str,
+        first_glyph: usize,
+        line_index: usize,
+        full_width: f32,
+        flat_color: Color,
+    ) -> Vec<Vec<Option<Color>>> {
         let glyphs = self.layout(line);
         let width = self.width(&glyphs);
 
-        let mut result = vec![vec![None; width.ceil() as usize]; self.size as usize];
-        for glyph in glyphs {
+        let mut result = vec![vec![None; width.ceil() as usize]; self.glyph_rows()];
+        for (index, (glyph, character)) in glyphs.iter().zip(self.known_chars(line)).enumerate() {
+            let color = match &self.glyph_color {
+                Some(GlyphColor(callback)) => {
+                    let center =
+                        glyph.position().x + glyph.unpositioned().h_metrics().advance_width / 2f32;
+                    callback(GlyphInfo {
+                        character,
+                        index: first_glyph + index,
+                        line: line_index,
+                        fraction: if full_width > 0f32 {
+                            (center / full_width).clamp(0f32, 1f32)
+                        } else {
+                            0f32
+                        },
+                    })
+                }
+                None => flat_color,
+            };
             if let Some(bounding_box) = glyph.pixel_bounding_box() {
                 glyph.draw(|x, y, v| {
                     let x = (x + i32::max(0, bounding_box.min.x) as u32) as usize;
                     let y = (y + i32::max(0, bounding_box.min.y) as u32) as usize;
                     if y < result.len() && x < result[0].len() {
                         result[y][x] = Some(Color {
-                            red: self.color.red,
-                            green: self.color.green,
-                            blue: self.color.blue,
-                            alpha: (self.color.alpha as f32 * v) as u8,
+                            red: color.red,
+                            green: color.green,
+                            blue: color.blue,
+                            alpha: (color.alpha as f32 * v) as u8,
                         })
                     }
                 })
@@ -213,50 +574,80 @@ impl Caption {
         result
     }
 
+    /// Place every row of line into row with given width according to alignment. Rows wider
+    /// than `width` are clipped, keeping the left, center or right part of them for
+    /// [`Alignment::Left`], [`Alignment::Center`] and [`Alignment::Right`] respectively
     fn align_line(&self, line: Vec<Vec<Option<Color>>>, width: usize) -> Vec<Vec<Option<Color>>> {
-        match self.alignment {
-            Alignment::Left => line
-                .into_iter()
-                .map(|mut row| {
-                    row.resize(width, None);
-                    row
-                })
-                .collect(),
-            Alignment::Right => line
-                .iter()
-                .map(|row| {
-                    let mut new_row = vec![None; width];
-                    let row_len = usize::min(width, row.len());
-                    &new_row[width - row_len..].copy_from_slice(&row[..row_len]);
-                    new_row
-                })
-                .collect(),
-            Alignment::Center => line
-                .iter()
-                .map(|row| {
-                    let mut new_row = vec![None; width];
-                    let row_len = usize::min(width, row.len());
-                    let offset = (width - row_len) / 2;
-                    &new_row[offset..row_len + offset].copy_from_slice(&row[..row_len]);
-                    new_row
-                })
-                .collect(),
-        }
+        line.into_iter()
+            .map(|row| {
+                let row_len = usize::min(width, row.len());
+                let (source_start, target_start) = match self.alignment {
+                    Alignment::Left => (0, 0),
+                    Alignment::Center if row.len() > width => ((row.len() - width) / 2, 0),
+                    Alignment::Center => (0, (width - row_len) / 2),
+                    Alignment::Right => (row.len() - row_len, width - row_len),
+                };
+                let mut new_row = vec![None; width];
+                new_row[target_start..target_start + row_len]
+                    .copy_from_slice(&row[source_start..source_start + row_len]);
+                new_row
+            })
+            .collect()
     }
 }
 
 impl Shape for Caption {
+    fn dimensions(&self) -> (usize, usize) {
+        self.measure()
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(
+            &self.text,
+            self.size,
+            Arc::as_ptr(&self.font) as usize,
+            self.color,
+            &self.color_ref,
+            self.max_width,
+            &self.alignment,
+            self.line_height,
+            self.glyph_color
+                .as_ref()
+                .map(|GlyphColor(callback)| Arc::as_ptr(callback) as *const () as usize),
+        ))
+    }
+
     fn render(&self) -> Vec<Vec<Option<Color>>> {
-        let line_gap = self
-            .font
-            .v_metrics(Scale::uniform(self.size as f32))
-            .line_gap
-            .round() as u32;
+        self.render_with_palette(None)
+    }
+
+    fn render_with_palette(&self, palette: Option<&Palette>) -> Vec<Vec<Option<Color>>> {
+        if self.text.is_empty() {
+            return Vec::new();
+        }
+        let color = self
+            .color_ref
+            .as_ref()
+            .and_then(|reference| palette?.get(reference.name()))
+            .unwrap_or(self.color);
+        let line_height = self.line_height();
+        let text_lines = self.split_text();
+        // Full text width is only needed for glyph color callback, so avoid laying text out twice
+        // when it's not set
+        let full_width = match self.glyph_color {
+            Some(_) => text_lines
+                .iter()
+                .map(|line| self.width(&self.layout(line)))
+                .fold(0f32, f32::max),
+            None => 0f32,
+        };
 
         let mut lines = vec![];
         let mut max_real_width = None;
-        for line in self.split_text() {
-            let mut rendered_line = self.render_line(line);
+        let mut first_glyph = 0;
+        for (line_index, line) in text_lines.into_iter().enumerate() {
+            let rendered_line = self.render_line(line, first_glyph, line_index, full_width, color);
+            first_glyph += self.known_chars(line).count();
             if let Some(max_width) = rendered_line.iter().map(Vec::len).max() {
                 max_real_width = if let Some(old_max_width) = max_real_width {
                     Some(usize::max(old_max_width, max_width))
@@ -264,9 +655,6 @@ impl Shape for Caption {
                     Some(max_width)
                 }
             }
-            for _ in 0..line_gap {
-                rendered_line.push(vec![None]);
-            }
             lines.push(rendered_line)
         }
 
@@ -275,11 +663,17 @@ impl Shape for Caption {
         } else {
             max_real_width.unwrap_or(0)
         };
+        if width == 0 {
+            return Vec::new();
+        }
 
         lines
             .into_iter()
-            .map(|line| self.align_line(line, width))
-            .flatten()
+            .flat_map(|line| {
+                let mut line = self.align_line(line, width);
+                line.resize(line_height, vec![None; width]);
+                line
+            })
             .collect()
     }
 }
@@ -0,0 +1,455 @@
+use std::time::Duration;
+
+use crate::canvas::{Canvas, RenderTarget};
+use crate::compositor::Compositor;
+use crate::shape::{hash_of, Color, Shape};
+
+/// Direction in which scenes move during [`Transition::slide`], [`Wipe`] and [`Push`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// New scene comes from the right edge
+    Left,
+    /// New scene comes from the left edge
+    Right,
+    /// New scene comes from the bottom edge
+    Up,
+    /// New scene comes from the top edge
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Effect {
+    Crossfade,
+    Slide(Direction),
+}
+
+/// Animated switch between two scenes. Both scenes are rendered once on creation, so every frame
+/// only mixes two ready canvases. Frames are addressed by progress `t` from 0 (old scene) to 1
+/// (new scene), use [`Transition::progress`] to get it from elapsed time:
+/// ```
+/// # #[cfg(feature = "framebuffer")] {
+/// # use std::time::{Duration, Instant};
+/// # use linfb::{Framebuffer, FrameLimiter, Transition};
+/// # use linfb::shape::{Rectangle, Shape};
+/// # use linfb::sys::fb_var_screeninfo;
+/// let screen_info = fb_var_screeninfo::argb8888(40, 30);
+/// let mut framebuffer = Framebuffer::in_memory(screen_info).unwrap();
+/// let old = framebuffer.compositor((0, 0, 0).into());
+/// let mut new = framebuffer.compositor((0, 0, 255).into());
+/// new.add("rect", Rectangle::builder()
+///     .width(10)
+///     .height(10)
+///     .border_width(0)
+///     .fill_color((255, 0, 0))
+///     .build()
+///     .unwrap()
+///     .at(5, 5));
+///
+/// let transition = Transition::crossfade(&old, &new, Duration::from_millis(50));
+/// let mut limiter = FrameLimiter::new(200.0);
+/// let start = Instant::now();
+/// loop {
+///     let t = transition.progress(start.elapsed());
+///     transition.draw(t, &mut framebuffer);
+///     framebuffer.flush_paced(&mut limiter);
+///     if t >= 1.0 {
+///         break;
+///     }
+/// }
+/// assert_eq!(framebuffer.get_pixel(0, 0), (0, 0, 255).into());
+/// assert_eq!(framebuffer.get_pixel(5, 5), (255, 0, 0).into());
+/// # }
+/// ```
+///
+/// Scenes of different size are drawn over background of the new scene, and frames have the size
+/// of the new scene.
+#[derive(Clone, Debug)]
+pub struct Transition {
+    from: Canvas,
+    to: Canvas,
+    effect: Effect,
+    duration: Duration,
+}
+
+impl Transition {
+    /// Blend old scene into new one:
+    /// ```
+    /// # use std::time::Duration;
+    /// # use linfb::{Compositor, Transition};
+    /// # use linfb::shape::{Color, Shape};
+    /// let old = Compositor::new(4, 3, (0, 100, 200).into());
+    /// let new = Compositor::new(4, 3, (200, 100, 0).into());
+    /// let transition = Transition::crossfade(&old, &new, Duration::from_secs(1));
+    /// assert_eq!(transition.frame(0.0).to_rows(), old.render());
+    /// assert_eq!(transition.frame(1.0).to_rows(), new.render());
+    /// assert_eq!(transition.frame(0.5).get(3, 2), Some((100, 100, 100).into()));
+    /// // Progress is clamped
+    /// assert_eq!(transition.frame(2.0).to_rows(), new.render());
+    /// ```
+    pub fn crossfade(from: &Compositor, to: &Compositor, duration: Duration) -> Self {
+        Self::new(from, to, Effect::Crossfade, duration)
+    }
+
+    /// Move new scene in from the edge, pushing out the old scene:
+    /// ```
+    /// # use std::time::Duration;
+    /// # use linfb::{Compositor, Direction, Transition};
+    /// # use linfb::shape::{Color, Shape};
+    /// let (black, white): (Color, Color) = ((0, 0, 0).into(), (255, 255, 255).into());
+    /// let old = Compositor::new(10, 4, black);
+    /// let new = Compositor::new(10, 4, white);
+    /// for &direction in &[Direction::Left, Direction::Right, Direction::Up, Direction::Down] {
+    ///     let transition = Transition::slide(&old, &new, direction, Duration::from_secs(1));
+    ///     assert_eq!(transition.frame(0.0).to_rows(), old.render());
+    ///     assert_eq!(transition.frame(1.0).to_rows(), new.render());
+    /// }
+    ///
+    /// let frame = Transition::slide(&old, &new, Direction::Left, Duration::from_secs(1)).frame(0.3);
+    /// assert_eq!(frame.get(6, 0), Some(black));
+    /// assert_eq!(frame.get(7, 0), Some(white));
+    /// let frame = Transition::slide(&old, &new, Direction::Down, Duration::from_secs(1)).frame(0.5);
+    /// assert_eq!(frame.get(0, 1), Some(white));
+    /// assert_eq!(frame.get(0, 2), Some(black));
+    /// ```
+    pub fn slide(
+        from: &Compositor,
+        to: &Compositor,
+        direction: Direction,
+        duration: Duration,
+    ) -> Self {
+        Self::new(from, to, Effect::Slide(direction), duration)
+    }
+
+    fn new(from: &Compositor, to: &Compositor, effect: Effect, duration: Duration) -> Self {
+        let mut to_canvas = Canvas::default();
+        to.render_into(&mut to_canvas);
+        let mut from_canvas = Canvas::default();
+        if (from.width, from.height) == (to.width, to.height) {
+            from.render_into(&mut from_canvas);
+        } else {
+            from_canvas.reset(to.width, to.height, to.background);
+            Shape::render_into(from, &mut from_canvas, 0, 0);
+        }
+        Self {
+            from: from_canvas,
+            to: to_canvas,
+            effect,
+            duration,
+        }
+    }
+
+    /// Duration of transition
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Progress of transition after given time since its start, from 0 to 1
+    pub fn progress(&self, elapsed: Duration) -> f32 {
+        if self.duration == Duration::default() {
+            return 1f32;
+        }
+        f32::min(elapsed.as_secs_f32() / self.duration.as_secs_f32(), 1f32)
+    }
+
+    /// Render frame at given progress. `t` is clamped to `[0, 1]`
+    pub fn frame(&self, t: f32) -> Canvas {
+        let mut canvas = Canvas::default();
+        self.frame_into(t, &mut canvas);
+        canvas
+    }
+
+    /// Render frame at given progress into caller-owned [`Canvas`], reusing its allocation
+    pub fn frame_into(&self, t: f32, canvas: &mut Canvas) {
+        let (width, height) = (self.to.width(), self.to.height());
+        canvas.reset(width, height, Color::from((0, 0, 0, 0)));
+        if width == 0 {
+            return;
+        }
+        for (y, row) in canvas.pixels_mut().chunks_exact_mut(width).enumerate() {
+            self.mix_row(t, y, row);
+        }
+    }
+
+    /// Draw frame at given progress directly onto target, e.g.
+    /// [`Framebuffer`](crate::Framebuffer)
+    pub fn draw(&self, t: f32, target: &mut dyn RenderTarget) {
+        let mut row = vec![Color::from((0, 0, 0, 0)); self.to.width()];
+        for y in 0..self.to.height() {
+            self.mix_row(t, y, &mut row);
+            target.draw_colors(0, y, &row);
+        }
+    }
+
+    /// Compute row `y` of frame at given progress
+    fn mix_row(&self, t: f32, y: usize, row: &mut [Color]) {
+        let t = t.clamp(0f32, 1f32);
+        let (width, height) = (self.to.width(), self.to.height());
+        match self.effect {
+            Effect::Crossfade => {
+                let from = row_of(&self.from, y).iter();
+                let to = row_of(&self.to, y).iter();
+                for (pixel, (from, to)) in row.iter_mut().zip(from.zip(to)) {
+                    *pixel = mix(*from, *to, t);
+                }
+            }
+            Effect::Slide(direction @ Direction::Left)
+            | Effect::Slide(direction @ Direction::Right) => {
+                let offset = (t * width as f32).round() as usize;
+                let (from, to) = (row_of(&self.from, y), row_of(&self.to, y));
+                if direction == Direction::Left {
+                    row[..width - offset].copy_from_slice(&from[offset..]);
+                    row[width - offset..].copy_from_slice(&to[..offset]);
+                } else {
+                    row[..offset].copy_from_slice(&to[width - offset..]);
+                    row[offset..].copy_from_slice(&from[..width - offset]);
+                }
+            }
+            Effect::Slide(Direction::Up) => {
+                let offset = (t * height as f32).round() as usize;
+                row.copy_from_slice(if y + offset < height {
+                    row_of(&self.from, y + offset)
+                } else {
+                    row_of(&self.to, y + offset - height)
+                });
+            }
+            Effect::Slide(Direction::Down) => {
+                let offset = (t * height as f32).round() as usize;
+                row.copy_from_slice(if y >= offset {
+                    row_of(&self.from, y - offset)
+                } else {
+                    row_of(&self.to, y + height - offset)
+                });
+            }
+        }
+    }
+}
+
+fn row_of(canvas: &Canvas, y: usize) -> &[Color] {
+    let width = canvas.width();
+    &canvas.pixels()[y * width..(y + 1) * width]
+}
+
+/// Linear interpolation between two colors, every channel is interpolated separately
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+    Color {
+        red: channel(from.red, to.red),
+        green: channel(from.green, to.green),
+        blue: channel(from.blue, to.blue),
+        alpha: channel(from.alpha, to.alpha),
+    }
+}
+
+type Grid = Vec<Vec<Option<Color>>>;
+
+/// Two shapes rendered once and padded to common size, aligned at top left
+#[derive(Clone, Debug, PartialEq)]
+struct Pair {
+    from: Grid,
+    to: Grid,
+    width: usize,
+    height: usize,
+    hash: u64,
+}
+
+impl Pair {
+    fn new(from: &dyn Shape, to: &dyn Shape) -> Self {
+        let (from_width, from_height) = from.dimensions();
+        let (to_width, to_height) = to.dimensions();
+        let (width, height) = (
+            usize::max(from_width, to_width),
+            usize::max(from_height, to_height),
+        );
+        let padded = |shape: &dyn Shape| {
+            let mut grid = shape.render();
+            grid.resize(height, Vec::new());
+            for row in grid.iter_mut() {
+                row.resize(width, None);
+            }
+            grid
+        };
+        Self {
+            from: padded(from),
+            to: padded(to),
+            width,
+            height,
+            hash: hash_of(&(from.content_hash(), to.content_hash())),
+        }
+    }
+
+    /// Wipe or push frame at progress `t`
+    fn composite(&self, direction: Direction, t: f32, push: bool) -> Grid {
+        let t = t.clamp(0f32, 1f32);
+        let (width, height) = (self.width, self.height);
+        let columns = (t * width as f32).round() as usize;
+        let rows = (t * height as f32).round() as usize;
+        (0..height)
+            .map(|y| {
+                let (from, to) = (&self.from, &self.to);
+                match (direction, push) {
+                    (Direction::Right, false) => [&to[y][..columns], &from[y][columns..]].concat(),
+                    (Direction::Left, false) => {
+                        [&from[y][..width - columns], &to[y][width - columns..]].concat()
+                    }
+                    (Direction::Right, true) => {
+                        [&to[y][width - columns..], &from[y][..width - columns]].concat()
+                    }
+                    (Direction::Left, true) => [&from[y][columns..], &to[y][..columns]].concat(),
+                    (Direction::Down, false) if y < rows => to[y].clone(),
+                    (Direction::Up, false) if y >= height - rows => to[y].clone(),
+                    (Direction::Down, false) | (Direction::Up, false) => from[y].clone(),
+                    (Direction::Down, true) if y < rows => to[y + height - rows].clone(),
+                    (Direction::Down, true) => from[y - rows].clone(),
+                    (Direction::Up, true) if y + rows < height => from[y + rows].clone(),
+                    (Direction::Up, true) => to[y + rows - height].clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Shape which reveals one shape over another with a moving straight edge. At progress 0 it looks
+/// like `from`, at progress 1 like `to`. Both shapes are rendered once on creation, shapes of
+/// different size are aligned at top left and missing area is transparent:
+/// ```
+/// # use linfb::Direction;
+/// # use linfb::shape::{Color, Rectangle, Shape, Wipe};
+/// let square = |width, height, color: (u8, u8, u8)| {
+///     Rectangle::builder()
+///         .width(width)
+///         .height(height)
+///         .border_width(0)
+///         .fill_color(color)
+///         .build()
+///         .unwrap()
+/// };
+/// let (red, blue): (Color, Color) = ((255, 0, 0).into(), (0, 0, 255).into());
+/// let mut wipe = Wipe::new(square(4, 2, (255, 0, 0)), square(4, 2, (0, 0, 255)), Direction::Right);
+/// assert_eq!(wipe.render(), vec![vec![Some(red); 4]; 2]);
+/// wipe.set_progress(0.5);
+/// let row = vec![Some(blue), Some(blue), Some(red), Some(red)];
+/// assert_eq!(wipe.render(), vec![row.clone(), row]);
+/// wipe.set_progress(1.0);
+/// assert_eq!(wipe.render(), vec![vec![Some(blue); 4]; 2]);
+///
+/// // Smaller shape is padded with transparency
+/// let mut wipe = Wipe::new(square(2, 1, (255, 0, 0)), square(4, 2, (0, 0, 255)), Direction::Down);
+/// assert_eq!(wipe.dimensions(), (4, 2));
+/// assert_eq!(wipe.render()[0], vec![Some(red), Some(red), None, None]);
+/// wipe.set_progress(0.5);
+/// assert_eq!(wipe.render(), vec![vec![Some(blue); 4], vec![None; 4]]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wipe {
+    pair: Pair,
+    direction: Direction,
+    progress: f32,
+}
+
+impl Wipe {
+    /// Create wipe at progress 0. Edge moves in given direction, e.g. [`Direction::Right`] reveals
+    /// `to` from left to right
+    pub fn new(from: impl Shape, to: impl Shape, direction: Direction) -> Self {
+        Self {
+            pair: Pair::new(&from, &to),
+            direction,
+            progress: 0f32,
+        }
+    }
+
+    /// Set progress from 0 to 1. Values outside of this range are clamped when rendering
+    pub fn set_progress(&mut self, t: f32) {
+        self.progress = t;
+    }
+
+    /// Current progress
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+}
+
+impl Shape for Wipe {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.pair.composite(self.direction, self.progress, false)
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.pair.width, self.pair.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(self.pair.hash, self.direction, self.progress.to_bits()))
+    }
+}
+
+/// Shape in which `to` slides in and pushes `from` out in given direction, like
+/// [`Transition::slide`] for arbitrary shapes. See [`Wipe`] for handling of shapes of different
+/// size:
+/// ```
+/// # use linfb::Direction;
+/// # use linfb::shape::{Color, Push, Rectangle, Shape};
+/// let stripes = |colors: [(u8, u8, u8); 2]| {
+///     let mut canvas = linfb::Compositor::new(2, 1, colors[0].into());
+///     canvas.add("right", Rectangle::builder()
+///         .width(1)
+///         .height(1)
+///         .border_width(0)
+///         .fill_color(colors[1])
+///         .build()
+///         .unwrap()
+///         .at(1, 0));
+///     canvas
+/// };
+/// let color = |rgb: (u8, u8, u8)| Some(Color::from(rgb));
+/// let (a, b, c, d) = ((255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255));
+/// let mut push = Push::new(stripes([a, b]), stripes([c, d]), Direction::Left);
+/// push.set_progress(0.5);
+/// assert_eq!(push.render(), vec![vec![color(b), color(c)]]);
+///
+/// let mut push = Push::new(stripes([a, b]), stripes([c, d]), Direction::Right);
+/// push.set_progress(0.5);
+/// assert_eq!(push.render(), vec![vec![color(d), color(a)]]);
+/// push.set_progress(1.0);
+/// assert_eq!(push.render(), vec![vec![color(c), color(d)]]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Push {
+    pair: Pair,
+    direction: Direction,
+    progress: f32,
+}
+
+impl Push {
+    /// Create push at progress 0
+    pub fn new(from: impl Shape, to: impl Shape, direction: Direction) -> Self {
+        Self {
+            pair: Pair::new(&from, &to),
+            direction,
+            progress: 0f32,
+        }
+    }
+
+    /// Set progress from 0 to 1. Values outside of this range are clamped when rendering
+    pub fn set_progress(&mut self, t: f32) {
+        self.progress = t;
+    }
+
+    /// Current progress
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+}
+
+impl Shape for Push {
+    fn render(&self) -> Vec<Vec<Option<Color>>> {
+        self.pair.composite(self.direction, self.progress, true)
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.pair.width, self.pair.height)
+    }
+
+    fn content_hash(&self) -> u64 {
+        hash_of(&(self.pair.hash, self.direction, self.progress.to_bits()))
+    }
+}
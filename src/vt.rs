@@ -0,0 +1,110 @@
+//! Switching to a free virtual terminal in graphics mode, so console doesn't draw over framebuffer
+#![allow(non_camel_case_types)]
+
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+
+use nix::{ioctl_read_bad, ioctl_write_int_bad};
+
+use crate::error::{Error::*, Result};
+use crate::Framebuffer;
+
+#[repr(C)]
+#[derive(Clone, Default, Debug)]
+struct vt_stat {
+    v_active: u16,
+    v_signal: u16,
+    v_state: u16,
+}
+
+ioctl_read_bad!(vt_openqry, 0x5600, i32);
+ioctl_write_int_bad!(vt_activate, 0x5606);
+ioctl_write_int_bad!(vt_waitactive, 0x5607);
+ioctl_write_int_bad!(kd_setmode, 0x4B3A);
+
+/// `VT_GETSTATE` ioctl. Written by hand, since functions generated by nix are `pub`, which Rust
+/// 1.73 doesn't allow with private `vt_stat`
+unsafe fn vt_getstate(fd: nix::libc::c_int, data: *mut vt_stat) -> nix::Result<nix::libc::c_int> {
+    nix::errno::Errno::result(nix::libc::ioctl(fd, 0x5603, data))
+}
+
+const KD_TEXT: i32 = 0;
+const KD_GRAPHICS: i32 = 1;
+
+/// Guard which switches back to the previous virtual terminal and restores text mode when
+/// dropped, see [`Framebuffer::open_on_new_vt`]
+pub struct VtGuard {
+    console: File,
+    tty: File,
+    previous: u16,
+    graphics: bool,
+}
+
+impl VtGuard {
+    /// Switch to a free virtual terminal and set it to graphics mode
+    fn switch() -> Result<Self> {
+        let console = open_tty("/dev/tty0")
+            .or_else(|_| open_tty("/dev/console"))
+            .map_err(VtAccess)?;
+        let fd = console.as_raw_fd();
+        let mut state = vt_stat::default();
+        unsafe { vt_getstate(fd, &mut state).map_err(VtSwitch)? };
+        let mut free = -1;
+        unsafe { vt_openqry(fd, &mut free).map_err(VtSwitch)? };
+        if free <= 0 {
+            return Err(NoFreeVt);
+        }
+        let tty = open_tty(&format!("/dev/tty{}", free)).map_err(VtAccess)?;
+
+        let mut guard = Self {
+            console,
+            tty,
+            previous: state.v_active,
+            graphics: false,
+        };
+        unsafe {
+            vt_activate(fd, free).map_err(VtSwitch)?;
+            vt_waitactive(fd, free).map_err(VtSwitch)?;
+            kd_setmode(guard.tty.as_raw_fd(), KD_GRAPHICS).map_err(VtMode)?;
+        }
+        guard.graphics = true;
+        Ok(guard)
+    }
+}
+
+impl Drop for VtGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if self.graphics {
+                let _ = kd_setmode(self.tty.as_raw_fd(), KD_TEXT);
+            }
+            let fd = self.console.as_raw_fd();
+            let previous = i32::from(self.previous);
+            if vt_activate(fd, previous).is_ok() {
+                let _ = vt_waitactive(fd, previous);
+            }
+        }
+    }
+}
+
+fn open_tty(path: &str) -> std::io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(path)
+}
+
+impl Framebuffer {
+    /// Switch to a free virtual terminal, set it to graphics mode, so the console cursor and kernel
+    /// messages are not drawn over framebuffer, and open `/dev/fb0` with [`Framebuffer::open`].
+    /// Returned guard switches back to the previous terminal and restores text mode when dropped,
+    /// so it must outlive drawing. Usually requires root privileges.
+    ///
+    /// Returns [`Error::VtAccess`](crate::Error::VtAccess) if console devices can't be opened,
+    /// e.g. because of permissions, [`Error::NoFreeVt`](crate::Error::NoFreeVt) if all terminals
+    /// are in use, [`Error::VtSwitch`](crate::Error::VtSwitch) and
+    /// [`Error::VtMode`](crate::Error::VtMode) if switching terminal or its mode fails. Terminal is
+    /// switched back on any error after switch
+    pub fn open_on_new_vt() -> Result<(Self, VtGuard)> {
+        let guard = VtGuard::switch()?;
+        let framebuffer = Self::open()?;
+        Ok((framebuffer, guard))
+    }
+}
@@ -0,0 +1,82 @@
+//! Image blitting against the per-pixel path it replaced and hashing of image contents
+#![cfg(feature = "images")]
+
+use linfb::shape::{Color, Image, Shape};
+use linfb::{Canvas, Rect, RenderTarget};
+
+/// Image with fully transparent, translucent and opaque pixels in random runs
+fn random_image(width: u32, height: u32, seed: u32) -> image::RgbaImage {
+    let mut seed = seed;
+    let mut random = move || {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    };
+    let mut alpha = 255;
+    image::RgbaImage::from_fn(width, height, |_, _| {
+        if random() % 4 == 0 {
+            alpha = [0, 255, random()][random() as usize % 3];
+        }
+        image::Rgba([random(), random(), random(), alpha])
+    })
+}
+
+/// Rows converted pixel by pixel, like `Image::render` did before rows were cached
+fn per_pixel_rows(buffer: &image::RgbaImage) -> Vec<Vec<Option<Color>>> {
+    buffer
+        .rows()
+        .map(|row| {
+            row.map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                Some((r, g, b, a).into()).filter(|_| a != 0)
+            })
+            .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn blitting_matches_per_pixel_drawing() {
+    for seed in 0..20 {
+        let buffer = random_image(17 + seed, 9 + seed % 5, seed);
+        let rows = per_pixel_rows(&buffer);
+        let image = Image::from(buffer);
+        assert_eq!(image.render(), rows);
+        assert_eq!(image.rows(), &rows[..]);
+
+        let background = Color::from((10, 20, 30, 200));
+        let mut blitted = Canvas::new(40, 20, background);
+        let mut expected = blitted.clone();
+        image.render_into(&mut blitted, 3, 2);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                if let Some(color) = color {
+                    expected.draw_pixel(3 + x, 2 + y, *color);
+                }
+            }
+        }
+        assert_eq!(blitted, expected);
+
+        let region = Rect::new(2, 1, 10, 6);
+        let mut blitted = Canvas::new(12, 8, background);
+        let mut expected = blitted.clone();
+        image.render_region_into(region, &mut blitted, 1, 1);
+        for (y, row) in image.render_region(region).iter().enumerate() {
+            expected.draw_row(1, 1 + y, row);
+        }
+        assert_eq!(blitted, expected);
+    }
+}
+
+#[test]
+fn content_hash_follows_pixels() {
+    let image = Image::from(random_image(8, 8, 1));
+    assert_eq!(image.content_hash(), image.clone().content_hash());
+    assert_eq!(
+        image.content_hash(),
+        Image::from(random_image(8, 8, 1)).content_hash()
+    );
+    assert_ne!(
+        image.content_hash(),
+        Image::from(random_image(8, 8, 2)).content_hash()
+    );
+}
@@ -0,0 +1,60 @@
+//! Rendering without framebuffer device. Run with
+//! `cargo test --no-default-features --features render-core` to check that the core builds alone
+#![cfg(feature = "render-core")]
+
+use linfb::shape::{Color, Rectangle, Shape};
+use linfb::{Canvas, Compositor, PixelFormat};
+
+fn square(size: usize, color: &str) -> Rectangle {
+    Rectangle::builder()
+        .width(size)
+        .height(size)
+        .border_width(0)
+        .fill_color(Color::hex(color).unwrap())
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn compositor_renders_into_caller_buffer() {
+    let mut compositor = Compositor::new(8, 6, (0, 0, 255).into());
+    compositor
+        .add("red", square(4, "#ff0000").at(1, 1))
+        .add("green", square(2, "#00ff0080").at(4, 3));
+    let mut canvas = Canvas::default();
+    compositor.render_into(&mut canvas);
+
+    // Buffer managed by caller, e.g. memory which is later copied by DMA
+    let mut memory = [0u8; 8 * 6 * 4];
+    memory.copy_from_slice(&canvas.as_bytes(PixelFormat::XRGB8888));
+
+    let pixel = |x: usize, y: usize| {
+        let start = (y * 8 + x) * 4;
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&memory[start..start + 4]);
+        PixelFormat::XRGB8888.decode(u32::from_ne_bytes(bytes))
+    };
+    assert_eq!(pixel(0, 0), (0, 0, 255).into());
+    assert_eq!(pixel(1, 1), (255, 0, 0).into());
+    for (index, color) in canvas.pixels().iter().enumerate() {
+        assert_eq!(
+            pixel(index % 8, index / 8),
+            Color {
+                alpha: 255,
+                ..*color
+            }
+        );
+    }
+}
+
+#[test]
+fn formats_differ_only_in_channel_order() {
+    let mut canvas = Canvas::new(3, 1, (0, 0, 0).into());
+    canvas.fill_rect(linfb::Rect::new(1, 0, 1, 1), (10, 20, 30).into());
+    let rgb = canvas.as_bytes(PixelFormat::XRGB8888);
+    let bgr = canvas.as_bytes(PixelFormat::XBGR8888);
+    assert_eq!(rgb.len(), 12);
+    let pixel = |bytes: &[u8]| u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    assert_eq!(pixel(&rgb), 0x000a141e);
+    assert_eq!(pixel(&bgr), 0x001e140a);
+}